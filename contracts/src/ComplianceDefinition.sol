@@ -50,9 +50,18 @@ contract ComplianceDefinition {
     /// @notice Human-readable name identifying this compliance definition.
     string public name;
 
+    /// @notice Emitted when control of this compliance definition passes to a new regulator.
+    /// @param previousRegulator The address that controlled the definition until now.
+    /// @param newRegulator The address that controls the definition from now on.
+    event RegulatorTransferred(address indexed previousRegulator, address indexed newRegulator);
+
     /// @notice Thrown when a non-regulator address attempts to call a regulator-only function.
     error NotRegulator();
 
+    /// @notice Thrown when control would be handed to the zero address, which could never
+    ///  call a regulator-only function again.
+    error ZeroRegulator();
+
     /// @notice Thrown when no version is active at the current block height.
     error NoActiveVersion();
 
@@ -154,6 +163,17 @@ contract ComplianceDefinition {
         );
     }
 
+    /// @notice Hands control of this compliance definition to a new regulator.
+    /// @dev Lets a deployer such as `ComplianceFactory` publish the first version and then
+    ///  pass the definition to the regulator that will maintain it.
+    ///  Reverts for the zero address, so the definition cannot be locked by mistake.
+    /// @param newRegulator The address authorized to manage this definition from now on.
+    function transferRegulator(address newRegulator) external onlyRegulator {
+        if (newRegulator == address(0)) revert ZeroRegulator();
+        emit RegulatorTransferred(regulator, newRegulator);
+        regulator = newRegulator;
+    }
+
     /// @notice Returns the compliance version that is active at the current block height.
     /// @dev Iterates backwards through the version array to return the most recently
     ///  published version whose validity window contains `block.number`. Reverts if no
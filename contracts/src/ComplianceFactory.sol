@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity >=0.8.21;
+
+import {ComplianceDefinition} from "./ComplianceDefinition.sol";
+
+/// @title ComplianceFactory
+/// @notice Deploys a verifier and a `ComplianceDefinition` and publishes the definition's
+///  first version in a single transaction, so a new definition is either fully deployed and
+///  registered or not deployed at all.
+/// @dev The definition is created with the factory as its regulator so the factory can call
+///  `updateCircuit`, then handed to the requested regulator with `transferRegulator`.
+contract ComplianceFactory {
+    /// @notice Emitted once per `deploy`, after the definition has been handed to `regulator`.
+    /// @param definition Address of the new compliance definition.
+    /// @param verifier Address of the verifier registered as its first version.
+    /// @param regulator The address that controls the new definition.
+    event ComplianceDeployed(
+        address indexed definition,
+        address indexed verifier,
+        address indexed regulator
+    );
+
+    /// @notice Thrown when the verifier's init code reverts or deploys nothing.
+    error VerifierDeploymentFailed();
+
+    /// @notice Deploys a verifier from `verifierInitCode` and a compliance definition
+    ///  controlled by `regulator`, with the verifier registered as its first version.
+    /// @param verifierInitCode Creation bytecode of the verifier, with libraries linked.
+    /// @param regulator The address authorized to manage the new definition.
+    /// @param name Human-readable name for the new definition.
+    /// @param merkleRoot Merkle root of the public parameter set for the first version.
+    /// @param tStart Block height at which the first version becomes active.
+    /// @param tEnd Block height at which the first version expires.
+    /// @param metadataHash IPFS hash of the compliance circuit to be proved.
+    /// @param leavesHash IPFS hash of the Merkle tree leaves.
+    /// @return definition Address of the new compliance definition.
+    /// @return verifier Address of the new verifier.
+    function deploy(
+        bytes calldata verifierInitCode,
+        address regulator,
+        string calldata name,
+        bytes32 merkleRoot,
+        uint256 tStart,
+        uint256 tEnd,
+        string calldata metadataHash,
+        string calldata leavesHash
+    ) external returns (address definition, address verifier) {
+        bytes memory initCode = verifierInitCode;
+        assembly {
+            verifier := create(0, add(initCode, 0x20), mload(initCode))
+        }
+        if (verifier == address(0) || verifier.code.length == 0) revert VerifierDeploymentFailed();
+
+        ComplianceDefinition cd = new ComplianceDefinition(address(this), name);
+        cd.updateCircuit(verifier, merkleRoot, tStart, tEnd, metadataHash, leavesHash);
+        cd.transferRegulator(regulator);
+
+        definition = address(cd);
+        emit ComplianceDeployed(definition, verifier, regulator);
+    }
+}
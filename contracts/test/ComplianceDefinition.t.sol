@@ -142,6 +142,35 @@ contract ComplianceDefinitionTest is Test {
         cd.updateParams(bytes32(uint256(2)), "");
     }
 
+    // -- transferRegulator --
+
+    function test_transferRegulatorHandsOverControl() public {
+        vm.prank(regulator);
+        cd.transferRegulator(nonRegulator);
+        assertEq(cd.regulator(), nonRegulator);
+
+        vm.prank(regulator);
+        vm.expectRevert(ComplianceDefinition.NotRegulator.selector);
+        cd.updateCircuit(address(mockVerifier), bytes32(0), 0, type(uint256).max, "", "");
+
+        vm.prank(nonRegulator);
+        cd.updateCircuit(address(mockVerifier), bytes32(0), 0, type(uint256).max, "", "");
+        assertEq(cd.getVersionCount(), 1);
+    }
+
+    function test_transferRegulatorRevertsForNonRegulator() public {
+        vm.prank(nonRegulator);
+        vm.expectRevert(ComplianceDefinition.NotRegulator.selector);
+        cd.transferRegulator(nonRegulator);
+    }
+
+    function test_transferRegulatorRevertsForZeroAddress() public {
+        vm.prank(regulator);
+        vm.expectRevert(ComplianceDefinition.ZeroRegulator.selector);
+        cd.transferRegulator(address(0));
+        assertEq(cd.regulator(), regulator);
+    }
+
     // -- getActiveVersion --
 
     function test_getActiveVersionRevertsWhenEmpty() public {
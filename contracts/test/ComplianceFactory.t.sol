@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: Apache-2.0
+pragma solidity >=0.8.21;
+
+import {Test} from "forge-std/Test.sol";
+import {Vm} from "forge-std/Vm.sol";
+import {ComplianceDefinition} from "../src/ComplianceDefinition.sol";
+import {ComplianceFactory} from "../src/ComplianceFactory.sol";
+import {MockVerifier} from "./ComplianceDefinition.t.sol";
+
+contract ComplianceFactoryTest is Test {
+    ComplianceFactory public factory;
+    address public regulator = address(0x1);
+
+    event ComplianceDeployed(
+        address indexed definition,
+        address indexed verifier,
+        address indexed regulator
+    );
+
+    function setUp() public {
+        factory = new ComplianceFactory();
+    }
+
+    function deployDefinition() internal returns (ComplianceDefinition cd, address verifier) {
+        (address definition, address deployedVerifier) = factory.deploy(
+            type(MockVerifier).creationCode,
+            regulator,
+            "Test Compliance",
+            bytes32(uint256(0xabc)),
+            0,
+            type(uint256).max,
+            "QmTestCid123",
+            "QmTestLeaves"
+        );
+        return (ComplianceDefinition(definition), deployedVerifier);
+    }
+
+    function test_deployRegistersVerifierAsFirstVersion() public {
+        (ComplianceDefinition cd, address verifier) = deployDefinition();
+
+        assertEq(cd.name(), "Test Compliance");
+        assertEq(cd.getVersionCount(), 1);
+        ComplianceDefinition.ComplianceVersion memory v = cd.getActiveVersion();
+        assertEq(v.verifier, verifier);
+        assertEq(v.merkleRoot, bytes32(uint256(0xabc)));
+        assertEq(v.metadataHash, "QmTestCid123");
+        assertEq(v.leavesHash, "QmTestLeaves");
+        assertTrue(cd.verify(""));
+    }
+
+    function test_deployHandsDefinitionToRegulator() public {
+        (ComplianceDefinition cd,) = deployDefinition();
+        assertEq(cd.regulator(), regulator);
+
+        vm.expectRevert(ComplianceDefinition.NotRegulator.selector);
+        cd.updateParams(bytes32(uint256(2)), "");
+    }
+
+    function test_deployEmitsAddresses() public {
+        vm.recordLogs();
+        (ComplianceDefinition cd, address verifier) = deployDefinition();
+
+        Vm.Log[] memory logs = vm.getRecordedLogs();
+        Vm.Log memory last = logs[logs.length - 1];
+        assertEq(last.emitter, address(factory));
+        assertEq(last.topics[0], ComplianceDeployed.selector);
+        assertEq(last.topics[1], bytes32(uint256(uint160(address(cd)))));
+        assertEq(last.topics[2], bytes32(uint256(uint160(verifier))));
+        assertEq(last.topics[3], bytes32(uint256(uint160(regulator))));
+    }
+
+    function test_deployRevertsOnFailingInitCode() public {
+        vm.expectRevert(ComplianceFactory.VerifierDeploymentFailed.selector);
+        factory.deploy(hex"fe", regulator, "", bytes32(0), 0, type(uint256).max, "", "");
+    }
+
+    function test_deployRevertsForZeroRegulator() public {
+        vm.expectRevert(ComplianceDefinition.ZeroRegulator.selector);
+        factory.deploy(
+            type(MockVerifier).creationCode,
+            address(0),
+            "",
+            bytes32(0),
+            0,
+            type(uint256).max,
+            "",
+            ""
+        );
+    }
+}
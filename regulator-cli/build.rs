@@ -0,0 +1,29 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Output of `git <args>`, if git is installed and the source is a checkout.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    (output.status.success() && !stdout.trim().is_empty()).then(|| stdout.trim().to_string())
+}
+
+/// Embed the commit the CLI is built from as `REGULATOR_CLI_GIT_COMMIT`, for
+/// `regulator-cli version`.  Builds outside a git checkout (e.g. from a source
+/// tarball) report `unknown`.
+fn main() {
+    let commit = git(&["rev-parse", "--short=12", "HEAD"]).unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=REGULATOR_CLI_GIT_COMMIT={commit}");
+
+    // Rebuild when HEAD moves: a checkout changes HEAD, a commit changes the branch ref.
+    if let Some(git_dir) = git(&["rev-parse", "--absolute-git-dir"]) {
+        let git_dir = Path::new(&git_dir);
+        let mut watched = vec![git_dir.join("HEAD"), git_dir.join("packed-refs")];
+        if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            watched.push(git_dir.join(head_ref));
+        }
+        for path in watched.iter().filter(|path| path.exists()) {
+            println!("cargo:rerun-if-changed={}", path.display());
+        }
+    }
+}
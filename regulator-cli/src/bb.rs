@@ -1,79 +1,500 @@
+use alloy::primitives::{Bytes, FixedBytes};
+use alloy::sol_types::SolCall;
 use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-
-/// Run `bb write_vk` to generate a verification key from compiled ACIR bytecode.
-/// Uses `--oracle_hash keccak` for EVM-compatible verification.
-pub fn write_vk(bytecode_path: &Path, output_dir: &Path) -> Result<PathBuf> {
-    let output = Command::new("bb")
-        .args([
-            "write_vk",
-            "-b",
-            &bytecode_path.display().to_string(),
-            "-o",
-            &output_dir.display().to_string(),
-            "--oracle_hash",
-            "keccak",
-        ])
-        .output()
-        .with_context(|| format!(
-            "failed to run `bb write_vk` for bytecode {} -- is barretenberg (bb) installed?",
-            bytecode_path.display()
-        ))?;
+use std::sync::OnceLock;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!(
-            "bb write_vk failed for bytecode {} (output dir: {}):\n{stderr}",
-            bytecode_path.display(),
-            output_dir.display()
-        );
+use crate::progress::ProgressReporter;
+use crate::style;
+use crate::tools::{self, Tool};
+
+/// Generates the verification key and on-chain verifier for a compiled circuit.
+///
+/// Implementations shell out to a proving system's tooling; the command pipelines only
+/// go through this trait, so a new backend only needs an implementation and a [`Backend`]
+/// variant.
+pub trait ProvingBackend: Send + Sync {
+    /// The installed tool's version, failing if it is too old to produce artifacts this
+    /// CLI can deploy.
+    fn version(&self) -> Result<String>;
+
+    /// Write the verification key for the ACIR bytecode at `bytecode_path` into
+    /// `output_dir` and return its path.
+    fn write_vk(&self, bytecode_path: &Path, output_dir: &Path) -> Result<PathBuf>;
+
+    /// Write a Solidity verifier contract for the verification key at `vk_path`.
+    fn write_solidity_verifier(&self, vk_path: &Path, output_path: &Path) -> Result<()>;
+
+    /// Prove the circuit at `bytecode_path` for the witness at `witness_path`, writing
+    /// a proof the Solidity verifier accepts into `output_dir`.
+    fn prove(&self, bytecode_path: &Path, witness_path: &Path, output_dir: &Path) -> Result<ProofFiles>;
+
+    /// Verify `proof` against the verification key at `vk_path` off-chain, failing if
+    /// it is rejected.
+    fn verify(&self, vk_path: &Path, proof: &ProofFiles) -> Result<()>;
+}
+
+/// A proof and its public inputs, as written by [`ProvingBackend::prove`].
+pub struct ProofFiles {
+    pub proof: PathBuf,
+    pub public_inputs: PathBuf,
+}
+
+/// Proving backends selectable with `--backend`.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum Backend {
+    /// Barretenberg (`bb`), UltraHonk with keccak oracle hashing
+    #[default]
+    Bb,
+}
+
+impl Backend {
+    pub fn get(self) -> &'static dyn ProvingBackend {
+        match self {
+            Self::Bb => &Barretenberg,
+        }
+    }
+}
+
+/// Oldest `bb` whose `write_vk --oracle_hash keccak` and `write_solidity_verifier`
+/// interface matches what [`Barretenberg`] runs; older builds take different flags or
+/// write vks the generated verifier cannot check.
+const MIN_BB_VERSION: (u64, u64, u64) = (0, 82, 0);
+
+/// `bb --version`, queried once per process.
+static BB_VERSION: OnceLock<String> = OnceLock::new();
+
+/// Parse the `major.minor.patch` out of `bb --version` output such as `0.82.2`,
+/// `v0.84.0`, or `1.0.0-nightly.20250701`.
+fn parse_bb_version(output: &str) -> Option<(u64, u64, u64)> {
+    let version = output.trim().trim_start_matches('v');
+    let core = version.split(['-', '+', ' ']).next()?;
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().ok());
+    Some((parts.next()??, parts.next()??, parts.next().unwrap_or(Some(0))?))
+}
+
+/// Fragments of bb errors from failing to decode a circuit's bytecode, which almost
+/// always means nargo and bb come from releases with different ACIR serializations.
+const BYTECODE_FORMAT_ERRORS: &[&str] = &[
+    "deserializ",
+    "msgpack",
+    "bincode",
+    "unknown variant",
+    "invalid type",
+    "Unexpected end",
+];
+
+/// The `noir_version` nargo records in a compiled circuit artifact, without the
+/// `+<commit>` suffix.  Artifacts carry no separate ACIR version; the compiler
+/// release determines the format.
+fn artifact_noir_version(bytecode_path: &Path) -> Option<String> {
+    let artifact: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(bytecode_path).ok()?).ok()?;
+    let version = artifact.get("noir_version")?.as_str()?;
+    Some(version.split('+').next().unwrap_or(version).to_string())
+}
+
+/// When a `bb write_vk` failure looks like a bytecode format mismatch, explain which
+/// toolchain versions disagree and how to align them.
+fn toolchain_mismatch_hint(bytecode_path: &Path, stderr: &str) -> Option<String> {
+    let lowercase = stderr.to_ascii_lowercase();
+    if !BYTECODE_FORMAT_ERRORS
+        .iter()
+        .any(|pattern| lowercase.contains(&pattern.to_ascii_lowercase()))
+    {
+        return None;
+    }
+    let noir_version = artifact_noir_version(bytecode_path)?;
+    let bb_version = BB_VERSION.get().map_or("(unknown version)", String::as_str);
+    Some(format!(
+        "this circuit was compiled with nargo {noir_version}, but bb {bb_version} could not read its bytecode -- the ACIR format changes between releases, so align your noirup/bbup versions (e.g. `bbup --noir-version {noir_version}`)"
+    ))
+}
+
+/// The Barretenberg CLI (`bb`).
+pub struct Barretenberg;
+
+impl ProvingBackend for Barretenberg {
+    /// Run `bb --version` and bail if it is older than [`MIN_BB_VERSION`].
+    fn version(&self) -> Result<String> {
+        if let Some(version) = BB_VERSION.get() {
+            return Ok(version.clone());
+        }
+
+        let output = tools::command(Tool::Bb)
+            .arg("--version")
+            .output()
+            .context("failed to run `bb --version` -- is barretenberg (bb) installed?")?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!("bb --version failed:\n{stderr}");
+        }
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+        let parsed = parse_bb_version(&version)
+            .with_context(|| format!("could not parse `bb --version` output: {version:?}"))?;
+        if parsed < MIN_BB_VERSION {
+            let (major, minor, patch) = MIN_BB_VERSION;
+            bail!(
+                "bb {version} is too old: at least {major}.{minor}.{patch} is required for `--oracle_hash keccak` verification keys and the current `write_solidity_verifier` -- upgrade with `bbup`"
+            );
+        }
+
+        Ok(BB_VERSION.get_or_init(|| version).clone())
+    }
+
+    /// Run `bb write_vk` to generate a verification key from compiled ACIR bytecode.
+    /// Uses `--oracle_hash keccak` for EVM-compatible verification.
+    fn write_vk(&self, bytecode_path: &Path, output_dir: &Path) -> Result<PathBuf> {
+        let output = tools::command(Tool::Bb)
+            .args([
+                "write_vk",
+                "-b",
+                &bytecode_path.display().to_string(),
+                "-o",
+                &output_dir.display().to_string(),
+                "--oracle_hash",
+                "keccak",
+            ])
+            .output()
+            .with_context(|| format!(
+                "failed to run `bb write_vk` for bytecode {} -- is barretenberg (bb) installed?",
+                bytecode_path.display()
+            ))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let hint = toolchain_mismatch_hint(bytecode_path, &stderr)
+                .map(|hint| format!("\n{hint}"))
+                .unwrap_or_default();
+            bail!(
+                "bb write_vk failed for bytecode {} (output dir: {}):\n{stderr}{hint}",
+                bytecode_path.display(),
+                output_dir.display()
+            );
+        }
+
+        let vk_path = output_dir.join("vk");
+        if !vk_path.exists() {
+            bail!(
+                "verification key not found at {} after running bb write_vk on {}",
+                vk_path.display(),
+                bytecode_path.display()
+            );
+        }
+
+        Ok(vk_path)
+    }
+
+    /// Run `bb write_solidity_verifier` to generate a Solidity verifier contract from a verification key.
+    fn write_solidity_verifier(&self, vk_path: &Path, output_path: &Path) -> Result<()> {
+        let output = tools::command(Tool::Bb)
+            .args([
+                "write_solidity_verifier",
+                "-k",
+                &vk_path.display().to_string(),
+                "-o",
+                &output_path.display().to_string(),
+            ])
+            .output()
+            .with_context(|| format!(
+                "failed to run `bb write_solidity_verifier` for vk {} -- is barretenberg (bb) installed?",
+                vk_path.display()
+            ))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "bb write_solidity_verifier failed for vk {} (output: {}):\n{stderr}",
+                vk_path.display(),
+                output_path.display()
+            );
+        }
+
+        if !output_path.exists() {
+            bail!(
+                "Solidity verifier not found at {} after running bb write_solidity_verifier on {}",
+                output_path.display(),
+                vk_path.display()
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Run `bb prove` with `--oracle_hash keccak`, matching the Solidity verifier.
+    fn prove(&self, bytecode_path: &Path, witness_path: &Path, output_dir: &Path) -> Result<ProofFiles> {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("failed to create {}", output_dir.display()))?;
+        let output = tools::command(Tool::Bb)
+            .args([
+                "prove",
+                "-b",
+                &bytecode_path.display().to_string(),
+                "-w",
+                &witness_path.display().to_string(),
+                "-o",
+                &output_dir.display().to_string(),
+                "--oracle_hash",
+                "keccak",
+            ])
+            .output()
+            .with_context(|| format!(
+                "failed to run `bb prove` for bytecode {} -- is barretenberg (bb) installed?",
+                bytecode_path.display()
+            ))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "bb prove failed for bytecode {} and witness {}:\n{stderr}",
+                bytecode_path.display(),
+                witness_path.display()
+            );
+        }
+
+        let files = ProofFiles {
+            proof: output_dir.join("proof"),
+            public_inputs: output_dir.join("public_inputs"),
+        };
+        if !files.proof.exists() {
+            bail!(
+                "proof not found at {} after running bb prove on {}",
+                files.proof.display(),
+                bytecode_path.display()
+            );
+        }
+
+        Ok(files)
+    }
+
+    /// Run `bb verify` with `--oracle_hash keccak`, as the proof was generated.
+    fn verify(&self, vk_path: &Path, proof: &ProofFiles) -> Result<()> {
+        let output = tools::command(Tool::Bb)
+            .args([
+                "verify",
+                "-k",
+                &vk_path.display().to_string(),
+                "-p",
+                &proof.proof.display().to_string(),
+                "-i",
+                &proof.public_inputs.display().to_string(),
+                "--oracle_hash",
+                "keccak",
+            ])
+            .output()
+            .with_context(|| format!(
+                "failed to run `bb verify` for proof {} -- is barretenberg (bb) installed?",
+                proof.proof.display()
+            ))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            bail!(
+                "bb verify rejected proof {} with vk {}:\n{stderr}",
+                proof.proof.display(),
+                vk_path.display()
+            );
+        }
+
+        Ok(())
     }
+}
+
+/// Name of the constant in bb's generated HonkVerifier that commits to the verification key.
+const VK_HASH_CONSTANT: &str = "VK_HASH";
+
+/// Extract the `VK_HASH` constant from a generated Solidity verifier, if present.
+fn embedded_vk_hash(verifier_source: &str) -> Option<FixedBytes<32>> {
+    let start = verifier_source.find(VK_HASH_CONSTANT)?;
+    let rest = &verifier_source[start..];
+    let hex_start = rest.find("0x")?;
+    let hex: String = rest[hex_start + 2..]
+        .chars()
+        .take_while(|c| c.is_ascii_hexdigit())
+        .collect();
+    format!("0x{hex:0>64}").parse().ok()
+}
+
+/// Check that the generated Solidity verifier embeds the hash of the verification key at
+/// `vk_path`, so a stale Verifier.sol is never deployed against a regenerated vk.
+///
+/// The expected hash is read from the `vk_hash` file bb writes next to the vk.  bb's
+/// hash is not a plain digest of the vk file, so when `vk_hash` is absent, or the
+/// verifier embeds no `VK_HASH` constant (older bb versions), the check is skipped with
+/// a warning.
+pub fn check_verifier_vk_hash(
+    vk_path: &Path,
+    verifier_path: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let verifier_source = std::fs::read_to_string(verifier_path)
+        .with_context(|| format!("failed to read {}", verifier_path.display()))?;
 
-    let vk_path = output_dir.join("vk");
-    if !vk_path.exists() {
+    let Some(embedded) = embedded_vk_hash(&verifier_source) else {
+        progress.info(&format!(
+            "{} no {VK_HASH_CONSTANT} constant in {}, skipping vk consistency check",
+            style::warning("warning:"),
+            verifier_path.display()
+        ));
+        return Ok(());
+    };
+
+    let vk_hash_path = vk_path.with_file_name("vk_hash");
+    if !vk_hash_path.exists() {
+        progress.info(&format!(
+            "{} bb wrote no {} next to the vk, skipping vk consistency check",
+            style::warning("warning:"),
+            vk_hash_path.display()
+        ));
+        return Ok(());
+    }
+    let bytes = std::fs::read(&vk_hash_path)
+        .with_context(|| format!("failed to read {}", vk_hash_path.display()))?;
+    let expected = FixedBytes::<32>::try_from(bytes.as_slice())
+        .with_context(|| format!("unexpected vk_hash length in {}", vk_hash_path.display()))?;
+
+    if embedded != expected {
         bail!(
-            "verification key not found at {} after running bb write_vk on {}",
-            vk_path.display(),
-            bytecode_path.display()
+            "verification key mismatch: {} embeds {VK_HASH_CONSTANT} {embedded} but {} records {expected} -- regenerate the verifier from the current vk",
+            verifier_path.display(),
+            vk_hash_path.display()
         );
     }
 
-    Ok(vk_path)
+    Ok(())
 }
 
-/// Run `bb write_solidity_verifier` to generate a Solidity verifier contract from a verification key.
-pub fn write_solidity_verifier(vk_path: &Path, output_path: &Path) -> Result<()> {
-    let output = Command::new("bb")
-        .args([
-            "write_solidity_verifier",
-            "-k",
-            &vk_path.display().to_string(),
-            "-o",
-            &output_path.display().to_string(),
-        ])
+/// Run `bb gates` on compiled ACIR bytecode and return the circuit size (gate count).
+pub fn gate_count(bytecode_path: &Path) -> Result<u64> {
+    let output = tools::command(Tool::Bb)
+        .args(["gates", "-b", &bytecode_path.display().to_string()])
         .output()
         .with_context(|| format!(
-            "failed to run `bb write_solidity_verifier` for vk {} -- is barretenberg (bb) installed?",
-            vk_path.display()
+            "failed to run `bb gates` for bytecode {} -- is barretenberg (bb) installed?",
+            bytecode_path.display()
         ))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         bail!(
-            "bb write_solidity_verifier failed for vk {} (output: {}):\n{stderr}",
-            vk_path.display(),
-            output_path.display()
+            "bb gates failed for bytecode {}:\n{stderr}",
+            bytecode_path.display()
         );
     }
 
-    if !output_path.exists() {
-        bail!(
-            "Solidity verifier not found at {} after running bb write_solidity_verifier on {}",
-            output_path.display(),
-            vk_path.display()
-        );
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout)
+        .context("failed to parse `bb gates` output")?;
+    report
+        .pointer("/functions/0/circuit_size")
+        .and_then(|v| v.as_u64())
+        .context("no functions[0].circuit_size in `bb gates` output")
+}
+
+alloy::sol! {
+    function verify(bytes calldata _proof, bytes32[] calldata _publicInputs) external returns (bool);
+}
+
+/// What submitting a proof to the generated verifier costs in calldata.
+#[derive(Debug, serde::Serialize)]
+pub struct ProofCalldata {
+    pub proof_bytes: usize,
+    pub public_inputs: usize,
+    /// Length of the ABI-encoded `verify(bytes,bytes32[])` call.
+    pub calldata_bytes: usize,
+    /// Intrinsic calldata gas under EIP-2028 (16 per non-zero byte, 4 per zero byte),
+    /// paid on top of the verifier's execution gas.
+    pub calldata_gas: u64,
+}
+
+/// The ABI-encoded `verify(bytes,bytes32[])` call for a `bb prove` proof, with public
+/// inputs read from bb's `public_inputs` file (32 bytes per field element).
+pub fn verify_calldata(proof_path: &Path, public_inputs_path: Option<&Path>) -> Result<Bytes> {
+    let proof = std::fs::read(proof_path)
+        .with_context(|| format!("failed to read proof {}", proof_path.display()))?;
+    let public_inputs = match public_inputs_path {
+        Some(path) => {
+            let bytes = std::fs::read(path)
+                .with_context(|| format!("failed to read public inputs {}", path.display()))?;
+            if bytes.len() % 32 != 0 {
+                bail!(
+                    "public inputs file {} is {} bytes, not a whole number of 32-byte fields",
+                    path.display(),
+                    bytes.len()
+                );
+            }
+            bytes.chunks(32).map(FixedBytes::<32>::from_slice).collect()
+        }
+        None => Vec::new(),
+    };
+
+    Ok(verifyCall {
+        _proof: Bytes::from(proof),
+        _publicInputs: public_inputs,
     }
+    .abi_encode()
+    .into())
+}
 
-    Ok(())
+/// Size and calldata gas of the `verify` call for a `bb prove` proof (see
+/// [`verify_calldata`]).
+pub fn proof_calldata_size(proof_path: &Path, public_inputs_path: Option<&Path>) -> Result<ProofCalldata> {
+    let calldata = verify_calldata(proof_path, public_inputs_path)?;
+    let call = verifyCall::abi_decode(&calldata).context("failed to decode verify calldata")?;
+    let calldata_gas = calldata
+        .iter()
+        .map(|&b| if b == 0 { 4 } else { 16 })
+        .sum();
+
+    Ok(ProofCalldata {
+        proof_bytes: call._proof.len(),
+        public_inputs: call._publicInputs.len(),
+        calldata_bytes: calldata.len(),
+        calldata_gas,
+    })
+}
+
+/// Circuit size above which verifying a keccak Honk proof on-chain gets impractically
+/// expensive for a given chain: its `gate_limit` in `--chains-file`, if any, or else a
+/// built-in default.  L1s are tuned conservatively; unknown chains get no limit.
+fn practical_gate_limit(chain_id: u64, gate_limits: &HashMap<u64, u64>) -> Option<u64> {
+    if let Some(&limit) = gate_limits.get(&chain_id) {
+        return Some(limit);
+    }
+    match chain_id {
+        // Ethereum mainnet and Sepolia
+        1 | 11155111 => Some(1 << 18),
+        // Rollups: Base, Arbitrum, Optimism (mainnets and testnets)
+        8453 | 84532 | 42161 | 421614 | 10 | 11155420 => Some(1 << 22),
+        // Polygon PoS
+        137 => Some(1 << 21),
+        _ => None,
+    }
+}
+
+/// Warn (advisory only) when the circuit's gate count makes verification impractical on
+/// the target chain.  Failures to count gates are reported and otherwise ignored.
+pub fn warn_if_impractical(
+    bytecode_path: &Path,
+    chain_id: u64,
+    network: &str,
+    indent: &str,
+    gate_limits: &HashMap<u64, u64>,
+    progress: &dyn ProgressReporter,
+) {
+    let Some(limit) = practical_gate_limit(chain_id, gate_limits) else {
+        return;
+    };
+    match gate_count(bytecode_path) {
+        Ok(gates) if gates > limit => progress.info(&format!(
+            "{indent}{} circuit has {gates} gates, above the practical limit of {limit} for {network} -- verification gas may be prohibitive; consider an L2",
+            style::warning("warning:")
+        )),
+        Ok(_) => {}
+        Err(e) => progress.info(&format!(
+            "{indent}{} could not determine gate count: {e:#}",
+            style::warning("warning:")
+        )),
+    }
 }
@@ -0,0 +1,56 @@
+use alloy::primitives::{Address, Bytes, FixedBytes};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::tools::{self, Tool};
+
+/// The fields of `cast send --json` output the CLI reads.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SendReceipt {
+    transaction_hash: FixedBytes<32>,
+    status: String,
+}
+
+/// Address of the Ledger's first account, from `cast wallet address --ledger`.
+pub fn ledger_address() -> Result<Address> {
+    let output = tools::command(Tool::Cast)
+        .args(["wallet", "address", "--ledger"])
+        .output()
+        .context("failed to run `cast wallet address --ledger` -- is foundry installed?")?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("cast wallet address --ledger failed -- is the Ledger connected and the Ethereum app open?\n{stderr}");
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout
+        .trim()
+        .parse()
+        .with_context(|| format!("cast wallet address printed an invalid address: {}", stdout.trim()))
+}
+
+/// Send `calldata` to `to` with `cast send --ledger`, returning the transaction hash
+/// once it is mined.  The transaction is confirmed on the device; cast estimates its
+/// gas and fees and picks the nonce.
+pub fn send_with_ledger(rpc_url: &str, to: Address, calldata: &Bytes, what: &str) -> Result<FixedBytes<32>> {
+    let output = tools::command(Tool::Cast)
+        .args(["send", "--ledger", "--json", "--rpc-url", rpc_url])
+        .arg(to.to_string())
+        .arg(calldata.to_string())
+        .output()
+        .with_context(|| format!("failed to run `cast send` for the {what} -- is foundry installed?"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("cast send --ledger failed for the {what}:\n{stderr}");
+    }
+    let receipt: SendReceipt = serde_json::from_slice(&output.stdout).with_context(|| {
+        format!(
+            "unexpected `cast send --json` output for the {what}: {}",
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })?;
+    if receipt.status != "0x1" && receipt.status != "1" {
+        bail!("{what} {} reverted", receipt.transaction_hash);
+    }
+    Ok(receipt.transaction_hash)
+}
@@ -0,0 +1,133 @@
+use alloy::primitives::{B256, keccak256};
+use alloy::providers::Provider;
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::eth;
+use crate::eth::DeployArgs;
+use crate::forge;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::retry;
+use crate::style;
+use crate::timings::Timings;
+
+#[derive(Debug, Serialize)]
+pub struct AuditData {
+    pub compliance_definition: String,
+    pub chain_id: u64,
+    pub deployed_code_hash: String,
+    pub reference_code_hash: String,
+    /// `artifact` when compared against the local build, `pinned` for `--reference-hash`.
+    pub reference: String,
+    pub matches: bool,
+}
+
+/// Hash runtime code with its metadata tail removed, so builds of the same contract
+/// compare equal even when the compiler's metadata hash (source paths, comments,
+/// compiler settings that do not affect codegen) differs.  ComplianceDefinition has
+/// no immutables, so the rest of the code is identical across deployments.
+fn code_fingerprint(code: &[u8]) -> B256 {
+    keccak256(&code[..code.len() - forge::metadata_len(code)])
+}
+
+/// Compare the runtime code at a ComplianceDefinition address against the locally
+/// built artifact, or against a pinned `--reference-hash`.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    address: &str,
+    rpc_url: &str,
+    chain_id: Option<u64>,
+    contract_dir: &Path,
+    reference_hash: Option<B256>,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    deploy: &DeployArgs,
+) -> Result<()> {
+    let (address_arg, prefixed_chain) = eth::AddressArg::parse(address)
+        .with_context(|| format!("invalid compliance definition address: {address}"))?;
+    let expected_chain_id = eth::merge_chain_id(chain_id, prefixed_chain)?;
+
+    let provider = eth::create_read_provider(rpc_url)?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    let cd_addr = address_arg.resolve(&provider, progress).await?;
+    if let Some(name) = address_arg.ens_name() {
+        progress.info(&format!("resolved {name} -> {cd_addr}"));
+    }
+
+    let mut timings = Timings::default();
+
+    let reference = match reference_hash {
+        Some(hash) => hash,
+        None => {
+            let artifact = forge::artifact_path(
+                contract_dir,
+                "ComplianceDefinition.sol",
+                "ComplianceDefinition",
+            );
+            if deploy.skip_build {
+                forge::check_prebuilt(&artifact, contract_dir)?;
+            } else {
+                progress.info("compiling contracts...");
+                progress.step_started("forge_build");
+                let started = Instant::now();
+                forge::build(contract_dir, deploy.optimizer_runs)?;
+                timings.record("forge_build", started);
+                progress.step_finished("forge_build");
+            }
+            code_fingerprint(&forge::deployed_bytecode(&artifact)?)
+        }
+    };
+
+    progress.info(&format!("fetching code at {cd_addr}..."));
+    let deployed = retry::retry_async("code lookup", progress, || async {
+        provider
+            .get_code_at(cd_addr)
+            .await
+            .with_context(|| format!("failed to fetch code at {cd_addr}"))
+    })
+    .await?;
+    if deployed.is_empty() {
+        bail!("no contract deployed at {cd_addr} on chain {chain_id}");
+    }
+    let deployed_hash = code_fingerprint(&deployed);
+    let matches = deployed_hash == reference;
+
+    receipts.print_value(
+        "compliance_definition",
+        &eth::format_chain_address(chain_id, cd_addr),
+    );
+    receipts.print_value("deployed_code_hash", &deployed_hash);
+    receipts.print_value("reference_code_hash", &reference);
+    receipts.print_value("match", &matches);
+
+    let data = AuditData {
+        compliance_definition: cd_addr.to_string(),
+        chain_id,
+        deployed_code_hash: deployed_hash.to_string(),
+        reference_code_hash: reference.to_string(),
+        reference: if reference_hash.is_some() { "pinned" } else { "artifact" }.to_string(),
+        matches,
+    };
+
+    if verbose {
+        timings.print();
+    }
+
+    Receipt::new("audit", data)
+        .with_timings(timings)
+        .write(receipts)?;
+
+    if !matches {
+        bail!(
+            "{cd_addr} does not match the reference ComplianceDefinition -- its runtime code differs beyond the metadata hash"
+        );
+    }
+    progress.info(&style::success(format!(
+        "{cd_addr} matches the reference ComplianceDefinition"
+    )));
+    Ok(())
+}
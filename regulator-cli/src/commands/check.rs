@@ -0,0 +1,135 @@
+use alloy::primitives::{Address, B256, Bytes};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::eth;
+use crate::eth::ComplianceCheck;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::style;
+
+#[derive(Debug, Serialize)]
+pub struct CheckData {
+    pub compliance_definition: String,
+    pub chain_id: u64,
+    pub account: String,
+    /// Block the check ran at: the `--block` number, or `latest`.
+    pub block: String,
+    pub compliant: bool,
+    /// Why `verify` reverted, when it did.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    /// Whether `--public-inputs` matched the inputs the definition checks the proof
+    /// against; absent without `--public-inputs`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_inputs_match: Option<bool>,
+}
+
+/// Warn about each public input in the `bb prove` file at `path` that differs from what
+/// the definition will pass to its verifier: the account, then the active merkle root.
+/// Returns whether they all match.
+fn check_public_inputs(path: &Path, account: Address, merkle_root: B256, progress: &dyn ProgressReporter) -> Result<bool> {
+    let bytes = std::fs::read(path).with_context(|| format!("failed to read public inputs {}", path.display()))?;
+    if bytes.len() % 32 != 0 {
+        bail!(
+            "public inputs file {} is {} bytes, not a whole number of 32-byte fields",
+            path.display(),
+            bytes.len()
+        );
+    }
+    let proven: Vec<B256> = bytes.chunks(32).map(B256::from_slice).collect();
+    let expected = [("account", account.into_word()), ("merkle root", merkle_root)];
+    if proven.len() != expected.len() {
+        progress.info(&format!(
+            "{} the proof has {} public inputs, but the definition checks it against {} (account, merkle root)",
+            style::warning("warning:"),
+            proven.len(),
+            expected.len()
+        ));
+        return Ok(false);
+    }
+    let mut matches = true;
+    for ((name, want), got) in expected.iter().zip(&proven) {
+        if want != got {
+            progress.info(&format!(
+                "{} the proof's {name} input is {got}, but the definition checks {want}",
+                style::warning("warning:")
+            ));
+            matches = false;
+        }
+    }
+    Ok(matches)
+}
+
+/// Ask a ComplianceDefinition whether `account` is compliant with the `bb prove` proof
+/// in `proof`, by calling its `verify(bytes)` from `account` at `block` (latest when
+/// `None`).  With `public_inputs`, the proof's public inputs are first compared with
+/// the ones the definition supplies, so a proof made for another account or merkle
+/// root is explained rather than only rejected.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    compliance_definition: &str,
+    account: Address,
+    proof: &Path,
+    public_inputs: Option<&Path>,
+    rpc_url: &str,
+    chain_id: Option<u64>,
+    block: Option<u64>,
+    receipts: &ReceiptOutput,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let (address_arg, prefixed_chain) = eth::AddressArg::parse(compliance_definition)
+        .with_context(|| format!("invalid compliance definition address: {compliance_definition}"))?;
+    let expected_chain_id = eth::merge_chain_id(chain_id, prefixed_chain)?;
+
+    let provider = eth::create_read_provider(rpc_url)?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    let cd_addr = address_arg.resolve(&provider, progress).await?;
+    if let Some(name) = address_arg.ens_name() {
+        progress.info(&format!("resolved {name} -> {cd_addr}"));
+    }
+
+    let proof = std::fs::read(proof).with_context(|| format!("failed to read proof {}", proof.display()))?;
+
+    let public_inputs_match = match public_inputs {
+        Some(path) => {
+            let merkle_root = eth::active_merkle_root(&provider, cd_addr, block).await?;
+            Some(check_public_inputs(path, account, merkle_root, progress)?)
+        }
+        None => None,
+    };
+
+    let block_label = block.map_or_else(|| "latest".to_string(), |n| n.to_string());
+    progress.info(&format!("calling verify() on {cd_addr} as {account} at block {block_label}..."));
+    let (compliant, revert_reason) =
+        match eth::check_compliance(&provider, cd_addr, account, Bytes::from(proof), block).await? {
+            ComplianceCheck::Returned(compliant) => (compliant, None),
+            ComplianceCheck::Reverted(reason) => (false, Some(reason)),
+        };
+
+    receipts.print_value("compliance_definition", &eth::format_chain_address(chain_id, cd_addr));
+    receipts.print_value("compliant", &compliant);
+
+    let data = CheckData {
+        compliance_definition: cd_addr.to_string(),
+        chain_id,
+        account: account.to_string(),
+        block: block_label.clone(),
+        compliant,
+        revert_reason: revert_reason.clone(),
+        public_inputs_match,
+    };
+    Receipt::new("check", data).write(receipts)?;
+
+    if !compliant {
+        match revert_reason {
+            Some(reason) => bail!("{account} is not compliant with {cd_addr} at block {block_label}: verify() reverted: {reason}"),
+            None => bail!("{account} is not compliant with {cd_addr} at block {block_label}: verify() returned false"),
+        }
+    }
+    progress.info(&style::success(format!(
+        "{account} is compliant with {cd_addr} at block {block_label}"
+    )));
+    Ok(())
+}
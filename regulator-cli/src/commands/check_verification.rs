@@ -0,0 +1,68 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::time::Instant;
+
+use crate::eth;
+use crate::etherscan;
+use crate::etherscan::VerifyArgs;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::timings::Timings;
+
+#[derive(Debug, Serialize)]
+pub struct CheckVerificationData {
+    pub guid: String,
+    pub contract_address: String,
+    pub chain_id: u64,
+    pub verification_status: String,
+}
+
+pub async fn run(
+    guid: &str,
+    chain_id: Option<u64>,
+    address: &str,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    verify: &VerifyArgs,
+) -> Result<()> {
+    let (contract_addr, prefixed_chain) = eth::parse_chain_address(address)
+        .with_context(|| format!("invalid contract address: {address}"))?;
+    let chain_id = eth::merge_chain_id(chain_id, prefixed_chain)?
+        .context("either --chain-id or a chain-prefixed --address must be provided")?;
+
+    let mut timings = Timings::default();
+    progress.step_started("verify");
+    let started = Instant::now();
+    let verification = etherscan::check_verification(
+        chain_id,
+        guid,
+        &contract_addr.to_string(),
+        verify,
+        "",
+        progress,
+    )
+    .await?;
+    timings.record("verify", started);
+    progress.step_finished("verify");
+
+    receipts.print_value("contract_address", &contract_addr);
+    receipts.print_value("chain_id", &chain_id);
+    receipts.print_value("verification", &verification);
+
+    let data = CheckVerificationData {
+        guid: guid.to_string(),
+        contract_address: contract_addr.to_string(),
+        chain_id,
+        verification_status: verification.to_string(),
+    };
+
+    if verbose {
+        timings.print();
+    }
+
+    let receipt = Receipt::new("check-verification", data).with_timings(timings);
+    receipt.write(receipts)?;
+
+    etherscan::enforce_verification(verify, &contract_addr.to_string(), &verification)
+}
@@ -0,0 +1,219 @@
+use alloy::hex;
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::bb;
+use crate::bb::ProvingBackend;
+use crate::nargo;
+use crate::nargo::NargoArgs;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::timings::Timings;
+
+/// File name of the `--compress-artifacts` bundle in `--out-dir`.
+pub const ARTIFACTS_ARCHIVE: &str = "artifacts.tar.zst";
+
+#[derive(Debug, Serialize)]
+pub struct CompileData {
+    /// The project directory, or `stdin` for `--stdin-circuit`.
+    pub source: String,
+    /// The loose artifacts, absent with `--compress-artifacts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytecode_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vk_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifier_path: Option<String>,
+    /// The bundle written in place of the loose artifacts by `--compress-artifacts`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts_archive: Option<ArtifactArchive>,
+    pub backend_version: String,
+    /// Libraries the circuit depends on, with the versions Nargo.lock pins them to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<nargo::Dependency>,
+}
+
+/// Copy an artifact into `out_dir` under `name`, returning the new path.
+fn copy_artifact(from: &Path, out_dir: &Path, name: &str) -> Result<PathBuf> {
+    let dest = out_dir.join(name);
+    std::fs::copy(from, &dest)
+        .with_context(|| format!("failed to copy {} to {}", from.display(), dest.display()))?;
+    Ok(dest)
+}
+
+/// A zstd-compressed tarball of the compiled artifacts.
+#[derive(Debug, Serialize)]
+pub struct ArtifactArchive {
+    pub path: String,
+    /// SHA-256 of the archive file, so a copy can be checked against the receipt.
+    pub sha256: String,
+    /// Names of the files in the archive, in archive order.
+    pub files: Vec<String>,
+}
+
+/// Write `files` (name in the archive, file to read) into a zstd-compressed tarball at
+/// `dest`.  Entries get a fixed mode and modification time, so the same artifacts
+/// always produce the same archive and hash.
+fn write_archive(files: &[(&str, &Path)], dest: &Path) -> Result<ArtifactArchive> {
+    let file = std::fs::File::create(dest).with_context(|| format!("failed to create {}", dest.display()))?;
+    let encoder = zstd::Encoder::new(file, zstd::DEFAULT_COMPRESSION_LEVEL)
+        .context("failed to start zstd compression")?;
+    let mut tarball = tar::Builder::new(encoder);
+    for (name, path) in files {
+        let contents = std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        tarball
+            .append_data(&mut header, name, contents.as_slice())
+            .with_context(|| format!("failed to add {name} to {}", dest.display()))?;
+    }
+    tarball
+        .into_inner()
+        .and_then(|encoder| encoder.finish())
+        .with_context(|| format!("failed to write {}", dest.display()))?;
+
+    let archived = std::fs::read(dest).with_context(|| format!("failed to read {}", dest.display()))?;
+    Ok(ArtifactArchive {
+        path: dest.display().to_string(),
+        sha256: hex::encode(Sha256::digest(&archived)),
+        files: files.iter().map(|(name, _)| name.to_string()).collect(),
+    })
+}
+
+/// Compile a circuit and write its bytecode, vk, and Solidity verifier to `out_dir`.
+///
+/// Without `circuit_dir`, the circuit's `main.nr` is read from stdin and compiled in a
+/// temporary project named `project_name`, which is removed afterwards.  With
+/// `compress`, the three files are written as one [`ARTIFACTS_ARCHIVE`] instead.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    circuit_dir: Option<PathBuf>,
+    project_name: &str,
+    out_dir: &Path,
+    compress: bool,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    nargo_args: &NargoArgs,
+    backend: &dyn ProvingBackend,
+) -> Result<()> {
+    // Holds the scaffolded project for --stdin-circuit until the artifacts are copied out.
+    let mut scratch = None;
+    let (project_dir, source) = match circuit_dir {
+        Some(dir) => {
+            let source = dir.display().to_string();
+            (dir, source)
+        }
+        None => {
+            let mut main_source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut main_source)
+                .context("failed to read circuit source from stdin")?;
+            if main_source.trim().is_empty() {
+                bail!("no circuit source on stdin");
+            }
+            let dir = tempfile::tempdir().context("failed to create temporary project")?;
+            let project_dir = dir.path().join(project_name);
+            nargo::init_project(&project_dir, project_name, &main_source)?;
+            scratch = Some(dir);
+            (project_dir, "stdin".to_string())
+        }
+    };
+    nargo::validate_manifest(&project_dir)?;
+    let dependencies = nargo::dependencies(&project_dir, progress)?;
+    for dependency in &dependencies {
+        progress.info(&format!("dependency {}", dependency.describe()));
+    }
+
+    let mut timings = Timings::default();
+
+    progress.info("compiling circuit...");
+    progress.step_started("nargo_compile");
+    let started = Instant::now();
+    let bytecode_path = nargo::compile(&project_dir, nargo_args, progress).await?;
+    timings.record("nargo_compile", started);
+    progress.step_finished("nargo_compile");
+
+    let target_dir = project_dir.join("target");
+    let backend_version = backend.version()?;
+    progress.info(&format!("generating verification key (bb {backend_version})..."));
+    progress.step_started("bb_write_vk");
+    let started = Instant::now();
+    let vk_path = backend.write_vk(&bytecode_path, &target_dir)?;
+    timings.record("bb_write_vk", started);
+    progress.step_finished("bb_write_vk");
+
+    progress.info("generating Solidity verifier...");
+    progress.step_started("bb_write_solidity_verifier");
+    let started = Instant::now();
+    let verifier_path = target_dir.join("Verifier.sol");
+    backend.write_solidity_verifier(&vk_path, &verifier_path)?;
+    bb::check_verifier_vk_hash(&vk_path, &verifier_path, progress)?;
+    timings.record("bb_write_solidity_verifier", started);
+    progress.step_finished("bb_write_solidity_verifier");
+
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("failed to create output directory {}", out_dir.display()))?;
+    let bytecode_name = bytecode_path
+        .file_name()
+        .context("compiled bytecode path has no file name")?
+        .to_string_lossy()
+        .into_owned();
+    let data = if compress {
+        let archive = write_archive(
+            &[
+                (bytecode_name.as_str(), bytecode_path.as_path()),
+                ("vk", vk_path.as_path()),
+                ("Verifier.sol", verifier_path.as_path()),
+            ],
+            &out_dir.join(ARTIFACTS_ARCHIVE),
+        )?;
+        drop(scratch);
+
+        receipts.print_value("artifacts_archive", &archive.path);
+        receipts.print_value("artifacts_sha256", &archive.sha256);
+
+        CompileData {
+            source,
+            bytecode_path: None,
+            vk_path: None,
+            verifier_path: None,
+            artifacts_archive: Some(archive),
+            backend_version,
+            dependencies,
+        }
+    } else {
+        let bytecode_path = copy_artifact(&bytecode_path, out_dir, &bytecode_name)?;
+        let vk_path = copy_artifact(&vk_path, out_dir, "vk")?;
+        let verifier_path = copy_artifact(&verifier_path, out_dir, "Verifier.sol")?;
+        drop(scratch);
+
+        receipts.print_value("bytecode_path", &bytecode_path.display());
+        receipts.print_value("vk_path", &vk_path.display());
+        receipts.print_value("verifier_path", &verifier_path.display());
+
+        CompileData {
+            source,
+            bytecode_path: Some(bytecode_path.display().to_string()),
+            vk_path: Some(vk_path.display().to_string()),
+            verifier_path: Some(verifier_path.display().to_string()),
+            artifacts_archive: None,
+            backend_version,
+            dependencies,
+        }
+    };
+
+    if verbose {
+        timings.print();
+    }
+
+    Receipt::new("compile", data)
+        .with_timings(timings)
+        .write(receipts)
+}
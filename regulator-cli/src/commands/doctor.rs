@@ -0,0 +1,124 @@
+use anyhow::{Context, Result, bail};
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+use crate::bb::ProvingBackend;
+use crate::nargo;
+use crate::nargo::NargoArgs;
+use crate::progress::TerminalProgress;
+use crate::style;
+use crate::tools::{self, Tool};
+
+/// Package name of the `--deep` self-test project.
+const SELF_TEST_NAME: &str = "doctor_self_test";
+
+/// The `--deep` self-test circuit: one private and one public input, so witness
+/// solving, proving, and public inputs are all exercised.
+const SELF_TEST_CIRCUIT: &str = "fn main(x: Field, y: pub Field) {\n    assert(x * x == y);\n}\n";
+
+/// Inputs the self-test circuit is proven with.
+const SELF_TEST_PROVER_TOML: &str = "x = \"3\"\ny = \"9\"\n";
+
+/// First line of `<tool> --version`.
+pub fn tool_version(tool: Tool, name: &str) -> Result<String> {
+    let output = tools::command(tool)
+        .arg("--version")
+        .output()
+        .with_context(|| format!("failed to run `{name} --version` -- is {name} installed?"))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("{name} --version failed:\n{stderr}");
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Ok(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
+
+/// Print one check's outcome, returning whether it passed.
+fn report(name: &str, result: &Result<String>) -> bool {
+    match result {
+        Ok(detail) => {
+            println!("  {name:<26} {} {detail}", style::success("ok"));
+            true
+        }
+        Err(e) => {
+            println!("  {name:<26} {} {e:#}", style::failure("FAILED"));
+            false
+        }
+    }
+}
+
+/// Check the external tools the CLI runs: that each is installed and answers
+/// `--version`, and the backend is new enough.  With `deep`, also run a built-in circuit
+/// through the whole pipeline -- compile, vk, Solidity verifier, witness, proof, and
+/// off-chain verification -- and report the first stage that fails.
+pub async fn run(deep: bool, nargo_args: &NargoArgs, backend: &dyn ProvingBackend) -> Result<()> {
+    println!("tools:");
+    let nargo_ok = report("nargo", &tool_version(Tool::Nargo, "nargo"));
+    let backend_ok = report("proving backend", &backend.version());
+    let forge_ok = report("forge", &tool_version(Tool::Forge, "forge"));
+
+    let mut failed: Vec<String> = [("nargo", nargo_ok), ("proving backend", backend_ok), ("forge", forge_ok)]
+        .iter()
+        .filter(|(_, ok)| !ok)
+        .map(|(name, _)| name.to_string())
+        .collect();
+
+    if deep {
+        println!("\nself-test (compile, prove, and verify a built-in circuit):");
+        if nargo_ok && backend_ok {
+            if let Err(stage) = self_test(nargo_args, backend).await {
+                failed.push(format!("self-test stage `{stage}`"));
+            }
+        } else {
+            println!("  skipped: nargo and the proving backend must both be usable");
+            failed.push("self-test (skipped)".to_string());
+        }
+    }
+
+    if !failed.is_empty() {
+        bail!("doctor found problems: {}", failed.join(", "));
+    }
+    println!("\nall checks passed");
+    Ok(())
+}
+
+/// Scaffold the self-test project, with its Prover.toml, in a temporary directory.
+fn create_project() -> Result<(TempDir, PathBuf)> {
+    let scratch = tempfile::tempdir().context("failed to create a temporary directory")?;
+    let project_dir = scratch.path().join(SELF_TEST_NAME);
+    nargo::init_project(&project_dir, SELF_TEST_NAME, SELF_TEST_CIRCUIT)?;
+    std::fs::write(project_dir.join("Prover.toml"), SELF_TEST_PROVER_TOML)
+        .with_context(|| format!("failed to write Prover.toml in {}", project_dir.display()))?;
+    Ok((scratch, project_dir))
+}
+
+/// Run the self-test in a temporary project, printing each stage as it completes.
+/// Returns the name of the stage that failed.
+async fn self_test(nargo_args: &NargoArgs, backend: &dyn ProvingBackend) -> std::result::Result<(), &'static str> {
+    let (_scratch, project_dir) = stage("create project", create_project())?;
+    let target = project_dir.join("target");
+
+    let bytecode = stage(
+        "compile",
+        nargo::compile(&project_dir, nargo_args, &TerminalProgress).await,
+    )?;
+    let vk = stage("write verification key", backend.write_vk(&bytecode, &target))?;
+    let verifier = target.join("Verifier.sol");
+    stage(
+        "write Solidity verifier",
+        backend
+            .write_solidity_verifier(&vk, &verifier)
+            .and_then(|()| crate::bb::check_verifier_vk_hash(&vk, &verifier, &TerminalProgress)),
+    )?;
+    let witness = stage("solve witness", nargo::execute(&project_dir))?;
+    let proof = stage("prove", backend.prove(&bytecode, &witness, &target.join("proof")))?;
+    stage("verify proof", backend.verify(&vk, &proof))?;
+    Ok(())
+}
+
+/// Report a self-test stage's outcome, turning a failure into the stage's name.
+fn stage<T>(name: &'static str, result: Result<T>) -> std::result::Result<T, &'static str> {
+    let detail = result.as_ref().map(|_| String::new()).map_err(|e| anyhow::anyhow!("{e:#}"));
+    report(name, &detail);
+    result.map_err(|_| name)
+}
@@ -0,0 +1,41 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+use crate::prover_toml;
+use crate::receipt::{Receipt, ReceiptOutput};
+
+#[derive(Debug, Serialize)]
+pub struct GenProverTomlData {
+    pub json: String,
+    pub abi: String,
+    pub output: String,
+    /// Circuit parameters written, in ABI order.
+    pub parameters: Vec<String>,
+}
+
+/// Write a `Prover.toml` for the circuit described by `abi` from the witness values in
+/// the `json` file.
+pub fn run(json: &Path, abi: &Path, output: &Path, receipts: &ReceiptOutput) -> Result<()> {
+    let parameters = prover_toml::read_parameters(abi)?;
+    let witness: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(json).with_context(|| format!("failed to read {}", json.display()))?,
+    )
+    .with_context(|| format!("failed to parse {}", json.display()))?;
+
+    let contents = prover_toml::from_json(&parameters, &witness)
+        .with_context(|| format!("{} does not match the circuit ABI in {}", json.display(), abi.display()))?;
+    std::fs::write(output, contents)
+        .with_context(|| format!("failed to write {}", output.display()))?;
+    eprintln!("wrote {}", output.display());
+
+    receipts.print_value("prover_toml", &output.display());
+
+    let data = GenProverTomlData {
+        json: json.display().to_string(),
+        abi: abi.display().to_string(),
+        output: output.display().to_string(),
+        parameters: parameters.into_iter().map(|p| p.name).collect(),
+    };
+    Receipt::new("gen-prover-toml", data).write(receipts)
+}
@@ -1,3 +1,16 @@
+pub mod audit;
+pub mod check;
+pub mod check_verification;
+pub mod compile;
+pub mod doctor;
+pub mod gen_prover_toml;
 pub mod new_compliance_definition;
+pub mod proof_calldata;
+pub mod register;
 pub mod update_circuit;
+pub mod serve;
+pub mod show_receipt;
 pub mod update_params;
+pub mod upload;
+pub mod verify;
+pub mod version;
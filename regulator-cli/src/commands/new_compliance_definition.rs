@@ -1,18 +1,30 @@
-use alloy::primitives::{Address, Bytes, FixedBytes, U256};
-use alloy::providers::Provider;
-use alloy::sol_types::SolValue;
+use alloy::dyn_abi::DynSolValue;
+use alloy::primitives::{Address, B256, FixedBytes, U256};
 use anyhow::{bail, Context, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::time::Instant;
 
 use crate::bb;
+use crate::bb::ProvingBackend;
 use crate::eth;
+use crate::eth::DeployArgs;
 use crate::etherscan;
 use crate::etherscan::VerifyArgs;
 use crate::forge;
 use crate::ipfs;
+use crate::ipfs::PinArgs;
+use crate::manifest::ComplianceManifest;
 use crate::nargo;
-use crate::receipt::Receipt;
+use crate::nargo::NargoArgs;
+use crate::progress::ProgressReporter;
+use crate::prover_toml;
+use crate::receipt;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::storage::{MetadataDocument, MetadataStore};
+use crate::style;
+use crate::timings::Timings;
 
 #[derive(Debug, Serialize)]
 pub struct NewComplianceDefinitionData {
@@ -20,6 +32,13 @@ pub struct NewComplianceDefinitionData {
     pub compliance_definition_address: String,
     pub compliance_definition_tx: String,
     pub compliance_definition_verification: String,
+    /// CREATE2 salt the definition was deployed with (`--compliance-definition-salt`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compliance_definition_salt: Option<String>,
+    /// ComplianceFactory that deployed and registered everything in one transaction
+    /// (`--factory`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub factory: Option<String>,
     pub regulator: String,
     pub chain_id: u64,
     pub rpc_url: String,
@@ -31,7 +50,86 @@ pub struct NewComplianceDefinitionData {
     pub verifier_tx: String,
     pub verifier_verification: String,
     pub update_tx: String,
+    /// `--on-chain-label`, and the `setLabel` transaction that set it (none if the
+    /// definition has no `setLabel` or with `--print-calldata`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_chain_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_tx: Option<String>,
     pub leaves_cid: String,
+    /// CIDs whose upload was skipped because the node already had them pinned
+    /// (`--ipfs-skip-existing`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_uploads: Vec<String>,
+    /// Pin state on the IPFS node of each CID uploaded in this run, keyed by CID.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pin_status: BTreeMap<String, ipfs::LocalPin>,
+    /// Optimizer runs recorded in the deployed verifier's artifact.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifier_optimizer_runs: Option<u64>,
+    /// keccak256 of the verifier's creation bytecode, as checked by
+    /// `--expected-verifier-bytecode-hash`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifier_bytecode_hash: Option<String>,
+    /// `bb --version` of the backend that generated the vk and verifier.
+    pub backend_version: String,
+    /// SHA-256 of the main source file's bytes as compiled.
+    pub source_sha256: String,
+    /// Libraries the circuit depends on, with the versions Nargo.lock pins them to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<nargo::Dependency>,
+    /// CID of the project directory (Nargo.toml and src/), with `--upload-project`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_cid: Option<String>,
+    /// Gas `verify()` on the deployed verifier took for a proof of the project's
+    /// Prover.toml, estimated with `--simulate-proof-gas`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_gas: Option<u64>,
+    /// Text of `--note` for this version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// CID of the metadata document (`{circuit_cid, created_at, note, public_inputs}`) written
+    /// for a `--note` or a `compliance.toml`, registered as the `metadataHash` in place
+    /// of `cid`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_cid: Option<String>,
+    /// Canonical CIDv1 (lowercase base32) of each CID in this receipt, keyed by the CID
+    /// as recorded, so receipts from nodes returning CIDv0 and CIDv1 compare equal.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cid_v1: BTreeMap<String, String>,
+}
+
+/// The deployment recorded in a `new-compliance-definition` receipt, used by
+/// `--from-receipt` on the follow-up commands.
+#[derive(Debug, Deserialize)]
+pub struct DefinitionRef {
+    pub compliance_definition_address: String,
+    pub chain_id: u64,
+    pub rpc_url: String,
+}
+
+impl DefinitionRef {
+    pub fn from_receipt(path: &Path) -> Result<Self> {
+        receipt::read_data(path, "new-compliance-definition")
+    }
+
+    /// Resolve the definition address, RPC URL, and chain ID for a follow-up command.
+    /// An explicit `rpc_url` overrides the receipt's; an explicit `chain_id` must match it.
+    pub fn resolve(self, rpc_url: Option<String>, chain_id: Option<u64>) -> Result<(String, String, u64)> {
+        if let Some(expected) = chain_id
+            && expected != self.chain_id
+        {
+            bail!(
+                "--chain-id {expected} does not match chain {} recorded in the receipt",
+                self.chain_id
+            );
+        }
+        Ok((
+            self.compliance_definition_address,
+            rpc_url.unwrap_or(self.rpc_url),
+            self.chain_id,
+        ))
+    }
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -40,141 +138,364 @@ pub async fn run(
     name: &str,
     verifier_output: Option<PathBuf>,
     ipfs_rpc_url: &str,
+    pin: &PinArgs,
+    store: &dyn MetadataStore,
     rpc_url: &str,
     private_key: &str,
     regulator: &str,
     contract_dir: &Path,
+    constructor_args_json: Option<PathBuf>,
+    compliance_definition_salt: Option<B256>,
+    factory: Option<Address>,
     merkle_root: &str,
     t_start: &str,
     t_end: &str,
     leaves_file: Option<PathBuf>,
     circuit_cid_override: Option<String>,
     leaves_cid_override: Option<String>,
-    receipts_dir: &Path,
+    note: Option<String>,
+    normalize_source: bool,
+    print_calldata: bool,
+    strict: bool,
+    expected_chain_id: Option<u64>,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    nargo_args: &NargoArgs,
+    backend: &dyn ProvingBackend,
+    deploy: &DeployArgs,
     verify: &VerifyArgs,
 ) -> Result<()> {
     if !path.is_dir() {
         bail!("not a directory: {}", path.display());
     }
-    if !path.join("Nargo.toml").exists() {
-        bail!(
-            "no Nargo.toml found in {} -- is this a Noir project?",
-            path.display()
-        );
+    nargo::validate_manifest(&path)?;
+    let dependencies = nargo::dependencies(&path, progress)?;
+    for dependency in &dependencies {
+        progress.info(&format!("  dependency {}", dependency.describe()));
+    }
+    let manifest = ComplianceManifest::load(&path)?;
+    if deploy.simulate_proof_gas {
+        nargo::require_prover_toml(&path)?;
+    }
+    if !deploy.allow_any_metadata {
+        ipfs::check_cid_flags(&[("--circuit-cid", circuit_cid_override.as_ref()), ("--leaves-cid", leaves_cid_override.as_ref())])?;
     }
 
-    let regulator_addr: Address = regulator
-        .parse()
+    let (regulator_arg, prefixed_chain) = eth::AddressArg::parse(regulator)
         .with_context(|| format!("invalid regulator address: {regulator}"))?;
+    let expected_chain_id = eth::merge_chain_id(expected_chain_id, prefixed_chain)?;
 
     let source_file = nargo::find_source_file(&path)?;
+    let mut timings = Timings::default();
 
     let provider = eth::create_provider(rpc_url, private_key)?;
-    let chain_id = provider
-        .get_chain_id()
-        .await
-        .context("failed to query chain ID from RPC")?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    let regulator_addr = regulator_arg.resolve(&provider, progress).await?;
+    if let Some(name) = regulator_arg.ens_name() {
+        progress.info(&format!("resolved {name} -> {regulator_addr}"));
+    }
     let network = etherscan::network_name(chain_id);
 
-    // ── ComplianceDefinition Contract ────────────────────────────────
-    eprintln!("\nComplianceDefinition Contract");
-    eprintln!("  Compiling contracts...");
-    forge::build(contract_dir)?;
+    let t_start_val: U256 = t_start
+        .parse()
+        .with_context(|| format!("invalid t_start (expected uint256): {t_start}"))?;
+    let t_end_val: U256 = t_end
+        .parse()
+        .with_context(|| format!("invalid t_end (expected uint256): {t_end}"))?;
+    let merkle_root_bytes: FixedBytes<32> = merkle_root
+        .parse()
+        .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
+    eth::check_window(&provider, t_start_val, t_end_val, strict, "", progress).await?;
+    if let Some(factory) = factory {
+        eth::check_factory(&provider, factory, progress).await?;
+    }
 
+    // ── ComplianceDefinition Contract ────────────────────────────────
+    progress.info("\nComplianceDefinition Contract");
     let cd_artifact =
         forge::artifact_path(contract_dir, "ComplianceDefinition.sol", "ComplianceDefinition");
-    let constructor_args = Bytes::from((regulator_addr, name.to_string()).abi_encode_params());
+    if deploy.skip_build {
+        progress.info("  Using prebuilt contracts (--skip-build)");
+        forge::check_prebuilt(&cd_artifact, contract_dir)?;
+    } else {
+        progress.info("  Compiling contracts...");
+        progress.step_started("forge_build");
+        let started = Instant::now();
+        forge::build(contract_dir, deploy.optimizer_runs)?;
+        timings.record("forge_build", started);
+        progress.step_finished("forge_build");
+    }
+    let constructor_args = match constructor_args_json {
+        Some(ref json_path) => eth::encode_constructor_args_json(&cd_artifact, json_path)?,
+        // A factory constructs the definition as its own regulator, then hands it over.
+        None => eth::ConstructorArgs::encode(&[
+            DynSolValue::Address(factory.unwrap_or(regulator_addr)),
+            DynSolValue::String(name.to_string()),
+        ]),
+    };
 
-    eprintln!("  Deploying to {network}...");
-    let cd_result =
-        eth::deploy_from_artifact(&provider, &cd_artifact, Some(constructor_args)).await?;
+    let cd_deployment = if let Some(factory) = factory {
+        progress.info(&format!("  Deployed with the verifier by factory {factory} (--factory)"));
+        None
+    } else {
+        progress.info(&format!("  Deploying to {network}..."));
+        progress.step_started("deploy");
+        let started = Instant::now();
+        let cd_result = match compliance_definition_salt {
+            Some(salt) => {
+                let init_code = eth::init_code(
+                    &provider,
+                    &cd_artifact,
+                    Some(&constructor_args),
+                    deploy,
+                    progress,
+                )
+                .await?;
+                progress.info(&format!(
+                    "  Predicted address: {} (CREATE2, salt {salt})",
+                    eth::create2_address(salt, &init_code)
+                ));
+                eth::deploy_create2(&provider, salt, init_code, deploy, progress).await?
+            }
+            None => {
+                eth::deploy_from_artifact(
+                    &provider,
+                    &cd_artifact,
+                    Some(&constructor_args),
+                    deploy,
+                    progress,
+                )
+                .await?
+            }
+        };
+        timings.record("deploy", started);
+        progress.step_finished("deploy");
 
-    let cd_verification = etherscan::verify_contract(
-        contract_dir,
-        &cd_artifact,
-        chain_id,
-        &cd_result.deployed_to.to_string(),
-        "src/ComplianceDefinition.sol:ComplianceDefinition",
-        Some(&alloy::hex::encode((regulator_addr, name.to_string()).abi_encode_params())),
-        verify,
-        "  ",
-    )
-    .await?;
+        progress.step_started("verify");
+        let started = Instant::now();
+        let cd_verification = etherscan::verify_contract(
+            contract_dir,
+            &cd_artifact,
+            chain_id,
+            &cd_result.deployed_to.to_string(),
+            "src/ComplianceDefinition.sol:ComplianceDefinition",
+            Some(&constructor_args),
+            verify,
+            "  ",
+            progress,
+        )
+        .await?;
+        timings.record("verify", started);
+        progress.step_finished("verify");
 
-    eprintln!("  Address:      {}", cd_result.deployed_to);
-    eprintln!("  Transaction:  {}", cd_result.transaction_hash);
-    eprintln!("  Chain ID:     {chain_id}");
-    eprintln!("  Verification: {cd_verification}");
+        progress.info(&format!("  Address:      {}", cd_result.deployed_to));
+        progress.info(&format!("  Transaction:  {}", cd_result.transaction_hash));
+        progress.info(&format!("  Chain ID:     {chain_id}"));
+        progress.info(&format!("  Verification: {}", cd_verification.colored()));
+        Some((cd_result, cd_verification))
+    };
 
     // ── Noir Circuit (<source_file>) ─────────────────────────────────
-    eprintln!("\nNoir Circuit ({})", source_file.display());
-    eprintln!("  Validating...");
-    nargo::check(&path)
+    progress.info(&format!("\nNoir Circuit ({})", source_file.display()));
+    progress.info("  Compiling...");
+    progress.step_started("nargo_compile");
+    let started = Instant::now();
+    let compiled_source = nargo::CompiledSource::read(&source_file)?;
+    let bytecode_path = nargo::compile(&path, nargo_args, progress)
+        .await
         .with_context(|| format!("circuit validation failed for {}", path.display()))?;
-
-    eprintln!("  Compiling...");
-    let bytecode_path = nargo::compile(&path)?;
+    timings.record("nargo_compile", started);
+    progress.step_finished("nargo_compile");
+    if let Some(ref manifest) = manifest {
+        manifest.check(&prover_toml::read_parameters(&bytecode_path)?)?;
+    }
+    bb::warn_if_impractical(
+        &bytecode_path,
+        chain_id,
+        network,
+        "  ",
+        &deploy.gate_limits,
+        progress,
+    );
 
     let target_dir = path.join("target");
-    eprintln!("  Generating verification key...");
-    let vk_path = bb::write_vk(&bytecode_path, &target_dir)?;
+    let backend_version = backend.version()?;
+    progress.info(&format!("  Generating verification key (bb {backend_version})..."));
+    progress.step_started("bb_write_vk");
+    let started = Instant::now();
+    let vk_path = backend.write_vk(&bytecode_path, &target_dir)?;
+    timings.record("bb_write_vk", started);
+    progress.step_finished("bb_write_vk");
 
     let verifier_path = verifier_output.unwrap_or_else(|| target_dir.join("Verifier.sol"));
-    eprintln!("  Generating Solidity verifier...");
-    bb::write_solidity_verifier(&vk_path, &verifier_path)?;
+    progress.info("  Generating Solidity verifier...");
+    progress.step_started("bb_write_solidity_verifier");
+    let started = Instant::now();
+    backend.write_solidity_verifier(&vk_path, &verifier_path)?;
+    timings.record("bb_write_solidity_verifier", started);
+    progress.step_finished("bb_write_solidity_verifier");
+
+    let proof = if deploy.simulate_proof_gas {
+        progress.info("  Proving with Prover.toml (--simulate-proof-gas)...");
+        progress.step_started("bb_prove");
+        let started = Instant::now();
+        let witness_path = nargo::execute(&path)?;
+        let proof = backend.prove(&bytecode_path, &witness_path, &target_dir.join("proof"))?;
+        timings.record("bb_prove", started);
+        progress.step_finished("bb_prove");
+        Some(proof)
+    } else {
+        None
+    };
 
     // ── IPFS Upload (or skip if --circuit-cid given) ─────────────────
-    eprintln!("\nIPFS Upload");
+    progress.info("\nIPFS Upload");
+    let mut skipped_uploads = Vec::new();
+    let mut pin_status = BTreeMap::new();
+    progress.step_started("ipfs_upload");
+    let started = Instant::now();
     let (circuit_cid, circuit_ipfs_size) = if let Some(cid) = circuit_cid_override {
-        eprintln!("  Using pre-pinned circuit CID: {cid}");
+        progress.info(&format!("  Using pre-pinned circuit CID: {cid}"));
         (cid, String::new())
     } else {
-        eprintln!("  Uploading compiled circuit {}...", bytecode_path.display());
-        let ipfs_response = ipfs::add_file(ipfs_rpc_url, &bytecode_path)
+        let upload_path = if normalize_source {
+            progress.info("  Normalizing circuit source...");
+            nargo::write_normalized_artifact(&bytecode_path)?
+        } else {
+            bytecode_path.clone()
+        };
+        compiled_source.check_upload(&upload_path, normalize_source, progress)?;
+        progress.info(&format!("  Uploading compiled circuit {}...", upload_path.display()));
+        let stored = store
+            .store(&upload_path, &format!("{name} circuit"))
             .await
-            .with_context(|| {
-                format!("failed to upload compiled circuit to IPFS at {ipfs_rpc_url}")
-            })?;
-        eprintln!("  CID: {}", ipfs_response.hash);
-        (ipfs_response.hash, ipfs_response.size)
+            .context("failed to store compiled circuit")?;
+        progress.info(&format!("  CID: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        (stored.uri, stored.size)
     };
 
     // ── Leaves Upload (or skip if --leaves-cid given) ────────────────
     let leaves_cid = if let Some(cid) = leaves_cid_override {
-        eprintln!("  Using pre-pinned leaves CID: {cid}");
+        progress.info(&format!("  Using pre-pinned leaves CID: {cid}"));
         cid
     } else if let Some(ref leaves_path) = leaves_file {
-        eprintln!("  Uploading leaves file {}...", leaves_path.display());
-        let leaves_response = ipfs::add_file(ipfs_rpc_url, leaves_path)
+        progress.info(&format!("  Uploading leaves file {}...", leaves_path.display()));
+        let stored = store
+            .store(leaves_path, &format!("{name} leaves"))
             .await
-            .with_context(|| {
-                format!("failed to upload leaves file to IPFS at {ipfs_rpc_url}")
-            })?;
-        eprintln!("  Leaves CID: {}", leaves_response.hash);
-        leaves_response.hash
+            .context("failed to store leaves file")?;
+        progress.info(&format!("  Leaves CID: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        stored.uri
     } else {
         String::new()
     };
 
+    let project_cid = if pin.upload_project {
+        progress.info(&format!("  Uploading project directory {}...", path.display()));
+        let project = ipfs::add_directory(ipfs_rpc_url, &path, pin, progress).await?;
+        progress.info(&format!("  Project CID: {}", project.hash));
+        pin_status.insert(project.hash.clone(), project.pin);
+        ipfs::remote_pin_if_enabled(pin, &project.hash, &format!("{name} project"), progress)
+            .await?;
+        Some(project.hash)
+    } else {
+        None
+    };
+    let metadata_cid = if note.is_some() || manifest.is_some() {
+        progress.info("  Uploading metadata document...");
+        let stored = MetadataDocument::new(&circuit_cid, note.as_deref(), manifest.as_ref())
+            .store(store)
+            .await?;
+        progress.info(&format!("  Metadata CID: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        Some(stored.uri)
+    } else {
+        None
+    };
+    let metadata_hash = metadata_cid.clone().unwrap_or_else(|| circuit_cid.clone());
+    timings.record("ipfs_upload", started);
+    progress.step_finished("ipfs_upload");
+
     // ── HonkVerifier Contract ────────────────────────────────────────
-    eprintln!("\nHonkVerifier Contract");
-    let deploy_verifier_path = contract_dir.join("src/Verifier.sol");
-    std::fs::copy(&verifier_path, &deploy_verifier_path).with_context(|| {
-        format!(
-            "failed to copy Verifier.sol to {}",
-            deploy_verifier_path.display()
-        )
-    })?;
+    progress.info("\nHonkVerifier Contract");
+    progress.info("  Checking verifier against verification key...");
+    bb::check_verifier_vk_hash(&vk_path, &verifier_path, progress)?;
 
-    eprintln!("  Compiling...");
-    forge::build(contract_dir)?;
+    let staged_verifier =
+        forge::StagedSource::copy(&verifier_path, contract_dir.join("src/Verifier.sol"))?;
 
     let verifier_artifact = forge::artifact_path(contract_dir, "Verifier.sol", "HonkVerifier");
+    if deploy.skip_build {
+        forge::check_prebuilt(&verifier_artifact, contract_dir)?;
+        forge::check_artifact_source(
+            &verifier_artifact,
+            "src/Verifier.sol",
+            &verifier_path,
+            progress,
+        )?;
+    } else {
+        let step = deploy.build_tool.step();
+        progress.info("  Compiling...");
+        progress.step_started(step);
+        let started = Instant::now();
+        deploy.build_tool.build_verifier(contract_dir, deploy.optimizer_runs)?;
+        timings.record(step, started);
+        progress.step_finished(step);
+    }
+    let verifier_bytecode_hash =
+        forge::check_bytecode_hash(&verifier_artifact, deploy.expected_verifier_hash)?;
+    progress.info(&format!("  Bytecode hash: {verifier_bytecode_hash}"));
 
-    eprintln!("  Deploying to {network}...");
-    let verifier_result =
-        eth::deploy_from_artifact(&provider, &verifier_artifact, None).await?;
+    progress.info(&format!("  Deploying to {network}..."));
+    progress.step_started("deploy");
+    let started = Instant::now();
+    let (verifier_result, bundle) = match factory {
+        Some(factory) => {
+            progress.info(&format!(
+                "  Deploying verifier and ComplianceDefinition through factory {factory}..."
+            ));
+            let init_code =
+                eth::init_code(&provider, &verifier_artifact, None, deploy, progress).await?;
+            let bundle = eth::deploy_via_factory(
+                &provider,
+                factory,
+                init_code,
+                regulator_addr,
+                name,
+                eth::FactoryVersion {
+                    merkle_root: merkle_root_bytes,
+                    t_start: t_start_val,
+                    t_end: t_end_val,
+                    metadata_hash: metadata_hash.clone(),
+                    leaves_hash: leaves_cid.clone(),
+                },
+                deploy,
+                progress,
+            )
+            .await?;
+            let verifier_result = eth::DeployOutput {
+                deployed_to: bundle.verifier,
+                transaction_hash: bundle.transaction_hash,
+            };
+            (verifier_result, Some(bundle))
+        }
+        None => (
+            eth::deploy_from_artifact(&provider, &verifier_artifact, None, deploy, progress).await?,
+            None,
+        ),
+    };
+    timings.record("deploy", started);
+    progress.step_finished("deploy");
+    if deploy.verify_interface {
+        eth::check_verifier_interface(&provider, verifier_result.deployed_to, progress).await?;
+        progress.info("  Verifier implements verify(bytes,bytes32[]) (--verify-interface)");
+    }
 
+    progress.step_started("verify");
+    let started = Instant::now();
     let verifier_verification = etherscan::verify_contract(
         contract_dir,
         &verifier_artifact,
@@ -184,58 +505,163 @@ pub async fn run(
         None,
         verify,
         "  ",
+        progress,
     )
     .await;
+    timings.record("verify", started);
+    progress.step_finished("verify");
 
-    let _ = std::fs::remove_file(&deploy_verifier_path);
+    drop(staged_verifier);
     let verifier_verification = verifier_verification?;
 
-    eprintln!("  Address:      {}", verifier_result.deployed_to);
-    eprintln!("  Transaction:  {}", verifier_result.transaction_hash);
-    eprintln!("  Verification: {verifier_verification}");
+    progress.info(&format!("  Address:      {}", verifier_result.deployed_to));
+    progress.info(&format!("  Transaction:  {}", verifier_result.transaction_hash));
+    progress.info(&format!("  Verification: {}", verifier_verification.colored()));
+
+    let verify_gas = match proof {
+        Some(ref proof) => {
+            match eth::estimate_verify_gas(&provider, verifier_result.deployed_to, proof).await {
+                Ok(gas) => {
+                    progress.info(&format!("  verify() gas: {gas}"));
+                    Some(gas)
+                }
+                Err(e) => {
+                    progress.info(&format!(
+                        "  {} could not measure verify() gas: {e:#}",
+                        style::warning("warning:")
+                    ));
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    let (cd_result, cd_verification) = match (cd_deployment, &bundle) {
+        (Some(deployed), _) => deployed,
+        (None, Some(bundle)) => {
+            progress.info("\nComplianceDefinition Contract");
+            progress.step_started("verify");
+            let started = Instant::now();
+            let cd_verification = etherscan::verify_contract(
+                contract_dir,
+                &cd_artifact,
+                chain_id,
+                &bundle.definition.to_string(),
+                "src/ComplianceDefinition.sol:ComplianceDefinition",
+                Some(&constructor_args),
+                verify,
+                "  ",
+                progress,
+            )
+            .await?;
+            timings.record("verify", started);
+            progress.step_finished("verify");
+
+            progress.info(&format!("  Address:      {}", bundle.definition));
+            progress.info(&format!("  Transaction:  {}", bundle.transaction_hash));
+            progress.info(&format!("  Chain ID:     {chain_id}"));
+            progress.info(&format!("  Verification: {}", cd_verification.colored()));
+            let cd_result = eth::DeployOutput {
+                deployed_to: bundle.definition,
+                transaction_hash: bundle.transaction_hash,
+            };
+            (cd_result, cd_verification)
+        }
+        (None, None) => unreachable!("the definition is deployed directly or by the factory"),
+    };
 
     // ── Compliance Registration ──────────────────────────────────────
-    eprintln!("\nCompliance Registration");
+    progress.info("\nCompliance Registration");
     let cid = &circuit_cid;
     let cd_addr = cd_result.deployed_to;
-    let merkle_root_bytes: FixedBytes<32> = merkle_root
-        .parse()
-        .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
-    let t_start_val: U256 = t_start
-        .parse()
-        .with_context(|| format!("invalid t_start (expected uint256): {t_start}"))?;
-    let t_end_val: U256 = t_end
-        .parse()
-        .with_context(|| format!("invalid t_end (expected uint256): {t_end}"))?;
 
-    eprintln!("  Registering verifier on {cd_addr}...");
-    let update_tx_hash = eth::call_update_circuit(
-        &provider,
-        cd_addr,
-        verifier_result.deployed_to,
-        merkle_root_bytes,
-        t_start_val,
-        t_end_val,
-        cid.to_string(),
-        leaves_cid.clone(),
-    )
-    .await?;
-    eprintln!("  Transaction:  {update_tx_hash}");
+    let (update_tx_hash, label_tx_hash) = if let Some(ref bundle) = bundle {
+        progress.info(&format!("  Registered on {cd_addr} by the factory transaction"));
+        let label_tx = eth::label_version(&provider, cd_addr, deploy, progress)
+            .await
+            .with_context(|| format!("version registered on {cd_addr}, but labelling it failed"))?;
+        (bundle.transaction_hash.to_string(), label_tx.map(|hash| hash.to_string()))
+    } else if print_calldata {
+        let calldata = eth::update_circuit_calldata(
+            eth::UpdateCircuitArgs {
+                verifier: verifier_result.deployed_to,
+                merkle_root: merkle_root_bytes,
+                t_start: t_start_val,
+                t_end: t_end_val,
+                metadata_hash: metadata_hash.clone(),
+                leaves_hash: leaves_cid.clone(),
+            },
+            deploy,
+        )?;
+        progress.info("  --print-calldata set, not broadcasting updateCircuit");
+        receipts.print_value("update_to", &cd_addr);
+        receipts.print_value("update_calldata", &calldata);
+        if let Some(ref label) = deploy.on_chain_label {
+            receipts.print_value("label_calldata", &eth::set_label_calldata(label));
+        }
+        (String::new(), None)
+    } else {
+        progress.info(&format!("  Registering verifier on {cd_addr}..."));
+        progress.step_started("register");
+        let started = Instant::now();
+        let tx_hash = eth::call_update_circuit(
+            &provider,
+            cd_addr,
+            eth::UpdateCircuitArgs {
+                verifier: verifier_result.deployed_to,
+                merkle_root: merkle_root_bytes,
+                t_start: t_start_val,
+                t_end: t_end_val,
+                metadata_hash: metadata_hash.clone(),
+                leaves_hash: leaves_cid.clone(),
+            },
+            deploy,
+            progress,
+        )
+        .await?;
+        timings.record("register", started);
+        progress.step_finished("register");
+        progress.info(&format!("  Transaction:  {tx_hash}"));
+        let label_tx = eth::label_version(&provider, cd_addr, deploy, progress)
+            .await
+            .with_context(|| format!("version registered on {cd_addr} in {tx_hash}, but labelling it failed"))?;
+        if let Some(label_tx) = label_tx {
+            progress.info(&format!("  Label tx:     {label_tx}"));
+        }
+        (tx_hash.to_string(), label_tx.map(|hash| hash.to_string()))
+    };
 
     // ── Done ─────────────────────────────────────────────────────────
-    eprintln!();
-    println!("compliance_definition={cd_addr}");
-    println!("verifier_address={}", verifier_result.deployed_to);
-    println!("cid={cid}");
-    println!("merkle_root={merkle_root}");
-    println!("chain_id={chain_id}");
+    progress.info("");
+    receipts.print_value("compliance_definition", &eth::format_chain_address(chain_id, cd_addr));
+    receipts.print_value(
+        "verifier_address",
+        &eth::format_chain_address(chain_id, verifier_result.deployed_to),
+    );
+    receipts.print_value("cid", &cid);
+    receipts.print_value("merkle_root", &merkle_root);
+    receipts.print_value("chain_id", &chain_id);
+    if let Some(gas) = verify_gas {
+        receipts.print_value("verify_gas", &gas);
+    }
 
+    let cid_v1 = ipfs::cid_v1_map(
+        [Some(cid.as_str()), Some(leaves_cid.as_str()), metadata_cid.as_deref(), project_cid.as_deref()]
+            .into_iter()
+            .flatten(),
+    );
     let data = NewComplianceDefinitionData {
         name: name.to_string(),
         compliance_definition_address: cd_addr.to_string(),
         compliance_definition_tx: cd_result.transaction_hash.to_string(),
         compliance_definition_verification: cd_verification.to_string(),
-        regulator: regulator.to_string(),
+        compliance_definition_salt: compliance_definition_salt.map(|salt| salt.to_string()),
+        factory: factory.map(|factory| factory.to_string()),
+        regulator: match regulator_arg.ens_name() {
+            Some(_) => regulator_addr.to_string(),
+            None => regulator.to_string(),
+        },
         chain_id,
         rpc_url: rpc_url.to_string(),
         source_file: source_file.display().to_string(),
@@ -245,12 +671,31 @@ pub async fn run(
         verifier_address: verifier_result.deployed_to.to_string(),
         verifier_tx: verifier_result.transaction_hash.to_string(),
         verifier_verification: verifier_verification.to_string(),
-        update_tx: update_tx_hash.to_string(),
+        update_tx: update_tx_hash,
+        on_chain_label: deploy.on_chain_label.clone(),
+        label_tx: label_tx_hash,
         leaves_cid,
+        skipped_uploads,
+        pin_status,
+        verifier_optimizer_runs: forge::artifact_optimizer_runs(&verifier_artifact),
+        verifier_bytecode_hash: Some(verifier_bytecode_hash.to_string()),
+        backend_version,
+        source_sha256: compiled_source.sha256().to_string(),
+        dependencies,
+        project_cid,
+        verify_gas,
+        note,
+        cid_v1,
+        metadata_cid,
     };
 
-    let receipt = Receipt::new("new-compliance-definition", data);
-    receipt.write_to_dir(receipts_dir)?;
+    if verbose {
+        timings.print();
+    }
+
+    let receipt = Receipt::new("new-compliance-definition", data).with_timings(timings);
+    receipt.write(receipts)?;
 
-    Ok(())
+    etherscan::enforce_verification(verify, "the HonkVerifier", &verifier_verification)?;
+    etherscan::enforce_verification(verify, "the ComplianceDefinition", &cd_verification)
 }
@@ -0,0 +1,17 @@
+use anyhow::Result;
+use std::path::Path;
+
+use crate::bb;
+use crate::receipt::{Receipt, ReceiptOutput};
+
+/// Report the calldata size and gas of submitting a proof to the generated verifier.
+pub fn run(proof: &Path, public_inputs: Option<&Path>, receipts: &ReceiptOutput) -> Result<()> {
+    let size = bb::proof_calldata_size(proof, public_inputs)?;
+
+    receipts.print_value("proof_bytes", &size.proof_bytes);
+    receipts.print_value("public_inputs", &size.public_inputs);
+    receipts.print_value("calldata_bytes", &size.calldata_bytes);
+    receipts.print_value("calldata_gas", &size.calldata_gas);
+
+    Receipt::new("proof-calldata", size).write(receipts)
+}
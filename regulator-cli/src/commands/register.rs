@@ -0,0 +1,200 @@
+use alloy::primitives::{Address, FixedBytes, U256};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+use crate::cast;
+use crate::eth;
+use crate::eth::DeployArgs;
+use crate::ipfs;
+use crate::keys::Signer;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::style;
+use crate::timings::Timings;
+
+#[derive(Debug, Serialize)]
+pub struct RegisterData {
+    pub compliance_definition: String,
+    pub chain_id: u64,
+    pub verifier_address: String,
+    /// The `metadataHash` registered: the circuit CID, or the metadata document's.
+    pub cid: String,
+    pub leaves_cid: String,
+    pub merkle_root: String,
+    pub t_start: String,
+    pub t_end: String,
+    /// Empty with `--print-calldata`.
+    pub update_tx_hash: String,
+    /// `--on-chain-label`, and the `setLabel` transaction that set it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_chain_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_tx_hash: Option<String>,
+    /// The transactions were signed on a Ledger through `cast send` (`--ledger`).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub ledger: bool,
+    /// Canonical CIDv1 (lowercase base32) of each CID in this receipt, keyed by the CID
+    /// as recorded, so receipts from nodes returning CIDv0 and CIDv1 compare equal.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cid_v1: BTreeMap<String, String>,
+}
+
+/// [`eth::label_version`] for `--ledger`: simulated from the Ledger's address, then
+/// sent through `cast send --ledger`.
+async fn label_with_ledger(
+    provider: &impl alloy::providers::Provider,
+    rpc_url: &str,
+    cd_addr: Address,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<FixedBytes<32>>> {
+    let Some(ref label) = deploy.on_chain_label else {
+        return Ok(None);
+    };
+    if !eth::can_label(provider, cd_addr, cast::ledger_address()?, label, progress).await? {
+        return Ok(None);
+    }
+    progress.info("confirm the setLabel transaction on the Ledger...");
+    cast::send_with_ledger(rpc_url, cd_addr, &eth::set_label_calldata(label), "setLabel transaction").map(Some)
+}
+
+/// Register an already-deployed verifier (e.g. from `update-circuit
+/// --deploy-verifier-only`) as a new version of a ComplianceDefinition.  With a
+/// Ledger signer the transactions go through `cast send --ledger`.
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    compliance_definition: &str,
+    verifier: &str,
+    cid: &str,
+    leaves_cid: &str,
+    merkle_root: &str,
+    t_start: &str,
+    t_end: &str,
+    rpc_url: &str,
+    signer: &Signer,
+    print_calldata: bool,
+    strict: bool,
+    expected_chain_id: Option<u64>,
+    expect_regulator: Option<Address>,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    deploy: &DeployArgs,
+) -> Result<()> {
+    let leaves_cid_flag = (!leaves_cid.is_empty()).then(|| leaves_cid.to_string());
+    if !deploy.allow_any_metadata {
+        ipfs::check_cid_flags(&[("--circuit-cid", Some(&cid.to_string())), ("--leaves-cid", leaves_cid_flag.as_ref())])?;
+    }
+
+    let (cd_arg, cd_chain) = eth::AddressArg::parse(compliance_definition)
+        .with_context(|| format!("invalid compliance definition address: {compliance_definition}"))?;
+    let (verifier_addr, verifier_chain) = eth::parse_chain_address(verifier)
+        .with_context(|| format!("invalid verifier address: {verifier}"))?;
+    let expected_chain_id = eth::merge_chain_id(expected_chain_id, cd_chain)?;
+    let expected_chain_id = eth::merge_chain_id(expected_chain_id, verifier_chain)
+        .with_context(|| format!("verifier {verifier}"))?;
+    let merkle_root_bytes: FixedBytes<32> = merkle_root
+        .parse()
+        .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
+    let t_start_val: U256 = t_start
+        .parse()
+        .with_context(|| format!("invalid t_start (expected uint256): {t_start}"))?;
+    let t_end_val: U256 = t_end
+        .parse()
+        .with_context(|| format!("invalid t_end (expected uint256): {t_end}"))?;
+
+    let wallet = match signer {
+        Signer::PrivateKey(private_key) => Some(eth::create_provider(rpc_url, private_key)?),
+        Signer::Ledger => None,
+    };
+    let provider = eth::create_read_provider(rpc_url)?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    eth::check_window(&provider, t_start_val, t_end_val, strict, "", progress).await?;
+    let cd_addr = cd_arg.resolve(&provider, progress).await?;
+    if let Some(name) = cd_arg.ens_name() {
+        progress.info(&format!("resolved {name} -> {cd_addr}"));
+    }
+    if let Some(expected) = expect_regulator {
+        eth::check_regulator(&provider, cd_addr, expected, progress).await?;
+    }
+    // The verifier was deployed in another run, so make sure the address is one.
+    eth::check_verifier_interface(&provider, verifier_addr, progress).await?;
+
+    let args = eth::UpdateCircuitArgs {
+        verifier: verifier_addr,
+        merkle_root: merkle_root_bytes,
+        t_start: t_start_val,
+        t_end: t_end_val,
+        metadata_hash: cid.to_string(),
+        leaves_hash: leaves_cid.to_string(),
+    };
+    let mut timings = Timings::default();
+    let (update_tx_hash, label_tx_hash) = if print_calldata {
+        let calldata = eth::update_circuit_calldata(args, deploy)?;
+        progress.info(&format!("--print-calldata set, not broadcasting updateCircuit to {cd_addr}"));
+        receipts.print_value("update_to", &cd_addr);
+        receipts.print_value("update_calldata", &calldata);
+        if let Some(ref label) = deploy.on_chain_label {
+            receipts.print_value("label_calldata", &eth::set_label_calldata(label));
+        }
+        (String::new(), None)
+    } else {
+        progress.info(&format!("registering verifier {verifier_addr} on {cd_addr}..."));
+        progress.step_started("register");
+        let started = Instant::now();
+        let tx_hash = match wallet {
+            Some(ref wallet) => {
+                eth::call_update_circuit(wallet, cd_addr, args, deploy, progress).await?
+            }
+            None => {
+                progress.info("confirm the updateCircuit transaction on the Ledger...");
+                let calldata = eth::update_circuit_calldata(args, deploy)?;
+                cast::send_with_ledger(rpc_url, cd_addr, &calldata, "updateCircuit transaction")?
+            }
+        };
+        timings.record("register", started);
+        progress.step_finished("register");
+        progress.info(&style::success(format!("compliance version registered on {cd_addr}")));
+        receipts.print_value("update_tx_hash", &tx_hash);
+        let label_tx = match wallet {
+            Some(ref wallet) => eth::label_version(wallet, cd_addr, deploy, progress).await,
+            None => label_with_ledger(&provider, rpc_url, cd_addr, deploy, progress).await,
+        }
+        .with_context(|| format!("version registered on {cd_addr} in {tx_hash}, but labelling it failed"))?;
+        if let Some(label_tx) = label_tx {
+            receipts.print_value("label_tx_hash", &label_tx);
+        }
+        (tx_hash.to_string(), label_tx.map(|hash| hash.to_string()))
+    };
+
+    receipts.print_value("compliance_definition", &eth::format_chain_address(chain_id, cd_addr));
+    receipts.print_value("verifier_address", &eth::format_chain_address(chain_id, verifier_addr));
+    receipts.print_value("chain_id", &chain_id);
+
+    let data = RegisterData {
+        compliance_definition: cd_addr.to_string(),
+        chain_id,
+        verifier_address: verifier_addr.to_string(),
+        cid: cid.to_string(),
+        leaves_cid: leaves_cid.to_string(),
+        merkle_root: merkle_root.to_string(),
+        t_start: t_start.to_string(),
+        t_end: t_end.to_string(),
+        update_tx_hash,
+        on_chain_label: deploy.on_chain_label.clone(),
+        label_tx_hash,
+        ledger: matches!(signer, Signer::Ledger),
+        cid_v1: ipfs::cid_v1_map([cid, leaves_cid]),
+    };
+
+    if verbose {
+        timings.print();
+    }
+
+    let receipt = Receipt::new("register", data).with_timings(timings);
+    receipt.write(receipts)?;
+
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use alloy::primitives::{Address, B256, U256};
+use anyhow::{bail, Context, Result};
+use axum::extract::{DefaultBodyLimit, Multipart, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::bb::Backend;
+use crate::commands::update_circuit;
+use crate::eth;
+use crate::eth::DeployArgs;
+use crate::etherscan::VerifyArgs;
+use crate::ipfs::PinArgs;
+use crate::nargo::NargoArgs;
+use crate::progress::TerminalProgress;
+use crate::receipt::ReceiptOutput;
+use crate::redact;
+use crate::storage::StorageBackend;
+
+/// Largest accepted request body (circuit tarball + params).
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+
+/// Server-side settings shared by every request.  Keys and RPC endpoints are
+/// configured on the server and never accepted over HTTP.
+pub struct ServeConfig {
+    pub token: Option<String>,
+    pub ipfs_rpc_url: String,
+    pub rpc_url: String,
+    pub private_key: String,
+    pub contract_dir: PathBuf,
+    pub receipts_dir: PathBuf,
+    pub pin: PinArgs,
+    pub nargo: NargoArgs,
+    pub backend: Backend,
+    pub storage: StorageBackend,
+    pub deploy: DeployArgs,
+    pub verify: VerifyArgs,
+}
+
+struct AppState {
+    config: ServeConfig,
+    /// Deployments stage Verifier.sol into the shared Foundry project, so requests
+    /// are processed one at a time.
+    busy: Mutex<()>,
+}
+
+/// Deployment parameters for `POST /update-circuit`, mirroring the CLI flags.
+#[derive(Deserialize)]
+struct UpdateCircuitParams {
+    compliance_definition: Vec<String>,
+    merkle_root: Option<String>,
+    t_start: Option<String>,
+    t_end: Option<String>,
+    circuit_cid: Option<String>,
+    leaves_cid: Option<String>,
+    note: Option<String>,
+    chain_id: Option<u64>,
+    expect_regulator: Option<Address>,
+    #[serde(default)]
+    continue_on_error: bool,
+    #[serde(default)]
+    strict: bool,
+    #[serde(default)]
+    force: bool,
+}
+
+struct ApiError {
+    status: StatusCode,
+    message: String,
+    /// Receipt of a run that failed part-way, so the caller sees what was deployed.
+    receipt: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            receipt: None,
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let mut body = serde_json::json!({ "error": self.message });
+        if let Some(receipt) = self.receipt {
+            body["receipt"] = receipt;
+        }
+        (self.status, Json(body)).into_response()
+    }
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", redact::Redacted(&e)))
+    }
+}
+
+fn bad_request(msg: impl Into<String>) -> ApiError {
+    ApiError::new(StatusCode::BAD_REQUEST, msg)
+}
+
+/// Serve the publish flow over HTTP on `host:port` until the process is stopped.
+/// Requests deploy with the server's key, so binding anything but a loopback
+/// address requires a `--token`.
+pub async fn run(host: IpAddr, port: u16, config: ServeConfig) -> Result<()> {
+    if !host.is_loopback() && config.token.is_none() {
+        bail!(
+            "refusing to listen on {host} without --token: anyone who can reach the port could deploy with the server's key"
+        );
+    }
+    let state = Arc::new(AppState {
+        config,
+        busy: Mutex::new(()),
+    });
+
+    let app = Router::new()
+        .route("/health", get(|| async { "ok" }))
+        .route("/update-circuit", post(update_circuit_handler))
+        .layer(DefaultBodyLimit::max(MAX_BODY_BYTES))
+        .with_state(state);
+
+    let addr = SocketAddr::new(host, port);
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to bind {addr}"))?;
+    eprintln!("listening on http://{addr}");
+
+    axum::serve(listener, app).await.context("server error")
+}
+
+fn authorize(config: &ServeConfig, headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(ref token) = config.token else {
+        return Ok(());
+    };
+    let supplied = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !supplied.is_some_and(|supplied| tokens_match(supplied, token)) {
+        return Err(ApiError::new(StatusCode::UNAUTHORIZED, "invalid or missing bearer token"));
+    }
+    Ok(())
+}
+
+/// Compare a supplied bearer token with the configured one in constant time.  Both are
+/// hashed first, so neither the length nor the first differing byte shows in the timing.
+fn tokens_match(supplied: &str, token: &str) -> bool {
+    let (supplied, token) = (Sha256::digest(supplied), Sha256::digest(token));
+    supplied.iter().zip(token.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Check the request's addresses, merkle root, and window up front, so malformed input
+/// is reported as a 400 rather than as a failed deployment.
+fn validate_params(params: &UpdateCircuitParams) -> Result<(), ApiError> {
+    if params.compliance_definition.is_empty() {
+        return Err(bad_request("params.compliance_definition must not be empty"));
+    }
+    let mut chain_id = params.chain_id;
+    for cd in &params.compliance_definition {
+        let (_, prefixed_chain) = eth::AddressArg::parse(cd).map_err(|e| {
+            bad_request(format!("invalid compliance definition address {cd}: {e:#}"))
+        })?;
+        chain_id = eth::merge_chain_id(chain_id, prefixed_chain)
+            .map_err(|e| bad_request(format!("compliance definition {cd}: {e:#}")))?;
+    }
+    if let Some(ref merkle_root) = params.merkle_root {
+        merkle_root.parse::<B256>().map_err(|_| {
+            bad_request(format!("invalid merkle_root (expected bytes32): {merkle_root}"))
+        })?;
+    }
+    for (name, value) in [("t_start", &params.t_start), ("t_end", &params.t_end)] {
+        if let Some(value) = value {
+            value
+                .parse::<U256>()
+                .map_err(|_| bad_request(format!("invalid {name} (expected uint256): {value}")))?;
+        }
+    }
+    Ok(())
+}
+
+/// Unpack a gzipped tarball into `dest` and return the Noir project root: `dest` itself
+/// or its single top-level directory, whichever contains Nargo.toml.
+fn unpack_circuit(tarball: &[u8], dest: &Path) -> Result<PathBuf> {
+    let decoder = flate2::read::GzDecoder::new(tarball);
+    tar::Archive::new(decoder)
+        .unpack(dest)
+        .context("failed to unpack circuit tarball (expected .tar.gz)")?;
+
+    if dest.join("Nargo.toml").exists() {
+        return Ok(dest.to_path_buf());
+    }
+    let mut dirs = std::fs::read_dir(dest)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join("Nargo.toml").exists());
+    match (dirs.next(), dirs.next()) {
+        (Some(dir), None) => Ok(dir),
+        _ => bail!("circuit tarball must contain Nargo.toml at its root or in a single top-level directory"),
+    }
+}
+
+/// Read the single receipt a command wrote into `dir`.
+fn read_receipt(dir: &Path) -> Result<serde_json::Value> {
+    let path = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .find(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .context("command did not write a receipt")?;
+    let contents = std::fs::read(&path)
+        .with_context(|| format!("failed to read receipt {}", path.display()))?;
+    serde_json::from_slice(&contents).context("failed to parse receipt")
+}
+
+/// Keep a copy of a request's receipts alongside the CLI's own receipts.
+fn copy_receipts(from: &Path, to: &Path) -> Result<()> {
+    std::fs::create_dir_all(to)
+        .with_context(|| format!("failed to create receipts directory {}", to.display()))?;
+    for entry in std::fs::read_dir(from)?.filter_map(|e| e.ok()) {
+        let dest = to.join(entry.file_name());
+        std::fs::copy(entry.path(), &dest)
+            .with_context(|| format!("failed to write receipt {}", dest.display()))?;
+    }
+    Ok(())
+}
+
+async fn update_circuit_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let config = &state.config;
+    authorize(config, &headers)?;
+
+    let mut tarball = None;
+    let mut params = None;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| bad_request(format!("invalid multipart body: {e}")))?
+    {
+        match field.name() {
+            Some("circuit") => {
+                tarball = Some(field.bytes().await.map_err(|e| bad_request(e.to_string()))?)
+            }
+            Some("params") => {
+                let bytes = field.bytes().await.map_err(|e| bad_request(e.to_string()))?;
+                params = Some(
+                    serde_json::from_slice::<UpdateCircuitParams>(&bytes)
+                        .map_err(|e| bad_request(format!("invalid params: {e}")))?,
+                );
+            }
+            _ => {}
+        }
+    }
+    let tarball = tarball.ok_or_else(|| bad_request("missing `circuit` field"))?;
+    let params = params.ok_or_else(|| bad_request("missing `params` field"))?;
+    validate_params(&params)?;
+
+    let _guard = state.busy.lock().await;
+
+    let workdir = tempfile::tempdir().context("failed to create work directory")?;
+    let project_dir = unpack_circuit(&tarball, &workdir.path().join("circuit"))
+        .map_err(|e| bad_request(format!("{e:#}")))?;
+
+    let receipts_dir = workdir.path().join("receipts");
+    let receipts = ReceiptOutput {
+        dir: receipts_dir.clone(),
+        stdout: false,
+        deterministic: false,
+        env_out: None,
+    };
+
+    let outcome = update_circuit::run(
+        project_dir,
+        None,
+        &config.ipfs_rpc_url,
+        &config.pin,
+        config.storage.store(&config.ipfs_rpc_url, &config.pin, &TerminalProgress).as_ref(),
+        &config.rpc_url,
+        &config.private_key,
+        &params.compliance_definition,
+        params.continue_on_error,
+        &config.contract_dir,
+        params.merkle_root.as_deref().unwrap_or(crate::BYTES32_ZERO),
+        params.t_start.as_deref().unwrap_or("0"),
+        params.t_end.as_deref().unwrap_or(crate::UINT256_MAX),
+        None,
+        params.circuit_cid,
+        params.leaves_cid,
+        params.note,
+        false,
+        false,
+        false,
+        params.strict,
+        params.force,
+        params.chain_id,
+        params.expect_regulator,
+        &receipts,
+        false,
+        &TerminalProgress,
+        &config.nargo,
+        config.backend.get(),
+        &config.deploy,
+        &config.verify,
+    )
+    .await
+    .and_then(|data| data.ensure_registered());
+
+    // A run that failed part-way may still have deployed a verifier or registered some
+    // definitions, so its receipt is kept and returned before the error is.
+    let receipt = read_receipt(&receipts_dir);
+    let copied = copy_receipts(&receipts_dir, &config.receipts_dir);
+    if let Err(e) = outcome {
+        if let Err(copy_error) = copied {
+            eprintln!("warning: {:#}", redact::Redacted(&copy_error));
+        }
+        return Err(ApiError {
+            receipt: receipt.ok(),
+            ..e.into()
+        });
+    }
+    copied?;
+
+    Ok(Json(receipt?))
+}
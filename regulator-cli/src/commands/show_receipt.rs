@@ -0,0 +1,274 @@
+use anyhow::Result;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::eth;
+use crate::etherscan;
+use crate::receipt::Receipt;
+
+/// Default gateway for `ipfs/<cid>` links.
+pub const DEFAULT_IPFS_GATEWAY: &str = "https://ipfs.io";
+
+/// The fields of each command's `data`, in the order they are shown, with their
+/// labels.  Fields a receipt has beyond these (added in later versions) are shown
+/// after them under their own names.
+fn known_fields(command: &str) -> &'static [(&'static str, &'static str)] {
+    match command {
+        "new-compliance-definition" => &[
+            ("name", "name"),
+            ("compliance_definition_address", "definition"),
+            ("compliance_definition_tx", "definition tx"),
+            ("compliance_definition_verification", "definition verification"),
+            ("compliance_definition_salt", "definition salt"),
+            ("factory", "factory"),
+            ("regulator", "regulator"),
+            ("rpc_url", "RPC"),
+            ("source_file", "source"),
+            ("cid", "circuit CID"),
+            ("ipfs_size", "circuit size"),
+            ("merkle_root", "merkle root"),
+            ("leaves_cid", "leaves CID"),
+            ("note", "note"),
+            ("metadata_cid", "metadata CID"),
+            ("verifier_address", "verifier"),
+            ("verifier_tx", "verifier tx"),
+            ("verifier_verification", "verifier verification"),
+            ("update_tx", "updateCircuit tx"),
+            ("on_chain_label", "on-chain label"),
+            ("label_tx", "setLabel tx"),
+        ],
+        "update-circuit" => &[
+            ("project_dir", "project"),
+            ("cid", "circuit CID"),
+            ("ipfs_size", "circuit size"),
+            ("merkle_root", "merkle root"),
+            ("leaves_cid", "leaves CID"),
+            ("note", "note"),
+            ("metadata_cid", "metadata CID"),
+            ("verifier_address", "verifier"),
+            ("deploy_tx_hash", "verifier tx"),
+            ("verification_status", "verification"),
+            ("compliance_definition", "definition"),
+            ("update_tx_hash", "updateCircuit tx"),
+            ("on_chain_label", "on-chain label"),
+            ("registrations", "registrations"),
+            ("project_cid", "project CID"),
+            ("verify_gas", "verify() gas"),
+            ("bytecode_path", "bytecode"),
+            ("vk_path", "verification key"),
+            ("verifier_path", "verifier source"),
+            ("backend_version", "backend version"),
+            ("source_sha256", "source sha256"),
+        ],
+        "update-params" => &[
+            ("compliance_definition", "definition"),
+            ("merkle_root", "merkle root"),
+            ("leaves_file", "leaves file"),
+            ("leaves_cid", "leaves CID"),
+            ("update_tx_hash", "updateParams tx"),
+        ],
+        "register" => &[
+            ("compliance_definition", "definition"),
+            ("verifier_address", "verifier"),
+            ("cid", "metadata CID"),
+            ("leaves_cid", "leaves CID"),
+            ("merkle_root", "merkle root"),
+            ("t_start", "t_start"),
+            ("t_end", "t_end"),
+            ("update_tx_hash", "updateCircuit tx"),
+            ("on_chain_label", "on-chain label"),
+            ("label_tx_hash", "setLabel tx"),
+        ],
+        "upload" => &[
+            ("project_dir", "project"),
+            ("source_file", "source"),
+            ("ipfs_source", "uploaded"),
+            ("compiled_file", "compiled circuit"),
+            ("cid", "CID"),
+            ("ipfs_size", "size"),
+            ("source_sha256", "source sha256"),
+        ],
+        "verify" | "verification-submitted" | "check-verification" => &[
+            ("contract_address", "contract"),
+            ("contract_name", "contract name"),
+            ("guid", "submission GUID"),
+            ("verification_status", "verification"),
+        ],
+        "audit" => &[
+            ("compliance_definition", "definition"),
+            ("matches", "code matches"),
+            ("deployed_code_hash", "deployed code hash"),
+            ("reference_code_hash", "reference code hash"),
+            ("reference", "reference"),
+        ],
+        "check" => &[
+            ("compliance_definition", "definition"),
+            ("account", "account"),
+            ("block", "block"),
+            ("compliant", "compliant"),
+            ("revert_reason", "revert reason"),
+            ("public_inputs_match", "public inputs match"),
+        ],
+        _ => &[],
+    }
+}
+
+/// How a `data` field is linked, by the naming the receipts use throughout.
+enum Link {
+    Address,
+    Tx,
+    Cid,
+}
+
+fn link_kind(key: &str) -> Option<Link> {
+    match key {
+        "regulator" | "factory" | "compliance_definition" | "account" => Some(Link::Address),
+        "cid" | "skipped_uploads" => Some(Link::Cid),
+        _ if key.ends_with("_address") => Some(Link::Address),
+        _ if key.ends_with("_tx") || key.ends_with("_tx_hash") => Some(Link::Tx),
+        _ if key.ends_with("_cid") => Some(Link::Cid),
+        _ => None,
+    }
+}
+
+/// Renders values with explorer and gateway links.
+struct Linker<'a> {
+    chain_id: Option<u64>,
+    gateway: &'a str,
+}
+
+impl Linker<'_> {
+    fn link(&self, kind: &Link, value: &str) -> Option<String> {
+        match kind {
+            Link::Cid => Some(format!("{}/ipfs/{value}", self.gateway.trim_end_matches('/'))),
+            Link::Address => {
+                let (address, prefixed) = eth::parse_chain_address(value).ok()?;
+                etherscan::explorer_link(prefixed.or(self.chain_id)?, "address", &address.to_string())
+            }
+            Link::Tx => etherscan::explorer_link(self.chain_id?, "tx", value),
+        }
+    }
+}
+
+/// The chain a receipt's deployment is on: its `chain_id`, else `--chain-id`, else the
+/// EIP-3770 prefix of its definition address.
+fn receipt_chain(data: &Value, chain_id: Option<u64>) -> Option<u64> {
+    data.get("chain_id").and_then(Value::as_u64).or(chain_id).or_else(|| {
+        ["compliance_definition", "compliance_definition_address"]
+            .iter()
+            .filter_map(|key| data.get(*key)?.as_str())
+            .find_map(|address| eth::parse_chain_address(address).ok()?.1)
+    })
+}
+
+/// Print a receipt as a report: the command and when it ran, the network, each result
+/// with explorer and IPFS gateway links, and the step timings.
+pub fn run(path: &Path, chain_id: Option<u64>, gateway: &str) -> Result<()> {
+    let receipt = Receipt::read(path)?;
+    let data = &receipt.data;
+    let chain_id = receipt_chain(data, chain_id);
+    let linker = Linker { chain_id, gateway };
+
+    println!("{} receipt ({})", receipt.command, path.display());
+    println!("  written:  {}", receipt.timestamp);
+    match chain_id {
+        Some(id) => println!("  network:  {} (chain {id})", etherscan::network_name(id)),
+        None => println!("  network:  not recorded (pass --chain-id for explorer links)"),
+    }
+
+    let Some(fields) = data.as_object() else {
+        println!("\n  {data}");
+        return Ok(());
+    };
+    let known = known_fields(&receipt.command);
+    let mut rows: Vec<(&str, &str, &Value)> = known
+        .iter()
+        .filter_map(|(key, label)| Some((*key, *label, fields.get(*key)?)))
+        .collect();
+    rows.extend(
+        fields
+            .iter()
+            .filter(|(key, _)| key.as_str() != "chain_id" && !known.iter().any(|(k, _)| k == key))
+            .map(|(key, value)| (key.as_str(), key.as_str(), value)),
+    );
+
+    let width = rows.iter().map(|(_, label, _)| label.len()).max().unwrap_or(0) + 1;
+    println!();
+    for (key, label, value) in rows {
+        let label = format!("{label}:");
+        if key == "registrations" {
+            print_registrations(&label, value, &linker);
+            continue;
+        }
+        let lines = render(key, value, &linker);
+        for (i, line) in lines.iter().enumerate() {
+            let label = if i == 0 { label.as_str() } else { "" };
+            println!("  {label:<width$}  {line}");
+        }
+    }
+
+    if !receipt.timings_ms.is_empty() {
+        println!("\ntimings:");
+        let width = receipt.timings_ms.steps().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, ms) in receipt.timings_ms.steps() {
+            println!("  {name:<width$}  {ms:>8} ms");
+        }
+    }
+    Ok(())
+}
+
+/// A field's value as display lines, each link on its own line below what it links.
+fn render(key: &str, value: &Value, linker: &Linker) -> Vec<String> {
+    let kind = link_kind(key);
+    let mut lines = Vec::new();
+    let mut push = |text: String| {
+        let link = kind.as_ref().and_then(|kind| linker.link(kind, &text));
+        lines.push(text);
+        lines.extend(link.map(|link| format!("  {link}")));
+    };
+    match value {
+        Value::String(s) if s.is_empty() => return vec!["(none)".to_string()],
+        Value::Array(items) if items.is_empty() => return vec!["(none)".to_string()],
+        Value::String(s) => push(s.clone()),
+        Value::Array(items) => items.iter().for_each(|item| push(scalar(item))),
+        Value::Object(map) => map.iter().for_each(|(k, v)| push(format!("{k}: {}", scalar(v)))),
+        other => push(other.to_string()),
+    }
+    lines
+}
+
+fn scalar(value: &Value) -> String {
+    value.as_str().map_or_else(|| value.to_string(), str::to_string)
+}
+
+/// `update-circuit`'s per-definition outcomes, one block per definition.
+fn print_registrations(label: &str, value: &Value, linker: &Linker) {
+    let registrations = value.as_array().map(Vec::as_slice).unwrap_or_default();
+    println!("  {label}");
+    for registration in registrations {
+        let field = |key| registration.get(key).and_then(Value::as_str).unwrap_or_default();
+        let definition = field("compliance_definition");
+        let outcome = if let Some(error) = registration.get("error").and_then(Value::as_str) {
+            format!("failed: {error}")
+        } else if registration.get("already_registered").and_then(Value::as_bool) == Some(true) {
+            "already registered".to_string()
+        } else if field("update_tx_hash").is_empty() {
+            "calldata printed, not sent".to_string()
+        } else {
+            format!("registered in {}", field("update_tx_hash"))
+        };
+        println!("    {definition}  {outcome}");
+        if !field("label_tx_hash").is_empty() {
+            println!("      labelled in {}", field("label_tx_hash"));
+        }
+        for (kind, id) in [
+            (Link::Address, definition),
+            (Link::Tx, field("update_tx_hash")),
+            (Link::Tx, field("label_tx_hash")),
+        ] {
+            if let Some(link) = (!id.is_empty()).then(|| linker.link(&kind, id)).flatten() {
+                println!("      {link}");
+            }
+        }
+    }
+}
@@ -1,17 +1,31 @@
 use alloy::primitives::{Address, FixedBytes, U256};
-use alloy::providers::Provider;
 use anyhow::{bail, Context, Result};
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::Semaphore;
 
 use crate::bb;
+use crate::bb::ProvingBackend;
 use crate::eth;
+use crate::eth::DeployArgs;
 use crate::etherscan;
-use crate::etherscan::VerifyArgs;
+use crate::etherscan::{VerificationOutcome, VerifyArgs};
 use crate::forge;
 use crate::ipfs;
+use crate::ipfs::PinArgs;
+use crate::manifest::{self, ComplianceManifest};
 use crate::nargo;
-use crate::receipt::Receipt;
+use crate::nargo::NargoArgs;
+use crate::progress::ProgressReporter;
+use crate::prover_toml;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::redact;
+use crate::storage::{MetadataDocument, MetadataStore};
+use crate::style;
+use crate::timings::Timings;
 
 #[derive(Debug, Serialize)]
 pub struct UpdateCircuitData {
@@ -27,7 +41,149 @@ pub struct UpdateCircuitData {
     pub compliance_definition: String,
     pub update_tx_hash: String,
     pub verification_status: String,
+    /// The outcome `verification_status` records, for `--fail-on-verification-failure`.
+    #[serde(skip)]
+    verification: VerificationOutcome,
     pub leaves_cid: String,
+    /// One entry per `--compliance-definition`, in the order given.
+    pub registrations: Vec<Registration>,
+    /// CIDs whose upload was skipped because the node already had them pinned
+    /// (`--ipfs-skip-existing`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_uploads: Vec<String>,
+    /// Pin state on the IPFS node of each CID uploaded in this run, keyed by CID.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pin_status: BTreeMap<String, ipfs::LocalPin>,
+    /// Optimizer runs recorded in the deployed verifier's artifact.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifier_optimizer_runs: Option<u64>,
+    /// keccak256 of the verifier's creation bytecode, as checked by
+    /// `--expected-verifier-bytecode-hash`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verifier_bytecode_hash: Option<String>,
+    /// `bb --version` of the backend that generated the vk and verifier.
+    pub backend_version: String,
+    /// SHA-256 of the main source file's bytes as compiled.
+    pub source_sha256: String,
+    /// Libraries the circuit depends on, with the versions Nargo.lock pins them to.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<nargo::Dependency>,
+    /// CID of the project directory (Nargo.toml and src/), with `--upload-project`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_cid: Option<String>,
+    /// Gas `verify()` on the deployed verifier took for a proof of the project's
+    /// Prover.toml, estimated with `--simulate-proof-gas`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verify_gas: Option<u64>,
+    /// Text of `--note` for this version.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// CID of the metadata document (`{circuit_cid, created_at, note, public_inputs}`) written
+    /// for a `--note` or a `compliance.toml`, registered as the `metadataHash` in place
+    /// of `cid`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata_cid: Option<String>,
+    /// `--deploy-verifier-only`: the verifier was deployed and verified but not
+    /// registered; `register` does that later.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub deploy_verifier_only: bool,
+    /// `--on-chain-label`, set with `setLabel` after each registration.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_chain_label: Option<String>,
+    /// Canonical CIDv1 (lowercase base32) of each CID in this receipt, keyed by the CID
+    /// as recorded, so receipts from nodes returning CIDv0 and CIDv1 compare equal.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cid_v1: BTreeMap<String, String>,
+}
+
+impl UpdateCircuitData {
+    /// Fail if any registration failed (only possible with `--continue-on-error`; without
+    /// it the first failure aborts the run).
+    pub fn ensure_registered(&self) -> Result<()> {
+        let failed = self.registrations.iter().filter(|r| r.error.is_some()).count();
+        if failed > 0 {
+            bail!("{failed} of {} registrations failed", self.registrations.len());
+        }
+        Ok(())
+    }
+
+    /// Fail if the verifier's explorer verification failed and
+    /// `--fail-on-verification-failure` is set.
+    pub fn ensure_verified(&self, verify: &VerifyArgs) -> Result<()> {
+        etherscan::enforce_verification(verify, &format!("verifier {}", self.verifier_address), &self.verification)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Registration {
+    pub compliance_definition: String,
+    pub update_tx_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The definition's latest version already matched; no transaction was sent.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub already_registered: bool,
+    /// The `setLabel` transaction, with `--on-chain-label`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_tx_hash: Option<String>,
+}
+
+/// Progress of a registration task, reported back to [`run`].
+enum RegistrationEvent {
+    /// The task got its `--concurrency` permit and is sending `updateCircuit`.
+    Started(usize),
+    /// The registration's transaction hash and, if it went through, the `setLabel` outcome.
+    Finished(usize, Instant, Result<(FixedBytes<32>, Result<Option<FixedBytes<32>>>)>),
+    /// A message from the task, for [`ProgressReporter::info`].
+    Info(String),
+}
+
+/// Reports a registration task's messages back to [`run`] as [`RegistrationEvent::Info`],
+/// since the task can't borrow the caller's reporter.
+struct EventProgress(tokio::sync::mpsc::UnboundedSender<RegistrationEvent>);
+
+impl ProgressReporter for EventProgress {
+    fn step_started(&self, _step: &str) {}
+
+    fn step_finished(&self, _step: &str) {}
+
+    fn info(&self, msg: &str) {
+        let _ = self.0.send(RegistrationEvent::Info(msg.to_string()));
+    }
+}
+
+/// Whether a registered version's `metadataHash` describes this circuit: the bare
+/// circuit CID or, with a `--note` or a compliance.toml, a metadata document with the
+/// same circuit, note, and inputs.  The document is fetched from IPFS, since its
+/// `created_at` gives every upload a new CID; a fetch failure counts as a mismatch.
+async fn registered_metadata_matches(
+    ipfs_rpc_url: &str,
+    registered: &str,
+    circuit_cid: &str,
+    note: Option<&str>,
+    manifest: Option<&ComplianceManifest>,
+    progress: &dyn ProgressReporter,
+) -> bool {
+    if note.is_none() && manifest.is_none() {
+        return registered == circuit_cid;
+    }
+    if registered == circuit_cid {
+        return false;
+    }
+    let document = match ipfs::cat(ipfs_rpc_url, registered, progress).await {
+        Ok(bytes) => serde_json::from_slice::<serde_json::Value>(&bytes).ok(),
+        Err(e) => {
+            progress.info(&format!(
+                "{} {:#}; assuming the registered version differs",
+                style::warning("warning:"),
+                redact::Redacted(&e)
+            ));
+            None
+        }
+    };
+    document.is_some_and(|document| {
+        MetadataDocument::new(circuit_cid, note, manifest).matches(&document)
+    })
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -35,9 +191,12 @@ pub async fn run(
     project_dir: PathBuf,
     verifier_output: Option<PathBuf>,
     ipfs_rpc_url: &str,
+    pin: &PinArgs,
+    store: &dyn MetadataStore,
     rpc_url: &str,
     private_key: &str,
-    compliance_definition: &str,
+    compliance_definitions: &[String],
+    continue_on_error: bool,
     contract_dir: &Path,
     merkle_root: &str,
     t_start: &str,
@@ -45,100 +204,361 @@ pub async fn run(
     leaves_file: Option<PathBuf>,
     circuit_cid_override: Option<String>,
     leaves_cid_override: Option<String>,
-    receipts_dir: &Path,
+    note: Option<String>,
+    deploy_verifier_only: bool,
+    normalize_source: bool,
+    print_calldata: bool,
+    strict: bool,
+    force: bool,
+    expected_chain_id: Option<u64>,
+    expect_regulator: Option<Address>,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    nargo_args: &NargoArgs,
+    backend: &dyn ProvingBackend,
+    deploy: &DeployArgs,
     verify: &VerifyArgs,
-) -> Result<()> {
+) -> Result<UpdateCircuitData> {
+    let mut expected_chain_id = expected_chain_id;
+    let mut cd_args = Vec::with_capacity(compliance_definitions.len());
+    for cd in compliance_definitions {
+        let (arg, prefixed_chain) = eth::AddressArg::parse(cd)
+            .with_context(|| format!("invalid compliance definition address: {cd}"))?;
+        expected_chain_id = eth::merge_chain_id(expected_chain_id, prefixed_chain)
+            .with_context(|| format!("compliance definition {cd}"))?;
+        cd_args.push(arg);
+    }
+
     if !project_dir.is_dir() {
         bail!("not a directory: {}", project_dir.display());
     }
 
-    if !project_dir.join("Nargo.toml").exists() {
-        bail!(
-            "no Nargo.toml found in {} -- is this a Noir project?",
-            project_dir.display()
-        );
+    nargo::validate_manifest(&project_dir)?;
+    let dependencies = nargo::dependencies(&project_dir, progress)?;
+    for dependency in &dependencies {
+        progress.info(&format!("dependency {}", dependency.describe()));
+    }
+    let manifest = ComplianceManifest::load(&project_dir)?;
+    if deploy.simulate_proof_gas {
+        nargo::require_prover_toml(&project_dir)?;
+    }
+    if !deploy.allow_any_metadata {
+        ipfs::check_cid_flags(&[("--circuit-cid", circuit_cid_override.as_ref()), ("--leaves-cid", leaves_cid_override.as_ref())])?;
     }
 
-    // 1. Validate circuit
-    eprintln!("validating circuit...");
-    nargo::check(&project_dir)
-        .with_context(|| format!("circuit validation failed for {}", project_dir.display()))?;
-    eprintln!("circuit validated successfully");
+    let mut timings = Timings::default();
 
-    // 2. Compile the circuit
-    eprintln!("compiling circuit...");
-    let bytecode_path = nargo::compile(&project_dir)?;
-    eprintln!("circuit compiled successfully");
+    // 1. Compile the circuit.  A successful compile is the validation, so the circuit
+    //    checked is the one compiled.
+    progress.info("compiling circuit...");
+    progress.step_started("nargo_compile");
+    let started = Instant::now();
+    let compiled_source = nargo::CompiledSource::read(&nargo::find_source_file(&project_dir)?)?;
+    let bytecode_path = nargo::compile(&project_dir, nargo_args, progress)
+        .await
+        .with_context(|| format!("circuit validation failed for {}", project_dir.display()))?;
+    timings.record("nargo_compile", started);
+    progress.step_finished("nargo_compile");
+    if let Some(ref manifest) = manifest {
+        manifest.check(&prover_toml::read_parameters(&bytecode_path)?)?;
+    }
+    progress.info("circuit compiled successfully");
 
-    // 3. Generate verification key
+    // 2. Generate verification key
     let target_dir = project_dir.join("target");
-    eprintln!("generating verification key...");
-    let vk_path = bb::write_vk(&bytecode_path, &target_dir)?;
-    eprintln!("verification key generated");
+    let backend_version = backend.version()?;
+    progress.info(&format!("generating verification key (bb {backend_version})..."));
+    progress.step_started("bb_write_vk");
+    let started = Instant::now();
+    let vk_path = backend.write_vk(&bytecode_path, &target_dir)?;
+    timings.record("bb_write_vk", started);
+    progress.step_finished("bb_write_vk");
+    progress.info("verification key generated");
 
-    // 4. Generate Solidity verifier
+    // 3. Generate Solidity verifier
     let verifier_path = verifier_output.unwrap_or_else(|| target_dir.join("Verifier.sol"));
-    eprintln!("generating Solidity verifier...");
-    bb::write_solidity_verifier(&vk_path, &verifier_path)?;
-    eprintln!("Solidity verifier generated");
+    progress.info("generating Solidity verifier...");
+    progress.step_started("bb_write_solidity_verifier");
+    let started = Instant::now();
+    backend.write_solidity_verifier(&vk_path, &verifier_path)?;
+    timings.record("bb_write_solidity_verifier", started);
+    progress.step_finished("bb_write_solidity_verifier");
+    progress.info("Solidity verifier generated");
+
+    // 3b. Prove the circuit with its Prover.toml, to measure verify() gas once the
+    //     verifier is deployed (--simulate-proof-gas)
+    let proof = if deploy.simulate_proof_gas {
+        progress.info("proving with Prover.toml (--simulate-proof-gas)...");
+        progress.step_started("bb_prove");
+        let started = Instant::now();
+        let witness_path = nargo::execute(&project_dir)?;
+        let proof = backend.prove(&bytecode_path, &witness_path, &target_dir.join("proof"))?;
+        timings.record("bb_prove", started);
+        progress.step_finished("bb_prove");
+        Some(proof)
+    } else {
+        None
+    };
 
-    // 5. Upload compiled circuit to IPFS (or skip if --circuit-cid given)
+    // 4. Upload compiled circuit to IPFS (or skip if --circuit-cid given)
+    let mut skipped_uploads = Vec::new();
+    let mut pin_status = BTreeMap::new();
+    progress.step_started("ipfs_upload");
+    let started = Instant::now();
     let (circuit_cid, circuit_ipfs_size) = if let Some(cid) = circuit_cid_override {
-        eprintln!("using pre-pinned circuit CID: {cid}");
+        progress.info(&format!("using pre-pinned circuit CID: {cid}"));
         (cid, String::new())
     } else {
-        eprintln!("uploading compiled circuit {}...", bytecode_path.display());
-        let response = ipfs::add_file(ipfs_rpc_url, &bytecode_path)
+        let upload_path = if normalize_source {
+            progress.info("normalizing circuit source...");
+            nargo::write_normalized_artifact(&bytecode_path)?
+        } else {
+            bytecode_path.clone()
+        };
+        compiled_source.check_upload(&upload_path, normalize_source, progress)?;
+        progress.info(&format!("uploading compiled circuit {}...", upload_path.display()));
+        let stored = store
+            .store(&upload_path, "compliance circuit")
             .await
-            .with_context(|| {
-                format!("failed to upload compiled circuit to IPFS at {ipfs_rpc_url}")
-            })?;
-        eprintln!("uploaded to IPFS: {}", response.hash);
-        (response.hash, response.size)
+            .context("failed to store compiled circuit")?;
+        progress.info(&format!("uploaded to IPFS: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        (stored.uri, stored.size)
     };
 
-    // 5b. Upload leaves file (or skip if --leaves-cid given)
+    // 4b. Upload leaves file (or skip if --leaves-cid given)
     let leaves_cid = if let Some(cid) = leaves_cid_override {
-        eprintln!("using pre-pinned leaves CID: {cid}");
+        progress.info(&format!("using pre-pinned leaves CID: {cid}"));
         cid
     } else if let Some(ref leaves_path) = leaves_file {
-        eprintln!("uploading leaves file {}...", leaves_path.display());
-        let leaves_response = ipfs::add_file(ipfs_rpc_url, leaves_path)
+        progress.info(&format!("uploading leaves file {}...", leaves_path.display()));
+        let stored = store
+            .store(leaves_path, "compliance leaves")
             .await
-            .with_context(|| {
-                format!("failed to upload leaves file to IPFS at {ipfs_rpc_url}")
-            })?;
-        eprintln!("leaves uploaded to IPFS: {}", leaves_response.hash);
-        leaves_response.hash
+            .context("failed to store leaves file")?;
+        progress.info(&format!("leaves uploaded to IPFS: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        stored.uri
     } else {
         String::new()
     };
 
-    // 6. Temporarily copy Verifier.sol into the Foundry project so forge can compile it
-    let deploy_verifier_path = contract_dir.join("src/Verifier.sol");
-    std::fs::copy(&verifier_path, &deploy_verifier_path).with_context(|| {
-        format!(
-            "failed to copy Verifier.sol to {}",
-            deploy_verifier_path.display()
-        )
-    })?;
-
-    // 7. Build the Foundry project with the new Verifier.sol
-    eprintln!("compiling verifier contract...");
-    forge::build(contract_dir)?;
-    eprintln!("verifier contract compiled");
-
-    // 8. Deploy the HonkVerifier contract
+    // 4c. Upload the project directory (with --upload-project)
+    let project_cid = if pin.upload_project {
+        progress.info(&format!("uploading project directory {}...", project_dir.display()));
+        let project = ipfs::add_directory(ipfs_rpc_url, &project_dir, pin, progress).await?;
+        progress.info(&format!("project uploaded to IPFS: {}", project.hash));
+        pin_status.insert(project.hash.clone(), project.pin);
+        ipfs::remote_pin_if_enabled(pin, &project.hash, "compliance project", progress).await?;
+        Some(project.hash)
+    } else {
+        None
+    };
+    timings.record("ipfs_upload", started);
+    progress.step_finished("ipfs_upload");
+
+    // 4d. Skip definitions whose latest version is already this exact circuit, params,
+    //     and window.  The verifier is generated from the circuit, so a matching
+    //     circuit CID means a matching verifier; re-running would only burn gas.
+    let merkle_root_bytes: FixedBytes<32> = merkle_root
+        .parse()
+        .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
+    let t_start_val: U256 = t_start
+        .parse()
+        .with_context(|| format!("invalid t_start (expected uint256): {t_start}"))?;
+    let t_end_val: U256 = t_end
+        .parse()
+        .with_context(|| format!("invalid t_end (expected uint256): {t_end}"))?;
+
     let provider = eth::create_provider(rpc_url, private_key)?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    eth::check_window(&provider, t_start_val, t_end_val, strict, "", progress).await?;
+    let mut cd_addrs = Vec::with_capacity(cd_args.len());
+    for arg in &cd_args {
+        let addr = arg.resolve(&provider, progress).await?;
+        if let Some(name) = arg.ens_name() {
+            progress.info(&format!("resolved {name} -> {addr}"));
+        }
+        cd_addrs.push(addr);
+    }
+    if let Some(expected) = expect_regulator {
+        for &cd_addr in &cd_addrs {
+            eth::check_regulator(&provider, cd_addr, expected, progress).await?;
+        }
+    }
+
+    let mut already_registered = Vec::with_capacity(cd_addrs.len());
+    for &cd_addr in &cd_addrs {
+        let latest = if force {
+            None
+        } else {
+            eth::latest_version(&provider, cd_addr, progress).await?
+        };
+        let registered = match latest {
+            Some(v) => {
+                v.merkle_root == merkle_root_bytes
+                    && v.t_start == t_start_val
+                    && v.t_end == t_end_val
+                    && v.leaves_hash == leaves_cid
+                    && registered_metadata_matches(
+                        ipfs_rpc_url,
+                        &v.metadata_hash,
+                        &circuit_cid,
+                        note.as_deref(),
+                        manifest.as_ref(),
+                        progress,
+                    )
+                    .await
+            }
+            None => false,
+        };
+        if registered {
+            progress.info(&style::warning(format!(
+                "constraint already registered on {cd_addr}, nothing to do (use --force to register again)"
+            )));
+        }
+        already_registered.push(registered);
+    }
+
+    if !deploy_verifier_only && already_registered.iter().all(|&r| r) {
+        let registrations: Vec<Registration> = cd_addrs
+            .iter()
+            .map(|cd_addr| Registration {
+                compliance_definition: cd_addr.to_string(),
+                update_tx_hash: String::new(),
+                error: None,
+                already_registered: true,
+                label_tx_hash: None,
+            })
+            .collect();
+
+        receipts.print_value("cid", &circuit_cid);
+        receipts.print_value("merkle_root", &merkle_root);
+        receipts.print_value("chain_id", &chain_id);
+
+        let cid_v1 = ipfs::cid_v1_map(
+            [Some(circuit_cid.as_str()), Some(leaves_cid.as_str()), project_cid.as_deref()]
+                .into_iter()
+                .flatten(),
+        );
+        let data = UpdateCircuitData {
+            project_dir: project_dir.display().to_string(),
+            bytecode_path: bytecode_path.display().to_string(),
+            vk_path: vk_path.display().to_string(),
+            verifier_path: verifier_path.display().to_string(),
+            cid: circuit_cid,
+            ipfs_size: circuit_ipfs_size,
+            merkle_root: merkle_root.to_string(),
+            verifier_address: String::new(),
+            deploy_tx_hash: String::new(),
+            compliance_definition: registrations[0].compliance_definition.clone(),
+            update_tx_hash: String::new(),
+            registrations,
+            verification_status: "skipped: constraint already registered".to_string(),
+            verification: VerificationOutcome::Skipped("constraint already registered".to_string()),
+            leaves_cid,
+            skipped_uploads,
+            pin_status,
+            verifier_optimizer_runs: None,
+            verifier_bytecode_hash: None,
+            backend_version: backend_version.clone(),
+            source_sha256: compiled_source.sha256().to_string(),
+            dependencies,
+            project_cid,
+            verify_gas: None,
+            note: None,
+            metadata_cid: None,
+            deploy_verifier_only,
+            on_chain_label: None,
+            cid_v1,
+        };
+
+        if verbose {
+            timings.print();
+        }
+
+        let receipt = Receipt::new("update-circuit", data).with_timings(timings);
+        receipt.write(receipts)?;
+        return Ok(receipt.data);
+    }
+
+    // 4e. Upload the metadata document (with a --note or a compliance.toml), which is
+    //     registered in place of the circuit CID.  Only now, since its created_at gives
+    //     it a new CID on every run.
+    let mut metadata_cid = None;
+    if note.is_some() || manifest.is_some() {
+        progress.info(&match note {
+            Some(_) => "uploading version note...".to_string(),
+            None => format!("uploading {} metadata document...", manifest::MANIFEST_FILE),
+        });
+        let stored = MetadataDocument::new(&circuit_cid, note.as_deref(), manifest.as_ref())
+            .store(store)
+            .await?;
+        progress.info(&format!("metadata document uploaded to IPFS: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        metadata_cid = Some(stored.uri);
+    }
+    let metadata_hash = metadata_cid.clone().unwrap_or_else(|| circuit_cid.clone());
+
+    // 4f. Make sure the verifier we are about to deploy was generated from this vk
+    bb::check_verifier_vk_hash(&vk_path, &verifier_path, progress)?;
+
+    // 5. Temporarily copy Verifier.sol into the Foundry project so forge can compile it
+    //    (removed again when the guard drops, including on error or Ctrl-C)
+    let staged_verifier =
+        forge::StagedSource::copy(&verifier_path, contract_dir.join("src/Verifier.sol"))?;
+
+    // 6. Build the Foundry project with the new Verifier.sol, or check that the
+    //    prebuilt artifact was compiled from it (--skip-build)
     let artifact = forge::artifact_path(contract_dir, "Verifier.sol", "HonkVerifier");
+    if deploy.skip_build {
+        progress.info("using prebuilt verifier contract (--skip-build)");
+        forge::check_prebuilt(&artifact, contract_dir)?;
+        forge::check_artifact_source(&artifact, "src/Verifier.sol", &verifier_path, progress)?;
+    } else {
+        let step = deploy.build_tool.step();
+        progress.info("compiling verifier contract...");
+        progress.step_started(step);
+        let started = Instant::now();
+        deploy.build_tool.build_verifier(contract_dir, deploy.optimizer_runs)?;
+        timings.record(step, started);
+        progress.step_finished(step);
+        progress.info("verifier contract compiled");
+    }
+    let verifier_bytecode_hash = forge::check_bytecode_hash(&artifact, deploy.expected_verifier_hash)?;
+    progress.info(&format!("verifier bytecode hash: {verifier_bytecode_hash}"));
 
-    eprintln!("deploying HonkVerifier...");
-    let deploy_result = eth::deploy_from_artifact(&provider, &artifact, None).await?;
-    eprintln!("HonkVerifier deployed to {}", deploy_result.deployed_to);
+    // 7. Deploy the HonkVerifier contract
+    bb::warn_if_impractical(
+        &bytecode_path,
+        chain_id,
+        etherscan::network_name(chain_id),
+        "",
+        &deploy.gate_limits,
+        progress,
+    );
+
+    progress.info("deploying HonkVerifier...");
+    progress.step_started("deploy");
+    let started = Instant::now();
+    let deploy_result =
+        eth::deploy_from_artifact(&provider, &artifact, None, deploy, progress).await?;
+    timings.record("deploy", started);
+    progress.step_finished("deploy");
+    progress.info(&format!(
+        "{} {}",
+        style::success("HonkVerifier deployed to"),
+        deploy_result.deployed_to
+    ));
+    if deploy.verify_interface {
+        eth::check_verifier_interface(&provider, deploy_result.deployed_to, progress).await?;
+        progress.info("verifier implements verify(bytes,bytes32[]) (--verify-interface)");
+    }
 
     // Verify via Etherscan API (needs Verifier.sol still present for standard JSON input)
-    let chain_id = provider.get_chain_id().await
-        .context("failed to query chain ID from RPC")?;
+    progress.step_started("verify");
+    let started = Instant::now();
     let verification = etherscan::verify_contract(
         contract_dir,
         &artifact,
@@ -148,51 +568,223 @@ pub async fn run(
         None,
         verify,
         "",
+        progress,
     )
     .await;
+    timings.record("verify", started);
+    progress.step_finished("verify");
 
     // Clean up the temporarily copied Verifier.sol
-    let _ = std::fs::remove_file(&deploy_verifier_path);
+    drop(staged_verifier);
 
     let verification = verification?;
 
-    // 9. Call updateCircuit on the ComplianceDefinition contract
+    // 7b. Estimate the gas of verify() with the proof, without sending anything
+    let verify_gas = match proof {
+        Some(ref proof) => {
+            match eth::estimate_verify_gas(&provider, deploy_result.deployed_to, proof).await {
+                Ok(gas) => {
+                    progress.info(&format!("verify() gas: {gas}"));
+                    Some(gas)
+                }
+                Err(e) => {
+                    progress.info(&format!(
+                        "{} could not measure verify() gas: {e:#}",
+                        style::warning("warning:")
+                    ));
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+
+    // 8. Call updateCircuit on each ComplianceDefinition contract (there are none with
+    //    --deploy-verifier-only)
     let cid = &circuit_cid;
-    let cd_addr: Address = compliance_definition
-        .parse()
-        .with_context(|| format!("invalid compliance definition address: {compliance_definition}"))?;
-    let merkle_root_bytes: FixedBytes<32> = merkle_root
-        .parse()
-        .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
-    let t_start_val: U256 = t_start
-        .parse()
-        .with_context(|| format!("invalid t_start (expected uint256): {t_start}"))?;
-    let t_end_val: U256 = t_end
-        .parse()
-        .with_context(|| format!("invalid t_end (expected uint256): {t_end}"))?;
+    if deploy_verifier_only {
+        progress.info(&format!(
+            "--deploy-verifier-only set, not registering; register it with `register --verifier {} --circuit-cid {metadata_hash}`",
+            deploy_result.deployed_to
+        ));
+        receipts.print_value("metadata_hash", &metadata_hash);
+    }
+    // Registrations run in up to --concurrency tasks at once.  Each reports back over
+    // `events`, so progress is printed here, in the order it happens.
+    let permits = Arc::new(Semaphore::new(deploy.concurrency));
+    let (events, mut pending_events) = tokio::sync::mpsc::unbounded_channel();
+    let mut pending = tokio::task::JoinSet::new();
+    let mut registrations = Vec::with_capacity(cd_addrs.len());
+    for (index, (&cd_addr, &registered)) in cd_addrs.iter().zip(&already_registered).enumerate() {
+        if registered {
+            registrations.push(Registration {
+                compliance_definition: cd_addr.to_string(),
+                update_tx_hash: String::new(),
+                error: None,
+                already_registered: true,
+                label_tx_hash: None,
+            });
+            continue;
+        }
 
-    eprintln!("registering compliance version...");
-    let update_tx_hash = eth::call_update_circuit(
-        &provider,
-        cd_addr,
-        deploy_result.deployed_to,
-        merkle_root_bytes,
-        t_start_val,
-        t_end_val,
-        cid.to_string(),
-        leaves_cid.clone(),
-    )
-    .await?;
-    eprintln!("compliance version registered");
+        if print_calldata {
+            let calldata = eth::update_circuit_calldata(
+                eth::UpdateCircuitArgs {
+                    verifier: deploy_result.deployed_to,
+                    merkle_root: merkle_root_bytes,
+                    t_start: t_start_val,
+                    t_end: t_end_val,
+                    metadata_hash: metadata_hash.clone(),
+                    leaves_hash: leaves_cid.clone(),
+                },
+                deploy,
+            )?;
+            progress.info(&format!(
+                "--print-calldata set, not broadcasting updateCircuit to {cd_addr}"
+            ));
+            receipts.print_value("update_to", &cd_addr);
+            receipts.print_value("update_calldata", &calldata);
+            if let Some(ref label) = deploy.on_chain_label {
+                receipts.print_value("label_calldata", &eth::set_label_calldata(label));
+            }
+            registrations.push(Registration {
+                compliance_definition: cd_addr.to_string(),
+                update_tx_hash: String::new(),
+                error: None,
+                already_registered: false,
+                label_tx_hash: None,
+            });
+            continue;
+        }
 
-    println!("verifier_address={}", deploy_result.deployed_to);
-    println!("deploy_tx_hash={}", deploy_result.transaction_hash);
-    println!("update_tx_hash={update_tx_hash}");
-    println!("cid={cid}");
-    println!("merkle_root={merkle_root}");
-    println!("chain_id={chain_id}");
-    println!("verification={verification}");
+        registrations.push(Registration {
+            compliance_definition: cd_addr.to_string(),
+            update_tx_hash: String::new(),
+            error: None,
+            already_registered: false,
+            label_tx_hash: None,
+        });
+        let args = eth::UpdateCircuitArgs {
+            verifier: deploy_result.deployed_to,
+            merkle_root: merkle_root_bytes,
+            t_start: t_start_val,
+            t_end: t_end_val,
+            metadata_hash: metadata_hash.clone(),
+            leaves_hash: leaves_cid.clone(),
+        };
+        let (provider, deploy, permits, events) =
+            (provider.clone(), deploy.clone(), permits.clone(), events.clone());
+        pending.spawn(async move {
+            // The semaphore is closed once a registration fails without
+            // --continue-on-error, so the ones still waiting are never sent.
+            let Ok(_permit) = permits.acquire().await else {
+                return;
+            };
+            let _ = events.send(RegistrationEvent::Started(index));
+            let started = Instant::now();
+            let progress = EventProgress(events);
+            let result =
+                match eth::call_update_circuit(&provider, cd_addr, args, &deploy, &progress).await {
+                    Ok(tx_hash) => {
+                        let label_tx_hash =
+                            eth::label_version(&provider, cd_addr, &deploy, &progress).await;
+                        Ok((tx_hash, label_tx_hash))
+                    }
+                    Err(e) => Err(e),
+                };
+            let _ = progress.0.send(RegistrationEvent::Finished(index, started, result));
+        });
+    }
+    drop(events);
+
+    let mut failure = None;
+    while let Some(event) = pending_events.recv().await {
+        let (index, started, result) = match event {
+            RegistrationEvent::Started(index) => {
+                progress.info(&format!("registering compliance version on {}...", cd_addrs[index]));
+                progress.step_started("register");
+                continue;
+            }
+            RegistrationEvent::Info(msg) => {
+                progress.info(&msg);
+                continue;
+            }
+            RegistrationEvent::Finished(index, started, result) => (index, started, result),
+        };
+        timings.record("register", started);
+        progress.step_finished("register");
+
+        let cd_addr = cd_addrs[index];
+        let registration = &mut registrations[index];
+        match result {
+            Ok((tx_hash, label_tx_hash)) => {
+                progress.info(&style::success(format!(
+                    "compliance version registered on {cd_addr}"
+                )));
+                registration.update_tx_hash = tx_hash.to_string();
+                match label_tx_hash {
+                    Ok(label_tx_hash) => {
+                        registration.label_tx_hash = label_tx_hash.map(|hash| hash.to_string());
+                    }
+                    Err(e) => {
+                        permits.close();
+                        registration.error = Some(format!("labelling failed: {:#}", redact::Redacted(&e)));
+                        failure.get_or_insert(e.context(format!(
+                            "version registered on {cd_addr} in {tx_hash}, but labelling it failed"
+                        )));
+                    }
+                }
+            }
+            Err(e) if continue_on_error => {
+                progress.info(&format!(
+                    "{} {e:#}",
+                    style::failure(format!("registration on {cd_addr} failed, continuing:"))
+                ));
+                registration.error = Some(format!("{:#}", redact::Redacted(&e)));
+            }
+            Err(e) => {
+                permits.close();
+                registration.error = Some(format!("{:#}", redact::Redacted(&e)));
+                failure.get_or_insert(e.context(format!("registration on {cd_addr} failed")));
+            }
+        }
+    }
+    while let Some(joined) = pending.join_next().await {
+        joined.context("registration task failed")?;
+    }
+    // After a failure the registrations still waiting for a permit were never sent;
+    // say so in the receipt, which is written before the error is returned.
+    if failure.is_some() {
+        for registration in registrations
+            .iter_mut()
+            .filter(|r| r.update_tx_hash.is_empty() && r.error.is_none() && !r.already_registered)
+        {
+            registration.error = Some("not sent: an earlier registration failed".to_string());
+        }
+    }
+    let update_tx_hash = registrations.first().map(|r| r.update_tx_hash.clone()).unwrap_or_default();
 
+    receipts.print_value(
+        "verifier_address",
+        &eth::format_chain_address(chain_id, deploy_result.deployed_to),
+    );
+    receipts.print_value("deploy_tx_hash", &deploy_result.transaction_hash);
+    for registration in &registrations {
+        receipts.print_value("update_tx_hash", &registration.update_tx_hash);
+    }
+    receipts.print_value("cid", &cid);
+    receipts.print_value("merkle_root", &merkle_root);
+    receipts.print_value("chain_id", &chain_id);
+    receipts.print_value("verification", &verification);
+    if let Some(gas) = verify_gas {
+        receipts.print_value("verify_gas", &gas);
+    }
+
+    let cid_v1 = ipfs::cid_v1_map(
+        [Some(cid.as_str()), Some(leaves_cid.as_str()), metadata_cid.as_deref(), project_cid.as_deref()]
+            .into_iter()
+            .flatten(),
+    );
     let data = UpdateCircuitData {
         project_dir: project_dir.display().to_string(),
         bytecode_path: bytecode_path.display().to_string(),
@@ -203,14 +795,39 @@ pub async fn run(
         merkle_root: merkle_root.to_string(),
         verifier_address: deploy_result.deployed_to.to_string(),
         deploy_tx_hash: deploy_result.transaction_hash.to_string(),
-        compliance_definition: compliance_definition.to_string(),
-        update_tx_hash: update_tx_hash.to_string(),
+        compliance_definition: registrations
+            .first()
+            .map(|r| r.compliance_definition.clone())
+            .unwrap_or_default(),
+        update_tx_hash,
+        registrations,
         verification_status: verification.to_string(),
+        verification,
         leaves_cid,
+        skipped_uploads,
+        pin_status,
+        verifier_optimizer_runs: forge::artifact_optimizer_runs(&artifact),
+        verifier_bytecode_hash: Some(verifier_bytecode_hash.to_string()),
+        backend_version,
+        source_sha256: compiled_source.sha256().to_string(),
+        dependencies,
+        project_cid,
+        verify_gas,
+        note,
+        metadata_cid,
+        deploy_verifier_only,
+        on_chain_label: deploy.on_chain_label.clone().filter(|_| !deploy_verifier_only),
+        cid_v1,
     };
 
-    let receipt = Receipt::new("update-circuit", data);
-    receipt.write_to_dir(receipts_dir)?;
+    if verbose {
+        timings.print();
+    }
 
-    Ok(())
+    let receipt = Receipt::new("update-circuit", data).with_timings(timings);
+    receipt.write(receipts)?;
+    if let Some(e) = failure {
+        return Err(e);
+    }
+    Ok(receipt.data)
 }
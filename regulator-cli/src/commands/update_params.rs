@@ -1,11 +1,18 @@
 use alloy::primitives::{Address, FixedBytes};
 use anyhow::{Context, Result};
 use serde::Serialize;
-use std::path::{Path, PathBuf};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Instant;
 
 use crate::eth;
+use crate::eth::DeployArgs;
 use crate::ipfs;
-use crate::receipt::Receipt;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::storage::MetadataStore;
+use crate::style;
+use crate::timings::Timings;
 
 #[derive(Debug, Serialize)]
 pub struct UpdateParamsData {
@@ -14,61 +21,105 @@ pub struct UpdateParamsData {
     pub leaves_file: String,
     pub leaves_cid: String,
     pub update_tx_hash: String,
+    /// CIDs whose upload was skipped because the node already had them pinned
+    /// (`--ipfs-skip-existing`).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_uploads: Vec<String>,
+    /// Pin state on the IPFS node of each CID uploaded in this run, keyed by CID.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub pin_status: BTreeMap<String, ipfs::LocalPin>,
+    /// Canonical CIDv1 (lowercase base32) of each CID in this receipt, keyed by the CID
+    /// as recorded, so receipts from nodes returning CIDv0 and CIDv1 compare equal.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cid_v1: BTreeMap<String, String>,
 }
 
 #[allow(clippy::too_many_arguments)]
 pub async fn run(
     compliance_definition: &str,
-    ipfs_rpc_url: &str,
+    store: &dyn MetadataStore,
     rpc_url: &str,
     private_key: &str,
     merkle_root: &str,
     leaves_file: Option<PathBuf>,
     leaves_cid_override: Option<String>,
-    receipts_dir: &Path,
+    expected_chain_id: Option<u64>,
+    expect_regulator: Option<Address>,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    deploy: &DeployArgs,
 ) -> Result<()> {
+    if !deploy.allow_any_metadata {
+        ipfs::check_cid_flags(&[("--leaves-cid", leaves_cid_override.as_ref())])?;
+    }
+    let mut timings = Timings::default();
+
     // 1. Resolve the leaves CID: either upload, or use the pre-pinned override.
+    let mut skipped_uploads = Vec::new();
+    let mut pin_status = BTreeMap::new();
+    progress.step_started("ipfs_upload");
+    let started = Instant::now();
     let (leaves_cid, leaves_file_display) = if let Some(cid) = leaves_cid_override {
-        eprintln!("using pre-pinned leaves CID: {cid}");
+        progress.info(&format!("using pre-pinned leaves CID: {cid}"));
         (cid, String::new())
     } else {
         let leaves_path = leaves_file
             .as_ref()
             .context("either --leaves-file or --leaves-cid must be provided")?;
-        eprintln!("uploading leaves file {}...", leaves_path.display());
-        let leaves_response = ipfs::add_file(ipfs_rpc_url, leaves_path)
+        progress.info(&format!("uploading leaves file {}...", leaves_path.display()));
+        let stored = store
+            .store(leaves_path, "compliance leaves")
             .await
-            .with_context(|| {
-                format!("failed to upload leaves file to IPFS at {ipfs_rpc_url}")
-            })?;
-        eprintln!("leaves uploaded to IPFS: {}", leaves_response.hash);
-        (leaves_response.hash, leaves_path.display().to_string())
+            .context("failed to store leaves file")?;
+        progress.info(&format!("leaves uploaded to IPFS: {}", stored.uri));
+        stored.record(&mut skipped_uploads, &mut pin_status);
+        (stored.uri, leaves_path.display().to_string())
     };
+    timings.record("ipfs_upload", started);
+    progress.step_finished("ipfs_upload");
 
     // 2. Call updateParams on the ComplianceDefinition contract
-    let cd_addr: Address = compliance_definition
-        .parse()
+    let (cd_arg, prefixed_chain) = eth::AddressArg::parse(compliance_definition)
         .with_context(|| format!("invalid compliance definition address: {compliance_definition}"))?;
+    let expected_chain_id = eth::merge_chain_id(expected_chain_id, prefixed_chain)?;
     let merkle_root_bytes: FixedBytes<32> = merkle_root
         .parse()
         .with_context(|| format!("invalid merkle_root (expected bytes32): {merkle_root}"))?;
 
     let provider = eth::create_provider(rpc_url, private_key)?;
+    let chain_id = eth::resolve_chain_id(&provider, expected_chain_id, progress).await?;
+    let cd_addr = cd_arg.resolve(&provider, progress).await?;
+    if let Some(name) = cd_arg.ens_name() {
+        progress.info(&format!("resolved {name} -> {cd_addr}"));
+    }
+    if let Some(expected) = expect_regulator {
+        eth::check_regulator(&provider, cd_addr, expected, progress).await?;
+    }
 
-    eprintln!("calling updateParams...");
+    progress.info("calling updateParams...");
+    progress.step_started("register");
+    let started = Instant::now();
     let update_tx_hash = eth::call_update_params(
         &provider,
         cd_addr,
         merkle_root_bytes,
         leaves_cid.to_string(),
+        deploy,
+        progress,
     )
     .await?;
-    eprintln!("updateParams succeeded");
+    timings.record("register", started);
+    progress.step_finished("register");
+    progress.info(&style::success("updateParams succeeded"));
 
-    println!("compliance_definition={compliance_definition}");
-    println!("merkle_root={merkle_root}");
-    println!("leaves_cid={leaves_cid}");
-    println!("update_tx_hash={update_tx_hash}");
+    receipts.print_value(
+        "compliance_definition",
+        &eth::format_chain_address(chain_id, cd_addr),
+    );
+    receipts.print_value("merkle_root", &merkle_root);
+    receipts.print_value("leaves_cid", &leaves_cid);
+    receipts.print_value("update_tx_hash", &update_tx_hash);
 
     let data = UpdateParamsData {
         compliance_definition: compliance_definition.to_string(),
@@ -76,10 +127,17 @@ pub async fn run(
         leaves_file: leaves_file_display,
         leaves_cid: leaves_cid.to_string(),
         update_tx_hash: update_tx_hash.to_string(),
+        skipped_uploads,
+        pin_status,
+        cid_v1: ipfs::cid_v1_map([leaves_cid.as_str()]),
     };
 
-    let receipt = Receipt::new("update-params", data);
-    receipt.write_to_dir(receipts_dir)?;
+    if verbose {
+        timings.print();
+    }
+
+    let receipt = Receipt::new("update-params", data).with_timings(timings);
+    receipt.write(receipts)?;
 
     Ok(())
 }
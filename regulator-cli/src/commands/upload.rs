@@ -0,0 +1,145 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::ipfs;
+use crate::ipfs::PinArgs;
+use crate::nargo;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::storage::{MetadataStore, StoredRef};
+
+#[derive(Debug, Serialize)]
+pub struct UploadData {
+    pub project_dir: String,
+    pub source_file: String,
+    /// What was uploaded under `cid` (`--ipfs-source`).
+    pub ipfs_source: IpfsSource,
+    /// The compiled circuit uploaded with `--ipfs-source compiled` or `both`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiled_file: Option<String>,
+    pub cid: String,
+    pub ipfs_size: String,
+    /// SHA-256 of the source file's bytes as uploaded.
+    pub source_sha256: String,
+    /// The node already had the CID pinned, so nothing was uploaded
+    /// (`--ipfs-skip-existing`).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub skipped: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pin_status: Option<ipfs::LocalPin>,
+    /// Canonical CIDv1 (lowercase base32) of each CID in this receipt, keyed by the CID
+    /// as recorded, so receipts from nodes returning CIDv0 and CIDv1 compare equal.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub cid_v1: BTreeMap<String, String>,
+}
+
+/// What `upload` archives (`--ipfs-source`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IpfsSource {
+    /// The main source file (`src/main.nr`, or `src/lib.nr` for a library)
+    #[default]
+    Entrypoint,
+    /// The compiled circuit (`target/<package>.json`), which alone determines the verifier
+    Compiled,
+    /// Both, uploaded to IPFS as a directory keeping their paths in the project
+    Both,
+}
+
+/// `path` relative to `project_dir`, with `/` separators, as named in an IPFS directory.
+fn project_relative(project_dir: &Path, path: &Path) -> String {
+    path.strip_prefix(project_dir)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// Upload a circuit's main source file, its compiled circuit, or both (`ipfs_source`)
+/// without compiling or deploying anything, e.g. to archive a circuit for review ahead
+/// of registration.  The compiled circuit must already be built from the current source.
+pub async fn run(
+    project_dir: &Path,
+    ipfs_source: IpfsSource,
+    ipfs_rpc_url: &str,
+    pin: &PinArgs,
+    store: &dyn MetadataStore,
+    receipts: &ReceiptOutput,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    if !project_dir.is_dir() {
+        bail!("not a directory: {}", project_dir.display());
+    }
+    nargo::validate_manifest(project_dir)?;
+    let source_file = nargo::find_source_file(project_dir)?;
+    let source = nargo::CompiledSource::read(&source_file)?;
+
+    let compiled_file = if ipfs_source == IpfsSource::Entrypoint {
+        None
+    } else {
+        let artifact = nargo::artifact_path(project_dir)?;
+        if !artifact.is_file() {
+            bail!(
+                "compiled circuit not found at {} -- compile it first (e.g. with `nargo compile`) to upload it with --ipfs-source",
+                artifact.display()
+            );
+        }
+        source.check_upload(&artifact, false, progress).with_context(|| {
+            format!("{} is not compiled from the current source -- recompile it", artifact.display())
+        })?;
+        Some(artifact)
+    };
+
+    let stored = match compiled_file {
+        Some(ref artifact) if ipfs_source == IpfsSource::Both => {
+            progress.info(&format!(
+                "uploading {} and {}...",
+                source_file.display(),
+                artifact.display()
+            ));
+            let files = vec![project_relative(project_dir, &source_file), project_relative(project_dir, artifact)];
+            let added = ipfs::add_files(ipfs_rpc_url, project_dir, files, pin, progress).await?;
+            ipfs::remote_pin_if_enabled(pin, &added.hash, "compliance circuit archive", progress)
+                .await?;
+            StoredRef {
+                uri: added.hash,
+                size: added.size,
+                skipped: false,
+                pin: Some(added.pin),
+            }
+        }
+        Some(ref artifact) => {
+            progress.info(&format!("uploading {}...", artifact.display()));
+            store
+                .store(artifact, "compiled compliance circuit")
+                .await
+                .context("failed to store compiled circuit")?
+        }
+        None => {
+            progress.info(&format!("uploading {}...", source_file.display()));
+            store
+                .store(&source_file, "compliance circuit source")
+                .await
+                .context("failed to store circuit source")?
+        }
+    };
+    progress.info(&format!("uploaded to IPFS: {}", stored.uri));
+
+    receipts.print_value("cid", &stored.uri);
+
+    let cid_v1 = ipfs::cid_v1_map([stored.uri.as_str()]);
+    let data = UploadData {
+        project_dir: project_dir.display().to_string(),
+        source_file: source_file.display().to_string(),
+        ipfs_source,
+        compiled_file: compiled_file.map(|path| path.display().to_string()),
+        cid: stored.uri,
+        ipfs_size: stored.size,
+        source_sha256: source.sha256().to_string(),
+        skipped: stored.skipped,
+        pin_status: stored.pin,
+        cid_v1,
+    };
+    Receipt::new("upload", data).write(receipts)
+}
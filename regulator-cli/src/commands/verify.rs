@@ -0,0 +1,191 @@
+use alloy::primitives::Address;
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::eth;
+use crate::etherscan;
+use crate::etherscan::{InputCheck, VerifyArgs};
+use crate::forge;
+use crate::progress::ProgressReporter;
+use crate::receipt::{Receipt, ReceiptOutput};
+use crate::style;
+use crate::timings::Timings;
+
+#[derive(Debug, Serialize)]
+pub struct VerifyData {
+    pub contract_address: String,
+    pub contract_name: String,
+    pub artifact_path: String,
+    pub chain_id: u64,
+    pub verification_status: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VerificationInputData {
+    pub contract_address: String,
+    pub contract_name: String,
+    pub artifact_path: String,
+    pub compiler_version: String,
+    /// `match`, `metadata_mismatch`, or `mismatch`.
+    pub result: String,
+}
+
+/// Split a fully-qualified contract name (`src/Verifier.sol:HonkVerifier`) into
+/// the artifact directory name (`Verifier.sol`) and the contract name.
+fn split_contract_name(contract_name: &str) -> Result<(&str, &str)> {
+    let (source, name) = contract_name.rsplit_once(':').with_context(|| {
+        format!("invalid contract name (expected <path>:<Contract>): {contract_name}")
+    })?;
+    let sol_file = Path::new(source)
+        .file_name()
+        .and_then(|f| f.to_str())
+        .with_context(|| format!("invalid contract source path: {source}"))?;
+    Ok((sol_file, name))
+}
+
+/// The forge artifact for `contract_name`, which must already be built.
+fn find_artifact(contract_dir: &Path, contract_name: &str) -> Result<PathBuf> {
+    let (sol_file, name) = split_contract_name(contract_name)?;
+    let artifact = forge::artifact_path(contract_dir, sol_file, name);
+    if !artifact.exists() {
+        bail!(
+            "artifact not found at {} -- run `forge build` in {} first",
+            artifact.display(),
+            contract_dir.display()
+        );
+    }
+    Ok(artifact)
+}
+
+/// `--check-verification-input`: recompile the verification input locally and compare
+/// it with the artifact, without contacting the explorer.
+fn check_verification_input(
+    contract_addr: Address,
+    contract_dir: &Path,
+    contract_name: &str,
+    receipts: &ReceiptOutput,
+    verify: &VerifyArgs,
+) -> Result<()> {
+    let artifact = find_artifact(contract_dir, contract_name)?;
+    eprintln!("recompiling the verification input for {contract_name} locally...");
+    let (check, compiler_version) = etherscan::check_verification_input(
+        contract_dir,
+        &artifact,
+        contract_name,
+        &verify.source_roots,
+    )?;
+
+    receipts.print_value("compiler_version", &compiler_version);
+    receipts.print_value("verification_input", &check);
+
+    let data = VerificationInputData {
+        contract_address: contract_addr.to_string(),
+        contract_name: contract_name.to_string(),
+        artifact_path: artifact.display().to_string(),
+        compiler_version,
+        result: check.to_string(),
+    };
+    Receipt::new("verify-input", data).write(receipts)?;
+
+    match check {
+        InputCheck::Match => {
+            eprintln!("{}", style::success("the verification input reproduces the artifact's runtime bytecode"));
+        }
+        InputCheck::MetadataMismatch => eprintln!(
+            "{} the verification input reproduces the code but not the metadata hash -- a source differs from the build only in comments or paths, or the artifact is stale; explorers requiring an exact match will reject it",
+            style::warning("warning:")
+        ),
+        InputCheck::Mismatch => bail!(
+            "the verification input does not reproduce the runtime bytecode of {} -- the sources or settings differ from the build (is the artifact stale? run `forge build`)",
+            artifact.display()
+        ),
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    address: &str,
+    contract_dir: &Path,
+    contract_name: &str,
+    chain_id: Option<u64>,
+    rpc_url: Option<&str>,
+    constructor_args: Option<&str>,
+    check_input: bool,
+    receipts: &ReceiptOutput,
+    verbose: bool,
+    progress: &dyn ProgressReporter,
+    verify: &VerifyArgs,
+) -> Result<()> {
+    let (contract_addr, prefixed_chain) = eth::parse_chain_address(address)
+        .with_context(|| format!("invalid contract address: {address}"))?;
+    let chain_id = eth::merge_chain_id(chain_id, prefixed_chain)?;
+    if check_input {
+        return check_verification_input(contract_addr, contract_dir, contract_name, receipts, verify);
+    }
+    if chain_id.is_none() && rpc_url.is_none() {
+        bail!("either --chain-id, --rpc-url, or a chain-prefixed --address must be provided");
+    }
+
+    let chain_id = match (chain_id, rpc_url) {
+        (Some(id), _) => id,
+        (None, Some(url)) => {
+            eth::resolve_chain_id(&eth::create_read_provider(url)?, None, progress).await?
+        }
+        (None, None) => bail!("either --chain-id or --rpc-url must be provided"),
+    };
+
+    if verify.etherscan_api_key.as_deref().unwrap_or_default().is_empty()
+        && verify.verifier_url.as_deref().unwrap_or_default().is_empty()
+        && !verify.chain_api_urls.contains_key(&chain_id)
+    {
+        bail!(
+            "verify requires an explorer API key (--etherscan-api-key or ETHERSCAN_API_KEY), or --verifier-url for a keyless explorer"
+        );
+    }
+
+    let artifact = find_artifact(contract_dir, contract_name)?;
+
+    let constructor_args = constructor_args.map(eth::ConstructorArgs::from_hex).transpose()?;
+
+    let mut timings = Timings::default();
+    progress.step_started("verify");
+    let started = Instant::now();
+    let verification = etherscan::verify_contract(
+        contract_dir,
+        &artifact,
+        chain_id,
+        &contract_addr.to_string(),
+        contract_name,
+        constructor_args.as_ref(),
+        verify,
+        "",
+        progress,
+    )
+    .await?;
+    timings.record("verify", started);
+    progress.step_finished("verify");
+
+    receipts.print_value("contract_address", &contract_addr);
+    receipts.print_value("chain_id", &chain_id);
+    receipts.print_value("verification", &verification);
+
+    let data = VerifyData {
+        contract_address: contract_addr.to_string(),
+        contract_name: contract_name.to_string(),
+        artifact_path: artifact.display().to_string(),
+        chain_id,
+        verification_status: verification.to_string(),
+    };
+
+    if verbose {
+        timings.print();
+    }
+
+    let receipt = Receipt::new("verify", data).with_timings(timings);
+    receipt.write(receipts)?;
+
+    etherscan::enforce_verification(verify, &contract_addr.to_string(), &verification)
+}
@@ -0,0 +1,63 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::commands::doctor::tool_version;
+use crate::tools::Tool;
+
+/// Commit the CLI was built from, embedded by build.rs (`unknown` outside a checkout).
+const GIT_COMMIT: &str = env!("REGULATOR_CLI_GIT_COMMIT");
+
+/// External tools reported, in the order they are printed.
+const TOOLS: [(Tool, &str); 4] = [(Tool::Nargo, "nargo"), (Tool::Bb, "bb"), (Tool::Forge, "forge"), (Tool::Cast, "cast")];
+
+/// What `<tool> --version` reported, or why it could not be run.
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ToolVersion {
+    Version(String),
+    Error(String),
+}
+
+#[derive(Serialize)]
+struct VersionReport {
+    version: &'static str,
+    git_commit: &'static str,
+    tools: BTreeMap<&'static str, ToolVersion>,
+}
+
+/// Print the CLI's version and build commit and the version of each external tool, as
+/// text or as one JSON object.  A missing tool is reported, not treated as a failure,
+/// so the output can be attached to a bug report as is.
+pub fn run(json: bool) -> Result<()> {
+    let versions: Vec<(&str, ToolVersion)> = TOOLS
+        .iter()
+        .map(|&(tool, name)| {
+            let version = match tool_version(tool, name) {
+                Ok(version) => ToolVersion::Version(version),
+                Err(e) => ToolVersion::Error(format!("{e:#}")),
+            };
+            (name, version)
+        })
+        .collect();
+
+    if json {
+        let report = VersionReport {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: GIT_COMMIT,
+            tools: versions.into_iter().collect(),
+        };
+        let json = serde_json::to_string_pretty(&report).context("failed to serialize version report")?;
+        println!("{json}");
+        return Ok(());
+    }
+
+    println!("regulator-cli {} (commit {GIT_COMMIT})", env!("CARGO_PKG_VERSION"));
+    for (name, version) in versions {
+        match version {
+            ToolVersion::Version(version) => println!("  {name:<6} {version}"),
+            ToolVersion::Error(e) => println!("  {name:<6} unavailable: {}", e.replace('\n', " ")),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use clap::parser::ValueSource;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Output format for `--show-config`.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ConfigFormat {
+    Table,
+    Json,
+}
+
+const REDACTED: &str = "<redacted>";
+
+/// One resolved setting and where its value came from.
+#[derive(Serialize)]
+struct Setting {
+    value: serde_json::Value,
+    source: String,
+}
+
+#[derive(Serialize)]
+struct EffectiveConfig {
+    command: String,
+    settings: BTreeMap<String, Setting>,
+}
+
+/// Print the value clap resolved for every set argument of `matches`, and of the
+/// subcommand it selects, without running anything.
+///
+/// Arguments that hide their environment values (private keys, tokens, API keys) are
+/// redacted.
+pub fn print(cmd: &clap::Command, matches: &ArgMatches, format: ConfigFormat) -> Result<()> {
+    let mut command = Vec::new();
+    let mut settings = BTreeMap::new();
+    collect(cmd, matches, &mut command, &mut settings);
+
+    let config = EffectiveConfig {
+        command: command.join(" "),
+        settings,
+    };
+    match format {
+        ConfigFormat::Json => {
+            let json =
+                serde_json::to_string_pretty(&config).context("failed to serialize config")?;
+            println!("{json}");
+        }
+        ConfigFormat::Table => {
+            println!("command: {}", config.command);
+            let width = config.settings.keys().map(|k| k.len()).max().unwrap_or(0);
+            for (name, setting) in &config.settings {
+                let value = match &setting.value {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Array(values) => values
+                        .iter()
+                        .filter_map(|v| v.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    other => other.to_string(),
+                };
+                println!("  {name:<width$}  {value}  ({})", setting.source);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn collect(
+    cmd: &clap::Command,
+    matches: &ArgMatches,
+    command: &mut Vec<String>,
+    settings: &mut BTreeMap<String, Setting>,
+) {
+    for arg in cmd.get_arguments() {
+        let id = arg.get_id().as_str();
+        if id == "show_config" || settings.contains_key(&display_name(arg)) {
+            continue;
+        }
+        let Some(source) = matches.value_source(id) else {
+            continue;
+        };
+        let Some(raw) = matches.get_raw(id) else {
+            continue;
+        };
+
+        let values: Vec<String> = raw.map(|v| v.to_string_lossy().into_owned()).collect();
+        let value = if arg.is_hide_env_values_set() {
+            REDACTED.into()
+        } else if values.len() == 1 {
+            values[0].clone().into()
+        } else {
+            values.into()
+        };
+        let source = match source {
+            ValueSource::CommandLine => "flag".to_string(),
+            ValueSource::EnvVariable => match arg.get_env() {
+                Some(name) => format!("env {}", name.to_string_lossy()),
+                None => "env".to_string(),
+            },
+            _ => "default".to_string(),
+        };
+        settings.insert(display_name(arg), Setting { value, source });
+    }
+
+    if let Some((name, sub_matches)) = matches.subcommand()
+        && let Some(sub) = cmd.find_subcommand(name)
+    {
+        command.push(name.to_string());
+        collect(sub, sub_matches, command, settings);
+    }
+}
+
+fn display_name(arg: &clap::Arg) -> String {
+    match arg.get_long() {
+        Some(long) => format!("--{long}"),
+        None => arg.get_id().to_string(),
+    }
+}
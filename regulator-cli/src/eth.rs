@@ -1,17 +1,49 @@
 use alloy::{
+    dyn_abi::{DynSolType, DynSolValue, JsonAbiExt, Specifier},
+    eips::BlockId,
     hex,
+    json_abi::{Function, JsonAbi},
     network::{Ethereum, EthereumWallet, TransactionBuilder},
-    primitives::{Address, Bytes, FixedBytes, U256},
-    providers::{Provider, ProviderBuilder},
+    primitives::{Address, B256, Bytes, FixedBytes, U256, address},
+    providers::{Provider, ProviderBuilder, WalletProvider},
     signers::local::PrivateKeySigner,
     sol,
+    sol_types::SolCall,
 };
-use anyhow::{Context, Result};
-use std::path::Path;
+use anyhow::{Context, Result, bail};
+use idna::uts46::{AsciiDenyList, Hyphens, Uts46};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::progress::ProgressReporter;
+use crate::redact;
+use crate::retry;
+use crate::style;
+
+const GAS_ORACLE_TIMEOUT: Duration = Duration::from_secs(10);
 
 sol! {
     #[sol(rpc)]
     contract ComplianceDefinition {
+        struct ComplianceVersion {
+            address verifier;
+            bytes32 merkleRoot;
+            uint256 tStart;
+            uint256 tEnd;
+            string metadataHash;
+            string leavesHash;
+        }
+
+        error NoActiveVersion();
+
+        /// Checks a proof against the active version, with `tx.origin` and the
+        /// version's merkle root as public inputs.
+        function verify(bytes calldata proof) external returns (bool);
+
+        function getActiveVersion() external view returns (ComplianceVersion memory);
+
         function updateCircuit(
             address newVerifier,
             bytes32 newMerkleRoot,
@@ -25,6 +57,740 @@ sol! {
             bytes32 newMerkleRoot,
             string calldata newLeavesHash
         ) external;
+
+        function versions(uint256 index) external view returns (
+            address verifier,
+            bytes32 merkleRoot,
+            uint256 tStart,
+            uint256 tEnd,
+            string memory metadataHash,
+            string memory leavesHash
+        );
+
+        function getVersionCount() external view returns (uint256);
+
+        function regulator() external view returns (address);
+
+        /// Optional: not every definition labels its versions (`--on-chain-label`).
+        function setLabel(string calldata label) external;
+    }
+
+    contract ComplianceFactory {
+        event ComplianceDeployed(
+            address indexed definition,
+            address indexed verifier,
+            address indexed regulator
+        );
+
+        function deploy(
+            bytes calldata verifierInitCode,
+            address regulator,
+            string calldata name,
+            bytes32 merkleRoot,
+            uint256 tStart,
+            uint256 tEnd,
+            string calldata metadataHash,
+            string calldata leavesHash
+        ) external returns (address definition, address verifier);
+    }
+
+    #[sol(rpc)]
+    contract EnsRegistry {
+        function resolver(bytes32 node) external view returns (address);
+    }
+
+    #[sol(rpc)]
+    contract EnsResolver {
+        function addr(bytes32 node) external view returns (address);
+        function supportsInterface(bytes4 interfaceId) external view returns (bool);
+        function resolve(bytes name, bytes data) external view returns (bytes);
+    }
+}
+
+/// The ENS registry, deployed at the same address on mainnet and its testnets.
+const ENS_REGISTRY: Address = alloy::primitives::address!("00000000000C2E074eC69A0dFb2997BA6C7d2e1e");
+
+/// ERC-165 interface ID of ENSIP-10's `resolve(bytes,bytes)`.
+const EXTENDED_RESOLVER_INTERFACE: FixedBytes<4> = FixedBytes(hex!("9061b923"));
+
+/// Characters UTS-46 maps to the `.` label separator.
+const ENS_LABEL_SEPARATORS: [char; 4] = ['.', '\u{3002}', '\u{ff0e}', '\u{ff61}'];
+
+/// Deployment settings shared by every transaction a command sends.
+#[derive(Clone, Default)]
+pub struct DeployArgs {
+    /// Pre-deployed libraries to link instead of deploying, keyed by library name or
+    /// fully-qualified `<path>:<Library>` name.
+    pub libraries: Vec<(String, Address)>,
+    /// External fee oracle queried before each transaction, used in place of the
+    /// node's EIP-1559 fee estimate.
+    pub gas_oracle: Option<String>,
+    /// Floor, in wei, that `maxFeePerGas` is raised to before sending, for chains whose
+    /// sequencer rejects fees under a network minimum.
+    pub min_gas_price: Option<u128>,
+    /// Deploy from the existing `forge build` output instead of rebuilding.
+    pub skip_build: bool,
+    /// Solidity optimizer runs passed to `forge build`.
+    pub optimizer_runs: u32,
+    /// Compiler the generated verifier is built with.
+    pub build_tool: crate::solc::BuildTool,
+    /// Explicit nonces from `--start-nonce`, used in place of a per-transaction fetch.
+    pub nonces: NonceSequence,
+    /// Resubmit transactions rejected for a stale or already-used nonce.
+    pub retry_on_nonce_error: bool,
+    /// Update function of a non-standard definition contract, called in place of the
+    /// built-in `updateCircuit` binding.
+    pub definition_abi: Option<DefinitionAbi>,
+    /// Prove the circuit with its `Prover.toml` and estimate the gas of `verify()` on
+    /// the deployed verifier.
+    pub simulate_proof_gas: bool,
+    /// Check that the deployed verifier implements `verify(bytes,bytes32[])` before
+    /// registering it.
+    pub verify_interface: bool,
+    /// Record `--circuit-cid` / `--leaves-cid` values on-chain without checking that
+    /// they are well-formed CIDs.
+    pub allow_any_metadata: bool,
+    /// Broadcast the deployments of a contract's libraries back to back and wait for
+    /// them together, instead of waiting for each before sending the next.
+    pub parallel_libraries: bool,
+    /// Label each registered version with the definition's `setLabel(string)`.
+    pub on_chain_label: Option<String>,
+    /// keccak256 the verifier's creation bytecode must hash to before it is deployed.
+    pub expected_verifier_hash: Option<B256>,
+    /// Most definitions `update-circuit` registers with at once; 1 registers them one
+    /// after another.
+    pub concurrency: usize,
+    /// Whether transactions are sent as legacy (type 0, `gasPrice`) transactions.
+    pub tx_type: TxTypeSelection,
+    /// Journal of deployment transactions consulted before each deployment, so a
+    /// rerun reuses a deployment that was mined instead of sending a duplicate.
+    pub idempotent: Option<DeploymentJournal>,
+    /// Practical gate limit per chain ID, from `--chains-file`.
+    pub gate_limits: HashMap<u64, u64>,
+}
+
+/// Nonces handed out in order to the transactions a run sends, starting at
+/// `--start-nonce`.  Without a start nonce the provider fetches one per transaction.
+///
+/// A nonce is only used up once its transaction is broadcast, so a transaction that
+/// fails to send (e.g. under `--continue-on-error`) leaves no gap.
+///
+/// Transactions sent concurrently (`--concurrency`) are broadcast one at a time under
+/// [`Self::broadcasting`], so no two are given the same nonce.
+#[derive(Clone, Default)]
+pub struct NonceSequence {
+    next: Arc<Mutex<Option<u64>>>,
+    broadcast: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl NonceSequence {
+    pub fn starting_at(start: Option<u64>) -> Self {
+        Self {
+            next: Arc::new(Mutex::new(start)),
+            broadcast: Arc::default(),
+        }
+    }
+
+    /// Nonce for the next transaction, if nonces are assigned explicitly.
+    fn peek(&self) -> Option<u64> {
+        *self.next.lock().expect("nonce lock poisoned")
+    }
+
+    /// Mark the nonce returned by [`Self::peek`] as used.
+    fn advance(&self) {
+        if let Some(next) = self.next.lock().expect("nonce lock poisoned").as_mut() {
+            *next += 1;
+        }
+    }
+
+    /// Assign nonces from `next` onwards, e.g. after the chain rejected one.
+    fn restart_at(&self, next: u64) {
+        *self.next.lock().expect("nonce lock poisoned") = Some(next);
+    }
+
+    /// Held from choosing a transaction's nonce until the node has accepted it.
+    async fn broadcasting(&self) -> tokio::sync::MutexGuard<'_, ()> {
+        self.broadcast.lock().await
+    }
+}
+
+/// Chooses between EIP-1559 and legacy (type 0) transactions for a run.
+///
+/// `--legacy-tx` forces legacy transactions.  Otherwise the chain is checked once,
+/// before the first transaction: a latest block without `baseFeePerGas` means the
+/// chain predates (or never adopted) EIP-1559, and every transaction is sent with a
+/// plain `gasPrice`.  A `--gas-oracle` is then unused, which is warned about.
+#[derive(Clone, Default)]
+pub struct TxTypeSelection {
+    forced_legacy: bool,
+    gas_oracle: bool,
+    detected_legacy: Arc<tokio::sync::OnceCell<bool>>,
+}
+
+impl TxTypeSelection {
+    pub fn new(forced_legacy: bool, gas_oracle: bool) -> Self {
+        Self {
+            forced_legacy,
+            gas_oracle,
+            detected_legacy: Arc::default(),
+        }
+    }
+
+    /// Whether to send legacy transactions, detecting it from the chain on first use.
+    /// The type chosen is reported once per run.  If the latest block cannot be
+    /// fetched, EIP-1559 is assumed, as it was before detection existed.
+    async fn legacy(
+        &self,
+        provider: &impl Provider<Ethereum>,
+        progress: &dyn ProgressReporter,
+    ) -> bool {
+        *self
+            .detected_legacy
+            .get_or_init(|| async {
+                if self.forced_legacy {
+                    progress
+                        .info("sending legacy (type 0) transactions with gasPrice (--legacy-tx)");
+                    return true;
+                }
+                let block = provider.get_block_by_number(alloy::eips::BlockNumberOrTag::Latest).await;
+                match block {
+                    Ok(Some(block)) if block.header.base_fee_per_gas.is_none() => {
+                        progress.info(&format!(
+                            "{} latest block has no baseFeePerGas, so the chain does not support EIP-1559; sending legacy (type 0) transactions with gasPrice",
+                            style::warning("note:")
+                        ));
+                        if self.gas_oracle {
+                            progress.info(&format!(
+                                "{} --gas-oracle returns EIP-1559 fees, so it is not used for legacy transactions; their gasPrice comes from the node",
+                                style::warning("warning:")
+                            ));
+                        }
+                        true
+                    }
+                    Ok(Some(_)) => {
+                        progress.info("sending EIP-1559 (type 2) transactions");
+                        false
+                    }
+                    Ok(None) | Err(_) => {
+                        progress.info(&format!(
+                            "{} could not fetch the latest block to detect EIP-1559 support; sending EIP-1559 (type 2) transactions (pass --legacy-tx if the chain rejects them)",
+                            style::warning("warning:")
+                        ));
+                        false
+                    }
+                }
+            })
+            .await
+    }
+}
+
+/// Deployment transactions sent under `--idempotent`, in a JSON file mapping
+/// `<chain id>:<deployer>:<keccak256 of the init code>` to the transaction hash.
+///
+/// The hash is recorded as soon as the node accepts the transaction, before waiting
+/// for it to be mined, so a run that times out or is interrupted while waiting
+/// leaves it behind for the retry to find.
+#[derive(Clone, Debug)]
+pub struct DeploymentJournal {
+    path: PathBuf,
+}
+
+/// File name of the [`DeploymentJournal`] in the receipts directory.
+pub const DEPLOYMENT_JOURNAL_FILE: &str = "deployments.lock.json";
+
+impl DeploymentJournal {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, FixedBytes<32>>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("failed to parse deployment journal {}", self.path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e).with_context(|| format!("failed to read deployment journal {}", self.path.display())),
+        }
+    }
+
+    /// Transaction recorded for `key`, if any.
+    fn get(&self, key: &str) -> Result<Option<FixedBytes<32>>> {
+        Ok(self.read()?.get(key).copied())
+    }
+
+    fn record(&self, key: &str, tx_hash: FixedBytes<32>) -> Result<()> {
+        let mut entries = self.read()?;
+        entries.insert(key.to_string(), tx_hash);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create directory {}", dir.display()))?;
+        }
+        let json = serde_json::to_string_pretty(&entries).context("failed to serialize deployment journal")?;
+        std::fs::write(&self.path, json)
+            .with_context(|| format!("failed to write deployment journal {}", self.path.display()))
+    }
+}
+
+/// An update function loaded from `--definition-abi`, for forks and variants of
+/// ComplianceDefinition whose `updateCircuit` is named or shaped differently.
+///
+/// Arguments are matched to the function's inputs by parameter name (ignoring case,
+/// a leading `_`, and a `new` prefix), so inputs may be reordered, retyped (e.g.
+/// `uint64 tStart`), or left out.
+#[derive(Clone)]
+pub struct DefinitionAbi {
+    function: Function,
+}
+
+/// Values available to a custom update function, in the order of `updateCircuit`.
+pub struct UpdateCircuitArgs {
+    pub verifier: Address,
+    pub merkle_root: FixedBytes<32>,
+    pub t_start: U256,
+    pub t_end: U256,
+    pub metadata_hash: String,
+    pub leaves_hash: String,
+}
+
+impl DefinitionAbi {
+    /// Load `update_function` from a JSON ABI file -- either a bare ABI array or a
+    /// forge/hardhat artifact with an `abi` field.  `update_function` is a function name,
+    /// or a full signature such as `update(address,bytes32)` to pick an overload.
+    pub fn load(path: &Path, update_function: &str) -> Result<Self> {
+        let json: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(path)
+                .with_context(|| format!("failed to read definition ABI: {}", path.display()))?,
+        )
+        .with_context(|| format!("failed to parse definition ABI JSON: {}", path.display()))?;
+        let abi_json = match json {
+            serde_json::Value::Object(mut artifact) => artifact
+                .remove("abi")
+                .with_context(|| format!("missing abi in {}", path.display()))?,
+            abi => abi,
+        };
+        let abi: JsonAbi = serde_json::from_value(abi_json)
+            .with_context(|| format!("invalid ABI in {}", path.display()))?;
+
+        let candidates: Vec<&Function> = if update_function.contains('(') {
+            abi.functions()
+                .filter(|f| f.signature() == update_function)
+                .collect()
+        } else {
+            abi.function(update_function)
+                .map(|overloads| overloads.iter().collect())
+                .unwrap_or_default()
+        };
+        match candidates.as_slice() {
+            [function] => Ok(Self {
+                function: (*function).clone(),
+            }),
+            [] => bail!("no function {update_function} in {}", path.display()),
+            overloads => bail!(
+                "{update_function} is overloaded in {} ({}); pass the full signature to --update-function",
+                path.display(),
+                overloads
+                    .iter()
+                    .map(|f| f.signature())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+
+    /// ABI-encode a call (selector + args) to the loaded function.
+    pub fn encode(&self, args: &UpdateCircuitArgs) -> Result<Bytes> {
+        let signature = self.function.signature();
+        let values = self
+            .function
+            .inputs
+            .iter()
+            .map(|param| {
+                let key = param.name.trim_start_matches('_').to_ascii_lowercase();
+                let key = key.strip_prefix("new").unwrap_or(&key);
+                let value = match key {
+                    "verifier" => args.verifier.to_string(),
+                    "merkleroot" | "root" => args.merkle_root.to_string(),
+                    "tstart" | "start" => args.t_start.to_string(),
+                    "tend" | "end" => args.t_end.to_string(),
+                    "metadatahash" | "metadatauri" | "metadata" | "circuitcid" => {
+                        args.metadata_hash.clone()
+                    }
+                    "leaveshash" | "leavescid" | "leaves" => args.leaves_hash.clone(),
+                    _ => bail!(
+                        "don't know what to pass for parameter {:?} of {signature}; expected names like verifier, merkleRoot, tStart, tEnd, metadataHash, leavesHash",
+                        param.name
+                    ),
+                };
+                let ty = param
+                    .resolve()
+                    .with_context(|| format!("unsupported parameter type {} in {signature}", param.ty))?;
+                ty.coerce_str(&value)
+                    .with_context(|| format!("cannot pass {value:?} as {} {}", param.ty, param.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let encoded = self
+            .function
+            .abi_encode_input(&values)
+            .with_context(|| format!("failed to ABI-encode {signature}"))?;
+        Ok(Bytes::from(encoded))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.function.name
+    }
+}
+
+impl DeployArgs {
+    /// Address supplied via `--library` for a library, if any.
+    fn library_address(&self, sol_file: &str, lib_name: &str) -> Option<Address> {
+        let fq_name = format!("{sol_file}:{lib_name}");
+        self.libraries
+            .iter()
+            .find(|(name, _)| *name == fq_name || name == lib_name)
+            .map(|(_, addr)| *addr)
+    }
+}
+
+/// Parse a `--library <name>=<address>` value.
+pub fn parse_library(s: &str) -> std::result::Result<(String, Address), String> {
+    let (name, addr) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected <name>=<address>, got {s:?}"))?;
+    let addr = addr
+        .parse::<Address>()
+        .map_err(|e| format!("invalid library address {addr:?}: {e}"))?;
+    Ok((name.to_string(), addr))
+}
+
+/// Parse an address, optionally in EIP-3770 form (`<shortName>:0x...`).  Returns the
+/// chain ID named by the prefix, if there is one.
+pub fn parse_chain_address(s: &str) -> Result<(Address, Option<u64>)> {
+    let (chain_id, addr) = match s.split_once(':') {
+        Some((short_name, addr)) => {
+            let chain_id = crate::etherscan::chain_id_from_short_name(short_name)
+                .with_context(|| format!("unknown EIP-3770 chain short name {short_name:?}"))?;
+            (Some(chain_id), addr)
+        }
+        None => (None, s),
+    };
+    let addr = addr.parse::<Address>()?;
+    Ok((addr, chain_id))
+}
+
+/// An address flag's value: a hex address or an ENS name still to be resolved.
+pub enum AddressArg {
+    Address(Address),
+    Ens(String),
+}
+
+impl AddressArg {
+    /// Parse a hex address (see [`parse_chain_address`]) or, for dotted inputs, an ENS
+    /// name under `.eth`, normalized with [`normalize_ens`].  ENS names carry no chain
+    /// prefix.
+    pub fn parse(s: &str) -> Result<(Self, Option<u64>)> {
+        if s.contains(ENS_LABEL_SEPARATORS) {
+            let name = normalize_ens(s)?;
+            if !name.ends_with(".eth") {
+                bail!("ENS name {s} is not under .eth");
+            }
+            return Ok((Self::Ens(name), None));
+        }
+        let (addr, chain_id) = parse_chain_address(s)?;
+        Ok((Self::Address(addr), chain_id))
+    }
+
+    /// The address, looking ENS names up through the registry on the provider's chain.
+    pub async fn resolve(
+        &self,
+        provider: &(impl Provider<Ethereum> + Clone),
+        progress: &dyn ProgressReporter,
+    ) -> Result<Address> {
+        match self {
+            Self::Address(addr) => Ok(*addr),
+            Self::Ens(name) => resolve_ens(provider, name, progress).await,
+        }
+    }
+
+    pub fn ens_name(&self) -> Option<&str> {
+        match self {
+            Self::Address(_) => None,
+            Self::Ens(name) => Some(name),
+        }
+    }
+}
+
+/// Normalize an ENS name with UTS-46 (non-transitional), the case folding and
+/// compatibility mapping ENSIP-15 builds on, so `Registry.ETH` and its full-width
+/// form name the same node as `registry.eth`.
+fn normalize_ens(name: &str) -> Result<String> {
+    let (normalized, result) =
+        Uts46::new().to_unicode(name.as_bytes(), AsciiDenyList::EMPTY, Hyphens::Allow);
+    if result.is_err() || normalized.split('.').any(str::is_empty) {
+        bail!("{name:?} is not a valid ENS name");
+    }
+    Ok(normalized.into_owned())
+}
+
+/// ENS namehash (EIP-137) of an already-normalized name.
+fn namehash(name: &str) -> FixedBytes<32> {
+    name.rsplit('.').fold(FixedBytes::ZERO, |node, label| {
+        let label = alloy::primitives::keccak256(label);
+        alloy::primitives::keccak256([node.as_slice(), label.as_slice()].concat())
+    })
+}
+
+/// DNS wire format of an already-normalized name, as `resolve(bytes,bytes)` takes it.
+fn dns_encode(name: &str) -> Result<Vec<u8>> {
+    let mut encoded = Vec::with_capacity(name.len() + 2);
+    for label in name.split('.') {
+        let len = u8::try_from(label.len())
+            .with_context(|| format!("ENS label {label:?} is longer than 255 bytes"))?;
+        encoded.push(len);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    Ok(encoded)
+}
+
+/// Resolve an ENS name to the address its resolver records.  As in ENSIP-10, a name
+/// without a resolver of its own is looked up through the resolver of its closest
+/// ancestor below `.eth`, which must then support wildcard resolution.
+async fn resolve_ens(
+    provider: &(impl Provider<Ethereum> + Clone),
+    name: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Address> {
+    let node = namehash(name);
+    let mut resolver_name = name;
+    let resolver = loop {
+        let resolver = retry::retry_async("ENS resolver lookup", progress, || async {
+            EnsRegistry::new(ENS_REGISTRY, provider)
+                .resolver(namehash(resolver_name))
+                .call()
+                .await
+                .with_context(|| {
+                    format!("failed to resolve ENS name {name} -- is ENS deployed on this chain?")
+                })
+        })
+        .await?;
+        if !resolver.is_zero() {
+            break resolver;
+        }
+        match resolver_name.split_once('.') {
+            Some((_, parent)) if parent.contains('.') => resolver_name = parent,
+            _ => bail!("failed to resolve ENS name {name}: no resolver is set for it"),
+        }
+    };
+
+    let extended = retry::retry_async("ENS resolver interface lookup", progress, || async {
+        match EnsResolver::new(resolver, provider)
+            .supportsInterface(EXTENDED_RESOLVER_INTERFACE)
+            .call()
+            .await
+        {
+            Ok(supported) => Ok(supported),
+            Err(alloy::contract::Error::TransportError(e)) if e.as_error_resp().is_none() => {
+                Err(e).with_context(|| format!("failed to query ENS resolver {resolver}"))
+            }
+            // Resolvers predating ERC-165 revert or return nothing.
+            Err(_) => Ok(false),
+        }
+    })
+    .await?;
+    let addr = if extended {
+        let name_bytes = Bytes::from(dns_encode(name)?);
+        let data = Bytes::from(EnsResolver::addrCall { node }.abi_encode());
+        let returned = retry::retry_async("ENS address lookup", progress, || async {
+            EnsResolver::new(resolver, provider)
+                .resolve(name_bytes.clone(), data.clone())
+                .call()
+                .await
+                .with_context(|| format!("failed to resolve ENS name {name} via resolver {resolver}"))
+        })
+        .await?;
+        EnsResolver::addrCall::abi_decode_returns(&returned).with_context(|| {
+            format!("resolver {resolver} returned a malformed address for ENS name {name}")
+        })?
+    } else if resolver_name != name {
+        bail!(
+            "failed to resolve ENS name {name}: it has no resolver, and the resolver of {resolver_name} does not support wildcard (ENSIP-10) resolution"
+        );
+    } else {
+        retry::retry_async("ENS address lookup", progress, || async {
+            EnsResolver::new(resolver, provider)
+                .addr(node)
+                .call()
+                .await
+                .with_context(|| format!("failed to resolve ENS name {name} via resolver {resolver}"))
+        })
+        .await?
+    };
+    if addr.is_zero() {
+        bail!("failed to resolve ENS name {name}: it has no address record");
+    }
+    Ok(addr)
+}
+
+/// Combine `--chain-id` with the chain named by a prefixed address, bailing if they
+/// disagree.
+pub fn merge_chain_id(expected: Option<u64>, from_address: Option<u64>) -> Result<Option<u64>> {
+    match (expected, from_address) {
+        (Some(expected), Some(prefixed)) if expected != prefixed => anyhow::bail!(
+            "address is prefixed for chain {prefixed}, but chain {expected} was expected"
+        ),
+        (expected, prefixed) => Ok(expected.or(prefixed)),
+    }
+}
+
+/// Format an address in EIP-3770 form when the chain has a known short name.
+pub fn format_chain_address(chain_id: u64, addr: Address) -> String {
+    match crate::etherscan::chain_short_name(chain_id) {
+        Some(short_name) => format!("{short_name}:{addr}"),
+        None => addr.to_string(),
+    }
+}
+
+/// EIP-1559 fees to set on a transaction, in wei.
+#[derive(Debug, Clone, Copy)]
+pub struct TxFees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+/// Parse a fee given as a JSON number or a decimal/`0x`-hex string.
+fn parse_fee(value: &serde_json::Value, field: &str) -> Result<u128> {
+    match value {
+        serde_json::Value::Number(n) => n
+            .as_u64()
+            .map(u128::from)
+            .with_context(|| format!("{field} is not a whole number of wei: {n}")),
+        serde_json::Value::String(s) => match s.strip_prefix("0x") {
+            Some(hex) => u128::from_str_radix(hex, 16),
+            None => s.parse(),
+        }
+        .with_context(|| format!("invalid {field}: {s:?}")),
+        _ => bail!("missing or non-numeric {field}"),
+    }
+}
+
+/// Fetch `{maxFeePerGas, maxPriorityFeePerGas}` (wei) from a gas oracle endpoint.
+async fn fetch_oracle_fees(url: &str) -> Result<TxFees> {
+    let body: serde_json::Value = reqwest::Client::new()
+        .get(url)
+        .timeout(GAS_ORACLE_TIMEOUT)
+        .send()
+        .await
+        .with_context(|| format!("failed to reach gas oracle at {url}"))?
+        .error_for_status()
+        .with_context(|| format!("gas oracle at {url} returned an error"))?
+        .json()
+        .await
+        .context("failed to parse gas oracle response")?;
+
+    let fees = TxFees {
+        max_fee_per_gas: parse_fee(&body["maxFeePerGas"], "maxFeePerGas")?,
+        max_priority_fee_per_gas: parse_fee(&body["maxPriorityFeePerGas"], "maxPriorityFeePerGas")?,
+    };
+    if fees.max_priority_fee_per_gas > fees.max_fee_per_gas {
+        bail!(
+            "gas oracle returned maxPriorityFeePerGas {} above maxFeePerGas {}",
+            fees.max_priority_fee_per_gas,
+            fees.max_fee_per_gas
+        );
+    }
+    Ok(fees)
+}
+
+/// The `--gas-oracle` suggestion, or `None` to let the node estimate (also when the
+/// oracle is unreachable or returns garbage).
+async fn oracle_fees(deploy: &DeployArgs, progress: &dyn ProgressReporter) -> Option<TxFees> {
+    let url = deploy.gas_oracle.as_deref()?;
+    match fetch_oracle_fees(url).await {
+        Ok(fees) => {
+            progress.info(&format!(
+                "using gas oracle fees: maxFeePerGas={} maxPriorityFeePerGas={}",
+                fees.max_fee_per_gas, fees.max_priority_fee_per_gas
+            ));
+            Some(fees)
+        }
+        Err(e) => {
+            progress.info(&format!(
+                "{} {:#}; falling back to the node's fee estimate",
+                style::warning("warning:"),
+                redact::Redacted(&e)
+            ));
+            None
+        }
+    }
+}
+
+/// Parse a `--min-gas-price` value given in gwei (decimals allowed) into wei.
+pub fn parse_gwei(s: &str) -> std::result::Result<u128, String> {
+    let wei = alloy::primitives::utils::parse_units(s, "gwei")
+        .map_err(|e| format!("invalid gwei amount {s:?}: {e}"))?
+        .get_absolute();
+    u128::try_from(wei).map_err(|_| format!("gas price {s} gwei is out of range"))
+}
+
+/// Fees to set on the next transaction, or `None` to let the node estimate: the
+/// `--gas-oracle` suggestion, with `maxFeePerGas` raised to `--min-gas-price` if it
+/// falls below it.  With a floor but no oracle, the node's estimate is fetched so it
+/// can be clamped.
+pub async fn tx_fees(
+    provider: &impl Provider<Ethereum>,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<TxFees>> {
+    let oracle = oracle_fees(deploy, progress).await;
+    let Some(floor) = deploy.min_gas_price else {
+        return Ok(oracle);
+    };
+    let mut fees = match oracle {
+        Some(fees) => fees,
+        None => {
+            let estimate = provider
+                .estimate_eip1559_fees()
+                .await
+                .context("failed to estimate EIP-1559 fees from RPC")?;
+            TxFees {
+                max_fee_per_gas: estimate.max_fee_per_gas,
+                max_priority_fee_per_gas: estimate.max_priority_fee_per_gas,
+            }
+        }
+    };
+    if fees.max_fee_per_gas < floor {
+        progress.info(&format!(
+            "raising maxFeePerGas from {} to the --min-gas-price floor of {floor} wei",
+            fees.max_fee_per_gas
+        ));
+        fees.max_fee_per_gas = floor;
+    }
+    Ok(Some(fees))
+}
+
+/// `gasPrice` for a legacy transaction: the node's `eth_gasPrice`, raised to
+/// `--min-gas-price`.  `--gas-oracle` returns EIP-1559 fees, so it is not consulted.
+async fn legacy_gas_price(
+    provider: &impl Provider<Ethereum>,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<u128> {
+    let price = provider
+        .get_gas_price()
+        .await
+        .context("failed to fetch the gas price from RPC")?;
+    match deploy.min_gas_price {
+        Some(floor) if price < floor => {
+            progress.info(&format!(
+                "raising gasPrice from {price} to the --min-gas-price floor of {floor} wei"
+            ));
+            Ok(floor)
+        }
+        _ => Ok(price),
     }
 }
 
@@ -36,11 +802,12 @@ pub struct DeployOutput {
 pub fn create_provider(
     rpc_url: &str,
     private_key: &str,
-) -> Result<impl Provider<Ethereum> + Clone> {
+) -> Result<impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone + use<>> {
     let signer: PrivateKeySigner = private_key
         .parse()
         .context("failed to parse private key")?;
 
+    redact::register_url(rpc_url);
     let url: reqwest::Url = rpc_url
         .parse()
         .with_context(|| format!("invalid RPC URL: {rpc_url}"))?;
@@ -52,17 +819,310 @@ pub fn create_provider(
     Ok(provider)
 }
 
+/// How long each candidate endpoint gets to answer [`select_rpc_url`]'s probe.
+const RPC_PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Choose the RPC endpoint for the run: the first of `rpc_url` (itself possibly a
+/// comma-separated list) and then `fallbacks` (`--rpc-fallback`) that answers a
+/// chain-ID query, tried in order.  A single candidate is returned without a probe.
+pub async fn select_rpc_url(
+    rpc_url: &str,
+    fallbacks: &[String],
+    progress: &dyn ProgressReporter,
+) -> Result<String> {
+    let candidates: Vec<&str> = rpc_url
+        .split(',')
+        .chain(fallbacks.iter().map(String::as_str))
+        .map(str::trim)
+        .filter(|url| !url.is_empty())
+        .collect();
+    for url in &candidates {
+        redact::register_url(url);
+    }
+    match candidates.as_slice() {
+        [] => bail!("--rpc-url is empty"),
+        [only] => return Ok(only.to_string()),
+        _ => {}
+    }
+
+    let mut failures = Vec::new();
+    for url in &candidates {
+        let probe = async {
+            create_read_provider(url)?
+                .get_chain_id()
+                .await
+                .context("chain ID query failed")
+        };
+        let outcome = match tokio::time::timeout(RPC_PROBE_TIMEOUT, probe).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(anyhow::anyhow!("no answer within {}s", RPC_PROBE_TIMEOUT.as_secs())),
+        };
+        match outcome {
+            Ok(chain_id) => {
+                progress.info(&format!("using RPC endpoint {url} (chain {chain_id})"));
+                return Ok(url.to_string());
+            }
+            Err(e) => {
+                let e = format!("{:#}", redact::Redacted(&e));
+                progress.info(&format!(
+                    "{} RPC endpoint {url} failed: {e}",
+                    style::warning("warning:")
+                ));
+                failures.push(format!("{url}: {e}"));
+            }
+        }
+    }
+    bail!("no RPC endpoint answered:\n  {}", failures.join("\n  "))
+}
+
+/// Query the chain ID from the RPC, bailing if it differs from `expected`.
+pub async fn resolve_chain_id(
+    provider: &impl Provider<Ethereum>,
+    expected: Option<u64>,
+    progress: &dyn ProgressReporter,
+) -> Result<u64> {
+    let chain_id = retry::retry_async("chain ID query", progress, || async {
+        provider
+            .get_chain_id()
+            .await
+            .context("failed to query chain ID from RPC")
+    })
+    .await?;
+
+    if let Some(expected) = expected
+        && expected != chain_id
+    {
+        anyhow::bail!("RPC is on chain {chain_id}, but --chain-id {expected} was expected");
+    }
+
+    Ok(chain_id)
+}
+
+/// How far before the current block a `t_start` may lie before it is flagged: about two
+/// weeks of 12s L1 blocks.
+const STALE_T_START_BLOCKS: u64 = 100_000;
+
+/// Check a version's `[t_start, t_end]` block window against the chain head.
+///
+/// A `t_end` that has already passed makes the version expired on arrival, and a
+/// `t_start` far in the past usually means timestamps and block numbers were mixed up.
+/// Both are warnings, or errors with `strict`.  The open-ended defaults (`t_start` 0,
+/// `t_end` `UINT256_MAX`) are never flagged.
+pub async fn check_window(
+    provider: &impl Provider<Ethereum>,
+    t_start: U256,
+    t_end: U256,
+    strict: bool,
+    indent: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    if t_start.is_zero() && t_end == U256::MAX {
+        return Ok(());
+    }
+    let current = retry::retry_async("block number query", progress, || async {
+        provider
+            .get_block_number()
+            .await
+            .context("failed to query the current block number from RPC")
+    })
+    .await?;
+    let current_block = U256::from(current);
+
+    let mut problems = Vec::new();
+    if t_end != U256::MAX && t_end <= current_block {
+        problems.push(format!(
+            "t_end {t_end} is not after the current block {current}, so the version would be expired as soon as it is registered"
+        ));
+    }
+    if !t_start.is_zero() && t_start.saturating_add(U256::from(STALE_T_START_BLOCKS)) < current_block {
+        problems.push(format!(
+            "t_start {t_start} is more than {STALE_T_START_BLOCKS} blocks before the current block {current}"
+        ));
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let hint = "(t_start and t_end are block numbers, not timestamps)";
+    if strict {
+        bail!("{} {hint}", problems.join("; "));
+    }
+    for problem in problems {
+        progress.info(&format!("{indent}{} {problem} {hint}", style::warning("warning:")));
+    }
+    Ok(())
+}
+
+/// Create a read-only provider (no signer) for queries such as the chain ID.
+pub fn create_read_provider(rpc_url: &str) -> Result<impl Provider<Ethereum> + Clone> {
+    redact::register_url(rpc_url);
+    let url: reqwest::Url = rpc_url
+        .parse()
+        .with_context(|| format!("invalid RPC URL: {rpc_url}"))?;
+
+    Ok(ProviderBuilder::new().connect_http(url))
+}
+
 /// Deploy a contract by reading its bytecode from a forge artifact JSON file.
 /// If `constructor_args` is provided, it is appended to the bytecode.
-///
-/// Automatically detects and deploys any unlinked libraries referenced in the
-/// artifact's `linkReferences`, then links them into the bytecode before deploying
-/// the main contract (similar to how Remix IDE handles library dependencies).
 pub async fn deploy_from_artifact(
-    provider: &(impl Provider<Ethereum> + Clone),
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone),
     artifact_path: &Path,
-    constructor_args: Option<Bytes>,
+    constructor_args: Option<&ConstructorArgs>,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
 ) -> Result<DeployOutput> {
+    let bytecode = init_code(provider, artifact_path, constructor_args, deploy, progress).await?;
+    let contract = artifact_path.file_stem().unwrap_or_default().to_string_lossy();
+    let what = format!("{contract} deployment");
+    send_deployment(provider, bytecode, deploy, &what, progress)
+        .await?
+        .finish(&what)
+        .await
+}
+
+/// A deployment that [`send_deployment`] broadcast, or found already mined.
+enum Deployment {
+    Pending(alloy::providers::PendingTransactionBuilder<Ethereum>),
+    Existing(DeployOutput),
+}
+
+impl Deployment {
+    /// Wait for the deployment to be mined and return where it deployed to.
+    async fn finish(self, what: &str) -> Result<DeployOutput> {
+        let pending_tx = match self {
+            Deployment::Existing(output) => return Ok(output),
+            Deployment::Pending(pending_tx) => pending_tx,
+        };
+        let tx_hash = *pending_tx.tx_hash();
+
+        let receipt = pending_tx
+            .get_receipt()
+            .await
+            .with_context(|| format!("{what} transaction failed"))?;
+
+        let deployed_to = receipt
+            .contract_address
+            .context("no contract address in deployment receipt")?;
+        crate::receipt::record_completed(what, "address", deployed_to);
+
+        Ok(DeployOutput {
+            deployed_to,
+            transaction_hash: tx_hash,
+        })
+    }
+}
+
+/// Broadcast a deployment of `init_code`.
+///
+/// With `--idempotent`, the transaction recorded in the [`DeploymentJournal`] for the
+/// same chain, deployer, and init code is looked up first: if it was mined, its
+/// contract is reused; if the node still has it pending, it is waited for.  Only a
+/// transaction the node no longer knows (or one that reverted) is sent again.
+async fn send_deployment(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum>),
+    init_code: Vec<u8>,
+    deploy: &DeployArgs,
+    what: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Deployment> {
+    let journal_key = match &deploy.idempotent {
+        Some(journal) => {
+            let chain_id = provider.get_chain_id().await.context("failed to fetch chain ID from RPC")?;
+            let key = format!(
+                "{chain_id}:{}:{}",
+                provider.default_signer_address(),
+                alloy::primitives::keccak256(&init_code)
+            );
+            if let Some(tx_hash) = journal.get(&key)?
+                && let Some(existing) = earlier_deployment(provider, tx_hash, what, progress).await?
+            {
+                return Ok(existing);
+            }
+            Some((journal, key))
+        }
+        None => None,
+    };
+
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_deploy_code(Bytes::from(init_code));
+    let pending_tx = send_transaction(provider, tx, deploy, what, progress).await?;
+    if let Some((journal, key)) = journal_key {
+        journal.record(&key, *pending_tx.tx_hash())?;
+    }
+    Ok(Deployment::Pending(pending_tx))
+}
+
+/// The deployment made by the journaled transaction `tx_hash`, if it can be reused.
+async fn earlier_deployment(
+    provider: &impl Provider<Ethereum>,
+    tx_hash: FixedBytes<32>,
+    what: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<Deployment>> {
+    let receipt = provider
+        .get_transaction_receipt(tx_hash)
+        .await
+        .with_context(|| format!("failed to fetch the receipt of earlier {what} {tx_hash}"))?;
+    match receipt {
+        Some(receipt) => match receipt.contract_address.filter(|_| receipt.status()) {
+            Some(deployed_to) => {
+                progress.info(&format!(
+                    "reusing {deployed_to} from earlier {what} {tx_hash} (--idempotent)"
+                ));
+                Ok(Some(Deployment::Existing(DeployOutput {
+                    deployed_to,
+                    transaction_hash: tx_hash,
+                })))
+            }
+            None => {
+                progress.info(&format!(
+                    "{} earlier {what} {tx_hash} reverted; deploying again",
+                    style::warning("warning:")
+                ));
+                Ok(None)
+            }
+        },
+        None => {
+            let known = provider
+                .get_transaction_by_hash(tx_hash)
+                .await
+                .with_context(|| format!("failed to look up earlier {what} {tx_hash}"))?;
+            if known.is_some() {
+                progress.info(&format!(
+                    "waiting for earlier {what} {tx_hash} instead of sending another (--idempotent)"
+                ));
+                let pending_tx = alloy::providers::PendingTransactionBuilder::new(provider.root().clone(), tx_hash);
+                Ok(Some(Deployment::Pending(pending_tx)))
+            } else {
+                progress.info(&format!(
+                    "{} earlier {what} {tx_hash} is unknown to the node (dropped?); deploying again",
+                    style::warning("warning:")
+                ));
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Build the init code (creation bytecode followed by `constructor_args`) for the
+/// contract in a forge artifact JSON file.
+///
+/// Automatically detects and deploys any unlinked libraries referenced in the
+/// artifact's `linkReferences`, then links them into the bytecode (similar to how
+/// Remix IDE handles library dependencies).  Libraries given in `deploy.libraries`
+/// are linked to their existing address instead.
+///
+/// With `--parallel-libraries`, the library deployments are broadcast one after
+/// another (so each gets the next nonce) without waiting in between, and their
+/// receipts are awaited together before linking.
+pub async fn init_code(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone),
+    artifact_path: &Path,
+    constructor_args: Option<&ConstructorArgs>,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<u8>> {
     let artifact_bytes = std::fs::read(artifact_path)
         .with_context(|| format!("failed to read artifact: {}", artifact_path.display()))?;
 
@@ -82,43 +1142,88 @@ pub async fn deploy_from_artifact(
         .to_string();
 
     // Auto-deploy any unlinked libraries and link them into the bytecode.
-    if let Some(link_refs) = artifact.pointer("/bytecode/linkReferences") {
-        if let Some(obj) = link_refs.as_object() {
-            let artifact_dir = artifact_path
-                .parent()
-                .and_then(|p| p.parent())
-                .context("cannot determine artifact output directory")?;
-
-            for (sol_file, libs) in obj {
-                let Some(libs) = libs.as_object() else {
-                    continue;
+    if let Some(link_refs) = artifact.pointer("/bytecode/linkReferences")
+        && let Some(obj) = link_refs.as_object()
+    {
+        let artifact_dir = artifact_path
+            .parent()
+            .and_then(|p| p.parent())
+            .context("cannot determine artifact output directory")?;
+
+        let mut pending_libraries = tokio::task::JoinSet::new();
+        for (sol_file, libs) in obj {
+            let Some(libs) = libs.as_object() else {
+                continue;
+            };
+            for lib_name in libs.keys() {
+                // linkReferences uses source paths like "src/Verifier.sol",
+                // but forge stores artifacts by filename: "out/Verifier.sol/".
+                let sol_filename = Path::new(sol_file)
+                    .file_name()
+                    .unwrap_or(sol_file.as_ref());
+                let lib_artifact_path = artifact_dir
+                    .join(sol_filename)
+                    .join(format!("{lib_name}.json"));
+
+                let fq_name = format!("{sol_file}:{lib_name}");
+                let lib_addr = match deploy.library_address(sol_file, lib_name) {
+                    Some(addr) => {
+                        progress.info(&format!("  linking library {lib_name} to existing {addr}"));
+                        addr
+                    }
+                    None if deploy.parallel_libraries => {
+                        progress.info(&format!("  deploying library {lib_name}..."));
+                        let lib_code = Box::pin(init_code(
+                            provider,
+                            &lib_artifact_path,
+                            None,
+                            deploy,
+                            progress,
+                        ))
+                        .await?;
+                        let what = format!("{lib_name} deployment");
+                        let lib_deployment =
+                            send_deployment(provider, lib_code, deploy, &what, progress).await?;
+                        let lib_name = lib_name.clone();
+                        pending_libraries.spawn(async move {
+                            let lib_deploy = lib_deployment
+                                .finish(&what)
+                                .await
+                                .with_context(|| format!("failed to deploy library {lib_name}"))?;
+                            Ok::<_, anyhow::Error>((fq_name, lib_name, lib_deploy.deployed_to))
+                        });
+                        continue;
+                    }
+                    None => {
+                        progress.info(&format!("  deploying library {lib_name}..."));
+                        let lib_deploy = Box::pin(deploy_from_artifact(
+                            provider,
+                            &lib_artifact_path,
+                            None,
+                            deploy,
+                            progress,
+                        ))
+                        .await?;
+                        progress.info(&format!(
+                            "  {lib_name} deployed to {}",
+                            lib_deploy.deployed_to
+                        ));
+                        lib_deploy.deployed_to
+                    }
                 };
-                for lib_name in libs.keys() {
-                    // linkReferences uses source paths like "src/Verifier.sol",
-                    // but forge stores artifacts by filename: "out/Verifier.sol/".
-                    let sol_filename = Path::new(sol_file)
-                        .file_name()
-                        .unwrap_or(sol_file.as_ref());
-                    let lib_artifact_path = artifact_dir
-                        .join(sol_filename)
-                        .join(format!("{lib_name}.json"));
-
-                    eprintln!("  deploying library {lib_name}...");
-                    let lib_deploy = Box::pin(deploy_from_artifact(
-                        provider,
-                        &lib_artifact_path,
-                        None,
-                    ))
-                    .await?;
-                    eprintln!("  {lib_name} deployed to {}", lib_deploy.deployed_to);
-
-                    let fq_name = format!("{sol_file}:{lib_name}");
-                    let placeholder = library_placeholder(&fq_name);
-                    let addr_hex = hex::encode(lib_deploy.deployed_to);
-                    bytecode_hex = bytecode_hex.replace(&placeholder, &addr_hex);
-                }
+
+                let placeholder = library_placeholder(&fq_name);
+                let addr_hex = hex::encode(lib_addr);
+                bytecode_hex = bytecode_hex.replace(&placeholder, &addr_hex);
             }
         }
+
+        while let Some(joined) = pending_libraries.join_next().await {
+            let (fq_name, lib_name, lib_addr) = joined.context("library deployment task failed")??;
+            progress.info(&format!("  {lib_name} deployed to {lib_addr}"));
+            let placeholder = library_placeholder(&fq_name);
+            bytecode_hex = bytecode_hex.replace(&placeholder, &hex::encode(lib_addr));
+        }
     }
 
     let raw = bytecode_hex.strip_prefix("0x").unwrap_or(&bytecode_hex);
@@ -130,34 +1235,447 @@ pub async fn deploy_from_artifact(
     })?;
 
     if let Some(args) = constructor_args {
-        bytecode.extend_from_slice(&args);
+        bytecode.extend_from_slice(args.bytes());
+    }
+
+    Ok(bytecode)
+}
+
+/// The deterministic deployment proxy (github.com/Arachnid/deterministic-deployment-proxy),
+/// deployed at this address on most chains and used by forge for CREATE2 deployments.
+/// Its calldata is the 32-byte salt followed by the init code.
+pub const CREATE2_FACTORY: Address = address!("4e59b44847b379578588920cA78FbF26c0B4956C");
+
+/// The address [`deploy_create2`] deploys `init_code` to with `salt`.  It depends on
+/// nothing else, so it is the same on every chain with the factory.
+pub fn create2_address(salt: B256, init_code: &[u8]) -> Address {
+    CREATE2_FACTORY.create2_from_code(salt, init_code)
+}
+
+/// Deploy `init_code` through [`CREATE2_FACTORY`] to [`create2_address`].  Fails
+/// before sending if the chain has no factory or the address is already taken.
+pub async fn deploy_create2(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum>),
+    salt: B256,
+    init_code: Vec<u8>,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<DeployOutput> {
+    let deployed_to = create2_address(salt, &init_code);
+
+    let factory_code = retry::retry_async("code lookup", progress, || async {
+        provider
+            .get_code_at(CREATE2_FACTORY)
+            .await
+            .context("failed to look up the CREATE2 factory")
+    })
+    .await?;
+    if factory_code.is_empty() {
+        bail!(
+            "no CREATE2 factory at {CREATE2_FACTORY} on this chain -- deploy the deterministic deployment proxy first, or drop the salt"
+        );
+    }
+    let existing_code = retry::retry_async("code lookup", progress, || async {
+        provider
+            .get_code_at(deployed_to)
+            .await
+            .with_context(|| format!("failed to look up code at {deployed_to}"))
+    })
+    .await?;
+    if !existing_code.is_empty() {
+        bail!("a contract is already deployed at {deployed_to} -- use a different salt");
     }
 
+    let mut calldata = salt.to_vec();
+    calldata.extend_from_slice(&init_code);
     let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
-        .with_deploy_code(Bytes::from(bytecode));
+        .with_to(CREATE2_FACTORY)
+        .with_input(calldata);
+    let pending_tx = send_transaction(provider, tx, deploy, "CREATE2 deployment", progress).await?;
 
-    let pending_tx = provider
-        .send_transaction(tx)
+    let tx_hash = *pending_tx.tx_hash();
+
+    let receipt = pending_tx
+        .get_receipt()
         .await
-        .context("failed to broadcast contract deployment")?;
+        .context("CREATE2 deployment transaction failed")?;
+    if !receipt.status() {
+        bail!("CREATE2 deployment transaction {tx_hash} reverted");
+    }
+    crate::receipt::record_completed("CREATE2 deployment", "address", deployed_to);
+
+    Ok(DeployOutput {
+        deployed_to,
+        transaction_hash: tx_hash,
+    })
+}
+
+/// The first version a `ComplianceFactory` registers; its verifier is the one the
+/// factory deploys in the same transaction.
+pub struct FactoryVersion {
+    pub merkle_root: FixedBytes<32>,
+    pub t_start: U256,
+    pub t_end: U256,
+    pub metadata_hash: String,
+    pub leaves_hash: String,
+}
+
+/// The contracts created by one `ComplianceFactory.deploy` transaction.
+pub struct FactoryDeployment {
+    pub definition: Address,
+    pub verifier: Address,
+    pub transaction_hash: FixedBytes<32>,
+}
+
+/// Bail unless there is a contract at `factory`, before anything is compiled or uploaded.
+pub async fn check_factory(
+    provider: &(impl Provider<Ethereum> + Clone),
+    factory: Address,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let code = retry::retry_async("code lookup", progress, || async {
+        provider
+            .get_code_at(factory)
+            .await
+            .with_context(|| format!("failed to look up code at {factory}"))
+    })
+    .await?;
+    if code.is_empty() {
+        bail!("no contract at --factory {factory} -- deploy contracts/src/ComplianceFactory.sol first");
+    }
+    Ok(())
+}
+
+/// Deploy a verifier from `verifier_init_code` and a ComplianceDefinition owned by
+/// `regulator` with the verifier as its first version, all in one
+/// `ComplianceFactory.deploy` transaction: either everything lands or nothing does.
+/// The addresses are read from the factory's `ComplianceDeployed` event.
+#[allow(clippy::too_many_arguments)]
+pub async fn deploy_via_factory(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum>),
+    factory: Address,
+    verifier_init_code: Vec<u8>,
+    regulator: Address,
+    name: &str,
+    version: FactoryVersion,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<FactoryDeployment> {
+    let calldata = ComplianceFactory::deployCall {
+        verifierInitCode: verifier_init_code.into(),
+        regulator,
+        name: name.to_string(),
+        merkleRoot: version.merkle_root,
+        tStart: version.t_start,
+        tEnd: version.t_end,
+        metadataHash: version.metadata_hash,
+        leavesHash: version.leaves_hash,
+    }
+    .abi_encode();
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_to(factory)
+        .with_input(calldata);
+    let pending_tx = send_transaction(provider, tx, deploy, "factory deployment", progress).await?;
 
     let tx_hash = *pending_tx.tx_hash();
 
     let receipt = pending_tx
         .get_receipt()
         .await
-        .context("contract deployment transaction failed")?;
+        .context("factory deployment transaction failed")?;
+    if !receipt.status() {
+        bail!("factory deployment transaction {tx_hash} reverted");
+    }
 
-    let deployed_to = receipt
-        .contract_address
-        .context("no contract address in deployment receipt")?;
+    let event = receipt
+        .logs()
+        .iter()
+        .filter(|log| log.address() == factory)
+        .find_map(|log| log.log_decode::<ComplianceFactory::ComplianceDeployed>().ok())
+        .with_context(|| {
+            format!("factory transaction {tx_hash} emitted no ComplianceDeployed event -- is {factory} a ComplianceFactory?")
+        })?;
+    let event = event.inner.data;
+    if event.regulator != regulator {
+        bail!(
+            "factory at {factory} handed the definition to {}, not the requested regulator {regulator}",
+            event.regulator
+        );
+    }
+    crate::receipt::record_completed("factory definition deployment", "address", event.definition);
+    crate::receipt::record_completed("factory verifier deployment", "address", event.verifier);
 
-    Ok(DeployOutput {
-        deployed_to,
+    Ok(FactoryDeployment {
+        definition: event.definition,
+        verifier: event.verifier,
         transaction_hash: tx_hash,
     })
 }
 
+/// RPC rejections caused by another sender of the same key claiming the nonce first.
+/// Reverts and other failures never match these.  Geth's "already known" is left out
+/// on purpose: it means the node already holds this very transaction, and resending
+/// it at a new nonce would broadcast it twice.
+const NONCE_ERRORS: &[&str] = &[
+    "nonce too low",
+    "replacement transaction underpriced",
+    "nonce has already been used",
+];
+
+/// How nodes word a rejection for a sender that cannot cover gas * price + value
+/// (geth: "insufficient funds for gas * price + value"; others vary the suffix).
+const INSUFFICIENT_FUNDS_ERROR: &str = "insufficient funds";
+
+/// Resubmissions after a nonce rejection with `--retry-on-nonce-error`.
+const NONCE_RETRIES: u32 = 3;
+
+fn is_nonce_error(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    NONCE_ERRORS.iter().any(|pattern| message.contains(pattern))
+}
+
+/// Broadcast `tx` with the run's fees and, under `--start-nonce`, its next nonce.
+///
+/// With `--retry-on-nonce-error`, a transaction rejected for a stale or taken nonce is
+/// resubmitted with the account's freshly fetched pending nonce, up to
+/// [`NONCE_RETRIES`] times.  From then on the run assigns nonces itself, counting up
+/// from that one, since the provider's cached nonce is known to be stale.  Every
+/// other error fails immediately.
+async fn send_transaction(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum>),
+    mut tx: <Ethereum as alloy::network::Network>::TransactionRequest,
+    deploy: &DeployArgs,
+    what: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<alloy::providers::PendingTransactionBuilder<Ethereum>> {
+    if deploy.tx_type.legacy(provider, progress).await {
+        tx = tx.with_gas_price(legacy_gas_price(provider, deploy, progress).await?);
+    } else if let Some(fees) = tx_fees(provider, deploy, progress).await? {
+        tx = tx
+            .with_max_fee_per_gas(fees.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(fees.max_priority_fee_per_gas);
+    }
+
+    let _broadcasting = deploy.nonces.broadcasting().await;
+    let mut attempt = 0;
+    loop {
+        let mut attempt_tx = tx.clone();
+        if let Some(nonce) = deploy.nonces.peek() {
+            attempt_tx = attempt_tx.with_nonce(nonce);
+        }
+        match provider.send_transaction(attempt_tx).await {
+            Ok(pending) => {
+                deploy.nonces.advance();
+                crate::receipt::record_completed(what, "tx_hash", pending.tx_hash());
+                return Ok(pending);
+            }
+            Err(e)
+                if deploy.retry_on_nonce_error
+                    && attempt < NONCE_RETRIES
+                    && is_nonce_error(&e.to_string()) =>
+            {
+                attempt += 1;
+                let sender = provider.default_signer_address();
+                let nonce = provider
+                    .get_transaction_count(sender)
+                    .pending()
+                    .await
+                    .with_context(|| format!("failed to fetch the pending nonce of {sender}"))?;
+                progress.info(&format!(
+                    "{} {what} rejected ({}), resubmitting with pending nonce {nonce} (retry {attempt}/{NONCE_RETRIES})...",
+                    style::warning("warning:"),
+                    redact::Redacted(&e)
+                ));
+                deploy.nonces.restart_at(nonce);
+            }
+            Err(e) if e.to_string().to_ascii_lowercase().contains(INSUFFICIENT_FUNDS_ERROR) => {
+                let hint = funding_hint(provider, &tx).await;
+                return Err(e).with_context(|| format!("failed to broadcast {what}: {hint}"));
+            }
+            Err(e) => return Err(e).with_context(|| format!("failed to broadcast {what}")),
+        }
+    }
+}
+
+/// Explain an insufficient-funds rejection: the sender, its balance, roughly what `tx`
+/// costs, and the network to fund it on.  Lookups that fail are left out, since the
+/// hint is secondary to the RPC error it accompanies.
+async fn funding_hint(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum>),
+    tx: &<Ethereum as alloy::network::Network>::TransactionRequest,
+) -> String {
+    use alloy::primitives::utils::format_ether;
+
+    let sender = provider.default_signer_address();
+    let network = match provider.get_chain_id().await {
+        Ok(chain_id) => crate::etherscan::network_name(chain_id).to_string(),
+        Err(_) => "the target chain".to_string(),
+    };
+    let balance = provider.get_balance(sender).await.ok();
+
+    // Estimate without the fee caps: with them, the node would reject the estimate for
+    // the same lack of funds.
+    let mut estimate_tx = tx.clone().with_from(sender);
+    estimate_tx.max_fee_per_gas = None;
+    estimate_tx.max_priority_fee_per_gas = None;
+    estimate_tx.gas_price = None;
+    let gas = provider.estimate_gas(estimate_tx).await.ok();
+    let max_fee = match tx.max_fee_per_gas.or(tx.gas_price) {
+        Some(fee) => Some(fee),
+        None => provider.estimate_eip1559_fees().await.ok().map(|fees| fees.max_fee_per_gas),
+    };
+    let cost = gas
+        .zip(max_fee)
+        .map(|(gas, fee)| U256::from(gas) * U256::from(fee) + tx.value.unwrap_or_default());
+
+    match (balance, cost) {
+        (Some(balance), Some(cost)) if cost > balance => format!(
+            "{sender} has {} ETH but the transaction needs up to {} ETH -- fund {sender} with at least {} ETH on {network}",
+            format_ether(balance),
+            format_ether(cost),
+            format_ether(cost - balance)
+        ),
+        (Some(balance), _) => format!(
+            "{sender} has {} ETH, not enough for this transaction -- fund {sender} on {network}",
+            format_ether(balance)
+        ),
+        (None, _) => format!("fund {sender} on {network}"),
+    }
+}
+
+/// Convert a JSON value into a `DynSolValue` of the given type.  Arrays and tuples map
+/// to JSON arrays; leaf values may be JSON strings, numbers, or booleans.
+fn json_to_sol_value(ty: &DynSolType, value: &serde_json::Value) -> Result<DynSolValue> {
+    match (ty, value) {
+        (DynSolType::Array(inner), serde_json::Value::Array(items)) => Ok(DynSolValue::Array(
+            items
+                .iter()
+                .map(|v| json_to_sol_value(inner, v))
+                .collect::<Result<_>>()?,
+        )),
+        (DynSolType::FixedArray(inner, len), serde_json::Value::Array(items)) => {
+            if items.len() != *len {
+                bail!("expected {len} elements for {ty}, got {}", items.len());
+            }
+            Ok(DynSolValue::FixedArray(
+                items
+                    .iter()
+                    .map(|v| json_to_sol_value(inner, v))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        (DynSolType::Tuple(types), serde_json::Value::Array(items)) => {
+            if items.len() != types.len() {
+                bail!("expected {} fields for {ty}, got {}", types.len(), items.len());
+            }
+            Ok(DynSolValue::Tuple(
+                types
+                    .iter()
+                    .zip(items)
+                    .map(|(t, v)| json_to_sol_value(t, v))
+                    .collect::<Result<_>>()?,
+            ))
+        }
+        (DynSolType::Array(_) | DynSolType::FixedArray(..) | DynSolType::Tuple(_), other) => {
+            bail!("expected a JSON array for {ty}, got {other}")
+        }
+        (_, serde_json::Value::String(s)) => ty
+            .coerce_str(s)
+            .with_context(|| format!("invalid {ty} value: {s:?}")),
+        (_, v @ (serde_json::Value::Number(_) | serde_json::Value::Bool(_))) => ty
+            .coerce_str(&v.to_string())
+            .with_context(|| format!("invalid {ty} value: {v}")),
+        (_, other) => bail!("unsupported JSON value for {ty}: {other}"),
+    }
+}
+
+/// ABI-encoded constructor arguments (no selector).  They are encoded once from typed
+/// values and then used for both the deployment and its explorer verification, so the
+/// arguments a contract was verified with are always the ones it was deployed with.
+#[derive(Clone, Debug)]
+pub struct ConstructorArgs(Bytes);
+
+impl ConstructorArgs {
+    /// Encode `values` as the constructor's parameters, in order.
+    pub fn encode(values: &[DynSolValue]) -> Self {
+        Self(Bytes::from(DynSolValue::Tuple(values.to_vec()).abi_encode_params()))
+    }
+
+    /// Already-encoded arguments, as hex with or without `0x`, for contracts whose
+    /// deployment this run did not perform (e.g. `verify --constructor-args`).
+    pub fn from_hex(encoded: &str) -> Result<Self> {
+        hex::decode(encoded.strip_prefix("0x").unwrap_or(encoded))
+            .map(|bytes| Self(Bytes::from(bytes)))
+            .with_context(|| format!("invalid constructor arguments (expected ABI-encoded hex): {encoded}"))
+    }
+
+    pub fn bytes(&self) -> &Bytes {
+        &self.0
+    }
+
+    /// The encoding as unprefixed hex, as block explorers expect it.
+    pub fn hex(&self) -> String {
+        hex::encode(&self.0)
+    }
+}
+
+/// ABI-encode constructor arguments read from a JSON array file, validated against the
+/// constructor ABI in the forge artifact.
+pub fn encode_constructor_args_json(artifact_path: &Path, json_path: &Path) -> Result<ConstructorArgs> {
+    let artifact: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(artifact_path)
+            .with_context(|| format!("failed to read artifact: {}", artifact_path.display()))?,
+    )
+    .with_context(|| format!("failed to parse artifact JSON: {}", artifact_path.display()))?;
+    let abi: JsonAbi = serde_json::from_value(artifact.get("abi").cloned().with_context(|| {
+        format!("missing abi in artifact: {}", artifact_path.display())
+    })?)
+    .with_context(|| format!("invalid abi in artifact: {}", artifact_path.display()))?;
+    let constructor = abi
+        .constructor()
+        .with_context(|| format!("no constructor in abi of {}", artifact_path.display()))?;
+
+    let args: Vec<serde_json::Value> = serde_json::from_slice(
+        &std::fs::read(json_path)
+            .with_context(|| format!("failed to read {}", json_path.display()))?,
+    )
+    .with_context(|| format!("{} must contain a JSON array of arguments", json_path.display()))?;
+
+    let signature: Vec<String> = constructor
+        .inputs
+        .iter()
+        .map(|p| format!("{} {}", p.ty, p.name))
+        .collect();
+    if args.len() != constructor.inputs.len() {
+        bail!(
+            "constructor expects {} argument(s) ({}), but {} has {}",
+            constructor.inputs.len(),
+            signature.join(", "),
+            json_path.display(),
+            args.len()
+        );
+    }
+
+    let values = constructor
+        .inputs
+        .iter()
+        .zip(&args)
+        .enumerate()
+        .map(|(i, (param, value))| {
+            let ty = param
+                .resolve()
+                .with_context(|| format!("unsupported constructor parameter type {}", param.ty))?;
+            json_to_sol_value(&ty, value)
+                .with_context(|| format!("argument {i} ({}) in {}", signature[i], json_path.display()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let encoded = constructor
+        .abi_encode_input(&values)
+        .context("failed to ABI-encode constructor arguments")?;
+    Ok(ConstructorArgs(Bytes::from(encoded)))
+}
+
 /// Compute the `__$<hash>$__` placeholder that Solidity uses for an unlinked library.
 /// `fully_qualified_name` is e.g. `"src/Verifier.sol:ZKTranscriptLib"`.
 fn library_placeholder(fully_qualified_name: &str) -> String {
@@ -166,47 +1684,346 @@ fn library_placeholder(fully_qualified_name: &str) -> String {
     format!("__${}$__", &hash_hex[..34])
 }
 
-pub async fn call_update_circuit(
+/// Outcome of a definition's `verify(bytes)` for an account (see [`check_compliance`]).
+pub enum ComplianceCheck {
+    /// `verify` returned this.
+    Returned(bool),
+    /// `verify` reverted -- typically the verifier rejecting the proof, or no version
+    /// being active -- for this reason.
+    Reverted(String),
+}
+
+/// Call `verify(proof)` on the definition at `cd` as `account`, at `block` (latest
+/// when `None`).  The definition binds the proof to `tx.origin`, so the call is made
+/// from `account`; nothing is sent.
+pub async fn check_compliance(
+    provider: &impl Provider<Ethereum>,
+    cd: Address,
+    account: Address,
+    proof: Bytes,
+    block: Option<u64>,
+) -> Result<ComplianceCheck> {
+    let contract = ComplianceDefinition::new(cd, provider);
+    let call = contract
+        .verify(proof)
+        .from(account)
+        .block(block.map_or_else(BlockId::latest, BlockId::number));
+    match call.call().await {
+        Ok(compliant) => Ok(ComplianceCheck::Returned(compliant)),
+        Err(e) => match e.as_revert_data() {
+            Some(data) => {
+                let reason = match e.as_decoded_interface_error::<ComplianceDefinition::ComplianceDefinitionErrors>() {
+                    Some(ComplianceDefinition::ComplianceDefinitionErrors::NoActiveVersion(_)) => {
+                        "NoActiveVersion: no version of the definition is active at that block".to_string()
+                    }
+                    _ if data.is_empty() => "no error data".to_string(),
+                    _ => format!("error data {data}"),
+                };
+                Ok(ComplianceCheck::Reverted(reason))
+            }
+            None => Err(e).with_context(|| format!("failed to call verify() on {cd}")),
+        },
+    }
+}
+
+/// Merkle root of the version of the definition at `cd` active at `block` (latest
+/// when `None`), the second public input `verify` checks a proof against.
+pub async fn active_merkle_root(provider: &impl Provider<Ethereum>, cd: Address, block: Option<u64>) -> Result<B256> {
+    let version = ComplianceDefinition::new(cd, provider)
+        .getActiveVersion()
+        .block(block.map_or_else(BlockId::latest, BlockId::number))
+        .call()
+        .await
+        .with_context(|| format!("failed to read the active version of {cd}"))?;
+    Ok(version.merkleRoot)
+}
+
+/// ABI-encode an `updateCircuit` call (selector + args) without sending it, for
+/// handing the transaction to an external signer.  Uses `deploy.definition_abi` when set.
+pub fn update_circuit_calldata(args: UpdateCircuitArgs, deploy: &DeployArgs) -> Result<Bytes> {
+    if let Some(ref abi) = deploy.definition_abi {
+        return abi.encode(&args);
+    }
+    Ok(ComplianceDefinition::updateCircuitCall {
+        newVerifier: args.verifier,
+        newMerkleRoot: args.merkle_root,
+        tStart: args.t_start,
+        tEnd: args.t_end,
+        metadataHash: args.metadata_hash,
+        leavesHash: args.leaves_hash,
+    }
+    .abi_encode()
+    .into())
+}
+
+/// Bail unless the contract at `verifier` implements `verify(bytes,bytes32[])`, so a
+/// wrong artifact (e.g. a library) is never registered as a verifier.  A call with an
+/// empty proof must be turned down by the verifier itself -- a revert with an error, or
+/// a `bool` result -- rather than revert without data, as an unknown selector does.
+pub async fn check_verifier_interface(
+    provider: &impl Provider<Ethereum>,
+    verifier: Address,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let code = retry::retry_async("code lookup", progress, || async {
+        provider
+            .get_code_at(verifier)
+            .await
+            .with_context(|| format!("failed to look up code at {verifier}"))
+    })
+    .await?;
+    if code.is_empty() {
+        bail!("no contract code at verifier {verifier}");
+    }
+
+    let calldata = crate::bb::verifyCall {
+        _proof: Bytes::new(),
+        _publicInputs: Vec::new(),
+    }
+    .abi_encode();
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_to(verifier)
+        .with_input(calldata);
+    let not_a_verifier = |how: &str| {
+        anyhow::anyhow!(
+            "{verifier} does not implement verify(bytes,bytes32[]): a call with an empty proof {how} -- was the wrong artifact deployed?"
+        )
+    };
+    match provider.call(tx).await {
+        Ok(returned) => match crate::bb::verifyCall::abi_decode_returns(&returned) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(not_a_verifier(&format!("returned {} bytes that are not a bool", returned.len()))),
+        },
+        Err(e) => match e.as_error_resp() {
+            Some(payload) if payload.message.contains("revert") => {
+                if payload.as_revert_data().is_some_and(|data| !data.is_empty()) {
+                    Ok(())
+                } else {
+                    Err(not_a_verifier("reverted without an error, as for an unknown function"))
+                }
+            }
+            _ => Err(e).with_context(|| format!("failed to call verify() on {verifier}")),
+        },
+    }
+}
+
+/// Gas of calling `verify(proof, publicInputs)` on the HonkVerifier at `verifier`,
+/// measured with `eth_estimateGas` -- nothing is sent.  Fails if the verifier rejects
+/// the proof.
+pub async fn estimate_verify_gas(
+    provider: &impl Provider<Ethereum>,
+    verifier: Address,
+    proof: &crate::bb::ProofFiles,
+) -> Result<u64> {
+    let calldata = crate::bb::verify_calldata(&proof.proof, Some(&proof.public_inputs))?;
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_to(verifier)
+        .with_input(calldata);
+
+    let returned = provider
+        .call(tx.clone())
+        .await
+        .with_context(|| format!("verify() on {verifier} reverted for the proof"))?;
+    let accepted = crate::bb::verifyCall::abi_decode_returns(&returned)
+        .with_context(|| format!("unexpected verify() return data from {verifier}"))?;
+    if !accepted {
+        bail!("verifier {verifier} rejected the proof (verify() returned false)");
+    }
+
+    provider
+        .estimate_gas(tx)
+        .await
+        .with_context(|| format!("failed to estimate the gas of verify() on {verifier}"))
+}
+
+/// Bail unless the ComplianceDefinition at `compliance_definition_addr` is still
+/// controlled by `expected` (it may have been handed to another regulator).
+pub async fn check_regulator(
     provider: &(impl Provider<Ethereum> + Clone),
     compliance_definition_addr: Address,
-    new_verifier: Address,
-    merkle_root: FixedBytes<32>,
-    t_start: U256,
-    t_end: U256,
-    metadata_uri: String,
-    leaves_hash: String,
-) -> Result<FixedBytes<32>> {
+    expected: Address,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let actual = retry::retry_async("regulator query", progress, || async {
+        ComplianceDefinition::new(compliance_definition_addr, provider)
+            .regulator()
+            .call()
+            .await
+            .with_context(|| format!("failed to read regulator from {compliance_definition_addr}"))
+    })
+    .await?;
+    if actual != expected {
+        bail!(
+            "regulator mismatch on {compliance_definition_addr}: expected {expected}, but the definition is controlled by {actual}"
+        );
+    }
+    Ok(())
+}
+
+/// A version as stored in a ComplianceDefinition's `versions` array.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ComplianceVersion {
+    pub verifier: Address,
+    pub merkle_root: FixedBytes<32>,
+    pub t_start: U256,
+    pub t_end: U256,
+    pub metadata_hash: String,
+    pub leaves_hash: String,
+}
+
+/// Read the most recently published version of a ComplianceDefinition, or `None` if
+/// nothing has been published yet.
+pub async fn latest_version(
+    provider: &(impl Provider<Ethereum> + Clone),
+    compliance_definition_addr: Address,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<ComplianceVersion>> {
     let contract = ComplianceDefinition::new(compliance_definition_addr, provider);
 
-    let pending_tx = contract
-        .updateCircuit(new_verifier, merkle_root, t_start, t_end, metadata_uri, leaves_hash)
-        .send()
-        .await
-        .context("failed to broadcast updateCircuit transaction")?;
+    let count = retry::retry_async("version count query", progress, || async {
+        contract
+            .getVersionCount()
+            .call()
+            .await
+            .with_context(|| format!("failed to read version count from {compliance_definition_addr}"))
+    })
+    .await?;
+    if count.is_zero() {
+        return Ok(None);
+    }
+
+    let v = retry::retry_async("version query", progress, || async {
+        contract
+            .versions(count - U256::from(1))
+            .call()
+            .await
+            .with_context(|| format!("failed to read latest version from {compliance_definition_addr}"))
+    })
+    .await?;
+
+    Ok(Some(ComplianceVersion {
+        verifier: v.verifier,
+        merkle_root: v.merkleRoot,
+        t_start: v.tStart,
+        t_end: v.tEnd,
+        metadata_hash: v.metadataHash,
+        leaves_hash: v.leavesHash,
+    }))
+}
+
+pub async fn call_update_circuit(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone),
+    compliance_definition_addr: Address,
+    args: UpdateCircuitArgs,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<FixedBytes<32>> {
+    let function = deploy
+        .definition_abi
+        .as_ref()
+        .map_or("updateCircuit", |abi| abi.name())
+        .to_string();
+
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_to(compliance_definition_addr)
+        .with_input(update_circuit_calldata(args, deploy)?);
+    let pending_tx =
+        send_transaction(provider, tx, deploy, &format!("{function} transaction"), progress).await?;
 
     let tx_hash = *pending_tx.tx_hash();
 
     pending_tx
         .get_receipt()
         .await
-        .context("updateCircuit transaction failed")?;
+        .with_context(|| format!("{function} transaction failed"))?;
 
     Ok(tx_hash)
 }
 
+/// ABI-encode the `setLabel(label)` call for `--on-chain-label`, for `--print-calldata`.
+pub fn set_label_calldata(label: &str) -> Bytes {
+    ComplianceDefinition::setLabelCall { label: label.to_string() }.abi_encode().into()
+}
+
+/// Simulate `setLabel(label)` from `from` on `compliance_definition_addr`.  Returns
+/// `false`, with a warning, for a definition without `setLabel` (the call reverts
+/// without data, as for an unknown selector); any other failure is an error.
+pub async fn can_label(
+    provider: &impl Provider<Ethereum>,
+    compliance_definition_addr: Address,
+    from: Address,
+    label: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<bool> {
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_from(from)
+        .with_to(compliance_definition_addr)
+        .with_input(set_label_calldata(label));
+    match provider.call(tx).await {
+        Ok(_) => Ok(true),
+        Err(e) => match e.as_error_resp() {
+            Some(payload)
+                if payload.message.contains("revert")
+                    && payload.as_revert_data().is_none_or(|data| data.is_empty()) =>
+            {
+                progress.info(&format!(
+                    "{} {compliance_definition_addr} has no setLabel(string), not labelling the version",
+                    style::warning("warning:")
+                ));
+                Ok(false)
+            }
+            _ => Err(e).with_context(|| format!("setLabel on {compliance_definition_addr} would fail")),
+        },
+    }
+}
+
+/// Label the version just registered on `compliance_definition_addr` with
+/// `--on-chain-label`, returning the `setLabel` transaction hash.  The call is
+/// simulated first with [`can_label`]; a definition without `setLabel` is skipped and
+/// `None` returned, as is a run without a label.
+pub async fn label_version(
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone),
+    compliance_definition_addr: Address,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<FixedBytes<32>>> {
+    let Some(ref label) = deploy.on_chain_label else {
+        return Ok(None);
+    };
+    let from = provider.default_signer_address();
+    if !can_label(provider, compliance_definition_addr, from, label, progress).await? {
+        return Ok(None);
+    }
+    let tx = <Ethereum as alloy::network::Network>::TransactionRequest::default()
+        .with_from(from)
+        .with_to(compliance_definition_addr)
+        .with_input(set_label_calldata(label));
+
+    let pending_tx =
+        send_transaction(provider, tx, deploy, "setLabel transaction", progress).await?;
+    let tx_hash = *pending_tx.tx_hash();
+    pending_tx
+        .get_receipt()
+        .await
+        .context("setLabel transaction failed")?;
+    Ok(Some(tx_hash))
+}
+
 pub async fn call_update_params(
-    provider: &(impl Provider<Ethereum> + Clone),
+    provider: &(impl Provider<Ethereum> + WalletProvider<Ethereum> + Clone),
     compliance_definition_addr: Address,
     merkle_root: FixedBytes<32>,
     leaves_hash: String,
+    deploy: &DeployArgs,
+    progress: &dyn ProgressReporter,
 ) -> Result<FixedBytes<32>> {
     let contract = ComplianceDefinition::new(compliance_definition_addr, provider);
 
-    let pending_tx = contract
+    let tx = contract
         .updateParams(merkle_root, leaves_hash)
-        .send()
-        .await
-        .context("failed to broadcast updateParams transaction")?;
+        .into_transaction_request();
+    let pending_tx =
+        send_transaction(provider, tx, deploy, "updateParams transaction", progress).await?;
 
     let tx_hash = *pending_tx.tx_hash();
 
@@ -1,20 +1,144 @@
 use anyhow::{Context, Result, bail};
-use serde::Deserialize;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::time::Duration;
 use tokio::time::sleep;
 
+use crate::eth::ConstructorArgs;
+use crate::receipt::Receipt;
+use crate::progress::ProgressReporter;
+use crate::redact;
+use crate::retry;
+use crate::style;
+use crate::tools::{self, Tool};
+
 const ETHERSCAN_V2_API: &str = "https://api.etherscan.io/v2/api";
 const POLL_INTERVAL: Duration = Duration::from_secs(5);
 const MAX_POLL_ATTEMPTS: u32 = 20;
-const SUBMIT_RETRIES: u32 = 3;
+/// Shortest wait between verification submissions: an explorer that cannot locate a
+/// fresh contract yet needs a few blocks to index it, not a network retry's 2s.
 const SUBMIT_RETRY_DELAY: Duration = Duration::from_secs(10);
+const DEFAULT_KEY_PARAM: &str = "apikey";
 
 /// Optional Etherscan/block-explorer verification settings.
 #[derive(Clone, Default)]
 pub struct VerifyArgs {
     pub etherscan_api_key: Option<String>,
     pub verifier_url: Option<String>,
+    /// Query parameter name the explorer expects the API key in (default `apikey`).
+    pub verifier_key_param: Option<String>,
+    /// HTTP header to send the API key in instead of a query parameter.
+    pub verifier_key_header: Option<String>,
+    /// Wall-clock ceiling on status polling, independent of `MAX_POLL_ATTEMPTS`.
+    pub total_timeout: Option<Duration>,
+    /// Extra directories searched (in order) for contract sources not found in the project.
+    pub source_roots: Vec<PathBuf>,
+    /// Where to record each accepted submission's GUID, so polling can be resumed with
+    /// `check-verification` if the process dies before the explorer answers.
+    pub pending_dir: Option<PathBuf>,
+    /// Verification API per chain ID, from `--chains-file`.  Chains without an entry
+    /// use Etherscan's unified v2 endpoint.
+    pub chain_api_urls: HashMap<u64, String>,
+    /// Exit non-zero when a verification ends in [`VerificationOutcome::Failed`].
+    pub fail_on_verification_failure: bool,
+}
+
+/// A `[[chain]]` entry of a `--chains-file`.
+#[derive(Deserialize)]
+struct ChainConfig {
+    id: u64,
+    /// Verification API of the chain's own explorer.
+    api_url: Option<String>,
+    /// Circuit size above which verification on the chain is impractically expensive,
+    /// in place of the built-in limit.
+    gate_limit: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct ChainsFile {
+    #[serde(default)]
+    chain: Vec<ChainConfig>,
+}
+
+/// Per-chain settings read from a `--chains-file`.
+#[derive(Default)]
+pub struct ChainSettings {
+    /// Verification API per chain ID.
+    pub api_urls: HashMap<u64, String>,
+    /// Practical gate limit per chain ID, overriding [`crate::bb`]'s defaults.
+    pub gate_limits: HashMap<u64, u64>,
+}
+
+/// Read the per-chain settings from a TOML chains file of `[[chain]]` entries, each
+/// with an `id` and optionally an `api_url` and a `gate_limit`.
+pub fn load_chains_file(path: &Path) -> Result<ChainSettings> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read chains file {}", path.display()))?;
+    let file: ChainsFile = toml::from_str(&contents)
+        .with_context(|| format!("failed to parse chains file {}", path.display()))?;
+
+    let mut settings = ChainSettings::default();
+    for chain in file.chain {
+        if let Some(api_url) = chain.api_url.filter(|u| !u.is_empty())
+            && settings.api_urls.insert(chain.id, api_url).is_some()
+        {
+            bail!("chain {} has more than one api_url in {}", chain.id, path.display());
+        }
+        if let Some(gate_limit) = chain.gate_limit
+            && settings.gate_limits.insert(chain.id, gate_limit).is_some()
+        {
+            bail!("chain {} has more than one gate_limit in {}", chain.id, path.display());
+        }
+    }
+    Ok(settings)
+}
+
+/// Receipt payload written as soon as the explorer accepts a submission.
+#[derive(Debug, Serialize)]
+pub struct VerificationSubmittedData {
+    pub guid: String,
+    pub contract_address: String,
+    pub contract_name: String,
+    pub chain_id: u64,
+}
+
+/// How the API key is attached to explorer requests.
+struct ApiAuth<'a> {
+    key: Option<&'a str>,
+    param: &'a str,
+    header: Option<&'a str>,
+}
+
+impl<'a> ApiAuth<'a> {
+    fn new(key: Option<&'a str>, verify: &'a VerifyArgs) -> Self {
+        Self {
+            key,
+            param: verify
+                .verifier_key_param
+                .as_deref()
+                .filter(|p| !p.is_empty())
+                .unwrap_or(DEFAULT_KEY_PARAM),
+            header: verify
+                .verifier_key_header
+                .as_deref()
+                .filter(|h| !h.is_empty()),
+        }
+    }
+
+    /// Attach the key as a header if one is configured, otherwise as a query parameter.
+    /// Requests to keyless explorers are left untouched.
+    fn apply(&self, req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let Some(key) = self.key else {
+            return req;
+        };
+        match self.header {
+            Some(header) => req.header(header, key),
+            None => req.query(&[(self.param, key)]),
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -35,7 +159,18 @@ pub enum VerificationOutcome {
     Verified,
     AlreadyVerified,
     Failed(String),
-    Skipped,
+    Skipped(String),
+}
+
+impl VerificationOutcome {
+    /// The outcome as text, colored by result when `--color` allows.
+    pub fn colored(&self) -> String {
+        match self {
+            Self::Verified | Self::AlreadyVerified => style::success(self),
+            Self::Failed(_) => style::failure(self),
+            Self::Skipped(_) => style::warning(self),
+        }
+    }
 }
 
 impl std::fmt::Display for VerificationOutcome {
@@ -44,24 +179,51 @@ impl std::fmt::Display for VerificationOutcome {
             Self::Verified => write!(f, "verified"),
             Self::AlreadyVerified => write!(f, "already_verified"),
             Self::Failed(reason) => write!(f, "failed: {reason}"),
-            Self::Skipped => write!(f, "skipped"),
+            Self::Skipped(reason) => write!(f, "skipped: {reason}"),
         }
     }
 }
 
+/// Under `--fail-on-verification-failure`, fail if the verification of `contract` ended
+/// in [`VerificationOutcome::Failed`].  Skipped verifications (no API key, an
+/// unsupported chain) still pass.  Callers check after writing the receipt, so the
+/// deployment is recorded either way.
+pub fn enforce_verification(
+    verify: &VerifyArgs,
+    contract: &str,
+    outcome: &VerificationOutcome,
+) -> Result<()> {
+    if verify.fail_on_verification_failure
+        && let VerificationOutcome::Failed(reason) = outcome
+    {
+        bail!("verification of {contract} failed: {reason} (--fail-on-verification-failure)");
+    }
+    Ok(())
+}
+
 /// Map a chain ID to its block explorer base URL for human-readable links.
 fn explorer_url(chain_id: u64) -> &'static str {
+    known_explorer_url(chain_id).unwrap_or("https://etherscan.io")
+}
+
+fn known_explorer_url(chain_id: u64) -> Option<&'static str> {
     match chain_id {
-        1 => "https://etherscan.io",
-        11155111 => "https://sepolia.etherscan.io",
-        8453 => "https://basescan.org",
-        42161 => "https://arbiscan.io",
-        137 => "https://polygonscan.com",
-        10 => "https://optimistic.etherscan.io",
-        _ => "https://etherscan.io",
+        1 => Some("https://etherscan.io"),
+        11155111 => Some("https://sepolia.etherscan.io"),
+        8453 => Some("https://basescan.org"),
+        42161 => Some("https://arbiscan.io"),
+        137 => Some("https://polygonscan.com"),
+        10 => Some("https://optimistic.etherscan.io"),
+        _ => None,
     }
 }
 
+/// Explorer page of an address or transaction (`kind` is `address` or `tx`), for
+/// chains whose explorer is known.
+pub fn explorer_link(chain_id: u64, kind: &str, id: &str) -> Option<String> {
+    known_explorer_url(chain_id).map(|explorer| format!("{explorer}/{kind}/{id}"))
+}
+
 /// Map a chain ID to a human-readable network name.
 pub fn network_name(chain_id: u64) -> &'static str {
     match chain_id {
@@ -78,11 +240,113 @@ pub fn network_name(chain_id: u64) -> &'static str {
     }
 }
 
+/// EIP-3770 short names from the ethereum-lists chains registry, for the chains above.
+const CHAIN_SHORT_NAMES: &[(u64, &str)] = &[
+    (1, "eth"),
+    (11155111, "sep"),
+    (8453, "base"),
+    (84532, "basesep"),
+    (42161, "arb1"),
+    (421614, "arb-sep"),
+    (10, "oeth"),
+    (11155420, "opsep"),
+    (137, "pol"),
+];
+
+/// Map a chain ID to its EIP-3770 short name (e.g. `base`), if known.
+pub fn chain_short_name(chain_id: u64) -> Option<&'static str> {
+    CHAIN_SHORT_NAMES
+        .iter()
+        .find(|(id, _)| *id == chain_id)
+        .map(|(_, name)| *name)
+}
+
+/// Map an EIP-3770 short name back to its chain ID, if known.
+pub fn chain_id_from_short_name(short_name: &str) -> Option<u64> {
+    CHAIN_SHORT_NAMES
+        .iter()
+        .find(|(_, name)| *name == short_name)
+        .map(|(id, _)| *id)
+}
+
+/// Read `prefix=target` remappings from `remappings.txt` and `foundry.toml`'s default profile.
+fn read_remappings(project_dir: &Path) -> Result<Vec<(String, String)>> {
+    let mut lines = Vec::new();
+
+    let txt_path = project_dir.join("remappings.txt");
+    if txt_path.exists() {
+        let contents = std::fs::read_to_string(&txt_path)
+            .with_context(|| format!("failed to read {}", txt_path.display()))?;
+        lines.extend(contents.lines().map(str::to_string));
+    }
+
+    let toml_path = project_dir.join("foundry.toml");
+    if toml_path.exists() {
+        let contents = std::fs::read_to_string(&toml_path)
+            .with_context(|| format!("failed to read {}", toml_path.display()))?;
+        let config: toml::Value = toml::from_str(&contents)
+            .with_context(|| format!("failed to parse {}", toml_path.display()))?;
+        if let Some(entries) = config
+            .get("profile")
+            .and_then(|p| p.get("default"))
+            .and_then(|d| d.get("remappings"))
+            .and_then(|r| r.as_array())
+        {
+            lines.extend(entries.iter().filter_map(|e| e.as_str()).map(str::to_string));
+        }
+    }
+
+    Ok(lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| {
+            // Drop an optional `context:` qualifier.
+            let l = l.split_once(':').map_or(l, |(_, rest)| rest);
+            l.split_once('=')
+                .map(|(prefix, target)| (prefix.to_string(), target.to_string()))
+        })
+        .collect())
+}
+
+/// Locate a source file referenced by the artifact metadata: first in the project,
+/// then via remappings, then under each extra source root.
+fn resolve_source(
+    project_dir: &Path,
+    source: &str,
+    remappings: &[(String, String)],
+    source_roots: &[PathBuf],
+) -> Result<PathBuf> {
+    let mut candidates = vec![project_dir.join(source)];
+    for (prefix, target) in remappings {
+        if let Some(rest) = source.strip_prefix(prefix.as_str()) {
+            candidates.push(project_dir.join(format!("{target}{rest}")));
+        }
+    }
+    candidates.extend(source_roots.iter().map(|root| root.join(source)));
+
+    candidates
+        .iter()
+        .find(|c| c.is_file())
+        .cloned()
+        .with_context(|| {
+            let tried: Vec<String> = candidates.iter().map(|c| c.display().to_string()).collect();
+            format!(
+                "failed to find source {source} (tried: {}) -- add its location with --contract-source-root",
+                tried.join(", ")
+            )
+        })
+}
+
 /// Build Solidity Standard JSON Input from a forge project's source files and artifact metadata.
 ///
 /// Reads all source files referenced in the artifact metadata and reconstructs the
 /// compiler input that Etherscan needs to reproduce the bytecode.
-fn build_standard_json_input(project_dir: &Path, artifact_path: &Path) -> Result<(String, String)> {
+fn build_standard_json_input(
+    project_dir: &Path,
+    artifact_path: &Path,
+    source_roots: &[PathBuf],
+) -> Result<(String, String)> {
     let artifact_bytes = std::fs::read(artifact_path)
         .with_context(|| format!("failed to read artifact: {}", artifact_path.display()))?;
     let artifact: serde_json::Value = serde_json::from_slice(&artifact_bytes)?;
@@ -139,9 +403,10 @@ fn build_standard_json_input(project_dir: &Path, artifact_path: &Path) -> Result
         .and_then(|s| s.as_object())
         .context("no sources in artifact metadata")?;
 
+    let remappings = read_remappings(project_dir)?;
     let mut sources = serde_json::Map::new();
     for path in source_keys.keys() {
-        let full_path = project_dir.join(path);
+        let full_path = resolve_source(project_dir, path, &remappings, source_roots)?;
         let content = std::fs::read_to_string(&full_path)
             .with_context(|| format!("failed to read source: {}", full_path.display()))?;
         sources.insert(path.clone(), serde_json::json!({ "content": content }));
@@ -159,11 +424,20 @@ fn build_standard_json_input(project_dir: &Path, artifact_path: &Path) -> Result
     Ok((json_str, compiler_version))
 }
 
+/// Whether a rejected submission may succeed later: a network failure, or an explorer
+/// that has not indexed the new contract yet ("Unable to locate ContractCode").  Other
+/// rejections, such as a bytecode mismatch, fail the same way every time.
+fn is_retryable_submission(error: &anyhow::Error) -> bool {
+    retry::is_transient(error)
+        || format!("{error:#}").to_ascii_lowercase().contains("unable to locate")
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn submit_verification(
     client: &reqwest::Client,
     base_url: &str,
     chain_id: u64,
-    api_key: &str,
+    auth: &ApiAuth<'_>,
     contract_address: &str,
     standard_json_input: &str,
     contract_name: &str,
@@ -182,9 +456,11 @@ async fn submit_verification(
         ("constructorArguments", constructor_args),
     ];
 
-    let resp = client
+    let req = client
         .post(base_url)
-        .query(&[("chainid", &chain_id_str), ("apikey", &api_key.to_string())])
+        .query(&[("chainid", &chain_id_str)]);
+    let resp = auth
+        .apply(req)
         .form(&form_params)
         .send()
         .await
@@ -204,35 +480,40 @@ async fn poll_status(
     client: &reqwest::Client,
     base_url: &str,
     chain_id: u64,
-    api_key: &str,
+    auth: &ApiAuth<'_>,
     guid: &str,
     indent: &str,
+    progress: &dyn ProgressReporter,
 ) -> Result<VerificationOutcome> {
     let chain_id_str = chain_id.to_string();
 
     for attempt in 1..=MAX_POLL_ATTEMPTS {
         sleep(POLL_INTERVAL).await;
 
-        let resp = client
-            .get(base_url)
-            .query(&[
+        let resp = retry::retry_async("Etherscan status check", progress, || async {
+            let req = client.get(base_url).query(&[
                 ("chainid", chain_id_str.as_str()),
                 ("module", "contract"),
                 ("action", "checkverifystatus"),
                 ("guid", guid),
-                ("apikey", api_key),
-            ])
-            .send()
-            .await
-            .context("failed to poll Etherscan verification status")?
-            .json::<EtherscanResponse<String>>()
-            .await
-            .context("failed to parse Etherscan status response")?;
-
-        eprintln!(
+            ]);
+            let response = auth
+                .apply(req)
+                .send()
+                .await
+                .and_then(retry::fail_transient)
+                .context("failed to poll Etherscan verification status")?;
+            response
+                .json::<EtherscanResponse<String>>()
+                .await
+                .context("failed to parse Etherscan status response")
+        })
+        .await?;
+
+        progress.info(&format!(
             "{indent}  verification check ({attempt}/{MAX_POLL_ATTEMPTS}): {}",
             resp.result
-        );
+        ));
 
         match resp.result.as_str() {
             "Pass - Verified" => return Ok(VerificationOutcome::Verified),
@@ -247,100 +528,363 @@ async fn poll_status(
     )))
 }
 
+/// Result of recompiling the verification input locally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputCheck {
+    /// The runtime bytecode is identical, metadata hash included.
+    Match,
+    /// Only the metadata hash differs: the code matches, but a source or setting that
+    /// does not affect codegen (comments, paths) is not what the artifact was built from.
+    MetadataMismatch,
+    Mismatch,
+}
+
+impl std::fmt::Display for InputCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Match => write!(f, "match"),
+            Self::MetadataMismatch => write!(f, "metadata_mismatch"),
+            Self::Mismatch => write!(f, "mismatch"),
+        }
+    }
+}
+
+/// A command running the solc for `version` (as recorded in artifact metadata): the
+/// `--solc-path` executable if one was given, otherwise the one forge installed under
+/// `~/.svm`, or else `solc` on `PATH`.  Anything but an svm install must report that
+/// version.
+fn find_solc(version: &str) -> Result<Command> {
+    let version = version.trim_start_matches('v');
+    let short = version.split('+').next().unwrap_or(version);
+
+    let overridden = tools::is_overridden(Tool::Solc);
+    if !overridden && let Some(home) = std::env::var_os("HOME") {
+        let svm = Path::new(&home).join(".svm").join(short).join(format!("solc-{short}"));
+        if svm.is_file() {
+            return Ok(Command::new(svm));
+        }
+    }
+
+    let (which, hint) = if overridden {
+        ("--solc-path", "point --solc-path at that version")
+    } else {
+        ("solc on PATH", "run `forge build` once to install it")
+    };
+    let output = tools::command(Tool::Solc).arg("--version").output().with_context(|| {
+        format!("failed to run {which} -- solc {short} is needed; {hint}")
+    })?;
+    let reported = String::from_utf8_lossy(&output.stdout);
+    if !reported.contains(&format!("Version: {short}+")) {
+        bail!(
+            "{which} is not solc {short}, which the artifact was compiled with ({}) -- {hint}",
+            reported.lines().last().unwrap_or_default().trim()
+        );
+    }
+    Ok(tools::command(Tool::Solc))
+}
+
+/// Decode runtime bytecode hex, treating unlinked library placeholders
+/// (`__$<34 hex>$__`) as zero addresses so both sides of a comparison decode alike.
+fn decode_runtime_code(hex: &str) -> Result<Vec<u8>> {
+    let mut hex = hex.strip_prefix("0x").unwrap_or(hex).to_string();
+    while let Some(start) = hex.find("__$") {
+        let end = (start + 40).min(hex.len());
+        hex.replace_range(start..end, &"0".repeat(end - start));
+    }
+    alloy::hex::decode(&hex).context("invalid runtime bytecode hex")
+}
+
+/// Recompile the standard JSON input [`verify_contract`] would submit for
+/// `contract_name` (`<path>:<Contract>`) with the artifact's solc, and compare the
+/// resulting runtime bytecode with the artifact's `deployedBytecode`.  Runs entirely
+/// offline, catching most "bytecode does not match" verification failures up front.
+/// Returns the outcome and the compiler version used.
+pub fn check_verification_input(
+    project_dir: &Path,
+    artifact_path: &Path,
+    contract_name: &str,
+    source_roots: &[PathBuf],
+) -> Result<(InputCheck, String)> {
+    let (source, name) = contract_name.rsplit_once(':').with_context(|| {
+        format!("invalid contract name (expected <path>:<Contract>): {contract_name}")
+    })?;
+    let (standard_json, compiler_version) =
+        build_standard_json_input(project_dir, artifact_path, source_roots)
+            .context("failed to build standard JSON input for verification")?;
+    let expected = decode_runtime_code(&crate::forge::deployed_bytecode_hex(artifact_path)?)
+        .with_context(|| format!("invalid deployedBytecode in {}", artifact_path.display()))?;
+
+    let mut solc = find_solc(&compiler_version)?;
+    let program = solc.get_program().to_string_lossy().into_owned();
+    let mut child = solc
+        .arg("--standard-json")
+        .current_dir(project_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("failed to run {program}"))?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(standard_json.as_bytes())
+        .context("failed to send standard JSON input to solc")?;
+    let output = child.wait_with_output().context("failed to wait for solc")?;
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).with_context(|| {
+        format!(
+            "solc produced no standard JSON output:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+    })?;
+
+    let errors = crate::solc::standard_json_errors(&result);
+    if !errors.is_empty() {
+        bail!("the verification input does not compile:\n{}", errors.join("\n"));
+    }
+
+    let compiled = result
+        .get("contracts")
+        .and_then(|c| c.get(source))
+        .and_then(|c| c.get(name))
+        .and_then(|c| c.pointer("/evm/deployedBytecode/object"))
+        .and_then(|o| o.as_str())
+        .with_context(|| format!("solc output has no runtime bytecode for {contract_name}"))?;
+    let compiled = decode_runtime_code(compiled)?;
+
+    let strip = |code: &[u8]| code.len() - crate::forge::metadata_len(code);
+    let check = if compiled == expected {
+        InputCheck::Match
+    } else if compiled[..strip(&compiled)] == expected[..strip(&expected)] {
+        InputCheck::MetadataMismatch
+    } else {
+        InputCheck::Mismatch
+    };
+    Ok((check, compiler_version))
+}
+
 /// Verify a deployed contract on Etherscan (or compatible explorer) using the v2 API.
 ///
-/// Returns the verification outcome. If no API key is configured, returns `Skipped`.
+/// Returns the verification outcome.  Etherscan itself requires an API key; without one
+/// (and without a custom `verifier_url` or chains-file `api_url` for the chain) this
+/// returns `Skipped`.  A custom URL may point at a keyless explorer such as Blockscout,
+/// so verification proceeds there with or without a key.
 /// Prints progress to stderr and the final explorer link to stdout.
+#[allow(clippy::too_many_arguments)]
 pub async fn verify_contract(
     project_dir: &Path,
     artifact_path: &Path,
     chain_id: u64,
     contract_address: &str,
     contract_name: &str,
-    constructor_args: Option<&str>,
+    constructor_args: Option<&ConstructorArgs>,
     verify: &VerifyArgs,
     indent: &str,
+    progress: &dyn ProgressReporter,
 ) -> Result<VerificationOutcome> {
-    let api_key = match verify
-        .etherscan_api_key
-        .as_deref()
-        .filter(|k| !k.is_empty())
-    {
-        Some(key) => key,
-        None => {
-            eprintln!("{indent}no Etherscan API key provided, skipping verification");
-            return Ok(VerificationOutcome::Skipped);
+    let (api_key, custom_url) = explorer_settings(verify, chain_id);
+
+    let base_url = match (custom_url, api_key) {
+        (Some(url), None) => {
+            progress.info(&format!(
+                "{indent}no API key provided, verifying against keyless explorer {url}"
+            ));
+            url
+        }
+        (Some(url), Some(_)) => url,
+        (None, Some(_)) => ETHERSCAN_V2_API,
+        (None, None) => {
+            let reason = "no Etherscan API key provided (set --etherscan-api-key, or --verifier-url for a keyless explorer)";
+            progress.info(&format!(
+                "{indent}{}",
+                style::warning(format!("skipping verification: {reason}"))
+            ));
+            return Ok(VerificationOutcome::Skipped(reason.to_string()));
         }
     };
 
-    let base_url = verify
-        .verifier_url
-        .as_deref()
-        .filter(|u| !u.is_empty())
-        .unwrap_or(ETHERSCAN_V2_API);
-
-    eprintln!("{indent}verifying {contract_address} on chain {chain_id}...");
+    progress.info(&format!("{indent}verifying {contract_address} on chain {chain_id}..."));
 
     let (standard_json, compiler_version) =
-        build_standard_json_input(project_dir, artifact_path)
+        build_standard_json_input(project_dir, artifact_path, &verify.source_roots)
             .context("failed to build standard JSON input for verification")?;
 
+    let auth = ApiAuth::new(api_key, verify);
     let client = reqwest::Client::new();
-    let constructor_args = constructor_args.unwrap_or("");
-
-    let mut guid = None;
-    for attempt in 1..=SUBMIT_RETRIES {
-        eprintln!("{indent}  submission attempt {attempt}/{SUBMIT_RETRIES}...");
-        match submit_verification(
-            &client,
-            base_url,
-            chain_id,
-            api_key,
-            contract_address,
-            &standard_json,
-            contract_name,
-            &compiler_version,
-            constructor_args,
-        )
-        .await
-        {
-            Ok(g) => {
-                guid = Some(g);
-                break;
-            }
-            Err(e) => {
-                if attempt < SUBMIT_RETRIES {
-                    eprintln!(
-                        "{indent}  attempt {attempt} failed: {e:#}, retrying in {}s...",
-                        SUBMIT_RETRY_DELAY.as_secs()
-                    );
-                    sleep(SUBMIT_RETRY_DELAY).await;
-                } else {
-                    eprintln!("{indent}  all {SUBMIT_RETRIES} attempts failed: {e:#}");
-                    return Ok(VerificationOutcome::Failed(format!("{e:#}")));
-                }
-            }
+    let constructor_args = constructor_args.map(ConstructorArgs::hex).unwrap_or_default();
+
+    let retry_config = retry::config().with_min_delay(SUBMIT_RETRY_DELAY);
+    let submitted = retry::retry_async_with(
+        retry_config,
+        "verification submission",
+        progress,
+        is_retryable_submission,
+        || {
+            submit_verification(
+                &client,
+                base_url,
+                chain_id,
+                &auth,
+                contract_address,
+                &standard_json,
+                contract_name,
+                &compiler_version,
+                &constructor_args,
+            )
+        },
+    )
+    .await;
+    let guid = match submitted {
+        Ok(guid) => guid,
+        Err(e) => {
+            let e = format!("{:#}", redact::Redacted(&e));
+            progress.info(&format!("{indent}  submission failed: {e}"));
+            return Ok(VerificationOutcome::Failed(e));
         }
+    };
+
+    progress.info(&format!("{indent}  submitted (guid: {guid}), polling for result..."));
+
+    if let Some(ref dir) = verify.pending_dir {
+        let data = VerificationSubmittedData {
+            guid: guid.clone(),
+            contract_address: contract_address.to_string(),
+            contract_name: contract_name.to_string(),
+            chain_id,
+        };
+        Receipt::new("verification-submitted", data).write_to_dir(dir)?;
     }
-    let guid = guid.expect("guid set if loop didn't return");
 
-    eprintln!("{indent}  submitted (guid: {guid}), polling for result...");
+    let outcome = await_outcome(
+        &client, base_url, chain_id, &auth, &guid, verify, indent, progress,
+    )
+    .await?;
+    report_outcome(&outcome, chain_id, contract_address, indent, progress);
 
-    let outcome = poll_status(&client, base_url, chain_id, api_key, &guid, indent).await?;
+    Ok(outcome)
+}
+
+/// The API key and custom explorer URL, ignoring empty values.  The custom URL is
+/// `--verifier-url`, or else the chain's `api_url` from `--chains-file`.
+fn explorer_settings(verify: &VerifyArgs, chain_id: u64) -> (Option<&str>, Option<&str>) {
+    let api_key = verify
+        .etherscan_api_key
+        .as_deref()
+        .filter(|k| !k.is_empty());
+    let custom_url = verify
+        .verifier_url
+        .as_deref()
+        .filter(|u| !u.is_empty())
+        .or_else(|| verify.chain_api_urls.get(&chain_id).map(String::as_str));
+    (api_key, custom_url)
+}
+
+/// Whether deployments on `chain_id` will be verified, and where, for `--print-plan`.
+/// `None` means the chain is only known once the RPC is asked.
+pub fn describe_verification(verify: &VerifyArgs, chain_id: Option<u64>) -> String {
+    let (api_key, custom_url) = match chain_id {
+        Some(chain_id) => explorer_settings(verify, chain_id),
+        None => (
+            verify.etherscan_api_key.as_deref().filter(|k| !k.is_empty()),
+            verify.verifier_url.as_deref().filter(|u| !u.is_empty()),
+        ),
+    };
+    match (custom_url, api_key) {
+        (Some(url), _) => format!("yes, on {url}"),
+        (None, Some(_)) => format!("yes, on {ETHERSCAN_V2_API}"),
+        (None, None) if chain_id.is_none() && !verify.chain_api_urls.is_empty() => {
+            "only if the RPC's chain is listed in --chains-file".to_string()
+        }
+        (None, None) => "no (no Etherscan API key or --verifier-url)".to_string(),
+    }
+}
+
+/// Poll until the explorer reports a result, bounded by `total_timeout` if set.
+#[allow(clippy::too_many_arguments)]
+async fn await_outcome(
+    client: &reqwest::Client,
+    base_url: &str,
+    chain_id: u64,
+    auth: &ApiAuth<'_>,
+    guid: &str,
+    verify: &VerifyArgs,
+    indent: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<VerificationOutcome> {
+    let poll = poll_status(client, base_url, chain_id, auth, guid, indent, progress);
+    Ok(match verify.total_timeout {
+        Some(limit) => match tokio::time::timeout(limit, poll).await {
+            Ok(outcome) => outcome?,
+            Err(_) => VerificationOutcome::Failed(format!(
+                "timed out after {}s",
+                limit.as_secs()
+            )),
+        },
+        None => poll.await?,
+    })
+}
 
+fn report_outcome(
+    outcome: &VerificationOutcome,
+    chain_id: u64,
+    contract_address: &str,
+    indent: &str,
+    progress: &dyn ProgressReporter,
+) {
     let explorer = explorer_url(chain_id);
-    match &outcome {
+    match outcome {
         VerificationOutcome::Verified => {
-            eprintln!("{indent}  verified: {explorer}/address/{contract_address}#code");
+            progress.info(&format!(
+                "{indent}  {} {explorer}/address/{contract_address}#code",
+                style::success("verified:")
+            ));
         }
         VerificationOutcome::AlreadyVerified => {
-            eprintln!("{indent}  already verified: {explorer}/address/{contract_address}#code");
+            progress.info(&format!(
+                "{indent}  {} {explorer}/address/{contract_address}#code",
+                style::success("already verified:")
+            ));
         }
         VerificationOutcome::Failed(reason) => {
-            eprintln!("{indent}  verification failed: {reason}");
+            progress.info(&format!(
+                "{indent}  {} {reason}",
+                style::failure("verification failed:")
+            ));
         }
-        VerificationOutcome::Skipped => {}
+        VerificationOutcome::Skipped(_) => {}
     }
+}
+
+/// Resume polling a submission that was already accepted by the explorer, e.g. one
+/// recorded in a `verification-submitted` receipt by an interrupted run.
+pub async fn check_verification(
+    chain_id: u64,
+    guid: &str,
+    contract_address: &str,
+    verify: &VerifyArgs,
+    indent: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<VerificationOutcome> {
+    let (api_key, custom_url) = explorer_settings(verify, chain_id);
+    let base_url = match (custom_url, api_key) {
+        (Some(url), _) => url,
+        (None, Some(_)) => ETHERSCAN_V2_API,
+        (None, None) => bail!(
+            "checking verification requires an explorer API key (--etherscan-api-key or ETHERSCAN_API_KEY), or --verifier-url for a keyless explorer"
+        ),
+    };
+
+    progress.info(&format!(
+        "{indent}checking verification {guid} for {contract_address} on chain {chain_id}..."
+    ));
+
+    let auth = ApiAuth::new(api_key, verify);
+    let client = reqwest::Client::new();
+    let outcome = await_outcome(
+        &client, base_url, chain_id, &auth, guid, verify, indent, progress,
+    )
+    .await?;
+    report_outcome(&outcome, chain_id, contract_address, indent, progress);
 
     Ok(outcome)
 }
@@ -1,17 +1,28 @@
+use alloy::primitives::{B256, keccak256};
 use anyhow::{Context, Result, bail};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+
+use crate::progress::ProgressReporter;
+use crate::style;
+use crate::tools::{self, Tool};
+
+/// Default `--optimizer-runs`, favouring small bytecode over cheap calls so the large
+/// generated verifier stays under the contract size limit.
+pub const DEFAULT_OPTIMIZER_RUNS: u32 = 1;
 
 /// Run `forge build` to compile the Solidity contracts in the given project directory.
-pub fn build(project_dir: &Path) -> Result<()> {
-    let output = Command::new("forge")
+///
+/// The optimizer settings end up in the artifact metadata, which is what contract
+/// verification submits, so they never need to be passed along separately.
+pub fn build(project_dir: &Path, optimizer_runs: u32) -> Result<()> {
+    let output = tools::command(Tool::Forge)
         .args([
             "build",
             "--root",
             &project_dir.display().to_string(),
             "--optimize",
             "--optimizer-runs",
-            "1",
+            &optimizer_runs.to_string(),
         ])
         .output()
         .with_context(|| {
@@ -22,9 +33,20 @@ pub fn build(project_dir: &Path) -> Result<()> {
         })?;
 
     if !output.status.success() {
+        let stdout = String::from_utf8_lossy(&output.stdout);
         let stderr = String::from_utf8_lossy(&output.stderr);
+        let full = format!("{stdout}{stderr}");
+        let log_path = project_dir.join(FORGE_ERROR_LOG);
+        let details = match std::fs::write(&log_path, &full) {
+            Ok(()) => format!(
+                "{}\n(full output in {})",
+                salient_error_lines(&full),
+                log_path.display()
+            ),
+            Err(_) => full,
+        };
         bail!(
-            "forge build failed for project {}:\n{stderr}",
+            "forge build failed for project {}:\n{details}",
             project_dir.display()
         );
     }
@@ -32,6 +54,37 @@ pub fn build(project_dir: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Where the complete output of a failed `forge build` is kept, inside the project.
+const FORGE_ERROR_LOG: &str = ".last-forge-error.log";
+
+/// Lines kept from a failed build when nothing in it looks like an error.
+const FORGE_ERROR_TAIL: usize = 20;
+
+/// The lines of forge output that say what went wrong: solc diagnostics (`Error (2314):
+/// ...` and their `--> file:line:col` locations), forge's own `Error:` lines, and the
+/// `Compiler run failed` banner.  Warnings and progress are dropped; if nothing matches,
+/// the last [`FORGE_ERROR_TAIL`] lines are returned instead.
+fn salient_error_lines(output: &str) -> String {
+    let mut salient = Vec::new();
+    let mut in_error = false;
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("Error") || trimmed.contains("Compiler run failed") {
+            in_error = true;
+            salient.push(line);
+        } else if trimmed.starts_with("Warning") || trimmed.is_empty() {
+            in_error = false;
+        } else if in_error && trimmed.starts_with("-->") {
+            salient.push(line);
+        }
+    }
+    if salient.is_empty() {
+        let lines: Vec<&str> = output.lines().collect();
+        salient = lines[lines.len().saturating_sub(FORGE_ERROR_TAIL)..].to_vec();
+    }
+    salient.join("\n")
+}
+
 /// Return the path to a forge build artifact JSON for a given contract.
 pub fn artifact_path(project_dir: &Path, sol_file: &str, contract_name: &str) -> PathBuf {
     project_dir
@@ -39,3 +92,165 @@ pub fn artifact_path(project_dir: &Path, sol_file: &str, contract_name: &str) ->
         .join(sol_file)
         .join(format!("{contract_name}.json"))
 }
+
+/// With `--skip-build`, make sure a previous `forge build` left the artifact behind.
+pub fn check_prebuilt(artifact: &Path, project_dir: &Path) -> Result<()> {
+    if !artifact.exists() {
+        bail!(
+            "no artifact found at {} -- did you forget to build? --skip-build expects `forge build` to have been run in {}",
+            artifact.display(),
+            project_dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// Bail if a prebuilt artifact was compiled from something other than the current
+/// contents of `source`, using the source hash forge records in the artifact metadata.
+/// Artifacts built without metadata cannot be checked and are accepted with a warning.
+pub fn check_artifact_source(
+    artifact: &Path,
+    source_key: &str,
+    source: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let json: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(artifact)
+            .with_context(|| format!("failed to read artifact: {}", artifact.display()))?,
+    )
+    .with_context(|| format!("failed to parse artifact JSON: {}", artifact.display()))?;
+
+    let Some(recorded) = json
+        .pointer(&format!("/metadata/sources/{}/keccak256", source_key.replace('/', "~1")))
+        .and_then(|v| v.as_str())
+    else {
+        progress.info(&format!(
+            "{} no source hash for {source_key} in {}, cannot confirm the prebuilt artifact is current",
+            style::warning("warning:"),
+            artifact.display()
+        ));
+        return Ok(());
+    };
+
+    let contents = std::fs::read(source)
+        .with_context(|| format!("failed to read {}", source.display()))?;
+    let actual = keccak256(contents);
+    if !recorded.eq_ignore_ascii_case(&actual.to_string()) {
+        bail!(
+            "prebuilt artifact {} was compiled from a different {source_key} than {} -- rebuild, or drop --skip-build",
+            artifact.display(),
+            source.display()
+        );
+    }
+    Ok(())
+}
+
+/// The optimizer runs an artifact was actually compiled with, as recorded in its
+/// metadata -- with `--skip-build` this can differ from `--optimizer-runs`.
+pub fn artifact_optimizer_runs(artifact: &Path) -> Option<u64> {
+    let json: serde_json::Value = serde_json::from_slice(&std::fs::read(artifact).ok()?).ok()?;
+    json.pointer("/metadata/settings/optimizer/runs")?.as_u64()
+}
+
+/// Length of the CBOR metadata solc appends to runtime code, or 0 if there is none.
+/// The last two bytes hold the CBOR length; the CBOR itself is a map.
+pub fn metadata_len(code: &[u8]) -> usize {
+    let Some(len_bytes) = code.len().checked_sub(2).map(|i| &code[i..]) else {
+        return 0;
+    };
+    let cbor_len = usize::from(u16::from_be_bytes([len_bytes[0], len_bytes[1]]));
+    match code.len().checked_sub(cbor_len + 2) {
+        Some(start) if cbor_len > 0 && (0xa1..=0xb7).contains(&code[start]) => cbor_len + 2,
+        _ => 0,
+    }
+}
+
+/// keccak256 of a contract artifact's creation bytecode (`bytecode.object`), hex-decoded
+/// as `cast keccak` would.  Unlinked library placeholders make the object invalid hex;
+/// it is then hashed as text, which still pins it since the placeholders are derived
+/// from the library names.
+pub fn bytecode_hash(artifact: &Path) -> Result<B256> {
+    let json: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(artifact)
+            .with_context(|| format!("failed to read artifact: {}", artifact.display()))?,
+    )
+    .with_context(|| format!("failed to parse artifact JSON: {}", artifact.display()))?;
+
+    let object = json
+        .pointer("/bytecode/object")
+        .and_then(|v| v.as_str())
+        .with_context(|| format!("no bytecode.object in artifact {}", artifact.display()))?;
+    let object = object.strip_prefix("0x").unwrap_or(object);
+    Ok(match alloy::hex::decode(object) {
+        Ok(code) => keccak256(code),
+        Err(_) => keccak256(object),
+    })
+}
+
+/// Bail if the verifier artifact's [`bytecode_hash`] is not `expected`
+/// (`--expected-verifier-bytecode-hash`).  Returns the hash, so it can be recorded
+/// whether or not one was expected.
+pub fn check_bytecode_hash(artifact: &Path, expected: Option<B256>) -> Result<B256> {
+    let actual = bytecode_hash(artifact)?;
+    if let Some(expected) = expected
+        && actual != expected
+    {
+        bail!(
+            "verifier bytecode hash {actual} does not match --expected-verifier-bytecode-hash {expected} \
+             -- the toolchain produced a different verifier than the one approved ({})",
+            artifact.display()
+        );
+    }
+    Ok(actual)
+}
+
+/// Runtime bytecode of a contract artifact (`deployedBytecode.object`), as hex.
+pub fn deployed_bytecode_hex(artifact: &Path) -> Result<String> {
+    let json: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(artifact)
+            .with_context(|| format!("failed to read artifact: {}", artifact.display()))?,
+    )
+    .with_context(|| format!("failed to parse artifact JSON: {}", artifact.display()))?;
+
+    json.pointer("/deployedBytecode/object")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .with_context(|| format!("no deployedBytecode.object in artifact {}", artifact.display()))
+}
+
+/// Runtime bytecode of a contract artifact (`deployedBytecode.object`).
+pub fn deployed_bytecode(artifact: &Path) -> Result<Vec<u8>> {
+    let object = deployed_bytecode_hex(artifact)?;
+    alloy::hex::decode(object.strip_prefix("0x").unwrap_or(&object)).with_context(|| {
+        format!(
+            "invalid hex in deployedBytecode.object of artifact {} -- unlinked libraries?",
+            artifact.display()
+        )
+    })
+}
+
+/// A source file temporarily copied into a Foundry project.  The copy is removed when
+/// the guard is dropped -- on success, on error, and when the command is interrupted.
+pub struct StagedSource {
+    path: PathBuf,
+}
+
+impl StagedSource {
+    /// Copy `source` to `dest` for the lifetime of the returned guard.
+    pub fn copy(source: &Path, dest: PathBuf) -> Result<Self> {
+        std::fs::copy(source, &dest).with_context(|| {
+            format!(
+                "failed to copy {} to {}",
+                source.display(),
+                dest.display()
+            )
+        })?;
+        Ok(Self { path: dest })
+    }
+}
+
+impl Drop for StagedSource {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,31 +1,492 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use reqwest::multipart;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::path::Path;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::progress::ProgressReporter;
+use crate::redact;
+use crate::retry;
+use crate::style;
+
+const PIN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_PIN_POLL_ATTEMPTS: u32 = 60;
+
+/// Kubo's default `ipfs add` chunk size and maximum links per node (balanced layout).
+const CHUNK_SIZE: usize = 256 * 1024;
+const MAX_LINKS: usize = 174;
+
+/// Multicodec of dag-pb, the codec of every CIDv0.
+const DAG_PB: u8 = 0x70;
+
+/// Default ceiling on a single upload, in bytes.
+pub const DEFAULT_MAX_UPLOAD_SIZE: u64 = 8 * 1024 * 1024;
+
+/// Upload and pinning settings: size limit, local pin checks, and optional remote
+/// pinning (IPFS Pinning Service API).
+#[derive(Clone, Default)]
+pub struct PinArgs {
+    /// Refuse to upload files larger than this many bytes.  `None` disables the check.
+    pub max_upload_size: Option<u64>,
+    /// Skip `add` when the file's CID is already pinned on the local node.
+    pub skip_existing: bool,
+    /// Also upload the Noir project (`Nargo.toml` and `src/`) as a directory.
+    pub upload_project: bool,
+    /// Pin uploads with `pin/add` when the post-upload check finds them unpinned.
+    pub local_pin: bool,
+    pub remote_pin: bool,
+    pub service_url: Option<String>,
+    pub token: Option<String>,
+}
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct AddResponse {
     pub hash: String,
     pub size: String,
+    /// The file was already pinned on the node, so nothing was uploaded.
+    #[serde(skip)]
+    pub skipped: bool,
+    /// Whether the node has the CID pinned after the upload.
+    #[serde(skip)]
+    pub pin: LocalPin,
+}
+
+/// Pin state of an uploaded CID on the IPFS node, as recorded in receipts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalPin {
+    /// Pinned by the add itself, or already pinned before it.
+    Pinned,
+    /// Left unpinned by the add, then pinned with `pin/add` (`--pin`).
+    PinnedAfterAdd,
+    /// Not pinned; the node's garbage collector may evict it.
+    NotPinned,
+    /// The node could not be asked.
+    #[default]
+    Unknown,
+}
+
+/// A UnixFS DAG node as Kubo would build it: its multihash, the number of file bytes
+/// beneath it, and its cumulative (block + descendants) size.
+struct DagNode {
+    multihash: Vec<u8>,
+    filesize: u64,
+    cumulative_size: u64,
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u64, v: u64) {
+    put_varint(buf, field << 3);
+    put_varint(buf, v);
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u64, bytes: &[u8]) {
+    put_varint(buf, (field << 3) | 2);
+    put_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+/// Encode a dag-pb block holding a UnixFS `File` node.
+fn dag_node(data: Option<&[u8]>, children: &[DagNode]) -> DagNode {
+    let filesize = match data {
+        Some(data) => data.len() as u64,
+        None => children.iter().map(|c| c.filesize).sum(),
+    };
+
+    let mut unixfs = Vec::new();
+    put_varint_field(&mut unixfs, 1, 2); // Type = File
+    if let Some(data) = data {
+        put_bytes_field(&mut unixfs, 2, data);
+    }
+    put_varint_field(&mut unixfs, 3, filesize);
+    for child in children {
+        put_varint_field(&mut unixfs, 4, child.filesize);
+    }
+
+    let mut block = Vec::new();
+    for child in children {
+        let mut link = Vec::new();
+        put_bytes_field(&mut link, 1, &child.multihash);
+        put_bytes_field(&mut link, 2, b"");
+        put_varint_field(&mut link, 3, child.cumulative_size);
+        put_bytes_field(&mut block, 2, &link);
+    }
+    put_bytes_field(&mut block, 1, &unixfs);
+
+    let mut multihash = vec![0x12, 0x20]; // sha2-256, 32 bytes
+    multihash.extend_from_slice(&Sha256::digest(&block));
+    DagNode {
+        multihash,
+        filesize,
+        cumulative_size: block.len() as u64 + children.iter().map(|c| c.cumulative_size).sum::<u64>(),
+    }
+}
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn base58btc(bytes: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    std::iter::repeat_n('1', zeros)
+        .chain(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize] as char))
+        .collect()
+}
+
+fn decode_base58btc(s: &str) -> Option<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    for c in s.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = (carry & 0xff) as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push((carry & 0xff) as u8);
+            carry >>= 8;
+        }
+    }
+    let zeros = s.bytes().take_while(|&c| c == b'1').count();
+    Some(std::iter::repeat_n(0, zeros).chain(bytes.into_iter().rev()).collect())
+}
+
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// RFC 4648 base32, lowercase and without padding.
+fn base32(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(BASE32_ALPHABET[((buffer >> bits) & 31) as usize] as char);
+        }
+        buffer &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        out.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 31) as usize] as char);
+    }
+    out
+}
+
+/// RFC 4648 base32 without padding, in either case.
+fn decode_base32(s: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in s.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|&a| a == c.to_ascii_lowercase())? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Check that `cid` is a well-formed CID: a base58 CIDv0 (`Qm...`), or a CIDv1 in
+/// base32 (`b...`), base58btc (`z...`), or base16 (`f...`) whose multihash length
+/// matches its digest.  Nothing is fetched, so the CID need not exist.
+pub fn check_cid(cid: &str) -> Result<()> {
+    cid_v1_bytes(cid).map(|_| ())
+}
+
+/// The binary CIDv1 of a well-formed `cid` (see [`check_cid`]); a CIDv0 becomes the
+/// dag-pb CIDv1 of the same multihash.
+fn cid_v1_bytes(cid: &str) -> Result<Vec<u8>> {
+    if cid.is_empty() {
+        bail!("empty CID");
+    }
+    if cid.len() == 46 && cid.starts_with("Qm") {
+        let multihash = decode_base58btc(cid).context("CIDv0 is not valid base58")?;
+        if multihash.len() != 34 || multihash[..2] != [0x12, 0x20] {
+            bail!("CIDv0 does not hold a sha2-256 multihash");
+        }
+        return Ok([&[0x01, DAG_PB][..], &multihash].concat());
+    }
+
+    let mut chars = cid.chars();
+    let prefix = chars.next().unwrap_or_default();
+    let rest = chars.as_str();
+    let bytes = match prefix {
+        'b' | 'B' => decode_base32(rest),
+        'z' => decode_base58btc(rest),
+        'f' | 'F' => alloy::hex::decode(rest).ok(),
+        _ => bail!("unsupported multibase prefix {prefix:?} (expected Qm..., b..., z..., or f...)"),
+    }
+    .context("CID does not decode in its multibase")?;
+
+    let cid_bytes = bytes;
+    let mut bytes = cid_bytes.as_slice();
+    let version = read_varint(&mut bytes).context("CID is truncated")?;
+    if version != 1 {
+        bail!("unsupported CID version {version}");
+    }
+    read_varint(&mut bytes).context("CID is missing its codec")?;
+    read_varint(&mut bytes).context("CID is missing its multihash")?;
+    let digest_len = read_varint(&mut bytes).context("CID multihash is missing its length")?;
+    if digest_len == 0 || digest_len != bytes.len() as u64 {
+        bail!("CID multihash declares {digest_len} digest bytes but has {}", bytes.len());
+    }
+    Ok(cid_bytes)
+}
+
+/// The canonical form of `cid` -- CIDv1 in lowercase base32 (`bafy...`), as
+/// `ipfs cid base32` prints it -- so a CIDv0 from one node and a CIDv1 from another
+/// compare equal.  `None` if `cid` is not a well-formed CID (e.g. another store's
+/// reference).
+pub fn cid_v1(cid: &str) -> Option<String> {
+    cid_v1_bytes(cid).ok().map(|bytes| format!("b{}", base32(&bytes)))
+}
+
+/// The [`cid_v1`] of each CID in `cids`, keyed by the CID as recorded, for a
+/// receipt's `cid_v1`.  Empty and non-CID references are left out.
+pub fn cid_v1_map<'a>(cids: impl IntoIterator<Item = &'a str>) -> BTreeMap<String, String> {
+    cids.into_iter()
+        .filter_map(|cid| Some((cid.to_string(), cid_v1(cid)?)))
+        .collect()
+}
+
+/// Bail unless each given `(flag, value)` is a well-formed CID, before it can be
+/// recorded on-chain.  `--allow-any-metadata` skips this.
+pub fn check_cid_flags(flags: &[(&str, Option<&String>)]) -> Result<()> {
+    for (flag, value) in flags {
+        if let Some(value) = value {
+            check_cid(value).with_context(|| {
+                format!("{flag} {value:?} is not a valid CID (pass --allow-any-metadata to record it anyway)")
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Compute the CIDv0 and cumulative size `ipfs add` would report for `bytes` with
+/// Kubo's defaults (256 KiB chunks, balanced layout, dag-pb leaves).  Returns `None`
+/// for empty files.
+pub fn local_cid(bytes: &[u8]) -> Option<(String, u64)> {
+    if bytes.is_empty() {
+        return None;
+    }
+    let mut level: Vec<DagNode> = bytes
+        .chunks(CHUNK_SIZE)
+        .map(|chunk| dag_node(Some(chunk), &[]))
+        .collect();
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_LINKS)
+            .map(|children| dag_node(None, children))
+            .collect();
+    }
+    let root = level.pop()?;
+    Some((base58btc(&root.multihash), root.cumulative_size))
+}
+
+/// Whether `cid` is recursively pinned on the node.
+async fn is_pinned(ipfs_rpc_url: &str, cid: &str, progress: &dyn ProgressReporter) -> Result<bool> {
+    let url = format!(
+        "{}/api/v0/pin/ls?arg={cid}&type=recursive",
+        ipfs_rpc_url.trim_end_matches('/')
+    );
+    let client = reqwest::Client::new();
+    let response = retry::retry_async("IPFS pin check", progress, || async {
+        client
+            .post(&url)
+            .send()
+            .await
+            .and_then(retry::fail_transient)
+            .with_context(|| format!("failed to query pins at {url}"))
+    })
+    .await?;
+    // Kubo answers 500 with "not pinned" for unknown CIDs.
+    Ok(response.status().is_success())
+}
+
+/// Pin `cid` on the node with `pin/add`.
+async fn pin_add(ipfs_rpc_url: &str, cid: &str, progress: &dyn ProgressReporter) -> Result<()> {
+    let url = format!(
+        "{}/api/v0/pin/add?arg={cid}",
+        ipfs_rpc_url.trim_end_matches('/')
+    );
+    let client = reqwest::Client::new();
+    let response = retry::retry_async("IPFS pin/add", progress, || async {
+        client
+            .post(&url)
+            .send()
+            .await
+            .and_then(retry::fail_transient)
+            .with_context(|| format!("failed to pin {cid} at {url}"))
+    })
+    .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("IPFS pin/add failed (HTTP {status} from {url}): {body}");
+    }
+    Ok(())
+}
+
+/// Fetch the content of `cid` from the node with `cat`.
+pub async fn cat(
+    ipfs_rpc_url: &str,
+    cid: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<u8>> {
+    let url = format!("{}/api/v0/cat?arg={cid}", ipfs_rpc_url.trim_end_matches('/'));
+    let client = reqwest::Client::new();
+    let response = retry::retry_async("IPFS cat", progress, || async {
+        client
+            .post(&url)
+            .send()
+            .await
+            .and_then(retry::fail_transient)
+            .with_context(|| format!("failed to fetch {cid} from {url}"))
+    })
+    .await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("IPFS cat failed (HTTP {status} from {url}): {body}");
+    }
+    let bytes = response.bytes().await.with_context(|| format!("failed to read {cid} from {url}"))?;
+    Ok(bytes.to_vec())
+}
+
+/// Confirm an upload is pinned, since an added but unpinned CID can be evicted by the
+/// node's garbage collector.  With `pin.local_pin`, an unpinned CID is pinned with
+/// `pin/add`; otherwise only a warning is printed.
+async fn check_pinned(
+    ipfs_rpc_url: &str,
+    cid: &str,
+    pin: &PinArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<LocalPin> {
+    match is_pinned(ipfs_rpc_url, cid, progress).await {
+        Ok(true) => Ok(LocalPin::Pinned),
+        Ok(false) if pin.local_pin => {
+            pin_add(ipfs_rpc_url, cid, progress).await?;
+            progress.info(&format!("  pinned {cid} on the IPFS node (--pin)"));
+            Ok(LocalPin::PinnedAfterAdd)
+        }
+        Ok(false) => {
+            progress.info(&format!(
+                "{} {cid} was added but is not pinned on the IPFS node, so it may be garbage-collected (pass --pin to pin it)",
+                style::warning("warning:")
+            ));
+            Ok(LocalPin::NotPinned)
+        }
+        Err(e) => {
+            progress.info(&format!(
+                "{} {:#}; could not confirm {cid} is pinned",
+                style::warning("warning:"),
+                redact::Redacted(&e)
+            ));
+            Ok(LocalPin::Unknown)
+        }
+    }
 }
 
 /// Upload a single file to IPFS (no directory wrapping).
 ///
+/// With `pin.skip_existing`, the CID is computed locally first and the upload is
+/// skipped if the node already has it pinned.
+///
 /// Returns the `AddResponse` for the uploaded file.
-pub async fn add_file(ipfs_rpc_url: &str, file_path: &Path) -> Result<AddResponse> {
+pub async fn add_file(
+    ipfs_rpc_url: &str,
+    file_path: &Path,
+    pin: &PinArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<AddResponse> {
     let file_name = file_path
         .file_name()
         .context("file path has no file name")?
         .to_string_lossy()
         .to_string();
 
+    // Check the size before reading, so a stray multi-gigabyte file is never loaded.
+    if let Some(limit) = pin.max_upload_size {
+        let len = tokio::fs::metadata(file_path)
+            .await
+            .with_context(|| format!("failed to read file: {}", file_path.display()))?
+            .len();
+        if len > limit {
+            bail!(
+                "refusing to upload {len} bytes, exceeds limit of {limit} bytes: {} (raise it with --max-upload-size)",
+                file_path.display()
+            );
+        }
+    }
+
     let file_bytes = tokio::fs::read(file_path)
         .await
         .with_context(|| format!("failed to read file: {}", file_path.display()))?;
 
-    let part = multipart::Part::bytes(file_bytes).file_name(file_name);
-    let form = multipart::Form::new().part("file", part);
+    if pin.skip_existing
+        && let Some((cid, cumulative_size)) = local_cid(&file_bytes)
+    {
+        match is_pinned(ipfs_rpc_url, &cid, progress).await {
+            Ok(true) => {
+                progress.info(&format!(
+                    "{} is already pinned on the IPFS node as {cid}, skipping upload",
+                    file_path.display()
+                ));
+                return Ok(AddResponse {
+                    hash: cid,
+                    size: cumulative_size.to_string(),
+                    skipped: true,
+                    pin: LocalPin::Pinned,
+                });
+            }
+            Ok(false) => {}
+            Err(e) => progress.info(&format!(
+                "{} {:#}; uploading anyway",
+                style::warning("warning:"),
+                redact::Redacted(&e)
+            )),
+        }
+    }
+
+    let file_len = file_bytes.len() as u64;
 
     let url = format!(
         "{}/api/v0/add",
@@ -33,12 +494,17 @@ pub async fn add_file(ipfs_rpc_url: &str, file_path: &Path) -> Result<AddRespons
     );
 
     let client = reqwest::Client::new();
-    let response = client
-        .post(&url)
-        .multipart(form)
-        .send()
-        .await
-        .with_context(|| format!("failed to upload file to IPFS at {url}"))?;
+    let response = retry::retry_async("IPFS upload", progress, || async {
+        let part = multipart::Part::bytes(file_bytes.clone()).file_name(file_name.clone());
+        client
+            .post(&url)
+            .multipart(multipart::Form::new().part("file", part))
+            .send()
+            .await
+            .and_then(retry::fail_transient)
+            .with_context(|| format!("failed to upload file to IPFS at {url}"))
+    })
+    .await?;
 
     let status = response.status();
     if !status.is_success() {
@@ -51,5 +517,411 @@ pub async fn add_file(ipfs_rpc_url: &str, file_path: &Path) -> Result<AddRespons
         .await
         .context("failed to read IPFS add response body")?;
 
-    serde_json::from_str(body.trim()).context("failed to parse IPFS add response")
+    let mut added: AddResponse =
+        serde_json::from_str(body.trim()).context("failed to parse IPFS add response")?;
+    check_reported_size(&added, file_len, file_path, progress);
+    added.pin = check_pinned(ipfs_rpc_url, &added.hash, pin, progress).await?;
+    Ok(added)
+}
+
+/// One line of the NDJSON stream `/api/v0/add` returns for a multi-file add.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AddedEntry {
+    name: String,
+    hash: String,
+    #[serde(default)]
+    size: String,
+}
+
+/// Kubo reports mid-stream failures as an error object in place of an entry.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StreamError {
+    message: String,
+}
+
+/// The files of a Noir project worth publishing: `Nargo.toml` and everything under
+/// `src/`, as sorted paths relative to `project_dir`.
+fn project_files(project_dir: &Path) -> Result<Vec<String>> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) -> Result<()> {
+        for entry in std::fs::read_dir(dir)
+            .with_context(|| format!("failed to read directory {}", dir.display()))?
+        {
+            let path = entry?.path();
+            if path.is_dir() {
+                walk(root, &path, files)?;
+            } else {
+                let relative = path.strip_prefix(root).unwrap_or(&path);
+                files.push(relative.to_string_lossy().replace('\\', "/"));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = vec!["Nargo.toml".to_string()];
+    walk(project_dir, &project_dir.join("src"), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Upload a Noir project directory (`Nargo.toml` and `src/`) to IPFS and return the
+/// directory's entry.
+///
+/// Kubo streams back one entry per file as it is added and the directory last.  If
+/// the upload fails part-way, the error lists the files that were already added, so
+/// their blocks -- on the node but not pinned until the directory completes -- are
+/// not silently orphaned.
+pub async fn add_directory(
+    ipfs_rpc_url: &str,
+    project_dir: &Path,
+    pin: &PinArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<AddResponse> {
+    add_files(ipfs_rpc_url, project_dir, project_files(project_dir)?, pin, progress).await
+}
+
+/// Upload `files` (paths relative to `project_dir`) to IPFS as a directory named after
+/// `project_dir`, keeping their relative paths, and return the directory's entry.
+/// Failures are reported as for [`add_directory`].
+pub async fn add_files(
+    ipfs_rpc_url: &str,
+    project_dir: &Path,
+    files: Vec<String>,
+    pin: &PinArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<AddResponse> {
+    let dir_name = std::fs::canonicalize(project_dir)
+        .with_context(|| format!("failed to resolve {}", project_dir.display()))?
+        .file_name()
+        .context("project directory has no name")?
+        .to_string_lossy()
+        .to_string();
+
+    // Kubo needs each directory as its own part, ahead of the files in it.  Directories
+    // are listed without contents.
+    let mut parts: Vec<(String, Option<Vec<u8>>)> = vec![(dir_name.clone(), None)];
+    let mut dirs_added: Vec<String> = Vec::new();
+    for file in &files {
+        let components: Vec<&str> = file.split('/').collect();
+        for depth in 1..components.len() {
+            let parent = components[..depth].join("/");
+            if !dirs_added.contains(&parent) {
+                parts.push((format!("{dir_name}/{parent}"), None));
+                dirs_added.push(parent);
+            }
+        }
+
+        let path = project_dir.join(file);
+        let bytes = tokio::fs::read(&path)
+            .await
+            .with_context(|| format!("failed to read file: {}", path.display()))?;
+        if let Some(limit) = pin.max_upload_size
+            && bytes.len() as u64 > limit
+        {
+            bail!(
+                "refusing to upload {} bytes, exceeds limit of {limit} bytes: {} (raise it with --max-upload-size)",
+                bytes.len(),
+                path.display()
+            );
+        }
+        parts.push((format!("{dir_name}/{file}"), Some(bytes)));
+    }
+    // A form is consumed by sending it, so each attempt builds its own.
+    let build_form = || -> Result<multipart::Form> {
+        let mut form = multipart::Form::new();
+        for (name, bytes) in &parts {
+            let part = match bytes {
+                Some(bytes) => multipart::Part::bytes(bytes.clone()).file_name(name.clone()),
+                None => multipart::Part::bytes(Vec::new())
+                    .file_name(name.clone())
+                    .mime_str("application/x-directory")?,
+            };
+            form = form.part("file", part);
+        }
+        Ok(form)
+    };
+
+    let url = format!(
+        "{}/api/v0/add?recursive=true",
+        ipfs_rpc_url.trim_end_matches('/')
+    );
+    let client = reqwest::Client::new();
+    let mut response = retry::retry_async("IPFS directory upload", progress, || async {
+        client
+            .post(&url)
+            .multipart(build_form()?)
+            .send()
+            .await
+            .and_then(retry::fail_transient)
+            .with_context(|| format!("failed to upload directory to IPFS at {url}"))
+    })
+    .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!("IPFS add failed (HTTP {status} from {url}): {body}");
+    }
+
+    // Parse entries as they arrive, so a stream cut off part-way still tells us what
+    // was added before it broke.
+    let mut added: Vec<AddedEntry> = Vec::new();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut failure = None;
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) => pending.extend_from_slice(&chunk),
+            Ok(None) => break,
+            Err(e) => {
+                failure = Some(anyhow::Error::new(e).context("IPFS add response was cut off"));
+                break;
+            }
+        }
+        while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if let Err(e) = parse_added_line(&line, &mut added) {
+                failure.get_or_insert(e);
+            }
+        }
+    }
+    if failure.is_none()
+        && let Err(e) = parse_added_line(&pending, &mut added)
+    {
+        failure = Some(e);
+    }
+
+    let root = added.last().filter(|entry| entry.name == dir_name);
+    let failure = match (failure, root) {
+        (None, Some(root)) => {
+            crate::receipt::record_completed(&format!("{dir_name}/ upload"), "cid", &root.hash);
+            let pin = check_pinned(ipfs_rpc_url, &root.hash, pin, progress).await?;
+            return Ok(AddResponse {
+                hash: root.hash.clone(),
+                size: root.size.clone(),
+                skipped: false,
+                pin,
+            });
+        }
+        (Some(e), _) => e,
+        (None, None) => anyhow::anyhow!("IPFS add response ended before the directory entry for {dir_name}"),
+    };
+
+    let added_files: Vec<String> = added
+        .iter()
+        .filter(|entry| {
+            entry
+                .name
+                .strip_prefix(&format!("{dir_name}/"))
+                .is_some_and(|name| files.iter().any(|f| f == name))
+        })
+        .map(|entry| format!("  {}  {}", entry.name, entry.hash))
+        .collect();
+    if added_files.is_empty() {
+        return Err(failure.context(format!(
+            "IPFS directory upload of {} failed before any file was added",
+            project_dir.display()
+        )));
+    }
+    Err(failure.context(format!(
+        "IPFS directory upload of {} failed after adding {} of {} files (added but not pinned, so the node may garbage-collect them):\n{}",
+        project_dir.display(),
+        added_files.len(),
+        files.len(),
+        added_files.join("\n")
+    )))
+}
+
+/// Record one NDJSON line of an add response, turning an error object into an error.
+fn parse_added_line(line: &[u8], added: &mut Vec<AddedEntry>) -> Result<()> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(());
+    }
+    if let Ok(entry) = serde_json::from_str::<AddedEntry>(line) {
+        added.push(entry);
+        return Ok(());
+    }
+    match serde_json::from_str::<StreamError>(line) {
+        Ok(error) => bail!("IPFS add failed: {}", error.message),
+        Err(_) => bail!("failed to parse IPFS add response line: {line}"),
+    }
+}
+
+/// Warn when the size IPFS reports for an upload is implausible for the file we sent.
+///
+/// Kubo reports the cumulative DAG size, which exceeds the raw byte count by a small
+/// amount of UnixFS/protobuf framing per block, so only gross mismatches are flagged --
+/// those usually mean a proxy altered the upload.
+fn check_reported_size(
+    added: &AddResponse,
+    file_len: u64,
+    file_path: &Path,
+    progress: &dyn ProgressReporter,
+) {
+    let Ok(reported) = added.size.parse::<u64>() else {
+        progress.info(&format!(
+            "{} IPFS returned a non-numeric size {:?} for {}",
+            style::warning("warning:"),
+            added.size,
+            file_path.display()
+        ));
+        return;
+    };
+    let max_overhead = (file_len / 100).max(1024);
+    if reported < file_len || reported > file_len + max_overhead {
+        progress.info(&format!(
+            "{} IPFS reported size {reported} for {} ({file_len} bytes uploaded) -- the upload may have been altered in transit",
+            style::warning("warning:"),
+            file_path.display()
+        ));
+    }
+}
+
+/// A pin request as returned by the Pinning Service API.
+#[derive(Debug, Deserialize)]
+pub struct PinStatus {
+    pub requestid: String,
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PinResults {
+    results: Vec<PinStatus>,
+}
+
+/// Look up an existing pin request for `cid`.  The listing is filtered by CID, so
+/// any result is a request for it and the first page of one is enough.
+async fn find_pin(
+    client: &reqwest::Client,
+    pins_url: &str,
+    token: &str,
+    cid: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<Option<PinStatus>> {
+    let query = [
+        ("cid", cid),
+        ("status", "queued,pinning,pinned"),
+        ("limit", "1"),
+    ];
+    let page: PinResults = retry::retry_async("pin listing", progress, || async {
+        client
+            .get(pins_url)
+            .bearer_auth(token)
+            .query(&query)
+            .send()
+            .await
+            .with_context(|| format!("failed to list pins at {pins_url}"))?
+            .error_for_status()
+            .with_context(|| format!("pinning service rejected pin listing at {pins_url}"))?
+            .json()
+            .await
+            .context("failed to parse pin listing response")
+    })
+    .await?;
+    Ok(page.results.into_iter().next())
+}
+
+/// Pin `cid` on a remote service via the IPFS Pinning Service API and wait until
+/// it is `pinned`.
+///
+/// An existing pin request for the same CID is resumed rather than duplicated, so
+/// re-running after an interrupted pin picks up where it left off.
+pub async fn remote_pin(
+    service_url: &str,
+    token: &str,
+    cid: &str,
+    name: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    let client = reqwest::Client::new();
+    let pins_url = format!("{}/pins", service_url.trim_end_matches('/'));
+
+    let mut pin = match find_pin(&client, &pins_url, token, cid, progress).await? {
+        Some(existing) => {
+            progress.info(&format!(
+                "  resuming pin request {} ({})",
+                existing.requestid, existing.status
+            ));
+            existing
+        }
+        None => retry::retry_async("pin request", progress, || async {
+            client
+                .post(&pins_url)
+                .bearer_auth(token)
+                .json(&serde_json::json!({ "cid": cid, "name": name }))
+                .send()
+                .await
+                .with_context(|| format!("failed to request pin at {pins_url}"))?
+                .error_for_status()
+                .with_context(|| format!("pinning service rejected pin request for {cid}"))?
+                .json()
+                .await
+                .context("failed to parse pin request response")
+        })
+        .await?,
+    };
+
+    for attempt in 1..=MAX_PIN_POLL_ATTEMPTS {
+        match pin.status.as_str() {
+            "pinned" => return Ok(()),
+            "failed" => bail!("remote pinning of {cid} failed (request {})", pin.requestid),
+            _ => {}
+        }
+        progress.info(&format!(
+            "  pin status ({attempt}/{MAX_PIN_POLL_ATTEMPTS}): {}",
+            pin.status
+        ));
+        sleep(PIN_POLL_INTERVAL).await;
+
+        let status_url = format!("{pins_url}/{}", pin.requestid);
+        pin = retry::retry_async("pin status check", progress, || async {
+            client
+                .get(&status_url)
+                .bearer_auth(token)
+                .send()
+                .await
+                .with_context(|| format!("failed to poll pin status at {status_url}"))?
+                .error_for_status()
+                .with_context(|| format!("pinning service rejected status request {status_url}"))?
+                .json()
+                .await
+                .context("failed to parse pin status response")
+        })
+        .await?;
+    }
+
+    bail!(
+        "remote pin of {cid} still {} after {MAX_PIN_POLL_ATTEMPTS} checks (request {})",
+        pin.status,
+        pin.requestid
+    )
+}
+
+/// Remote-pin `cid` if `--remote-pin` is enabled; a no-op otherwise.
+pub async fn remote_pin_if_enabled(
+    pin: &PinArgs,
+    cid: &str,
+    name: &str,
+    progress: &dyn ProgressReporter,
+) -> Result<()> {
+    if !pin.remote_pin {
+        return Ok(());
+    }
+    let service_url = pin
+        .service_url
+        .as_deref()
+        .context("--remote-pin requires --pin-service-url")?;
+    let token = pin
+        .token
+        .as_deref()
+        .context("--remote-pin requires --pin-token")?;
+
+    progress.info(&format!("  pinning {cid} to {service_url}..."));
+    remote_pin(service_url, token, cid, name, progress)
+        .await
+        .with_context(|| format!("failed to pin {cid} to remote service {service_url}"))?;
+    progress.info(&format!("  pinned {cid} remotely"));
+    Ok(())
 }
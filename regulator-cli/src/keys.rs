@@ -0,0 +1,188 @@
+use alloy::signers::local::PrivateKeySigner;
+use anyhow::{Context, Result, bail};
+
+use crate::redact;
+
+/// Service name under which keys are stored in the OS keychain.
+const KEYRING_SERVICE: &str = "regulator-cli";
+
+/// Where the deployer private key comes from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeySource {
+    /// `--private-key` / `PRIVATE_KEY`
+    #[default]
+    Env,
+    /// The OS keychain (macOS Keychain, Windows Credential Manager, Secret Service)
+    Keyring,
+}
+
+fn entry(name: &str) -> Result<keyring::Entry> {
+    keyring::Entry::new(KEYRING_SERVICE, name)
+        .with_context(|| format!("failed to open keychain entry {name:?}"))
+}
+
+/// Private key selection shared by every command that sends transactions.
+#[derive(clap::Args)]
+pub struct KeyArgs {
+    /// Private key for the deployer/regulator account
+    #[arg(long, env = "PRIVATE_KEY", hide_env_values = true, required_unless_present_any = ["key_name", "ledger"])]
+    private_key: Option<String>,
+
+    /// Where to read the private key from
+    #[arg(long, value_enum, default_value_t = KeySource::Env)]
+    key_source: KeySource,
+
+    /// Name of the OS keychain entry holding the private key (with --key-source keyring)
+    #[arg(long, env = "KEY_NAME", value_name = "NAME")]
+    key_name: Option<String>,
+
+    /// Sign on a Ledger through `cast send --ledger` instead of with a private key
+    /// (`register` only)
+    #[arg(long, conflicts_with = "key_name")]
+    ledger: bool,
+
+    /// Whether `--private-key` was passed on the command line, rather than picked up
+    /// from `PRIVATE_KEY`.  Set by [`Self::record_sources`].
+    #[arg(skip)]
+    private_key_on_command_line: bool,
+}
+
+/// What signs a command's transactions.
+pub enum Signer {
+    PrivateKey(String),
+    /// A Ledger, through `cast send --ledger`.
+    Ledger,
+}
+
+impl KeyArgs {
+    /// Resolve the private key from the selected source, and mask it in all output
+    /// from then on.
+    ///
+    /// Keychain backends may block, so the lookup runs off the async runtime.
+    pub async fn resolve(self) -> Result<String> {
+        if self.ledger {
+            bail!(
+                "--ledger is only supported by `register`; to sign other commands elsewhere, use --print-calldata where available"
+            );
+        }
+        let key = self.read().await?;
+        redact::register(&key);
+        Ok(key)
+    }
+
+    /// Like [`Self::resolve`], but also accepting `--ledger`, for commands that can
+    /// send through `cast`.  A private key given alongside `--ledger` (e.g. `PRIVATE_KEY`
+    /// in `.env`) is not used.
+    pub async fn resolve_signer(self) -> Result<Signer> {
+        if self.ledger {
+            return Ok(Signer::Ledger);
+        }
+        self.resolve().await.map(Signer::PrivateKey)
+    }
+
+    /// Whether `--ledger` was given.
+    pub fn ledger(&self) -> bool {
+        self.ledger
+    }
+
+    /// Record where `--private-key` came from, from the matches of the subcommand these
+    /// arguments belong to.  A `PRIVATE_KEY` in the environment (or `.env`) is then
+    /// ignored with `--key-source keyring`; only an explicit `--private-key` conflicts.
+    pub fn record_sources(&mut self, matches: &clap::ArgMatches) {
+        self.private_key_on_command_line =
+            matches.value_source("private_key") == Some(clap::parser::ValueSource::CommandLine);
+    }
+
+    async fn read(self) -> Result<String> {
+        match self.key_source {
+            KeySource::Env => self.private_key.context(
+                "--key-name is only used with --key-source keyring; pass --private-key or select the keychain",
+            ),
+            KeySource::Keyring => {
+                if self.private_key_on_command_line {
+                    bail!("--private-key cannot be combined with --key-source keyring");
+                }
+                let name = self
+                    .key_name
+                    .context("--key-source keyring requires --key-name")?;
+                tokio::task::spawn_blocking(move || {
+                    entry(&name)?.get_password().with_context(|| {
+                        format!(
+                            "failed to read key {name:?} from the OS keychain -- store it first with `regulator-cli key import --key-name {name}`"
+                        )
+                    })
+                })
+                .await
+                .context("keychain lookup panicked")?
+            }
+        }
+    }
+}
+
+/// Read a private key line from stdin.  When stdin is a terminal (on Unix), echo is
+/// off while it is typed, so the key never appears on screen or in scrollback.
+pub fn read_private_key() -> Result<String> {
+    #[cfg(unix)]
+    let _echo_off = unix::EchoOff::stdin();
+    let mut private_key = String::new();
+    std::io::stdin()
+        .read_line(&mut private_key)
+        .context("failed to read private key from stdin")?;
+    Ok(private_key)
+}
+
+/// Validate `private_key` and store it in the OS keychain under `name`, returning the
+/// address it controls.
+pub async fn import(name: &str, private_key: &str) -> Result<String> {
+    let private_key = private_key.trim().to_string();
+    let signer: PrivateKeySigner = private_key
+        .parse()
+        .context("failed to parse private key")?;
+
+    let name = name.to_string();
+    tokio::task::spawn_blocking(move || {
+        entry(&name)?
+            .set_password(&private_key)
+            .with_context(|| format!("failed to store key {name:?} in the OS keychain"))
+    })
+    .await
+    .context("keychain write panicked")??;
+
+    Ok(signer.address().to_string())
+}
+
+#[cfg(unix)]
+mod unix {
+    /// Terminal echo on stdin, turned off until dropped.
+    pub struct EchoOff(libc::termios);
+
+    impl EchoOff {
+        /// Turn echo off, keeping the newline that ends the line.  `None` if stdin is
+        /// not a terminal.
+        pub fn stdin() -> Option<Self> {
+            // SAFETY: `termios` is plain data, filled in by `tcgetattr` before use.
+            unsafe {
+                let mut termios: libc::termios = std::mem::zeroed();
+                if libc::tcgetattr(libc::STDIN_FILENO, &mut termios) != 0 {
+                    return None;
+                }
+                let original = termios;
+                termios.c_lflag &= !libc::ECHO;
+                termios.c_lflag |= libc::ECHONL;
+                if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &termios) != 0 {
+                    return None;
+                }
+                Some(Self(original))
+            }
+        }
+    }
+
+    impl Drop for EchoOff {
+        fn drop(&mut self) {
+            // SAFETY: restores the attributes `tcgetattr` returned for the same descriptor.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Mirrors everything the process writes to stdout and stderr into a log file, each
+/// line prefixed with a UTC timestamp and the stream it came from.  The terminal
+/// still receives the output unchanged, except that registered secrets (see
+/// [`crate::redact`]) are masked in both.
+///
+/// The process's file descriptors 1 and 2 are redirected through pipes, so output
+/// from every module is captured, not just what goes through the progress reporter.
+/// Dropping the guard restores the original descriptors and waits for the log to be
+/// fully written.
+pub struct LogFile {
+    #[cfg(unix)]
+    streams: Vec<unix::Tee>,
+}
+
+impl LogFile {
+    #[cfg(unix)]
+    pub fn tee(path: &Path) -> Result<Self> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open log file {}", path.display()))?;
+        let file = std::sync::Arc::new(std::sync::Mutex::new(file));
+        let streams = [(libc::STDOUT_FILENO, "stdout"), (libc::STDERR_FILENO, "stderr")]
+            .into_iter()
+            .map(|(fd, name)| unix::Tee::start(fd, name, file.clone()))
+            .collect::<Result<_>>()
+            .context("failed to redirect output to the log file")?;
+        Ok(Self { streams })
+    }
+
+    #[cfg(not(unix))]
+    pub fn tee(_path: &Path) -> Result<Self> {
+        anyhow::bail!("--log-file is only supported on Unix")
+    }
+}
+
+impl Drop for LogFile {
+    fn drop(&mut self) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        #[cfg(unix)]
+        for stream in self.streams.drain(..) {
+            stream.finish();
+        }
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use anyhow::{Result, bail};
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::fd::{FromRawFd, RawFd};
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+
+    /// One redirected descriptor: `fd` now writes into a pipe, and a thread copies
+    /// the pipe to the original destination and to the log.
+    pub struct Tee {
+        fd: RawFd,
+        original: RawFd,
+        copier: JoinHandle<()>,
+    }
+
+    fn check(ret: libc::c_int) -> Result<libc::c_int> {
+        if ret < 0 {
+            bail!("{}", std::io::Error::last_os_error());
+        }
+        Ok(ret)
+    }
+
+    /// Drop ANSI color sequences (`ESC [ ... m`), which `--color` may add for the
+    /// terminal, from a line bound for the log.
+    fn strip_ansi(line: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(line.len());
+        let mut bytes = line.iter().copied();
+        while let Some(b) = bytes.next() {
+            if b == 0x1b {
+                for c in bytes.by_ref() {
+                    if c == b'm' {
+                        break;
+                    }
+                }
+            } else {
+                out.push(b);
+            }
+        }
+        out
+    }
+
+    impl Tee {
+        pub fn start(fd: RawFd, name: &'static str, log: Arc<Mutex<File>>) -> Result<Self> {
+            let mut pipe = [0; 2];
+            // SAFETY: plain descriptor syscalls on descriptors this function owns; each
+            // result is checked before use.
+            let (original, terminal) = unsafe {
+                check(libc::pipe(pipe.as_mut_ptr()))?;
+                let original = check(libc::dup(fd))?;
+                let terminal = check(libc::dup(original))?;
+                check(libc::dup2(pipe[1], fd))?;
+                libc::close(pipe[1]);
+                (original, terminal)
+            };
+            // SAFETY: both descriptors were just created above and are owned by the
+            // copier from here on.
+            let (reader, mut terminal) = unsafe { (File::from_raw_fd(pipe[0]), File::from_raw_fd(terminal)) };
+
+            let copier = std::thread::spawn(move || {
+                let mut reader = BufReader::new(reader);
+                let mut line = Vec::new();
+                while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                    let line = crate::redact::redact_bytes(&std::mem::take(&mut line));
+                    let _ = terminal.write_all(&line);
+                    let stamp = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ");
+                    if let Ok(mut log) = log.lock() {
+                        let _ = write!(log, "{stamp} [{name}] ");
+                        let _ = log.write_all(&strip_ansi(&line));
+                        if !line.ends_with(b"\n") {
+                            let _ = log.write_all(b"\n");
+                        }
+                    }
+                }
+            });
+            Ok(Self { fd, original, copier })
+        }
+
+        /// Point `fd` back at its original destination, closing the pipe's last write
+        /// end so the copier sees EOF, and wait for it to drain.
+        pub fn finish(self) {
+            // SAFETY: `original` is a descriptor this Tee owns.
+            unsafe {
+                libc::dup2(self.original, self.fd);
+                libc::close(self.original);
+            }
+            let _ = self.copier.join();
+        }
+    }
+}
@@ -1,36 +1,327 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use alloy::primitives::{Address, B256};
+use anyhow::{Context, Result};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::net::IpAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 
 mod bb;
+mod cast;
 mod commands;
+mod config;
 mod eth;
 mod etherscan;
 mod forge;
 mod ipfs;
+mod keys;
+mod log_file;
+mod manifest;
+mod metadata_schema;
 mod nargo;
+mod plan;
+mod progress;
+mod prover_toml;
 mod receipt;
+mod redact;
+mod retry;
+mod solc;
+mod storage;
+mod style;
+mod summary;
+mod timings;
+mod tools;
+mod watch;
 
 #[derive(Parser)]
-#[command(name = "regulator-cli")]
+#[command(name = "regulator-cli", version)]
 #[command(about = "CLI for managing privacy-preserving compliance definitions")]
 struct Cli {
     /// IPFS RPC endpoint URL
-    #[arg(long, global = true, env = "IPFS_RPC_URL")]
-    ipfs_rpc_url: Option<String>,
+    #[arg(long, global = true, env = "IPFS_RPC_URL", default_value = DEFAULT_IPFS_RPC_URL)]
+    ipfs_rpc_url: String,
+
+    /// Where circuit artifacts and leaves files are stored; the reference it returns is
+    /// recorded on-chain as the metadataHash / leavesHash
+    #[arg(long, global = true, value_enum, env = "STORAGE_BACKEND", default_value_t = storage::StorageBackend::Ipfs)]
+    storage_backend: storage::StorageBackend,
+
+    /// Color key output lines (successes, failures, warnings)
+    #[arg(long, global = true, value_enum, default_value_t = style::ColorChoice::Auto)]
+    color: style::ColorChoice,
+
+    /// Print the effective settings for the given command -- after `.env`, environment
+    /// variables, defaults, and flags are merged -- and exit without running it.  Secrets
+    /// are redacted.
+    #[arg(long, global = true, value_enum, value_name = "FORMAT", num_args = 0..=1, require_equals = true, default_missing_value = "table")]
+    show_config: Option<config::ConfigFormat>,
+
+    /// Print the ordered steps the command would run, with the resolved contract dir,
+    /// chain, definition addresses, and whether verification will run, and exit
+    /// without running anything or contacting the network
+    #[arg(long, global = true, conflicts_with = "show_config")]
+    print_plan: bool,
+
+    /// Append everything written to stdout and stderr to this file, each line prefixed
+    /// with a UTC timestamp (Unix only)
+    #[arg(long, global = true, env = "LOG_FILE", value_name = "FILE")]
+    log_file: Option<PathBuf>,
+
+    /// Print extra diagnostics, such as a per-step timing breakdown
+    #[arg(short, long, global = true)]
+    verbose: bool,
 
     /// Directory for JSON receipts (one per command run)
-    #[arg(long, global = true, value_name = "DIR")]
-    receipts_dir: Option<PathBuf>,
+    #[arg(long, global = true, value_name = "DIR", default_value = DEFAULT_RECEIPTS_DIR)]
+    receipts_dir: PathBuf,
+
+    /// Also print the receipt JSON to stdout as the last line; `key=value` results move
+    /// to stderr so stdout contains only the receipt
+    #[arg(long, global = true)]
+    receipt_stdout: bool,
+
+    /// Name receipts `<command>-<content-hash>.json` instead of by timestamp, so
+    /// re-running an identical deployment produces the same path
+    #[arg(long, global = true)]
+    deterministic_receipt: bool,
+
+    /// Also write the command's results as `export KEY='value'` lines to FILE, for
+    /// `source FILE` in a shell script
+    #[arg(long, global = true, value_name = "FILE", visible_alias = "output-addresses-file")]
+    env_out: Option<PathBuf>,
 
     /// Etherscan API key -- when set, deployed contracts are verified on the block explorer
-    #[arg(long, global = true, env = "ETHERSCAN_API_KEY")]
+    #[arg(long, global = true, env = "ETHERSCAN_API_KEY", hide_env_values = true)]
     etherscan_api_key: Option<String>,
 
     /// Block explorer verification URL (for non-Etherscan explorers like Blockscout)
     #[arg(long, global = true, env = "VERIFIER_URL")]
     verifier_url: Option<String>,
 
+    /// TOML file of `[[chain]]` entries (`id`, `api_url`, `gate_limit`) routing verification
+    /// for those chains to their own explorer API instead of Etherscan's unified v2
+    /// endpoint, and overriding the circuit size above which verification is flagged as
+    /// impractically expensive
+    #[arg(long, global = true, env = "CHAINS_FILE", value_name = "FILE")]
+    chains_file: Option<PathBuf>,
+
+    /// Query parameter name used to send the API key to the verifier [default: apikey]
+    #[arg(long, global = true, env = "VERIFIER_KEY_PARAM", value_name = "NAME")]
+    verifier_key_param: Option<String>,
+
+    /// HTTP header used to send the API key to the verifier (instead of a query parameter)
+    #[arg(
+        long,
+        global = true,
+        env = "VERIFIER_KEY_HEADER",
+        value_name = "NAME",
+        conflicts_with = "verifier_key_param"
+    )]
+    verifier_key_header: Option<String>,
+
+    /// Upper bound in seconds on waiting for a verification result.  Deployment
+    /// still succeeds when exceeded; verification is reported as failed.
+    #[arg(long, global = true, value_name = "SECS")]
+    verify_total_timeout: Option<u64>,
+
+    /// Wall-clock limit in seconds on the whole command.  When exceeded the command is
+    /// stopped wherever it is, its temporary files are removed, and the CLI exits 124
+    #[arg(long, global = true, value_name = "SECS")]
+    deadline: Option<u64>,
+
+    /// Exit non-zero when a contract's explorer verification fails (a skipped
+    /// verification, e.g. without an API key, still succeeds)
+    #[arg(long, global = true)]
+    fail_on_verification_failure: bool,
+
+    /// Additional directory to search for contract sources during verification (repeatable)
+    #[arg(long, global = true, value_name = "DIR")]
+    contract_source_root: Vec<PathBuf>,
+
+    /// Proving backend used to generate the verification key and Solidity verifier
+    #[arg(long, global = true, value_enum, default_value_t = bb::Backend::Bb)]
+    backend: bb::Backend,
+
+    /// `bb` executable to run instead of the one on PATH
+    #[arg(long, global = true, env = "BB_BINARY", value_name = "PATH")]
+    bb_path: Option<PathBuf>,
+
+    /// `nargo` executable to run instead of the one on PATH
+    #[arg(long, global = true, env = "NARGO_BINARY", value_name = "PATH")]
+    nargo_path: Option<PathBuf>,
+
+    /// `forge` executable to run instead of the one on PATH
+    #[arg(long, global = true, env = "FORGE_BINARY", value_name = "PATH")]
+    forge_path: Option<PathBuf>,
+
+    /// `cast` executable to run instead of the one on PATH (for `register --ledger`)
+    #[arg(long, global = true, env = "CAST_BINARY", value_name = "PATH")]
+    cast_path: Option<PathBuf>,
+
+    /// `solc` executable to run instead of the one on PATH (for `--build-tool solc`)
+    #[arg(long, global = true, env = "SOLC_BINARY", value_name = "PATH")]
+    solc_path: Option<PathBuf>,
+
+    /// Extra argument appended to `nargo compile`, e.g. `--nargo-arg=--force` (repeatable)
+    #[arg(long, global = true, value_name = "ARG", allow_hyphen_values = true, value_parser = nargo::parse_extra_arg)]
+    nargo_arg: Vec<String>,
+
+    /// Times to retry a network call (IPFS, RPC queries, Etherscan, the pinning service)
+    /// after a connection failure, timeout, rate limit or gateway error.  Transactions
+    /// are never resent this way
+    #[arg(long, global = true, value_name = "N", default_value_t = retry::DEFAULT_RETRIES)]
+    retries: u32,
+
+    /// Seconds before the first retry of a network call; each later retry waits twice
+    /// as long, up to 30s
+    #[arg(long, global = true, value_name = "SECS", default_value_t = retry::DEFAULT_BASE_DELAY_SECS)]
+    retry_base_delay: u64,
+
+    /// RPC endpoints to fail over to, in order, when --rpc-url does not answer
+    /// (comma-separated or repeated); the first that answers is used for the whole run
+    #[arg(long, global = true, env = "RPC_FALLBACK", value_name = "URL", value_delimiter = ',')]
+    rpc_fallback: Vec<String>,
+
+    /// Times to re-run `nargo compile` after a transient filesystem or lock error
+    #[arg(long, global = true, value_name = "N", default_value_t = nargo::DEFAULT_COMPILE_RETRIES)]
+    nargo_compile_retries: u32,
+
+    /// Deprecated: use --nargo-arg.  Circuits are validated by `nargo compile`, so these
+    /// are appended to it after any --nargo-arg, with a warning
+    #[arg(long, global = true, value_name = "ARG", allow_hyphen_values = true, value_parser = nargo::parse_extra_arg)]
+    nargo_check_arg: Vec<String>,
+
+    /// Link an already-deployed library instead of deploying it: <name>=<address>,
+    /// where <name> is the library name or <path>:<Library> (repeatable)
+    #[arg(long = "library", global = true, value_name = "NAME=ADDRESS", value_parser = eth::parse_library)]
+    libraries: Vec<(String, Address)>,
+
+    /// Gas oracle URL returning `{maxFeePerGas, maxPriorityFeePerGas}` in wei; used in
+    /// place of the node's fee estimate, falling back to it if the oracle fails
+    #[arg(long, global = true, env = "GAS_ORACLE_URL", value_name = "URL")]
+    gas_oracle: Option<String>,
+
+    /// Largest file, in bytes, that will be uploaded to IPFS; 0 disables the check
+    #[arg(long, global = true, value_name = "BYTES", default_value_t = ipfs::DEFAULT_MAX_UPLOAD_SIZE)]
+    max_upload_size: u64,
+
+    /// Deploy contracts from the existing forge `out/` directory instead of running
+    /// `forge build` (e.g. when CI built them in an earlier stage)
+    #[arg(long, global = true)]
+    skip_build: bool,
+
+    /// Solidity optimizer runs used when compiling the contracts; raise it to trade a
+    /// larger verifier for cheaper proof verification
+    #[arg(long, global = true, value_name = "N", default_value_t = forge::DEFAULT_OPTIMIZER_RUNS)]
+    optimizer_runs: u32,
+
+    /// Compiler for the generated verifier: `forge build` over the contracts project,
+    /// or `solc --standard-json` on Verifier.sol alone, so deploying a verifier does
+    /// not need Foundry
+    #[arg(long, global = true, value_enum, default_value_t = solc::BuildTool::Forge)]
+    build_tool: solc::BuildTool,
+
+    /// Nonce of the first transaction; later transactions in the run count up from it
+    /// instead of fetching the account nonce before each one
+    #[arg(long, global = true, value_name = "N")]
+    start_nonce: Option<u64>,
+
+    /// Resubmit a transaction with a freshly fetched nonce when the node rejects it with
+    /// "nonce too low" or "replacement transaction underpriced" (e.g. another process
+    /// shares the key)
+    #[arg(long, global = true)]
+    retry_on_nonce_error: bool,
+
+    /// Minimum maxFeePerGas in gwei (e.g. `0.01`); lower estimates or oracle fees are
+    /// raised to it, for L2s that reject transactions under a fee floor
+    #[arg(long, global = true, env = "MIN_GAS_PRICE", value_name = "GWEI", value_parser = eth::parse_gwei)]
+    min_gas_price: Option<u128>,
+
+    /// JSON ABI (bare array or forge artifact) of a non-standard ComplianceDefinition;
+    /// its --update-function is called in place of the built-in `updateCircuit`
+    #[arg(long, global = true, value_name = "FILE")]
+    definition_abi: Option<PathBuf>,
+
+    /// Update function to call from --definition-abi: a name, or a full signature to
+    /// select an overload
+    #[arg(long, global = true, value_name = "NAME", requires = "definition_abi", default_value = "updateCircuit")]
+    update_function: String,
+
+    /// Prove the circuit with its Prover.toml after deploying the verifier, and record
+    /// the gas `verify()` takes for that proof (estimated, not sent)
+    #[arg(long, global = true)]
+    simulate_proof_gas: bool,
+
+    /// After deploying the verifier, check that it answers `verify(bytes,bytes32[])`
+    /// (a staticcall with an empty proof) before registering it
+    #[arg(long, global = true)]
+    verify_interface: bool,
+
+    /// Record --circuit-cid / --leaves-cid on-chain as given, without checking that
+    /// they are well-formed CIDs
+    #[arg(long, global = true)]
+    allow_any_metadata: bool,
+
+    /// Send the deployments of the verifier's libraries back to back and wait for
+    /// their receipts together, instead of one at a time
+    #[arg(long, global = true)]
+    parallel_libraries: bool,
+
+    /// After registering a version, label it on-chain with the definition's
+    /// `setLabel(string)`.  Skipped with a warning if the definition has no `setLabel`
+    #[arg(long, global = true, value_name = "TEXT", visible_alias = "deployment-label")]
+    on_chain_label: Option<String>,
+
+    /// keccak256 of the verifier artifact's `bytecode.object` that the build must
+    /// reproduce; the run stops before deploying if it differs.  The computed hash is
+    /// printed either way, so it can be captured on a trusted first build
+    #[arg(long, global = true, value_name = "HASH", visible_alias = "expected-verifier-hash")]
+    expected_verifier_bytecode_hash: Option<B256>,
+
+    /// Most compliance definitions `update-circuit` registers with at once.  Their
+    /// transactions are still broadcast one at a time, so nonces never collide
+    #[arg(long, global = true, value_name = "N", default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..))]
+    concurrency: u16,
+
+    /// Send legacy (type 0) transactions priced with `gasPrice` instead of EIP-1559
+    /// fees.  Chains whose blocks carry no base fee are detected and get legacy
+    /// transactions without this flag.  Conflicts with `--gas-oracle`, whose EIP-1559
+    /// fees legacy transactions cannot use
+    #[arg(long, global = true, conflicts_with = "gas_oracle")]
+    legacy_tx: bool,
+
+    /// Record each deployment transaction in <receipts-dir>/deployments.lock.json and,
+    /// on a rerun, reuse the contract it deployed (or wait for it if still pending)
+    /// instead of deploying the same bytecode again
+    #[arg(long, global = true)]
+    idempotent: bool,
+
+    /// Compute each file's CID locally and skip the IPFS upload if the node already
+    /// has it pinned
+    #[arg(long, global = true)]
+    ipfs_skip_existing: bool,
+
+    /// Also upload the Noir project (Nargo.toml and src/) to IPFS as a directory and
+    /// record its CID in the receipt, so the circuit's source can be audited
+    #[arg(long, global = true)]
+    upload_project: bool,
+
+    /// After each upload, pin the CID on the IPFS node with `pin/add` if the node
+    /// reports it unpinned (otherwise only a warning is printed)
+    #[arg(long, global = true)]
+    pin: bool,
+
+    /// Pin uploaded CIDs to a remote IPFS Pinning Service after the local add
+    #[arg(long, global = true, requires_all = ["pin_service_url", "pin_token"])]
+    remote_pin: bool,
+
+    /// Pinning Service API base URL (e.g. https://api.pinata.cloud/psa)
+    #[arg(long, global = true, env = "PIN_SERVICE_URL")]
+    pin_service_url: Option<String>,
+
+    /// Bearer token for the pinning service
+    #[arg(long, global = true, env = "PIN_TOKEN", hide_env_values = true)]
+    pin_token: Option<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -55,11 +346,11 @@ enum Commands {
         #[arg(long, env = "RPC_URL")]
         rpc_url: String,
 
-        /// Private key for the deployer account
-        #[arg(long, env = "PRIVATE_KEY")]
-        private_key: String,
+        #[command(flatten)]
+        key: keys::KeyArgs,
 
         /// Address of the regulator that will control the compliance definition
+        /// (plain, EIP-3770 prefixed, e.g. `base:0x...`, or an ENS name)
         #[arg(long, env = "PUBLIC_KEY")]
         regulator: String,
 
@@ -67,6 +358,27 @@ enum Commands {
         #[arg(long, default_value = "contracts", value_name = "DIR")]
         contract_dir: PathBuf,
 
+        /// JSON array of ComplianceDefinition constructor arguments, encoded against the
+        /// constructor ABI.  Overrides the arguments derived from --regulator and --name.
+        #[arg(long, value_name = "FILE")]
+        constructor_args_json: Option<PathBuf>,
+
+        /// Deploy the ComplianceDefinition through the CREATE2 factory with this salt
+        /// (bytes32), so the same regulator, name, and salt give the same address on
+        /// every chain.  The predicted address is printed before sending.
+        #[arg(long, value_name = "BYTES32")]
+        compliance_definition_salt: Option<B256>,
+
+        /// ComplianceFactory (contracts/src/ComplianceFactory.sol) that deploys the
+        /// verifier and the ComplianceDefinition and registers the verifier in a single
+        /// transaction, so either everything lands or nothing does
+        #[arg(
+            long,
+            value_name = "ADDRESS",
+            conflicts_with_all = ["compliance_definition_salt", "constructor_args_json", "print_calldata"]
+        )]
+        factory: Option<Address>,
+
         /// Path to write the generated Solidity verifier [default: <DIR>/target/Verifier.sol]
         #[arg(long, value_name = "FILE")]
         verifier_output: Option<PathBuf>,
@@ -98,6 +410,31 @@ enum Commands {
         /// Mutually exclusive with --leaves-file.
         #[arg(long, value_name = "CID")]
         leaves_cid: Option<String>,
+
+        /// Note on this version (e.g. "Q3 sanctions list update").  The circuit CID
+        /// and the note are uploaded as a JSON document whose CID is registered as the
+        /// metadataHash
+        #[arg(long, value_name = "TEXT", visible_alias = "definition-version-note")]
+        note: Option<String>,
+
+        /// Strip comments and normalize whitespace in the circuit sources embedded
+        /// in the uploaded artifact, so formatting-only changes keep the same CID
+        #[arg(long, conflicts_with = "circuit_cid")]
+        normalize_source: bool,
+
+        /// Print the target address and ABI-encoded updateCircuit calldata instead
+        /// of broadcasting the registration (for external signers such as a Safe)
+        #[arg(long)]
+        print_calldata: bool,
+
+        /// Fail, instead of warning, when t_end has already passed or t_start is far
+        /// in the past
+        #[arg(long)]
+        strict: bool,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
     },
     /// Update the circuit of an existing ComplianceDefinition: compile, deploy a new verifier, and register it
     UpdateCircuit {
@@ -105,17 +442,33 @@ enum Commands {
         #[arg(long, value_name = "DIR")]
         circuit_dir: PathBuf,
 
-        /// RPC URL of the target chain
-        #[arg(long, env = "RPC_URL")]
-        rpc_url: String,
+        /// RPC URL of the target chain [default: taken from --from-receipt]
+        #[arg(long, env = "RPC_URL", required_unless_present = "from_receipt")]
+        rpc_url: Option<String>,
+
+        #[command(flatten)]
+        key: keys::KeyArgs,
+
+        /// Address of a deployed ComplianceDefinition contract (plain, EIP-3770
+        /// prefixed, or an ENS name).  Repeat to register the same verifier on several
+        /// definitions.
+        #[arg(long, required_unless_present_any = ["from_receipt", "deploy_verifier_only"])]
+        compliance_definition: Vec<String>,
 
-        /// Private key for the deployer account
-        #[arg(long, env = "PRIVATE_KEY")]
-        private_key: String,
+        /// Take the definition address, chain, and RPC URL from a
+        /// new-compliance-definition receipt
+        #[arg(long, value_name = "FILE", conflicts_with = "compliance_definition")]
+        from_receipt: Option<PathBuf>,
 
-        /// Address of the deployed ComplianceDefinition contract
+        /// Keep registering on the remaining definitions when one fails (the command
+        /// still exits non-zero)
         #[arg(long)]
-        compliance_definition: String,
+        continue_on_error: bool,
+
+        /// Write a JSON rollup of the run -- every definition with its outcome, plus the
+        /// verifier address and CIDs -- to this file, whether or not the run succeeds
+        #[arg(long, value_name = "FILE")]
+        summary_json: Option<PathBuf>,
 
         /// Path to write the generated Solidity verifier [default: <DIR>/target/Verifier.sol]
         #[arg(long, value_name = "FILE")]
@@ -152,20 +505,72 @@ enum Commands {
         /// Mutually exclusive with --leaves-file.
         #[arg(long, value_name = "CID")]
         leaves_cid: Option<String>,
+
+        /// Note on this version (e.g. "Q3 sanctions list update").  The circuit CID
+        /// and the note are uploaded as a JSON document whose CID is registered as the
+        /// metadataHash
+        #[arg(long, value_name = "TEXT", visible_alias = "definition-version-note")]
+        note: Option<String>,
+
+        /// Strip comments and normalize whitespace in the circuit sources embedded
+        /// in the uploaded artifact, so formatting-only changes keep the same CID
+        #[arg(long, conflicts_with = "circuit_cid")]
+        normalize_source: bool,
+
+        /// Print the target address and ABI-encoded updateCircuit calldata instead
+        /// of broadcasting the registration (for external signers such as a Safe)
+        #[arg(long)]
+        print_calldata: bool,
+
+        /// Fail, instead of warning, when t_end has already passed or t_start is far
+        /// in the past
+        #[arg(long)]
+        strict: bool,
+
+        /// Register even if a definition's latest version already has this exact
+        /// circuit, merkle root, window, and leaves
+        #[arg(long)]
+        force: bool,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Abort unless the definition's on-chain regulator() is this address
+        #[arg(long, value_name = "ADDRESS")]
+        expect_regulator: Option<Address>,
+
+        /// Deploy and verify the verifier, print its address, and stop without
+        /// registering it (register it later with `register`)
+        #[arg(
+            long,
+            conflicts_with_all = ["compliance_definition", "from_receipt", "print_calldata", "watch", "force", "expect_regulator", "continue_on_error"]
+        )]
+        deploy_verifier_only: bool,
+
+        /// Keep running and re-publish whenever a `.nr` file under <DIR>/src changes
+        /// (local dev chains only)
+        #[arg(long, conflicts_with_all = ["print_calldata", "circuit_cid"])]
+        watch: bool,
     },
     /// Update the public parameters of an existing ComplianceDefinition
     UpdateParams {
-        /// Address of the deployed ComplianceDefinition contract
-        #[arg(long)]
-        compliance_definition: String,
+        /// Address of the deployed ComplianceDefinition contract (plain, EIP-3770
+        /// prefixed, e.g. `base:0x...`, or an ENS name)
+        #[arg(long, required_unless_present = "from_receipt")]
+        compliance_definition: Option<String>,
 
-        /// RPC URL of the target chain
-        #[arg(long, env = "RPC_URL")]
-        rpc_url: String,
+        /// Take the definition address, chain, and RPC URL from a
+        /// new-compliance-definition receipt
+        #[arg(long, value_name = "FILE", conflicts_with = "compliance_definition")]
+        from_receipt: Option<PathBuf>,
 
-        /// Private key for the regulator account
-        #[arg(long, env = "PRIVATE_KEY")]
-        private_key: String,
+        /// RPC URL of the target chain [default: taken from --from-receipt]
+        #[arg(long, env = "RPC_URL", required_unless_present = "from_receipt")]
+        rpc_url: Option<String>,
+
+        #[command(flatten)]
+        key: keys::KeyArgs,
 
         /// New Merkle root of the public parameter set (bytes32)
         #[arg(long)]
@@ -180,120 +585,1014 @@ enum Commands {
         /// Mutually exclusive with --leaves-file.
         #[arg(long, value_name = "CID")]
         leaves_cid: Option<String>,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Abort unless the definition's on-chain regulator() is this address
+        #[arg(long, value_name = "ADDRESS")]
+        expect_regulator: Option<Address>,
+    },
+    /// Register an already-deployed verifier (see update-circuit --deploy-verifier-only)
+    /// as a new version of a ComplianceDefinition
+    Register {
+        /// Address of the deployed ComplianceDefinition contract (plain, EIP-3770
+        /// prefixed, or an ENS name)
+        #[arg(long, required_unless_present = "from_receipt")]
+        compliance_definition: Option<String>,
+
+        /// Take the definition address, chain, and RPC URL from a
+        /// new-compliance-definition receipt
+        #[arg(long, value_name = "FILE", conflicts_with = "compliance_definition")]
+        from_receipt: Option<PathBuf>,
+
+        /// RPC URL of the target chain [default: taken from --from-receipt]
+        #[arg(long, env = "RPC_URL", required_unless_present = "from_receipt")]
+        rpc_url: Option<String>,
+
+        #[command(flatten)]
+        key: keys::KeyArgs,
+
+        /// Address of the deployed HonkVerifier, optionally EIP-3770 prefixed
+        #[arg(long, value_name = "ADDRESS")]
+        verifier: String,
+
+        /// CID recorded as the metadataHash: the circuit's, or the metadata document's
+        /// printed by update-circuit --deploy-verifier-only
+        #[arg(long, value_name = "CID")]
+        circuit_cid: String,
+
+        /// CID of the leaves file recorded as the leavesHash
+        #[arg(long, value_name = "CID", default_value = "")]
+        leaves_cid: String,
+
+        /// Merkle root of the compliance membership set (bytes32)
+        #[arg(long, default_value = BYTES32_ZERO)]
+        merkle_root: String,
+
+        /// Block height when this version becomes active
+        #[arg(long, default_value = "0")]
+        t_start: String,
+
+        /// Block height when this version expires
+        #[arg(long, default_value = UINT256_MAX)]
+        t_end: String,
+
+        /// Print the target address and ABI-encoded updateCircuit calldata instead
+        /// of broadcasting the registration (for external signers such as a Safe)
+        #[arg(long)]
+        print_calldata: bool,
+
+        /// Fail, instead of warning, when t_end has already passed or t_start is far
+        /// in the past
+        #[arg(long)]
+        strict: bool,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Abort unless the definition's on-chain regulator() is this address
+        #[arg(long, value_name = "ADDRESS")]
+        expect_regulator: Option<Address>,
+    },
+    /// Serve the update-circuit flow over HTTP (POST /update-circuit, GET /health)
+    Serve {
+        /// Address to listen on; anything but loopback requires --token
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
+
+        /// Port to listen on
+        #[arg(long, default_value = "8080")]
+        port: u16,
+
+        /// Bearer token required on every request (recommended)
+        #[arg(long, env = "SERVE_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        /// RPC URL of the target chain
+        #[arg(long, env = "RPC_URL")]
+        rpc_url: String,
+
+        #[command(flatten)]
+        key: keys::KeyArgs,
+
+        /// Path to the Foundry project for deploying the verifier
+        #[arg(long, default_value = "contracts", value_name = "DIR")]
+        contract_dir: PathBuf,
+    },
+    /// Manage deployer keys in the OS keychain
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+    /// Verify an already-deployed contract on the block explorer
+    Verify {
+        /// Address of the deployed contract, optionally EIP-3770 prefixed (e.g. `base:0x...`)
+        #[arg(long)]
+        address: String,
+
+        /// Path to the Foundry project containing the contract's build artifact
+        #[arg(long, default_value = "contracts", value_name = "DIR")]
+        contract_dir: PathBuf,
+
+        /// Fully-qualified contract name, e.g. src/Verifier.sol:HonkVerifier
+        #[arg(long, value_name = "PATH:CONTRACT")]
+        contract_name: String,
+
+        /// Chain ID of the deployment.  Taken from a chain-prefixed --address, or
+        /// queried from --rpc-url, when omitted.
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// RPC URL of the target chain (used to look up the chain ID)
+        #[arg(long, env = "RPC_URL")]
+        rpc_url: Option<String>,
+
+        /// ABI-encoded constructor arguments (hex)
+        #[arg(long, value_name = "HEX")]
+        constructor_args: Option<String>,
+
+        /// Only recompile the verification input locally with solc and compare it with
+        /// the artifact's runtime bytecode, without contacting the explorer
+        #[arg(long)]
+        check_verification_input: bool,
+    },
+    /// Check that a deployed ComplianceDefinition runs the expected code, comparing it
+    /// with the local build (or a pinned hash) while ignoring the metadata hash
+    Audit {
+        /// Address of the ComplianceDefinition contract (plain, EIP-3770 prefixed, or
+        /// an ENS name)
+        #[arg(long)]
+        address: String,
+
+        /// RPC URL of the chain the contract is deployed on
+        #[arg(long, env = "RPC_URL")]
+        rpc_url: String,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Path to the Foundry project containing ComplianceDefinition.sol
+        #[arg(long, default_value = "contracts", value_name = "DIR")]
+        contract_dir: PathBuf,
+
+        /// Compare against this code hash (as printed by a previous audit) instead of
+        /// building the contract locally
+        #[arg(long, value_name = "HASH")]
+        reference_hash: Option<B256>,
+    },
+    /// Check whether an account is compliant: call the definition's `verify(bytes)` with
+    /// a `bb prove` proof, from the account, optionally at a past block
+    Check {
+        /// Address of the ComplianceDefinition contract (plain, EIP-3770 prefixed, or
+        /// an ENS name)
+        #[arg(long)]
+        compliance_definition: String,
+
+        /// Account the proof was made for; the call is made from it, since the
+        /// definition binds proofs to `tx.origin`
+        #[arg(long)]
+        account: Address,
+
+        /// Proof file written by `bb prove`
+        #[arg(long, value_name = "FILE")]
+        proof: PathBuf,
+
+        /// Public inputs file written by `bb prove`, compared with the account and
+        /// active merkle root the definition checks the proof against
+        #[arg(long, value_name = "FILE")]
+        public_inputs: Option<PathBuf>,
+
+        /// RPC URL of the chain the contract is deployed on
+        #[arg(long, env = "RPC_URL")]
+        rpc_url: String,
+
+        /// Expected chain ID; the command aborts if the RPC reports a different chain
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Check at this block instead of the latest (needs an archive node for old
+        /// blocks)
+        #[arg(long, value_name = "NUMBER")]
+        block: Option<u64>,
+    },
+    /// Report the calldata size and calldata gas of submitting a `bb prove` proof to the
+    /// generated verifier
+    ProofCalldata {
+        /// Proof file written by `bb prove`
+        #[arg(long, value_name = "FILE")]
+        proof: PathBuf,
+
+        /// Public inputs file written by `bb prove` (32 bytes per field)
+        #[arg(long, value_name = "FILE")]
+        public_inputs: Option<PathBuf>,
+    },
+    /// Generate a circuit's Prover.toml from a JSON object of witness values, typed
+    /// against the parameters in the compiled circuit's ABI
+    GenProverToml {
+        /// JSON object mapping each circuit parameter to its value
+        #[arg(long, value_name = "FILE")]
+        json: PathBuf,
+
+        /// Compiled circuit (`target/<name>.json`) whose ABI describes the parameters
+        #[arg(long, value_name = "FILE")]
+        abi: PathBuf,
+
+        /// Where to write the Prover.toml
+        #[arg(long, default_value = "Prover.toml", value_name = "FILE")]
+        output: PathBuf,
+    },
+    /// Upload a circuit's main source file (or its compiled circuit) to IPFS and print
+    /// its CID, without compiling or deploying anything
+    Upload {
+        /// Path to the Noir project directory (containing Nargo.toml)
+        #[arg(long, value_name = "DIR")]
+        circuit_dir: PathBuf,
+
+        /// What to upload: the main source file, the compiled circuit, or both as a
+        /// directory
+        #[arg(long, value_enum, default_value_t = commands::upload::IpfsSource::Entrypoint)]
+        ipfs_source: commands::upload::IpfsSource,
+    },
+    /// Compile a circuit and write its bytecode, verification key, and Solidity verifier
+    /// to a directory, without uploading or deploying anything
+    Compile {
+        /// Path to the Noir project directory (containing Nargo.toml)
+        #[arg(long, value_name = "DIR", required_unless_present = "stdin_circuit", conflicts_with = "stdin_circuit")]
+        circuit_dir: Option<PathBuf>,
+
+        /// Read the circuit's main.nr from stdin and compile it in a temporary project
+        /// that is removed afterwards
+        #[arg(long)]
+        stdin_circuit: bool,
+
+        /// Package name of the temporary project created for --stdin-circuit
+        #[arg(long, value_name = "NAME", requires = "stdin_circuit", default_value = "circuit")]
+        project_name: String,
+
+        /// Directory the bytecode, vk, and Verifier.sol are written to
+        #[arg(long, value_name = "DIR")]
+        out_dir: PathBuf,
+
+        /// Write the bytecode, vk, and Verifier.sol as one zstd-compressed tarball,
+        /// artifacts.tar.zst, instead of loose files; its SHA-256 goes in the receipt
+        #[arg(long)]
+        compress_artifacts: bool,
+    },
+    /// Print the keccak256 digest of a receipt's canonical JSON (sorted keys, no
+    /// whitespace), the value a receipt signature covers
+    ReceiptDigest {
+        /// Receipt file to digest
+        #[arg(long, value_name = "FILE")]
+        receipt: PathBuf,
+    },
+    /// Print a receipt as a readable report: network, results with explorer and IPFS
+    /// gateway links, and step timings
+    ShowReceipt {
+        /// Receipt file to show
+        #[arg(value_name = "PATH")]
+        receipt: PathBuf,
+
+        /// Chain the receipt's deployment is on, for receipts that do not record it
+        /// (update-circuit, update-params)
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// IPFS gateway used for CID links
+        #[arg(long, env = "IPFS_GATEWAY", value_name = "URL", default_value = commands::show_receipt::DEFAULT_IPFS_GATEWAY)]
+        ipfs_gateway: String,
+    },
+    /// Print the JSON Schema the metadata document registered for a version (with a
+    /// --note or a compliance.toml) follows
+    MetadataSchema,
+    /// Check that nargo, the proving backend, and forge are installed and usable
+    Doctor {
+        /// Also compile, prove, and verify a built-in circuit end to end, to catch
+        /// nargo and backend versions that do not work together
+        #[arg(long)]
+        deep: bool,
+    },
+    /// Print the CLI version, the commit it was built from, and the versions of nargo,
+    /// bb, forge, and cast, for bug reports
+    Version {
+        /// Print one JSON object instead of text
+        #[arg(long)]
+        json: bool,
+    },
+    /// Resume polling a verification that was already submitted to the block explorer
+    CheckVerification {
+        /// Submission GUID, as recorded in a verification-submitted receipt
+        #[arg(long)]
+        guid: String,
+
+        /// Chain ID of the deployment.  Taken from a chain-prefixed --address when omitted.
+        #[arg(long)]
+        chain_id: Option<u64>,
+
+        /// Address of the contract being verified, optionally EIP-3770 prefixed
+        #[arg(long)]
+        address: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeyAction {
+    /// Store a private key, read from stdin, in the OS keychain
+    Import {
+        /// Name of the keychain entry to create or replace
+        #[arg(long, value_name = "NAME")]
+        key_name: String,
     },
 }
 
 const DEFAULT_IPFS_RPC_URL: &str = "http://localhost:5001";
 const DEFAULT_RECEIPTS_DIR: &str = "receipts";
+/// Exit code used when a command is cancelled with Ctrl-C (128 + SIGINT).
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+/// Exit code used when a command exceeds `--deadline` (as `timeout(1)` uses).
+const DEADLINE_EXIT_CODE: i32 = 124;
+
+impl Commands {
+    /// The private key selection of commands that send transactions.
+    fn key_args_mut(&mut self) -> Option<&mut keys::KeyArgs> {
+        match self {
+            Self::NewComplianceDefinition { key, .. }
+            | Self::UpdateCircuit { key, .. }
+            | Self::UpdateParams { key, .. }
+            | Self::Register { key, .. }
+            | Self::Serve { key, .. } => Some(key),
+            _ => None,
+        }
+    }
+
+    /// The subcommand name, as used in receipt filenames.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::NewComplianceDefinition { .. } => "new-compliance-definition",
+            Self::UpdateCircuit { .. } => "update-circuit",
+            Self::UpdateParams { .. } => "update-params",
+            Self::Register { .. } => "register",
+            Self::Serve { .. } => "serve",
+            Self::Key { .. } => "key",
+            Self::Verify { .. } => "verify",
+            Self::CheckVerification { .. } => "check-verification",
+            Self::ReceiptDigest { .. } => "receipt-digest",
+            Self::ShowReceipt { .. } => "show-receipt",
+            Self::Doctor { .. } => "doctor",
+            Self::Version { .. } => "version",
+            Self::MetadataSchema => "metadata-schema",
+            Self::Upload { .. } => "upload",
+            Self::Compile { .. } => "compile",
+            Self::Audit { .. } => "audit",
+            Self::Check { .. } => "check",
+            Self::ProofCalldata { .. } => "proof-calldata",
+            Self::GenProverToml { .. } => "gen-prover-toml",
+        }
+    }
+}
+
+/// Resolve the target definition(s), RPC URL, and expected chain ID, reading them from
+/// a new-compliance-definition receipt when `--from-receipt` is given.  Clap ensures the
+/// flags are present otherwise.
+fn definition_target(
+    from_receipt: Option<PathBuf>,
+    compliance_definition: Vec<String>,
+    rpc_url: Option<String>,
+    chain_id: Option<u64>,
+) -> Result<(Vec<String>, String, Option<u64>)> {
+    match from_receipt {
+        Some(path) => {
+            let (address, rpc_url, chain_id) =
+                commands::new_compliance_definition::DefinitionRef::from_receipt(&path)?
+                    .resolve(rpc_url, chain_id)?;
+            Ok((vec![address], rpc_url, Some(chain_id)))
+        }
+        None => Ok((
+            compliance_definition,
+            rpc_url.context("--rpc-url is required")?,
+            chain_id,
+        )),
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load .env file if present (before clap parses, so env vars are available).
     dotenv::dotenv().ok();
 
-    let cli = Cli::parse();
+    // Keep the raw matches: they record where each value came from, for --show-config.
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if let (Some(key), Some((_, command_matches))) =
+        (cli.command.key_args_mut(), matches.subcommand())
+    {
+        key.record_sources(command_matches);
+    }
+    style::init(cli.color);
+    retry::init(retry::RetryConfig::new(cli.retries, Duration::from_secs(cli.retry_base_delay)));
+    tools::init(tools::ToolPaths {
+        nargo: cli.nargo_path.clone(),
+        bb: cli.bb_path.clone(),
+        forge: cli.forge_path.clone(),
+        cast: cli.cast_path.clone(),
+        solc: cli.solc_path.clone(),
+    });
+    let log_file = cli.log_file.as_deref().map(log_file::LogFile::tee).transpose()?;
 
-    let ipfs_url = cli
-        .ipfs_rpc_url
-        .unwrap_or_else(|| DEFAULT_IPFS_RPC_URL.to_string());
+    if let Some(format) = cli.show_config {
+        return config::print(&Cli::command(), &matches, format);
+    }
 
-    let receipts_dir = cli
-        .receipts_dir
-        .unwrap_or_else(|| PathBuf::from(DEFAULT_RECEIPTS_DIR));
+    for secret in [&cli.etherscan_api_key, &cli.pin_token].into_iter().flatten() {
+        redact::register(secret);
+    }
+    let urls = [cli.verifier_url.as_ref(), cli.pin_service_url.as_ref()];
+    for url in urls.into_iter().flatten().chain([&cli.ipfs_rpc_url]).chain(&cli.rpc_fallback) {
+        redact::register_url(url);
+    }
+
+    let ipfs_url = cli.ipfs_rpc_url;
 
+    let receipts = receipt::ReceiptOutput {
+        dir: cli.receipts_dir,
+        stdout: cli.receipt_stdout,
+        deterministic: cli.deterministic_receipt,
+        env_out: cli.env_out,
+    };
+
+    let chains = cli
+        .chains_file
+        .map(|path| etherscan::load_chains_file(&path))
+        .transpose()?
+        .unwrap_or_default();
     let verify = etherscan::VerifyArgs {
         etherscan_api_key: cli.etherscan_api_key,
         verifier_url: cli.verifier_url,
+        verifier_key_param: cli.verifier_key_param,
+        verifier_key_header: cli.verifier_key_header,
+        total_timeout: cli.verify_total_timeout.map(Duration::from_secs),
+        source_roots: cli.contract_source_root,
+        pending_dir: Some(receipts.dir.clone()),
+        chain_api_urls: chains.api_urls,
+        fail_on_verification_failure: cli.fail_on_verification_failure,
+    };
+
+    if !cli.nargo_check_arg.is_empty() {
+        eprintln!(
+            "{} --nargo-check-arg is deprecated: circuits are validated by `nargo compile`, so {} will be passed to `nargo compile`; use --nargo-arg instead",
+            style::warning("warning:"),
+            cli.nargo_check_arg.join(" ")
+        );
+    }
+    let nargo_args = nargo::NargoArgs {
+        compile_args: cli.nargo_arg.into_iter().chain(cli.nargo_check_arg).collect(),
+        compile_retries: cli.nargo_compile_retries,
+    };
+
+    let tx_type = eth::TxTypeSelection::new(cli.legacy_tx, cli.gas_oracle.is_some());
+    let deploy = eth::DeployArgs {
+        libraries: cli.libraries,
+        gas_oracle: cli.gas_oracle,
+        min_gas_price: cli.min_gas_price,
+        skip_build: cli.skip_build,
+        optimizer_runs: cli.optimizer_runs,
+        build_tool: cli.build_tool,
+        nonces: eth::NonceSequence::starting_at(cli.start_nonce),
+        retry_on_nonce_error: cli.retry_on_nonce_error,
+        definition_abi: cli
+            .definition_abi
+            .map(|path| eth::DefinitionAbi::load(&path, &cli.update_function))
+            .transpose()?,
+        simulate_proof_gas: cli.simulate_proof_gas,
+        verify_interface: cli.verify_interface,
+        allow_any_metadata: cli.allow_any_metadata,
+        parallel_libraries: cli.parallel_libraries,
+        on_chain_label: cli.on_chain_label,
+        expected_verifier_hash: cli.expected_verifier_bytecode_hash,
+        concurrency: usize::from(cli.concurrency),
+        tx_type,
+        idempotent: cli
+            .idempotent
+            .then(|| eth::DeploymentJournal::new(receipts.dir.join(eth::DEPLOYMENT_JOURNAL_FILE))),
+        gate_limits: chains.gate_limits,
     };
 
-    match cli.command {
-        Commands::NewComplianceDefinition {
-            circuit_dir,
-            name,
-            rpc_url,
-            private_key,
-            regulator,
-            contract_dir,
-            verifier_output,
-            merkle_root,
-            t_start,
-            t_end,
-            leaves_file,
-            circuit_cid,
-            leaves_cid,
-        } => {
-            commands::new_compliance_definition::run(
+    let pin = ipfs::PinArgs {
+        max_upload_size: (cli.max_upload_size > 0).then_some(cli.max_upload_size),
+        skip_existing: cli.ipfs_skip_existing,
+        upload_project: cli.upload_project,
+        local_pin: cli.pin,
+        remote_pin: cli.remote_pin,
+        service_url: cli.pin_service_url,
+        token: cli.pin_token,
+    };
+
+    let progress: &dyn progress::ProgressReporter = &progress::TerminalProgress;
+    let store = cli.storage_backend.store(&ipfs_url, &pin, progress);
+
+    if cli.print_plan {
+        return plan::print(&cli.command, &ipfs_url, &pin, &deploy, &verify);
+    }
+
+    let command_name = cli.command.name();
+    let deadline = cli.deadline;
+
+    let run = async {
+        match cli.command {
+            Commands::NewComplianceDefinition {
                 circuit_dir,
-                &name,
+                name,
+                rpc_url,
+                key,
+                regulator,
+                contract_dir,
+                constructor_args_json,
+                compliance_definition_salt,
+                factory,
                 verifier_output,
-                &ipfs_url,
-                &rpc_url,
-                &private_key,
-                &regulator,
-                &contract_dir,
-                &merkle_root,
-                &t_start,
-                &t_end,
+                merkle_root,
+                t_start,
+                t_end,
                 leaves_file,
                 circuit_cid,
                 leaves_cid,
-                &receipts_dir,
-                &verify,
-            )
-            .await
-        }
-        Commands::UpdateCircuit {
-            circuit_dir,
-            rpc_url,
-            private_key,
-            compliance_definition,
-            verifier_output,
-            contract_dir,
-            merkle_root,
-            t_start,
-            t_end,
-            leaves_file,
-            circuit_cid,
-            leaves_cid,
-        } => {
-            commands::update_circuit::run(
+                note,
+                normalize_source,
+                print_calldata,
+                strict,
+                chain_id,
+            } => {
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                let private_key = key.resolve().await?;
+                commands::new_compliance_definition::run(
+                    circuit_dir,
+                    &name,
+                    verifier_output,
+                    &ipfs_url,
+                    &pin,
+                    store.as_ref(),
+                    &rpc_url,
+                    &private_key,
+                    &regulator,
+                    &contract_dir,
+                    constructor_args_json,
+                    compliance_definition_salt,
+                    factory,
+                    &merkle_root,
+                    &t_start,
+                    &t_end,
+                    leaves_file,
+                    circuit_cid,
+                    leaves_cid,
+                    note,
+                    normalize_source,
+                    print_calldata,
+                    strict,
+                    chain_id,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &nargo_args,
+                    cli.backend.get(),
+                    &deploy,
+                    &verify,
+                )
+                .await
+            }
+            Commands::UpdateCircuit {
                 circuit_dir,
+                rpc_url,
+                key,
+                compliance_definition,
+                from_receipt,
+                continue_on_error,
+                summary_json,
                 verifier_output,
-                &ipfs_url,
-                &rpc_url,
-                &private_key,
-                &compliance_definition,
-                &contract_dir,
-                &merkle_root,
-                &t_start,
-                &t_end,
+                contract_dir,
+                merkle_root,
+                t_start,
+                t_end,
                 leaves_file,
                 circuit_cid,
                 leaves_cid,
-                &receipts_dir,
-                &verify,
-            )
-            .await
-        }
-        Commands::UpdateParams {
-            compliance_definition,
-            rpc_url,
-            private_key,
-            merkle_root,
-            leaves_file,
-            leaves_cid,
-        } => {
-            commands::update_params::run(
-                &compliance_definition,
-                &ipfs_url,
-                &rpc_url,
-                &private_key,
-                &merkle_root,
+                note,
+                normalize_source,
+                print_calldata,
+                strict,
+                force,
+                chain_id,
+                expect_regulator,
+                deploy_verifier_only,
+                watch,
+            } => {
+                let (compliance_definition, rpc_url, chain_id) =
+                    definition_target(from_receipt, compliance_definition, rpc_url, chain_id)?;
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                let private_key = key.resolve().await?;
+                // The nargo artifact embeds the circuit's source, so without
+                // normalization every comment or whitespace edit would give a new CID
+                // and redeploy the verifier under --watch.
+                let normalize_source = normalize_source || watch;
+                let publish = async || {
+                    let outcome = commands::update_circuit::run(
+                        circuit_dir.clone(),
+                        verifier_output.clone(),
+                        &ipfs_url,
+                        &pin,
+                        store.as_ref(),
+                        &rpc_url,
+                        &private_key,
+                        &compliance_definition,
+                        continue_on_error,
+                        &contract_dir,
+                        &merkle_root,
+                        &t_start,
+                        &t_end,
+                        leaves_file.clone(),
+                        circuit_cid.clone(),
+                        leaves_cid.clone(),
+                        note.clone(),
+                        deploy_verifier_only,
+                        normalize_source,
+                        print_calldata,
+                        strict,
+                        force,
+                        chain_id,
+                        expect_regulator,
+                        &receipts,
+                        cli.verbose,
+                        progress,
+                        &nargo_args,
+                        cli.backend.get(),
+                        &deploy,
+                        &verify,
+                    )
+                    .await;
+                    if let Some(ref path) = summary_json {
+                        let project = summary::ProjectSummary::update_circuit(
+                            &circuit_dir,
+                            &compliance_definition,
+                            &outcome,
+                        );
+                        summary::write(path, "update-circuit", vec![project])?;
+                    }
+                    let data = outcome?;
+                    data.ensure_registered()?;
+                    data.ensure_verified(&verify)
+                };
+                if !watch {
+                    return publish().await;
+                }
+
+                // Sources are normalized under --watch, so an unchanged circuit keeps
+                // its CID and re-runs triggered by comment-only or reverted edits are
+                // no-ops: the definition already has that version and nothing is
+                // deployed.
+                watch::ensure_dev_chain(&rpc_url, chain_id).await?;
+                let mut watcher = watch::CircuitWatcher::new(&circuit_dir)?;
+                loop {
+                    if let Err(e) = publish().await {
+                        eprintln!("{} {:#}", style::failure("error:"), redact::Redacted(&e));
+                    }
+                    eprintln!("\nwatching {} for changes (Ctrl-C to stop)...", watcher.src_dir().display());
+                    let changed = watcher.changed().await?;
+                    for path in changed {
+                        eprintln!("changed: {}", path.display());
+                    }
+                }
+            }
+            Commands::UpdateParams {
+                compliance_definition,
+                from_receipt,
+                rpc_url,
+                key,
+                merkle_root,
                 leaves_file,
                 leaves_cid,
-                &receipts_dir,
-            )
-            .await
+                chain_id,
+                expect_regulator,
+            } => {
+                let (compliance_definition, rpc_url, chain_id) = definition_target(
+                    from_receipt,
+                    compliance_definition.into_iter().collect(),
+                    rpc_url,
+                    chain_id,
+                )?;
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                let private_key = key.resolve().await?;
+                commands::update_params::run(
+                    &compliance_definition[0],
+                    store.as_ref(),
+                    &rpc_url,
+                    &private_key,
+                    &merkle_root,
+                    leaves_file,
+                    leaves_cid,
+                    chain_id,
+                    expect_regulator,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &deploy,
+                )
+                .await
+            }
+            Commands::Register {
+                compliance_definition,
+                from_receipt,
+                rpc_url,
+                key,
+                verifier,
+                circuit_cid,
+                leaves_cid,
+                merkle_root,
+                t_start,
+                t_end,
+                print_calldata,
+                strict,
+                chain_id,
+                expect_regulator,
+            } => {
+                let (compliance_definition, rpc_url, chain_id) = definition_target(
+                    from_receipt,
+                    compliance_definition.into_iter().collect(),
+                    rpc_url,
+                    chain_id,
+                )?;
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                let signer = key.resolve_signer().await?;
+                commands::register::run(
+                    &compliance_definition[0],
+                    &verifier,
+                    &circuit_cid,
+                    &leaves_cid,
+                    &merkle_root,
+                    &t_start,
+                    &t_end,
+                    &rpc_url,
+                    &signer,
+                    print_calldata,
+                    strict,
+                    chain_id,
+                    expect_regulator,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &deploy,
+                )
+                .await
+            }
+            Commands::Serve {
+                host,
+                port,
+                token,
+                rpc_url,
+                key,
+                contract_dir,
+            } => {
+                if let Some(ref token) = token {
+                    redact::register(token);
+                }
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                let private_key = key.resolve().await?;
+                if token.is_none() {
+                    eprintln!(
+                        "{} serving without --token; any local process that can reach the port can deploy",
+                        style::warning("warning:")
+                    );
+                }
+                commands::serve::run(
+                    host,
+                    port,
+                    commands::serve::ServeConfig {
+                        token,
+                        ipfs_rpc_url: ipfs_url.clone(),
+                        rpc_url,
+                        private_key,
+                        contract_dir,
+                        receipts_dir: receipts.dir.clone(),
+                        pin: pin.clone(),
+                        nargo: nargo_args.clone(),
+                        backend: cli.backend,
+                        storage: cli.storage_backend,
+                        deploy: deploy.clone(),
+                        verify: verify.clone(),
+                    },
+                )
+                .await
+            }
+            Commands::Key {
+                action: KeyAction::Import { key_name },
+            } => {
+                eprintln!("paste the private key and press enter:");
+                let private_key = keys::read_private_key()?;
+                let address = keys::import(&key_name, &private_key).await?;
+                eprintln!("stored key {key_name:?} in the OS keychain");
+                receipts.print_value("address", &address);
+                Ok(())
+            }
+            Commands::ProofCalldata {
+                proof,
+                public_inputs,
+            } => commands::proof_calldata::run(&proof, public_inputs.as_deref(), &receipts),
+            Commands::Upload {
+                circuit_dir,
+                ipfs_source,
+            } => {
+                commands::upload::run(
+                    &circuit_dir,
+                    ipfs_source,
+                    &ipfs_url,
+                    &pin,
+                    store.as_ref(),
+                    &receipts,
+                    progress,
+                )
+                .await
+            }
+            Commands::GenProverToml { json, abi, output } => {
+                commands::gen_prover_toml::run(&json, &abi, &output, &receipts)
+            }
+            Commands::Audit {
+                address,
+                rpc_url,
+                chain_id,
+                contract_dir,
+                reference_hash,
+            } => {
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                commands::audit::run(
+                    &address,
+                    &rpc_url,
+                    chain_id,
+                    &contract_dir,
+                    reference_hash,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &deploy,
+                )
+                .await
+            }
+            Commands::Check {
+                compliance_definition,
+                account,
+                proof,
+                public_inputs,
+                rpc_url,
+                chain_id,
+                block,
+            } => {
+                let rpc_url = eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?;
+                commands::check::run(
+                    &compliance_definition,
+                    account,
+                    &proof,
+                    public_inputs.as_deref(),
+                    &rpc_url,
+                    chain_id,
+                    block,
+                    &receipts,
+                    progress,
+                )
+                .await
+            }
+            Commands::Compile {
+                circuit_dir,
+                stdin_circuit: _,
+                project_name,
+                out_dir,
+                compress_artifacts,
+            } => {
+                commands::compile::run(
+                    circuit_dir,
+                    &project_name,
+                    &out_dir,
+                    compress_artifacts,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &nargo_args,
+                    cli.backend.get(),
+                )
+                .await
+            }
+            Commands::ReceiptDigest { receipt } => {
+                let digest = receipt::digest_file(&receipt)?;
+                receipts.print_value("digest", &digest);
+                Ok(())
+            }
+            Commands::ShowReceipt {
+                receipt,
+                chain_id,
+                ipfs_gateway,
+            } => commands::show_receipt::run(&receipt, chain_id, &ipfs_gateway),
+            Commands::Doctor { deep } => {
+                commands::doctor::run(deep, &nargo_args, cli.backend.get()).await
+            }
+            Commands::Version { json } => commands::version::run(json),
+            Commands::MetadataSchema => {
+                print!("{}", metadata_schema::SCHEMA);
+                Ok(())
+            }
+            Commands::Verify {
+                address,
+                contract_dir,
+                contract_name,
+                chain_id,
+                rpc_url,
+                constructor_args,
+                check_verification_input,
+            } => {
+                let rpc_url = match rpc_url {
+                    Some(rpc_url) => {
+                        Some(eth::select_rpc_url(&rpc_url, &cli.rpc_fallback, progress).await?)
+                    }
+                    None => None,
+                };
+                commands::verify::run(
+                    &address,
+                    &contract_dir,
+                    &contract_name,
+                    chain_id,
+                    rpc_url.as_deref(),
+                    constructor_args.as_deref(),
+                    check_verification_input,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &verify,
+                )
+                .await
+            }
+            Commands::CheckVerification {
+                guid,
+                chain_id,
+                address,
+            } => {
+                commands::check_verification::run(
+                    &guid,
+                    chain_id,
+                    &address,
+                    &receipts,
+                    cli.verbose,
+                    progress,
+                    &verify,
+                )
+                .await
+            }
+        }
+    };
+
+    let deadline_passed = async {
+        match deadline {
+            Some(secs) => tokio::time::sleep(Duration::from_secs(secs)).await,
+            None => std::future::pending().await,
+        }
+    };
+
+    // Racing the command against Ctrl-C and --deadline drops the command future when
+    // either fires, which runs its cleanup guards (e.g. the staged Verifier.sol) before
+    // we exit.
+    let result = tokio::select! {
+        biased;
+        _ = tokio::signal::ctrl_c() => Err(("cancelled by Ctrl-C".to_string(), INTERRUPTED_EXIT_CODE)),
+        _ = deadline_passed => Err((
+            format!("command exceeded {}s deadline", deadline.unwrap_or_default()),
+            DEADLINE_EXIT_CODE,
+        )),
+        result = run => Ok(result),
+    };
+
+    match result {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => {
+            eprintln!("{} {:?}", style::failure("Error:"), redact::Redacted(&e));
+            drop(log_file);
+            std::process::exit(1);
+        }
+        Err((reason, code)) => {
+            if code == INTERRUPTED_EXIT_CODE {
+                eprintln!("\ninterrupted, cleaned up temporary files");
+            } else {
+                eprintln!("{} {reason}, cleaned up temporary files", style::failure("Error:"));
+            }
+            let receipt = receipt::Receipt::new(
+                command_name,
+                receipt::InterruptedData {
+                    interrupted: true,
+                    reason,
+                    completed: receipt::completed_steps(),
+                },
+            );
+            if let Err(e) = receipt.write(&receipts) {
+                eprintln!("failed to write partial receipt: {:#}", redact::Redacted(&e));
+            }
+            drop(log_file);
+            std::process::exit(code);
         }
     }
 }
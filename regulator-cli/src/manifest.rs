@@ -0,0 +1,97 @@
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::prover_toml::AbiParameter;
+
+/// Optional file in a Noir project documenting what each public input means.
+pub const MANIFEST_FILE: &str = "compliance.toml";
+
+/// A project's `compliance.toml`:
+///
+/// ```toml
+/// [[inputs]]
+/// name = "root"
+/// description = "Merkle root of the sanctions list"
+/// merkle_root = true
+///
+/// [[inputs]]
+/// name = "timestamp"
+/// description = "Time the proof is valid at"
+/// unit = "seconds since the Unix epoch"
+/// ```
+///
+/// Every public input of the compiled circuit must be listed, and nothing else, so the
+/// documentation cannot drift from the circuit it is registered with.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComplianceManifest {
+    #[serde(default)]
+    pub inputs: Vec<InputDoc>,
+}
+
+/// What one public input means.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct InputDoc {
+    pub name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// The input checked against the definition's merkle root (the sanctions root).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub merkle_root: bool,
+}
+
+impl ComplianceManifest {
+    /// Read `<project_dir>/compliance.toml`, if the project has one.
+    pub fn load(project_dir: &Path) -> Result<Option<Self>> {
+        let path = project_dir.join(MANIFEST_FILE);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let manifest: Self =
+            toml::from_str(&text).with_context(|| format!("failed to parse {}", path.display()))?;
+        if manifest.inputs.iter().filter(|input| input.merkle_root).count() > 1 {
+            bail!("{}: only one input can be the merkle_root", path.display());
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Check that the manifest documents exactly the public inputs of the compiled
+    /// circuit `parameters`, each once.
+    pub fn check(&self, parameters: &[AbiParameter]) -> Result<()> {
+        let public: Vec<&str> = parameters
+            .iter()
+            .filter(|p| p.is_public())
+            .map(|p| p.name.as_str())
+            .collect();
+        let documented: Vec<&str> = self.inputs.iter().map(|input| input.name.as_str()).collect();
+
+        let missing: Vec<&str> = public.iter().copied().filter(|name| !documented.contains(name)).collect();
+        let unknown: Vec<&str> = documented.iter().copied().filter(|name| !public.contains(name)).collect();
+        let mut problems = Vec::new();
+        if !missing.is_empty() {
+            problems.push(format!("public inputs not documented: {}", missing.join(", ")));
+        }
+        if !unknown.is_empty() {
+            problems.push(format!("not public inputs of the circuit: {}", unknown.join(", ")));
+        }
+        if problems.is_empty() && documented.len() != public.len() {
+            problems.push(format!(
+                "{} inputs documented for {} public inputs (an input is listed twice)",
+                documented.len(),
+                public.len()
+            ));
+        }
+        if !problems.is_empty() {
+            bail!(
+                "{MANIFEST_FILE} does not match the compiled circuit: {}",
+                problems.join("; ")
+            );
+        }
+        Ok(())
+    }
+}
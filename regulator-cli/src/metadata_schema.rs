@@ -0,0 +1,81 @@
+use anyhow::{Result, bail};
+use serde_json::Value;
+
+/// JSON Schema of the metadata document, printed by `metadata-schema`.
+pub const SCHEMA: &str = include_str!("metadata.schema.json");
+
+/// Check `document` against [`SCHEMA`], listing every violation.
+///
+/// Only the keywords the schema uses are interpreted (`type`, `required`,
+/// `properties`, `additionalProperties`, `items`, `minLength`, `format: date-time`),
+/// so a keyword added to the schema must be added here too.
+pub fn validate(document: &Value) -> Result<()> {
+    let schema: Value = serde_json::from_str(SCHEMA).expect("built-in metadata schema is valid JSON");
+    let mut violations = Vec::new();
+    check(&schema, document, "", &mut violations);
+    if !violations.is_empty() {
+        bail!(
+            "metadata document does not match the metadata schema (see `regulator-cli metadata-schema`):\n  {}",
+            violations.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+fn check(schema: &Value, value: &Value, at: &str, violations: &mut Vec<String>) {
+    let location = if at.is_empty() { "document" } else { at };
+
+    if let Some(expected) = schema.get("type").and_then(Value::as_str) {
+        let matches = match expected {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "boolean" => value.is_boolean(),
+            _ => true,
+        };
+        if !matches {
+            violations.push(format!("{location}: expected {expected}"));
+            return;
+        }
+    }
+
+    if let Some(text) = value.as_str() {
+        if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+            && (text.chars().count() as u64) < min
+        {
+            violations.push(format!("{location}: shorter than {min} characters"));
+        }
+        if schema.get("format").and_then(Value::as_str) == Some("date-time")
+            && chrono::DateTime::parse_from_rfc3339(text).is_err()
+        {
+            violations.push(format!("{location}: {text:?} is not an RFC 3339 date-time"));
+        }
+    }
+
+    if let (Some(items), Some(elements)) = (schema.get("items"), value.as_array()) {
+        for (i, element) in elements.iter().enumerate() {
+            check(items, element, &format!("{at}/{i}"), violations);
+        }
+    }
+
+    let Some(object) = value.as_object() else {
+        return;
+    };
+    for field in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+        if let Some(field) = field.as_str()
+            && !object.contains_key(field)
+        {
+            violations.push(format!("{location}: missing required field `{field}`"));
+        }
+    }
+    let properties = schema.get("properties").and_then(Value::as_object);
+    for (field, field_value) in object {
+        match properties.and_then(|p| p.get(field)) {
+            Some(field_schema) => check(field_schema, field_value, &format!("{at}/{field}"), violations),
+            None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                violations.push(format!("{location}: unexpected field `{field}`"));
+            }
+            None => {}
+        }
+    }
+}
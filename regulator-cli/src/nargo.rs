@@ -1,7 +1,13 @@
+use alloy::hex;
 use anyhow::{bail, Context, Result};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::time::Duration;
+
+use crate::progress::ProgressReporter;
+use crate::style;
+use crate::tools::{self, Tool};
 
 #[derive(Deserialize)]
 struct NargoToml {
@@ -15,84 +21,601 @@ struct NargoPackage {
     package_type: Option<String>,
 }
 
+/// Flags the CLI relies on controlling itself: it runs nargo in the project directory
+/// and reads the artifact from `<project>/target/<package>.json`.
+const RESERVED_ARGS: &[&str] = &["--program-dir", "--target-dir", "--package", "--workspace"];
+
+/// Default number of times a transiently failing `nargo compile` is retried.
+pub const DEFAULT_COMPILE_RETRIES: u32 = 2;
+const COMPILE_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Output fragments of filesystem/lock failures that go away on their own, as opposed
+/// to compilation errors in the circuit.
+const TRANSIENT_COMPILE_ERRORS: &[&str] = &[
+    "Resource temporarily unavailable",
+    "Text file busy",
+    "Device or resource busy",
+    "being used by another process",
+    "Blocking waiting for file lock",
+    "failed to acquire lock",
+    "Permission denied (os error 13)",
+];
+
+/// Extra arguments passed through to nargo invocations.
+#[derive(Clone, Default)]
+pub struct NargoArgs {
+    /// Appended to `nargo compile`.
+    pub compile_args: Vec<String>,
+    /// How many times `nargo compile` is re-run after a transient failure.
+    pub compile_retries: u32,
+}
+
+/// Parse a `--nargo-arg`/`--nargo-check-arg` value, rejecting flags the CLI sets itself.
+pub fn parse_extra_arg(s: &str) -> std::result::Result<String, String> {
+    let flag = s.split_once('=').map_or(s, |(flag, _)| flag);
+    if RESERVED_ARGS.contains(&flag) {
+        return Err(format!("{flag} is set by regulator-cli and cannot be overridden"));
+    }
+    Ok(s.to_string())
+}
+
 fn read_nargo_toml(project_dir: &Path) -> Result<NargoToml> {
     let toml_path = project_dir.join("Nargo.toml");
-    let contents = std::fs::read_to_string(&toml_path)
-        .with_context(|| format!("failed to read {}", toml_path.display()))?;
-    toml::from_str(&contents)
-        .with_context(|| format!("failed to parse {}", toml_path.display()))
+    let contents = match std::fs::read_to_string(&toml_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => bail!(
+            "no Nargo.toml found in {} -- is this a Noir project?",
+            project_dir.display()
+        ),
+        Err(e) => bail!("failed to read {}: {e}", toml_path.display()),
+    };
+
+    let manifest: toml::Table = toml::from_str(&contents)
+        .with_context(|| format!("{} is not valid TOML", toml_path.display()))?;
+    let Some(package) = manifest.get("package") else {
+        if manifest.contains_key("workspace") {
+            bail!(
+                "{} is a workspace manifest -- point --circuit-dir at one of its member packages",
+                toml_path.display()
+            );
+        }
+        bail!(
+            "{} has no [package] section -- add one with at least `name = \"...\"`",
+            toml_path.display()
+        );
+    };
+    let package = package
+        .clone()
+        .try_into()
+        .with_context(|| format!("invalid [package] section in {}", toml_path.display()))?;
+    Ok(NargoToml { package })
+}
+
+/// Check that `project_dir` holds a usable Nargo.toml before any work starts, telling
+/// apart a missing manifest, an unreadable one, invalid TOML, and a missing `[package]`.
+pub fn validate_manifest(project_dir: &Path) -> Result<()> {
+    read_nargo_toml(project_dir).map(|_| ())
+}
+
+/// A library the circuit depends on, from the `[dependencies]` of its Nargo.toml or,
+/// transitively, of a `path` dependency's.
+#[derive(Debug, Serialize)]
+pub struct Dependency {
+    pub name: String,
+    /// `git+<url>` or `path+<dir>`, as declared.
+    pub source: String,
+    /// The `tag` (or `rev`/`branch`) Nargo.toml asks for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requested: Option<String>,
+    /// The commit or version Nargo.lock pins it to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<String>,
+    /// Sub-directory of a git dependency the package lives in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directory: Option<String>,
+}
+
+impl Dependency {
+    /// One line for the progress output, e.g. `poseidon: git+https://... @ v0.1.0`.
+    pub fn describe(&self) -> String {
+        match self.resolved.as_ref().or(self.requested.as_ref()) {
+            Some(version) => format!("{}: {} @ {version}", self.name, self.source),
+            None => format!("{}: {}", self.name, self.source),
+        }
+    }
+}
+
+/// Git reference keys of a dependency, in the order they are reported as `requested`.
+const GIT_REFERENCE_KEYS: &[&str] = &["tag", "rev", "branch"];
+
+/// Pin recorded for each package in Nargo.lock, keyed by package name: its `commit`
+/// (or `rev`), else its `version`.  `None` if the project has no Nargo.lock.
+fn read_lock_pins(project_dir: &Path) -> Result<Option<toml::Table>> {
+    let lock_path = project_dir.join("Nargo.lock");
+    let contents = match std::fs::read_to_string(&lock_path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => bail!("failed to read {}: {e}", lock_path.display()),
+    };
+    let lock: toml::Table = toml::from_str(&contents)
+        .with_context(|| format!("{} is not valid TOML", lock_path.display()))?;
+    let mut pins = toml::Table::new();
+    for package in lock.get("package").and_then(|p| p.as_array()).into_iter().flatten() {
+        let Some(name) = package.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let pin = ["commit", "rev", "version"]
+            .iter()
+            .find_map(|key| package.get(*key).and_then(|v| v.as_str()));
+        if let Some(pin) = pin {
+            pins.insert(name.to_string(), pin.into());
+        }
+    }
+    Ok(Some(pins))
+}
+
+/// The `[dependencies]` of the project's Nargo.toml, following `path` dependencies into
+/// their own manifests, each with the version Nargo.lock pins it to.  Warns when there
+/// are dependencies but no Nargo.lock, since the build then is not reproducible.
+pub fn dependencies(
+    project_dir: &Path,
+    progress: &dyn ProgressReporter,
+) -> Result<Vec<Dependency>> {
+    let pins = read_lock_pins(project_dir)?;
+    let mut found = Vec::new();
+    let mut visited = vec![project_dir.canonicalize().unwrap_or_else(|_| project_dir.to_path_buf())];
+    let mut queue = vec![project_dir.to_path_buf()];
+    while let Some(dir) = queue.pop() {
+        let toml_path = dir.join("Nargo.toml");
+        let contents = std::fs::read_to_string(&toml_path)
+            .with_context(|| format!("failed to read {}", toml_path.display()))?;
+        let manifest: toml::Table = toml::from_str(&contents)
+            .with_context(|| format!("{} is not valid TOML", toml_path.display()))?;
+        let Some(declared) = manifest.get("dependencies").and_then(|d| d.as_table()) else {
+            continue;
+        };
+        for (name, spec) in declared {
+            let field = |key: &str| spec.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            let source = match (field("git"), field("path")) {
+                (Some(git), _) => format!("git+{git}"),
+                (None, Some(path)) => {
+                    let dep_dir = dir.join(&path);
+                    let canonical = dep_dir.canonicalize().unwrap_or_else(|_| dep_dir.clone());
+                    if !visited.contains(&canonical) {
+                        visited.push(canonical);
+                        queue.push(dep_dir);
+                    }
+                    format!("path+{path}")
+                }
+                (None, None) => bail!(
+                    "dependency `{name}` in {} has neither `git` nor `path`",
+                    toml_path.display()
+                ),
+            };
+            found.push(Dependency {
+                name: name.clone(),
+                source,
+                requested: GIT_REFERENCE_KEYS.iter().find_map(|key| field(key)),
+                resolved: pins
+                    .as_ref()
+                    .and_then(|pins| pins.get(name))
+                    .and_then(|pin| pin.as_str())
+                    .map(str::to_string),
+                directory: field("directory"),
+            });
+        }
+    }
+
+    if !found.is_empty() && pins.is_none() {
+        progress.info(&format!(
+            "{} {} has dependencies but no Nargo.lock, so the versions they resolve to are not pinned and the build may not be reproducible",
+            style::warning("warning:"),
+            project_dir.join("Nargo.toml").display()
+        ));
+    }
+    Ok(found)
+}
+
+/// Scaffold a binary Nargo project named `name` in `dir`, with `main_source` as its
+/// `src/main.nr` -- what `nargo init` would create, without needing an empty directory.
+pub fn init_project(dir: &Path, name: &str, main_source: &str) -> Result<()> {
+    let valid = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        bail!(
+            "invalid project name {name:?}: use letters, digits, and underscores, starting with a letter"
+        );
+    }
+
+    let src_dir = dir.join("src");
+    std::fs::create_dir_all(&src_dir)
+        .with_context(|| format!("failed to create {}", src_dir.display()))?;
+    std::fs::write(
+        dir.join("Nargo.toml"),
+        format!("[package]\nname = \"{name}\"\ntype = \"bin\"\nauthors = [\"\"]\n\n[dependencies]\n"),
+    )
+    .with_context(|| format!("failed to write Nargo.toml in {}", dir.display()))?;
+    std::fs::write(src_dir.join("main.nr"), main_source)
+        .with_context(|| format!("failed to write main.nr in {}", src_dir.display()))?;
+    Ok(())
 }
 
 /// Determine the main source file for a Nargo project based on its package type.
 pub fn find_source_file(project_dir: &Path) -> Result<PathBuf> {
     let config = read_nargo_toml(project_dir)?;
 
-    let source_file = match config.package.package_type.as_deref() {
+    let package_type = config.package.package_type.as_deref();
+    let source_file = match package_type {
         Some("lib") => project_dir.join("src/lib.nr"),
         _ => project_dir.join("src/main.nr"),
     };
 
     if !source_file.exists() {
-        bail!("source file not found: {}", source_file.display());
+        bail!(
+            "source file not found: {}\n{}",
+            source_file.display(),
+            missing_source_hint(&project_dir.join("src"), package_type)
+        );
     }
 
     Ok(source_file)
 }
 
-/// Run `nargo check` in the given project directory to validate the circuit compiles.
-pub fn check(project_dir: &Path) -> Result<()> {
-    let output = Command::new("nargo")
-        .arg("check")
-        .current_dir(project_dir)
-        .output()
-        .with_context(|| format!(
-            "failed to run `nargo check` in {} -- is nargo installed?",
-            project_dir.display()
-        ))?;
+/// Explain a missing main source file: what `src/` does contain, and the likely fix
+/// when the other entry point is there (a `lib.nr` in a `bin` package, or vice versa).
+fn missing_source_hint(src_dir: &Path, package_type: Option<&str>) -> String {
+    let Ok(entries) = std::fs::read_dir(src_dir) else {
+        return format!("  {} does not exist -- Noir sources go in src/main.nr (or src/lib.nr for a library)", src_dir.display());
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if e.path().is_dir() { format!("{name}/") } else { name }
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return "  src/ is empty -- add src/main.nr (or src/lib.nr for a library)".to_string();
+    }
+
+    let declared = package_type.unwrap_or("bin");
+    let has = |name: &str| files.iter().any(|f| f == name);
+    let suggestion = if declared != "lib" && has("lib.nr") {
+        format!("found src/lib.nr -- did you mean type = \"lib\" in Nargo.toml? (the package is type = \"{declared}\")")
+    } else if declared == "lib" && has("main.nr") {
+        "found src/main.nr -- did you mean type = \"bin\" in Nargo.toml? (the package is type = \"lib\")".to_string()
+    } else {
+        let expected = if declared == "lib" { "lib.nr" } else { "main.nr" };
+        format!("rename the entry point to src/{expected}, or set `type` in Nargo.toml to match it")
+    };
+    format!("  files under src/: {}\n  {suggestion}", files.join(", "))
+}
+
+/// A circuit's main source file (see [`find_source_file`]) as it was read just before
+/// `nargo compile`, used to confirm that the artifact uploaded to IPFS was built from
+/// exactly these bytes and that nothing edited the file in between.
+pub struct CompiledSource {
+    path: PathBuf,
+    contents: Vec<u8>,
+    sha256: String,
+}
+
+impl CompiledSource {
+    pub fn read(source_file: &Path) -> Result<Self> {
+        let contents = std::fs::read(source_file)
+            .with_context(|| format!("failed to read {}", source_file.display()))?;
+        let path = source_file
+            .canonicalize()
+            .with_context(|| format!("failed to resolve {}", source_file.display()))?;
+        Ok(Self {
+            path,
+            sha256: hex::encode(Sha256::digest(&contents)),
+            contents,
+        })
+    }
+
+    /// Hex SHA-256 of the compiled source bytes.
+    pub fn sha256(&self) -> &str {
+        &self.sha256
+    }
+
+    /// Bail if `artifact` (about to be uploaded; `normalized` if written by
+    /// [`write_normalized_artifact`]) embeds anything other than the source that was
+    /// read, or if the source file has changed since.
+    pub fn check_upload(
+        &self,
+        artifact: &Path,
+        normalized: bool,
+        progress: &dyn ProgressReporter,
+    ) -> Result<()> {
+        let json: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(artifact)
+                .with_context(|| format!("failed to read {}", artifact.display()))?,
+        )
+        .with_context(|| format!("failed to parse {}", artifact.display()))?;
+        let embedded = json
+            .get("file_map")
+            .and_then(|f| f.as_object())
+            .into_iter()
+            .flat_map(|files| files.values())
+            .find(|file| {
+                file.get("path")
+                    .and_then(|p| p.as_str())
+                    .is_some_and(|p| Path::new(p) == self.path)
+            })
+            .and_then(|file| file.get("source"))
+            .and_then(|source| source.as_str());
+        match embedded {
+            Some(embedded) => {
+                let compiled = String::from_utf8_lossy(&self.contents);
+                let expected = if normalized {
+                    normalize_source(&compiled)
+                } else {
+                    compiled.into_owned()
+                };
+                if embedded != expected {
+                    bail!(
+                        "{} embeds a different {} than the one read before compiling -- it was edited during `nargo compile`; re-run to publish the edited circuit",
+                        artifact.display(),
+                        self.path.display()
+                    );
+                }
+            }
+            None => progress.info(&format!(
+                "{} {} does not embed {}, cannot confirm it was compiled from that source",
+                style::warning("warning:"),
+                artifact.display(),
+                self.path.display()
+            )),
+        }
+
+        let current = std::fs::read(&self.path)
+            .with_context(|| format!("failed to re-read {}", self.path.display()))?;
+        let current_sha256 = hex::encode(Sha256::digest(&current));
+        if current_sha256 != self.sha256 {
+            bail!(
+                "{} changed after it was compiled (sha256 {} -> {current_sha256}) -- re-run to publish the edited circuit",
+                self.path.display(),
+                self.sha256
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Run `nargo compile` in the given project directory and return the path to the compiled JSON.
+/// A successful compile also validates the circuit, so no separate `nargo check` is run:
+/// the circuit validated is exactly the one compiled.
+///
+/// Failures that look like transient filesystem or lock errors (see
+/// `TRANSIENT_COMPILE_ERRORS`) are retried up to `extra.compile_retries` times; genuine
+/// compilation errors fail immediately.  nargo runs, and the delay between attempts
+/// passes, without blocking the async runtime.
+pub async fn compile(
+    project_dir: &Path,
+    extra: &NargoArgs,
+    progress: &dyn ProgressReporter,
+) -> Result<PathBuf> {
+    let attempts = extra.compile_retries + 1;
+    for attempt in 1..=attempts {
+        let output = tokio::process::Command::from(tools::command(Tool::Nargo))
+            .arg("compile")
+            .args(&extra.compile_args)
+            .current_dir(project_dir)
+            .output()
+            .await
+            .with_context(|| format!(
+                "failed to run `nargo compile` in {} -- is nargo installed?",
+                project_dir.display()
+            ))?;
+
+        if output.status.success() {
+            break;
+        }
 
-    if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
+        let transient = TRANSIENT_COMPILE_ERRORS
+            .iter()
+            .find(|pattern| stderr.contains(**pattern));
+        match transient {
+            Some(pattern) if attempt < attempts => {
+                progress.info(&format!(
+                    "{} nargo compile failed with a transient error ({pattern}), retrying in {}s (attempt {}/{attempts})...",
+                    style::warning("warning:"),
+                    COMPILE_RETRY_DELAY.as_secs(),
+                    attempt + 1
+                ));
+                tokio::time::sleep(COMPILE_RETRY_DELAY).await;
+            }
+            _ => bail!(
+                "nargo compile failed in {}:\n{stderr}",
+                project_dir.display()
+            ),
+        }
+    }
+
+    let bytecode_path = artifact_path(project_dir)?;
+    if !bytecode_path.exists() {
         bail!(
-            "nargo check failed in {}:\n{stderr}",
-            project_dir.display()
+            "compiled bytecode not found at {} -- did nargo compile succeed for project '{}'?",
+            bytecode_path.display(),
+            read_nargo_toml(project_dir)?.package.name
         );
     }
 
+    Ok(bytecode_path)
+}
+
+/// Where `nargo compile` writes the project's compiled circuit: `target/<package>.json`.
+pub fn artifact_path(project_dir: &Path) -> Result<PathBuf> {
+    let config = read_nargo_toml(project_dir)?;
+    Ok(project_dir
+        .join("target")
+        .join(format!("{}.json", config.package.name)))
+}
+
+/// Bail unless `project_dir` has the `Prover.toml` that [`execute`] solves the witness
+/// from, so `--simulate-proof-gas` fails before anything is deployed.
+pub fn require_prover_toml(project_dir: &Path) -> Result<()> {
+    let prover_toml = project_dir.join("Prover.toml");
+    if !prover_toml.exists() {
+        bail!(
+            "--simulate-proof-gas proves the circuit with {}, which does not exist -- write one (e.g. with `gen-prover-toml`)",
+            prover_toml.display()
+        );
+    }
     Ok(())
 }
 
-/// Run `nargo compile` in the given project directory and return the path to the compiled JSON.
-pub fn compile(project_dir: &Path) -> Result<PathBuf> {
-    let output = Command::new("nargo")
-        .arg("compile")
+/// Run `nargo execute` to solve the witness from the project's `Prover.toml`, and return
+/// the path to the witness (`target/<package>.gz`).
+pub fn execute(project_dir: &Path) -> Result<PathBuf> {
+    let output = tools::command(Tool::Nargo)
+        .arg("execute")
         .current_dir(project_dir)
         .output()
         .with_context(|| format!(
-            "failed to run `nargo compile` in {} -- is nargo installed?",
+            "failed to run `nargo execute` in {} -- is nargo installed?",
             project_dir.display()
         ))?;
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         bail!(
-            "nargo compile failed in {}:\n{stderr}",
+            "nargo execute failed in {} (are the values in Prover.toml valid inputs?):\n{stderr}",
             project_dir.display()
         );
     }
 
     let config = read_nargo_toml(project_dir)?;
-
-    let bytecode_path = project_dir
+    let witness_path = project_dir
         .join("target")
-        .join(format!("{}.json", config.package.name));
-
-    if !bytecode_path.exists() {
+        .join(format!("{}.gz", config.package.name));
+    if !witness_path.exists() {
         bail!(
-            "compiled bytecode not found at {} -- did nargo compile succeed for project '{}'?",
-            bytecode_path.display(),
-            config.package.name
+            "witness not found at {} after running nargo execute",
+            witness_path.display()
         );
     }
 
-    Ok(bytecode_path)
+    Ok(witness_path)
+}
+
+/// Normalize Noir source so that formatting-only changes produce identical bytes.
+///
+/// Strips line and (nested) block comments, normalizes CRLF to LF, and collapses
+/// whitespace: runs are dropped entirely except between two word characters,
+/// where a single space is kept.  String literals are copied verbatim.
+pub fn normalize_source(source: &str) -> String {
+    let source = source.replace("\r\n", "\n");
+    let chars: Vec<char> = source.chars().collect();
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+
+    let mut out = String::with_capacity(source.len());
+    let mut pending_space = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        if c == '/' && next == Some('/') {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            pending_space = true;
+            continue;
+        }
+
+        if c == '/' && next == Some('*') {
+            let mut depth = 0;
+            while i < chars.len() {
+                if chars[i] == '/' && chars.get(i + 1) == Some(&'*') {
+                    depth += 1;
+                    i += 2;
+                } else if chars[i] == '*' && chars.get(i + 1) == Some(&'/') {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+            pending_space = true;
+            continue;
+        }
+
+        if c.is_whitespace() {
+            pending_space = true;
+            i += 1;
+            continue;
+        }
+
+        if pending_space && out.chars().last().is_some_and(is_word) && is_word(c) {
+            out.push(' ');
+        }
+        pending_space = false;
+
+        if c == '"' {
+            out.push(c);
+            i += 1;
+            while i < chars.len() {
+                out.push(chars[i]);
+                if chars[i] == '\\' {
+                    if let Some(&escaped) = chars.get(i + 1) {
+                        out.push(escaped);
+                    }
+                    i += 2;
+                    continue;
+                }
+                i += 1;
+                if chars[i - 1] == '"' {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        out.push(c);
+        i += 1;
+    }
+
+    out.push('\n');
+    out
+}
+
+/// Write a copy of a compiled Noir artifact with every embedded source normalized
+/// (see [`normalize_source`]) and location-dependent debug info removed, so that
+/// formatting-only changes to the circuit do not change the uploaded bytes.
+///
+/// The copy is written next to the original as `<name>.normalized.json`.
+pub fn write_normalized_artifact(bytecode_path: &Path) -> Result<PathBuf> {
+    let contents = std::fs::read(bytecode_path)
+        .with_context(|| format!("failed to read {}", bytecode_path.display()))?;
+    let mut artifact: serde_json::Value = serde_json::from_slice(&contents)
+        .with_context(|| format!("failed to parse {}", bytecode_path.display()))?;
+
+    let obj = artifact
+        .as_object_mut()
+        .with_context(|| format!("unexpected artifact format in {}", bytecode_path.display()))?;
+    obj.remove("debug_symbols");
+    if let Some(files) = obj.get_mut("file_map").and_then(|f| f.as_object_mut()) {
+        for file in files.values_mut() {
+            if let Some(source) = file.get_mut("source")
+                && let Some(text) = source.as_str()
+            {
+                *source = serde_json::Value::String(normalize_source(text));
+            }
+        }
+    }
+
+    let normalized_path = bytecode_path.with_extension("normalized.json");
+    let json = serde_json::to_vec(&artifact).context("failed to serialize normalized artifact")?;
+    std::fs::write(&normalized_path, json)
+        .with_context(|| format!("failed to write {}", normalized_path.display()))?;
+
+    Ok(normalized_path)
 }
@@ -0,0 +1,477 @@
+use anyhow::{Context, Result};
+use std::path::Path;
+
+use crate::eth::{self, DeployArgs};
+use crate::etherscan::{self, VerifyArgs};
+use crate::ipfs::PinArgs;
+use crate::manifest;
+use crate::solc::BuildTool;
+use crate::Commands;
+
+/// What `--print-plan` prints: the resolved parameters, then the numbered steps.
+#[derive(Default)]
+struct Plan {
+    params: Vec<(&'static str, String)>,
+    steps: Vec<String>,
+}
+
+impl Plan {
+    fn param(&mut self, name: &'static str, value: impl Into<String>) {
+        self.params.push((name, value.into()));
+    }
+
+    fn step(&mut self, step: impl Into<String>) {
+        self.steps.push(step.into());
+    }
+
+    fn print(&self, command: &str) {
+        println!("plan for {command} (nothing has been run):");
+        let width = self.params.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        for (name, value) in &self.params {
+            println!("  {:<width$}  {value}", format!("{name}:"), width = width + 1);
+        }
+        println!("steps:");
+        for (i, step) in self.steps.iter().enumerate() {
+            println!("  {:>2}. {step}", i + 1);
+        }
+    }
+}
+
+/// Print the steps `command` would run, with the parameters they would run with, and
+/// exit without running any.  Nothing is contacted: chains, ENS names, and fees are
+/// described as they would be resolved, not looked up.
+pub fn print(
+    command: &Commands,
+    ipfs_url: &str,
+    pin: &PinArgs,
+    deploy: &DeployArgs,
+    verify: &VerifyArgs,
+) -> Result<()> {
+    let mut plan = Plan::default();
+    match command {
+        Commands::NewComplianceDefinition {
+            circuit_dir,
+            name,
+            rpc_url,
+            regulator,
+            contract_dir,
+            constructor_args_json,
+            compliance_definition_salt,
+            factory,
+            leaves_file,
+            circuit_cid,
+            leaves_cid,
+            note,
+            normalize_source,
+            print_calldata,
+            chain_id,
+            ..
+        } => {
+            let (regulator_arg, prefixed_chain) = eth::AddressArg::parse(regulator)
+                .with_context(|| format!("invalid regulator address: {regulator}"))?;
+            let chain_id = eth::merge_chain_id(*chain_id, prefixed_chain)?;
+            plan.param("name", name.as_str());
+            plan.param("circuit dir", circuit_dir.display().to_string());
+            plan.param("contract dir", contract_dir.display().to_string());
+            plan.param("chain", describe_chain(chain_id, rpc_url));
+            plan.param("regulator", describe_address(regulator, &regulator_arg));
+            plan.param("verification", etherscan::describe_verification(verify, chain_id));
+            plan.param("ipfs", ipfs_url);
+
+            plan.step(format!("check the chain and the t_start/t_end window on {rpc_url}"));
+            if let Some(factory) = factory {
+                plan.step(format!("check that factory {factory} is deployed"));
+            }
+            plan.step(build_step("the contracts", contract_dir, deploy));
+            match (factory, compliance_definition_salt) {
+                (Some(_), _) => {}
+                (None, Some(salt)) => plan.step(format!(
+                    "deploy ComplianceDefinition with CREATE2 (salt {salt}){}",
+                    constructor_note(constructor_args_json.as_deref())
+                )),
+                (None, None) => plan.step(format!(
+                    "deploy ComplianceDefinition{}{}",
+                    constructor_note(constructor_args_json.as_deref()),
+                    idempotent_note(deploy)
+                )),
+            }
+            if factory.is_none() {
+                plan.step(verify_step("ComplianceDefinition", verify, chain_id));
+            }
+            circuit_steps(&mut plan, circuit_dir, *normalize_source, deploy);
+            upload_steps(&mut plan, ipfs_url, pin, circuit_cid.as_deref(), leaves_file.as_deref(), leaves_cid.as_deref());
+            if let Some(step) = metadata_step(ipfs_url, pin, note.as_deref(), circuit_dir) {
+                plan.step(step);
+            }
+            plan.step(verifier_build_step(contract_dir, deploy));
+            plan.step(bytecode_hash_step(deploy));
+            match factory {
+                Some(factory) => {
+                    plan.step(format!(
+                        "call {factory}.deploy to deploy HonkVerifier and ComplianceDefinition, register the verifier, and hand the definition to the regulator"
+                    ));
+                    plan.step(verify_step("HonkVerifier", verify, chain_id));
+                    post_deploy_steps(&mut plan, deploy);
+                    plan.step(verify_step("ComplianceDefinition", verify, chain_id));
+                }
+                None => {
+                    plan.step(verifier_step(deploy));
+                    plan.step(verify_step("HonkVerifier", verify, chain_id));
+                    post_deploy_steps(&mut plan, deploy);
+                    plan.step(register_step("the new ComplianceDefinition", *print_calldata, deploy));
+                }
+            }
+        }
+        Commands::UpdateCircuit {
+            circuit_dir,
+            rpc_url,
+            compliance_definition,
+            from_receipt,
+            contract_dir,
+            leaves_file,
+            circuit_cid,
+            leaves_cid,
+            note,
+            normalize_source,
+            print_calldata,
+            force,
+            chain_id,
+            expect_regulator,
+            deploy_verifier_only,
+            watch,
+            ..
+        } => {
+            let (definitions, rpc_url, chain_id) = crate::definition_target(
+                from_receipt.clone(),
+                compliance_definition.clone(),
+                rpc_url.clone(),
+                *chain_id,
+            )?;
+            let chain_id = definitions_chain(&definitions, chain_id)?;
+            plan.param("circuit dir", circuit_dir.display().to_string());
+            plan.param("contract dir", contract_dir.display().to_string());
+            plan.param("chain", describe_chain(chain_id, &rpc_url));
+            if !deploy_verifier_only {
+                plan.param("definitions", definitions.join(", "));
+            }
+            plan.param("verification", etherscan::describe_verification(verify, chain_id));
+            plan.param("ipfs", ipfs_url);
+
+            if *watch {
+                plan.step(format!("check that {rpc_url} is a local dev chain (--watch)"));
+            }
+            circuit_steps(&mut plan, circuit_dir, *normalize_source, deploy);
+            upload_steps(&mut plan, ipfs_url, pin, circuit_cid.as_deref(), leaves_file.as_deref(), leaves_cid.as_deref());
+            // Without a note the document is uploaded before the check, which compares it.
+            let metadata = metadata_step(ipfs_url, pin, note.as_deref(), circuit_dir);
+            if note.is_none()
+                && let Some(ref step) = metadata
+            {
+                plan.step(step);
+            }
+            if !deploy_verifier_only {
+                let mut checks = vec!["resolve each definition"];
+                if expect_regulator.is_some() {
+                    checks.push("check its regulator (--expect-regulator)");
+                }
+                if !force {
+                    checks.push("skip it if its latest version already matches");
+                }
+                plan.step(checks.join(", "));
+            }
+            if note.is_some()
+                && let Some(step) = metadata
+            {
+                plan.step(step);
+            }
+            plan.step(verifier_build_step(contract_dir, deploy));
+            plan.step(bytecode_hash_step(deploy));
+            plan.step(verifier_step(deploy));
+            plan.step(verify_step("HonkVerifier", verify, chain_id));
+            post_deploy_steps(&mut plan, deploy);
+            if *deploy_verifier_only {
+                plan.step("print the verifier address and metadata hash and stop, without registering (--deploy-verifier-only)");
+            } else {
+                let target = match deploy.concurrency {
+                    1 => "each definition".to_string(),
+                    n => format!("each definition, up to {n} at once (--concurrency)"),
+                };
+                plan.step(register_step(&target, *print_calldata, deploy));
+            }
+            if *watch {
+                plan.step(format!("watch {} and repeat from step 2 on every change", circuit_dir.join("src").display()));
+            }
+        }
+        Commands::UpdateParams {
+            compliance_definition,
+            from_receipt,
+            rpc_url,
+            merkle_root,
+            leaves_file,
+            leaves_cid,
+            chain_id,
+            expect_regulator,
+            ..
+        } => {
+            let (definitions, rpc_url, chain_id) = crate::definition_target(
+                from_receipt.clone(),
+                compliance_definition.iter().cloned().collect(),
+                rpc_url.clone(),
+                *chain_id,
+            )?;
+            let chain_id = definitions_chain(&definitions, chain_id)?;
+            plan.param("chain", describe_chain(chain_id, &rpc_url));
+            plan.param("definition", definitions.join(", "));
+            plan.param("merkle root", merkle_root.as_str());
+            plan.param("ipfs", ipfs_url);
+
+            plan.step(leaves_step(ipfs_url, pin, leaves_file.as_deref(), leaves_cid.as_deref()));
+            if expect_regulator.is_some() {
+                plan.step("check the definition's regulator (--expect-regulator)");
+            }
+            plan.step(format!("call updateParams on {}", definitions.join(", ")));
+        }
+        Commands::Register {
+            compliance_definition,
+            from_receipt,
+            rpc_url,
+            verifier,
+            circuit_cid,
+            print_calldata,
+            chain_id,
+            expect_regulator,
+            key,
+            ..
+        } => {
+            let (definitions, rpc_url, chain_id) = crate::definition_target(
+                from_receipt.clone(),
+                compliance_definition.iter().cloned().collect(),
+                rpc_url.clone(),
+                *chain_id,
+            )?;
+            let chain_id = definitions_chain(&definitions, chain_id)?;
+            plan.param("chain", describe_chain(chain_id, &rpc_url));
+            plan.param("definition", definitions.join(", "));
+            plan.param("verifier", verifier.as_str());
+            plan.param("metadata hash", circuit_cid.as_str());
+
+            if expect_regulator.is_some() {
+                plan.step("check the definition's regulator (--expect-regulator)");
+            }
+            plan.step(format!("check that {verifier} implements verify(bytes,bytes32[])"));
+            let mut step = register_step(&definitions.join(", "), *print_calldata, deploy);
+            if key.ledger() && !print_calldata {
+                step.push_str(", signed on the Ledger through `cast send --ledger`");
+            }
+            plan.step(step);
+        }
+        other => plan.step(format!("run {} (a single step)", other.name())),
+    }
+    plan.print(command.name());
+    Ok(())
+}
+
+/// The chain the run will be checked against, or who decides it.
+fn describe_chain(chain_id: Option<u64>, rpc_url: &str) -> String {
+    match chain_id {
+        Some(id) => format!("{id} ({}) via {rpc_url}", etherscan::network_name(id)),
+        None => format!("whichever chain {rpc_url} reports"),
+    }
+}
+
+fn describe_address(input: &str, arg: &eth::AddressArg) -> String {
+    match arg.ens_name() {
+        Some(name) => format!("{name} (ENS, resolved on the chain)"),
+        None => input.to_string(),
+    }
+}
+
+/// Fold chain prefixes on the definition addresses into the expected chain, as the
+/// commands do.
+fn definitions_chain(definitions: &[String], mut chain_id: Option<u64>) -> Result<Option<u64>> {
+    for definition in definitions {
+        let (_, prefixed_chain) = eth::AddressArg::parse(definition)
+            .with_context(|| format!("invalid compliance definition address: {definition}"))?;
+        chain_id = eth::merge_chain_id(chain_id, prefixed_chain)?;
+    }
+    Ok(chain_id)
+}
+
+fn build_step(what: &str, contract_dir: &Path, deploy: &DeployArgs) -> String {
+    if deploy.skip_build {
+        format!("use {what} prebuilt in {}/out (--skip-build)", contract_dir.display())
+    } else {
+        format!(
+            "build {what} in {} with forge (optimizer runs {})",
+            contract_dir.display(),
+            deploy.optimizer_runs
+        )
+    }
+}
+
+/// Building the generated verifier, with forge or (`--build-tool solc`) with solc.
+fn verifier_build_step(contract_dir: &Path, deploy: &DeployArgs) -> String {
+    match deploy.build_tool {
+        BuildTool::Solc if !deploy.skip_build => format!(
+            "compile src/Verifier.sol in {} with solc --standard-json (optimizer runs {}, --build-tool solc)",
+            contract_dir.display(),
+            deploy.optimizer_runs
+        ),
+        _ => build_step("the contracts with the new Verifier.sol", contract_dir, deploy),
+    }
+}
+
+/// The `--expected-verifier-bytecode-hash` check, which follows every verifier build.
+fn bytecode_hash_step(deploy: &DeployArgs) -> String {
+    match deploy.expected_verifier_hash {
+        Some(hash) => format!("check that the verifier bytecode hashes to {hash} (--expected-verifier-bytecode-hash)"),
+        None => "print the verifier bytecode hash".to_string(),
+    }
+}
+
+fn constructor_note(constructor_args_json: Option<&Path>) -> String {
+    match constructor_args_json {
+        Some(path) => format!(" with constructor arguments from {}", path.display()),
+        None => String::new(),
+    }
+}
+
+fn verifier_step(deploy: &DeployArgs) -> String {
+    let step = if deploy.parallel_libraries {
+        "deploy HonkVerifier, sending its library deployments together (--parallel-libraries)"
+    } else {
+        "deploy HonkVerifier"
+    };
+    format!("{step}{}", idempotent_note(deploy))
+}
+
+/// How `--idempotent` changes a deployment step.
+fn idempotent_note(deploy: &DeployArgs) -> String {
+    match &deploy.idempotent {
+        Some(journal) => format!(
+            ", or reuse the deployment of the same bytecode recorded in {} (--idempotent)",
+            journal.path().display()
+        ),
+        None => String::new(),
+    }
+}
+
+fn verify_step(contract: &str, verify: &VerifyArgs, chain_id: Option<u64>) -> String {
+    format!(
+        "verify {contract} on the block explorer: {}",
+        etherscan::describe_verification(verify, chain_id)
+    )
+}
+
+fn circuit_steps(plan: &mut Plan, circuit_dir: &Path, normalize_source: bool, deploy: &DeployArgs) {
+    plan.step(format!("nargo compile in {} (validates the circuit)", circuit_dir.display()));
+    if circuit_dir.join(manifest::MANIFEST_FILE).is_file() {
+        plan.step(format!(
+            "check that {} documents exactly the circuit's public inputs",
+            manifest::MANIFEST_FILE
+        ));
+    }
+    plan.step("generate the verification key and Solidity verifier with bb");
+    if deploy.simulate_proof_gas {
+        plan.step(format!(
+            "prove the circuit with {} (--simulate-proof-gas)",
+            circuit_dir.join("Prover.toml").display()
+        ));
+    }
+    if normalize_source {
+        plan.step("normalize the circuit source embedded in the artifact (--normalize-source)");
+    }
+}
+
+fn post_deploy_steps(plan: &mut Plan, deploy: &DeployArgs) {
+    if deploy.verify_interface {
+        plan.step("check that HonkVerifier implements verify(bytes,bytes32[]) (--verify-interface)");
+    }
+    if deploy.simulate_proof_gas {
+        plan.step("estimate the gas of verify() with the proof (nothing is sent)");
+    }
+}
+
+/// How uploads will be treated, e.g. ` (pinned on the node)`.
+fn pin_notes(pin: &PinArgs) -> String {
+    let mut notes = Vec::new();
+    if pin.skip_existing {
+        notes.push("skipped if already pinned");
+    }
+    if pin.local_pin {
+        notes.push("pinned on the node");
+    }
+    if pin.remote_pin {
+        notes.push("pinned to the remote pinning service");
+    }
+    if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) }
+}
+
+fn upload_steps(
+    plan: &mut Plan,
+    ipfs_url: &str,
+    pin: &PinArgs,
+    circuit_cid: Option<&str>,
+    leaves_file: Option<&Path>,
+    leaves_cid: Option<&str>,
+) {
+    plan.step(match circuit_cid {
+        Some(cid) => format!("use the pre-pinned circuit CID {cid} (no upload)"),
+        None => format!("upload the compiled circuit to IPFS at {ipfs_url}{}", pin_notes(pin)),
+    });
+    if leaves_file.is_some() || leaves_cid.is_some() {
+        plan.step(leaves_step(ipfs_url, pin, leaves_file, leaves_cid));
+    }
+    if pin.upload_project {
+        plan.step(format!("upload the Noir project (Nargo.toml and src/) to IPFS at {ipfs_url}{}", pin_notes(pin)));
+    }
+}
+
+/// The upload of the metadata document a `--note` or a `compliance.toml` adds, if any.
+fn metadata_step(ipfs_url: &str, pin: &PinArgs, note: Option<&str>, circuit_dir: &Path) -> Option<String> {
+    let inputs = circuit_dir.join(manifest::MANIFEST_FILE).is_file();
+    let (document, contents) = match (note, inputs) {
+        (Some(note), false) => ("a version note document", format!("the circuit CID and {note:?}")),
+        (Some(note), true) => (
+            "a version note document",
+            format!("the circuit CID, the {} input descriptions, and {note:?}", manifest::MANIFEST_FILE),
+        ),
+        (None, true) => (
+            "a metadata document",
+            format!("the circuit CID and the {} input descriptions", manifest::MANIFEST_FILE),
+        ),
+        (None, false) => return None,
+    };
+    let flag = if note.is_some() { " (--note)" } else { "" };
+    Some(format!(
+        "upload {document} with {contents} to IPFS at {ipfs_url}{} and register its CID as the metadataHash{flag}",
+        pin_notes(pin)
+    ))
+}
+
+fn leaves_step(ipfs_url: &str, pin: &PinArgs, leaves_file: Option<&Path>, leaves_cid: Option<&str>) -> String {
+    match (leaves_cid, leaves_file) {
+        (Some(cid), _) => format!("use the pre-pinned leaves CID {cid} (no upload)"),
+        (None, Some(path)) => format!("upload {} to IPFS at {ipfs_url}{}", path.display(), pin_notes(pin)),
+        (None, None) => "leave the leaves hash empty".to_string(),
+    }
+}
+
+fn register_step(target: &str, print_calldata: bool, deploy: &DeployArgs) -> String {
+    let function = match deploy.definition_abi {
+        Some(_) => "the --update-function",
+        None => "updateCircuit",
+    };
+    let label = match deploy.on_chain_label {
+        Some(ref label) if print_calldata => format!(", and the setLabel({label:?}) calldata"),
+        Some(ref label) => format!(
+            ", then setLabel({label:?}) to label the version (skipped if the definition has no setLabel)"
+        ),
+        None => String::new(),
+    };
+    if print_calldata {
+        format!("print the {function} calldata for {target} instead of sending it (--print-calldata){label}")
+    } else {
+        format!("call {function} on {target} to register the verifier{label}")
+    }
+}
@@ -0,0 +1,31 @@
+use crate::redact;
+
+/// Receives progress from the command pipelines in `commands/`, so embedders can route
+/// it somewhere other than stderr (e.g. forward it to a websocket).
+pub trait ProgressReporter: Send + Sync {
+    /// A pipeline step, named as in the receipt's `timings_ms` (e.g. `nargo_compile`),
+    /// is starting.
+    fn step_started(&self, step: &str);
+
+    /// The step most recently started under this name has finished.
+    fn step_finished(&self, step: &str);
+
+    /// A human-readable progress line.
+    fn info(&self, msg: &str);
+}
+
+/// The CLI's reporter: progress lines go to stderr, keeping stdout for results, with
+/// secrets masked.
+/// Step boundaries are already described by the progress lines, so they are not
+/// printed separately.
+pub struct TerminalProgress;
+
+impl ProgressReporter for TerminalProgress {
+    fn step_started(&self, _step: &str) {}
+
+    fn step_finished(&self, _step: &str) {}
+
+    fn info(&self, msg: &str) {
+        eprintln!("{}", redact::redact(msg));
+    }
+}
@@ -0,0 +1,222 @@
+use alloy::primitives::{U256, uint};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::path::Path;
+
+/// Order of the BN254 scalar field; every Noir `Field` is below it.
+const FIELD_MODULUS: U256 =
+    uint!(0x30644e72e131a029b85045b68181585d2833e84879b9709143e1f593f0000001_U256);
+
+/// A circuit parameter from the `abi` of a compiled Noir artifact.
+#[derive(Deserialize)]
+pub struct AbiParameter {
+    pub name: String,
+    #[serde(rename = "type")]
+    typ: AbiType,
+    /// `public` or `private`; absent for struct fields.
+    #[serde(default)]
+    visibility: Option<String>,
+}
+
+impl AbiParameter {
+    /// Whether the parameter is a public input of the circuit.
+    pub fn is_public(&self) -> bool {
+        self.visibility.as_deref() == Some("public")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum AbiType {
+    Field,
+    Boolean,
+    Integer { sign: Sign, width: u32 },
+    String { length: usize },
+    Array {
+        length: usize,
+        #[serde(rename = "type")]
+        typ: Box<AbiType>,
+    },
+    Tuple { fields: Vec<AbiType> },
+    Struct { path: String, fields: Vec<AbiParameter> },
+}
+
+#[derive(Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Sign {
+    Signed,
+    Unsigned,
+}
+
+#[derive(Deserialize)]
+struct Abi {
+    parameters: Vec<AbiParameter>,
+}
+
+/// Read the circuit parameters from a compiled artifact (`target/<name>.json`) or a
+/// bare ABI object.
+pub fn read_parameters(abi_path: &Path) -> Result<Vec<AbiParameter>> {
+    let json: serde_json::Value = serde_json::from_slice(
+        &std::fs::read(abi_path).with_context(|| format!("failed to read {}", abi_path.display()))?,
+    )
+    .with_context(|| format!("failed to parse {}", abi_path.display()))?;
+    let abi = json.get("abi").unwrap_or(&json);
+    let abi: Abi = serde_json::from_value(abi.clone()).with_context(|| {
+        format!(
+            "{} is not a compiled Noir circuit (expected an `abi` with `parameters`)",
+            abi_path.display()
+        )
+    })?;
+    Ok(abi.parameters)
+}
+
+/// Map a JSON object of witness values onto the circuit `parameters`, returning the
+/// contents of a `Prover.toml`.  Every parameter must be present with a value of its
+/// ABI type; keys that are not parameters are rejected.
+pub fn from_json(parameters: &[AbiParameter], witness: &serde_json::Value) -> Result<String> {
+    let object = witness
+        .as_object()
+        .context("witness JSON must be an object keyed by parameter name")?;
+
+    let missing: Vec<&str> = parameters
+        .iter()
+        .map(|p| p.name.as_str())
+        .filter(|name| !object.contains_key(*name))
+        .collect();
+    if !missing.is_empty() {
+        bail!("witness JSON is missing parameters: {}", missing.join(", "));
+    }
+    if let Some(unknown) = object.keys().find(|k| !parameters.iter().any(|p| &p.name == *k)) {
+        bail!("witness JSON has `{unknown}`, which is not a parameter of the circuit");
+    }
+
+    let mut table = toml::Table::new();
+    for param in parameters {
+        table.insert(param.name.clone(), to_toml(&param.typ, &object[&param.name], &param.name)?);
+    }
+    toml::to_string(&table).context("failed to serialize Prover.toml")
+}
+
+/// Convert one JSON value to the TOML nargo expects for `typ`.  `at` names the value
+/// (e.g. `user.balances[2]`) in errors.
+fn to_toml(typ: &AbiType, value: &serde_json::Value, at: &str) -> Result<toml::Value> {
+    use serde_json::Value as Json;
+
+    match (typ, value) {
+        (AbiType::Field, Json::String(_) | Json::Number(_)) => {
+            let n = parse_integer(value).with_context(|| format!("{at}: invalid field element {value}"))?;
+            if n.negative || n.magnitude >= FIELD_MODULUS {
+                bail!("{at}: {value} is not a field element (must be in 0..p for the BN254 scalar field)");
+            }
+            Ok(toml::Value::String(n.text))
+        }
+        (AbiType::Integer { sign, width }, Json::String(_) | Json::Number(_)) => {
+            let n = parse_integer(value).with_context(|| format!("{at}: invalid integer {value}"))?;
+            let signed = *sign == Sign::Signed;
+            let bits = if signed { width - 1 } else { *width };
+            let limit = U256::from(1) << bits;
+            let in_range = if n.negative {
+                signed && n.magnitude <= limit
+            } else {
+                n.magnitude < limit
+            };
+            if !in_range {
+                let ty = if signed { 'i' } else { 'u' };
+                bail!("{at}: {value} does not fit in {ty}{width}");
+            }
+            Ok(toml::Value::String(n.text))
+        }
+        (AbiType::Boolean, Json::Bool(b)) => Ok(toml::Value::Boolean(*b)),
+        (AbiType::String { length }, Json::String(s)) => {
+            if s.len() != *length {
+                bail!("{at}: expected a string of exactly {length} bytes, got {}", s.len());
+            }
+            Ok(toml::Value::String(s.clone()))
+        }
+        (AbiType::Array { length, typ }, Json::Array(items)) => {
+            if items.len() != *length {
+                bail!("{at}: expected an array of {length} elements, got {}", items.len());
+            }
+            items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| to_toml(typ, item, &format!("{at}[{i}]")))
+                .collect::<Result<_>>()
+                .map(toml::Value::Array)
+        }
+        (AbiType::Tuple { fields }, Json::Array(items)) => {
+            if items.len() != fields.len() {
+                bail!("{at}: expected a tuple of {} elements, got {}", fields.len(), items.len());
+            }
+            fields
+                .iter()
+                .zip(items)
+                .enumerate()
+                .map(|(i, (typ, item))| to_toml(typ, item, &format!("{at}.{i}")))
+                .collect::<Result<_>>()
+                .map(toml::Value::Array)
+        }
+        (AbiType::Struct { path, fields }, Json::Object(object)) => {
+            if let Some(unknown) = object.keys().find(|k| !fields.iter().any(|f| &f.name == *k)) {
+                bail!("{at}: `{unknown}` is not a field of struct {path}");
+            }
+            let mut table = toml::Table::new();
+            for field in fields {
+                let item = object
+                    .get(&field.name)
+                    .with_context(|| format!("{at}: missing field `{}` of struct {path}", field.name))?;
+                table.insert(field.name.clone(), to_toml(&field.typ, item, &format!("{at}.{}", field.name))?);
+            }
+            Ok(toml::Value::Table(table))
+        }
+        (typ, value) => bail!("{at}: expected {}, got {value}", typ.describe()),
+    }
+}
+
+impl AbiType {
+    fn describe(&self) -> String {
+        match self {
+            Self::Field => "a field element (decimal or 0x-hex string, or a number)".into(),
+            Self::Boolean => "a boolean".into(),
+            Self::Integer { sign: Sign::Signed, width } => format!("an i{width} (string or number)"),
+            Self::Integer { sign: Sign::Unsigned, width } => format!("a u{width} (string or number)"),
+            Self::String { length } => format!("a string of {length} bytes"),
+            Self::Array { length, .. } => format!("an array of {length} elements"),
+            Self::Tuple { fields } => format!("a tuple (array) of {} elements", fields.len()),
+            Self::Struct { path, .. } => format!("an object for struct {path}"),
+        }
+    }
+}
+
+/// An integer witness value, kept in the notation it was given in.
+struct ParsedInteger {
+    text: String,
+    negative: bool,
+    magnitude: U256,
+}
+
+/// Parse a JSON number, or a decimal / `0x`-hex string (optionally negative).  JSON
+/// numbers must be integers; large values should be strings to avoid float rounding.
+fn parse_integer(value: &serde_json::Value) -> Result<ParsedInteger> {
+    let text = match value {
+        serde_json::Value::Number(n) if n.is_i64() || n.is_u64() => n.to_string(),
+        serde_json::Value::Number(_) => bail!("not an integer"),
+        serde_json::Value::String(s) => s.trim().to_string(),
+        _ => bail!("not a number"),
+    };
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.as_str()),
+    };
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) if !hex.is_empty() => U256::from_str_radix(hex, 16),
+        None if !digits.is_empty() => U256::from_str_radix(digits, 10),
+        _ => bail!("empty number"),
+    }
+    .map_err(|e| anyhow::anyhow!("{e}"))?;
+    Ok(ParsedInteger {
+        text,
+        negative: negative && !magnitude.is_zero(),
+        magnitude,
+    })
+}
@@ -1,34 +1,131 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use chrono::Utc;
-use serde::Serialize;
-use std::path::Path;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+use crate::timings::Timings;
+
+/// Version of the receipt format.  Within a version, fields are only ever added to a
+/// command's `data`; renaming, removing, or retyping a field bumps the version.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Where receipts and `key=value` results are written.
+#[derive(Clone)]
+pub struct ReceiptOutput {
+    pub dir: PathBuf,
+    /// Also print the receipt JSON to stdout, keeping stdout free of anything else.
+    pub stdout: bool,
+    /// Name receipt files after a hash of their `data` instead of the time they were
+    /// written, so identical runs produce the same path.
+    pub deterministic: bool,
+    /// Also write the receipt's results as shell `export` lines to this file.
+    pub env_out: Option<PathBuf>,
+}
+
+impl ReceiptOutput {
+    /// Print a `key=value` result line: to stdout normally, to stderr when stdout is
+    /// reserved for the receipt.
+    pub fn print_value(&self, key: &str, value: &dyn Display) {
+        if self.stdout {
+            eprintln!("{key}={value}");
+        } else {
+            println!("{key}={value}");
+        }
+    }
+}
+
+/// Receipt payload written when a command is cancelled with Ctrl-C or exceeds `--deadline`.
 #[derive(Debug, Serialize)]
+pub struct InterruptedData {
+    pub interrupted: bool,
+    pub reason: String,
+    /// What the command got done before it was stopped, in order.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub completed: Vec<CompletedStep>,
+}
+
+/// A transaction sent, contract deployed, or file uploaded by the running command.
+#[derive(Clone, Debug, Serialize)]
+pub struct CompletedStep {
+    /// What was done, e.g. `HonkVerifier deployment` or `compliance circuit upload`.
+    pub step: String,
+    /// `tx_hash`, `address`, or `cid`.
+    pub kind: &'static str,
+    pub value: String,
+}
+
+/// Steps completed by this run, for the partial receipt of an interrupted command.
+static COMPLETED: Mutex<Vec<CompletedStep>> = Mutex::new(Vec::new());
+
+/// Note a completed step, so an interrupted command's receipt still says what was
+/// sent, deployed, or uploaded before it stopped.
+pub fn record_completed(step: &str, kind: &'static str, value: impl Display) {
+    if let Ok(mut completed) = COMPLETED.lock() {
+        completed.push(CompletedStep {
+            step: step.to_string(),
+            kind,
+            value: value.to_string(),
+        });
+    }
+}
+
+/// The steps recorded with [`record_completed`] so far.
+pub fn completed_steps() -> Vec<CompletedStep> {
+    COMPLETED.lock().map(|completed| completed.clone()).unwrap_or_default()
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Receipt<T: Serialize> {
+    pub schema_version: u32,
     pub command: String,
     pub timestamp: String,
     pub data: T,
+    #[serde(default, skip_serializing_if = "Timings::is_empty")]
+    pub timings_ms: Timings,
 }
 
 impl<T: Serialize> Receipt<T> {
     pub fn new(command: &str, data: T) -> Self {
         Self {
+            schema_version: SCHEMA_VERSION,
             command: command.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             data,
+            timings_ms: Timings::default(),
         }
     }
 
+    /// Attach per-step timings to the receipt.
+    pub fn with_timings(mut self, timings: Timings) -> Self {
+        self.timings_ms = timings;
+        self
+    }
+
     /// Write the receipt as JSON to a file inside `dir`, creating the directory if needed.
     /// The filename is `<command>-<timestamp>.json` (filesystem-safe).
     pub fn write_to_dir(&self, dir: &Path) -> Result<()> {
+        let safe_ts = Utc::now().format("%Y%m%dT%H%M%S").to_string();
+        self.write_file(dir, &format!("{}-{}.json", self.command, safe_ts))
+    }
+
+    /// Write the receipt as `<command>-<hash>.json`, where `<hash>` is the first 16 hex
+    /// digits of the keccak256 of the canonical `data`.  The timestamp and timings are
+    /// left out of the hash but still recorded inside the file.
+    pub fn write_content_addressed(&self, dir: &Path) -> Result<()> {
+        let data = serde_json::to_value(&self.data).context("failed to serialize receipt")?;
+        let hash = alloy::primitives::keccak256(canonical_bytes(&data));
+        let short = &alloy::hex::encode(hash)[..16];
+        self.write_file(dir, &format!("{}-{short}.json", self.command))
+    }
+
+    fn write_file(&self, dir: &Path, filename: &str) -> Result<()> {
         std::fs::create_dir_all(dir)
             .with_context(|| format!("failed to create receipts directory {}", dir.display()))?;
 
-        let safe_ts = Utc::now().format("%Y%m%dT%H%M%S").to_string();
-        let filename = format!("{}-{}.json", self.command, safe_ts);
-        let path = dir.join(&filename);
-
+        let path = dir.join(filename);
         let json =
             serde_json::to_string_pretty(self).context("failed to serialize receipt")?;
         std::fs::write(&path, &json)
@@ -36,4 +133,151 @@ impl<T: Serialize> Receipt<T> {
         eprintln!("receipt written to {}", path.display());
         Ok(())
     }
+
+    /// Write the receipt to the receipts directory and, if requested, print it to stdout
+    /// as a single JSON line.
+    pub fn write(&self, output: &ReceiptOutput) -> Result<()> {
+        if output.deterministic {
+            self.write_content_addressed(&output.dir)?;
+        } else {
+            self.write_to_dir(&output.dir)?;
+        }
+        if output.stdout {
+            let json = serde_json::to_string(self).context("failed to serialize receipt")?;
+            println!("{json}");
+        }
+        if let Some(path) = &output.env_out {
+            let data = serde_json::to_value(&self.data).context("failed to serialize receipt")?;
+            std::fs::write(path, env_exports(&data))
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            eprintln!("exports written to {}", path.display());
+        }
+        Ok(())
+    }
+}
+
+/// A receipt's `data` as shell-sourceable lines, one `export KEY='value'` per scalar
+/// field, the key upper-cased (`compliance_definition` -> `COMPLIANCE_DEFINITION`).
+/// Lists and maps are left out; empty values are kept so a stale variable is cleared.
+fn env_exports(data: &serde_json::Value) -> String {
+    let Some(fields) = data.as_object() else {
+        return String::new();
+    };
+    fields
+        .iter()
+        .filter_map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                _ => return None,
+            };
+            Some(format!("export {}={}\n", key.to_uppercase(), shell_quote(&value)))
+        })
+        .collect()
+}
+
+/// Single-quote `value` for a POSIX shell, so nothing in it is expanded.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+impl Receipt<serde_json::Value> {
+    /// Read a receipt written by any command, keeping its `data` as JSON.
+    pub fn read(path: &Path) -> Result<Self> {
+        let (contents, receipt) = read_json(path)?;
+        check_schema_version(&receipt, path)?;
+        // Parsed again from the file, since timings are kept in the order they ran.
+        serde_json::from_slice(&contents)
+            .with_context(|| format!("{} is not a regulator-cli receipt", path.display()))
+    }
+}
+
+fn read_json(path: &Path) -> Result<(Vec<u8>, serde_json::Value)> {
+    let contents = std::fs::read(path)
+        .with_context(|| format!("failed to read receipt {}", path.display()))?;
+    let receipt = serde_json::from_slice(&contents)
+        .with_context(|| format!("failed to parse receipt {}", path.display()))?;
+    Ok((contents, receipt))
+}
+
+/// Reject receipts of a schema version this build does not know.
+fn check_schema_version(receipt: &serde_json::Value, path: &Path) -> Result<()> {
+    let version = receipt.get("schema_version").and_then(|v| v.as_u64());
+    if version != Some(u64::from(SCHEMA_VERSION)) {
+        bail!(
+            "receipt {} has unsupported schema_version {} (expected {SCHEMA_VERSION})",
+            path.display(),
+            version.map_or("(missing)".to_string(), |v| v.to_string())
+        );
+    }
+    Ok(())
+}
+
+/// Read the `data` of a receipt written by `command`, rejecting other commands'
+/// receipts and schema versions this build does not know.
+pub fn read_data<T: DeserializeOwned>(path: &Path, command: &str) -> Result<T> {
+    let (_, receipt) = read_json(path)?;
+    check_schema_version(&receipt, path)?;
+    let found = receipt.get("command").and_then(|c| c.as_str()).unwrap_or_default();
+    if found != command {
+        bail!(
+            "receipt {} was written by `{found}`, expected a `{command}` receipt",
+            path.display()
+        );
+    }
+
+    let data = receipt.get("data").cloned().unwrap_or_default();
+    serde_json::from_value(data).with_context(|| {
+        format!(
+            "receipt {} does not contain the results of a completed `{command}` run",
+            path.display()
+        )
+    })
+}
+
+/// Serialize a receipt (or any JSON) deterministically, for signing: object keys sorted
+/// by their UTF-8 bytes and no insignificant whitespace.  Logically equal receipts
+/// always produce the same bytes, so a signature can be checked by re-canonicalizing
+/// the receipt file, however it was reformatted.
+pub fn canonical_bytes(value: &serde_json::Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &serde_json::Value, out: &mut Vec<u8>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+            out.push(b'{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(&serde_json::Value::String(key.clone()), out);
+                out.push(b':');
+                write_canonical(value, out);
+            }
+            out.push(b'}');
+        }
+        serde_json::Value::Array(items) => {
+            out.push(b'[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(b']');
+        }
+        scalar => out.extend(serde_json::to_vec(scalar).expect("JSON scalars always serialize")),
+    }
+}
+
+/// keccak256 of the canonical form of the receipt at `path`.
+pub fn digest_file(path: &Path) -> Result<alloy::primitives::B256> {
+    let (_, receipt) = read_json(path)?;
+    Ok(alloy::primitives::keccak256(canonical_bytes(&receipt)))
 }
@@ -0,0 +1,105 @@
+use std::fmt::{self, Debug, Display};
+use std::sync::RwLock;
+
+/// What a secret is replaced with.
+const MASK: &str = "***";
+
+/// Secrets shorter than this are not registered: masking them would mangle ordinary
+/// output without protecting anything.
+const MIN_SECRET_LEN: usize = 8;
+
+/// Shortest part of a URL taken for an API key; shorter ones are routes (`v3`, `api`,
+/// a network name) or ordinary parameters (`chainid=1`, `module=contract`).
+const MIN_URL_KEY_LEN: usize = 20;
+
+/// Secrets in use by this run: the private key and any API keys or tokens.
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Mask `secret` in everything [`redact`] is applied to from now on.  A hex key is
+/// registered with and without its `0x` prefix, since tools print it either way.
+pub fn register(secret: &str) {
+    let secret = secret.trim();
+    let Ok(mut secrets) = SECRETS.write() else {
+        return;
+    };
+    for form in [secret, secret.strip_prefix("0x").unwrap_or(secret)] {
+        if form.len() >= MIN_SECRET_LEN && !secrets.iter().any(|s| s == form) {
+            secrets.push(form.to_string());
+        }
+    }
+    // Longest first, so a key is masked whole before its unprefixed form is looked for.
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+}
+
+/// Mask the credentials a configured endpoint URL carries: its password, and a user
+/// name, query parameter value, or path segment that looks like an API key (Infura's
+/// `/v3/<key>`, Alchemy's `/v2/<key>`, `?apikey=<key>`).  A URL that does not parse
+/// registers nothing.
+pub fn register_url(url: &str) {
+    let Ok(url) = reqwest::Url::parse(url.trim()) else {
+        return;
+    };
+    if let Some(password) = url.password() {
+        register(password);
+    }
+    let path = url.path_segments().into_iter().flatten().map(str::to_string);
+    let query = url.query_pairs().map(|(_, value)| value.into_owned());
+    for part in std::iter::once(url.username().to_string()).chain(path).chain(query) {
+        if looks_like_key(&part) {
+            register(&part);
+        }
+    }
+}
+
+/// A long token of letters, digits, `-`, and `_` with at least one digit, as API keys
+/// and project IDs are.
+fn looks_like_key(part: &str) -> bool {
+    part.len() >= MIN_URL_KEY_LEN
+        && part.bytes().any(|b| b.is_ascii_digit())
+        && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// `text` with every registered secret replaced by `***`.
+pub fn redact(text: &str) -> String {
+    let Ok(secrets) = SECRETS.read() else {
+        return text.to_string();
+    };
+    secrets
+        .iter()
+        .fold(text.to_string(), |text, secret| text.replace(secret.as_str(), MASK))
+}
+
+/// [`redact`] for raw output bytes; bytes that are not UTF-8 are passed through.
+pub fn redact_bytes(bytes: &[u8]) -> Vec<u8> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => redact(text).into_bytes(),
+        Err(_) => bytes.to_vec(),
+    }
+}
+
+/// Formats the wrapped value as it would format itself (`{}`, `{:#}`, `{:?}`), with
+/// every registered secret masked -- for printing errors that may quote a command
+/// line, URL, or server response.
+pub struct Redacted<T>(pub T);
+
+impl<T: Display> Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = if f.alternate() {
+            format!("{:#}", self.0)
+        } else {
+            self.0.to_string()
+        };
+        f.write_str(&redact(&text))
+    }
+}
+
+impl<T: Debug> Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = if f.alternate() {
+            format!("{:#?}", self.0)
+        } else {
+            format!("{:?}", self.0)
+        };
+        f.write_str(&redact(&text))
+    }
+}
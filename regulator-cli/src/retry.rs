@@ -0,0 +1,177 @@
+use alloy::transports::{RpcError, TransportError, TransportErrorKind};
+use anyhow::Result;
+use std::future::Future;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::progress::ProgressReporter;
+use crate::redact::Redacted;
+use crate::style;
+
+/// Default `--retries`: attempts after the first before a network call gives up.
+pub const DEFAULT_RETRIES: u32 = 2;
+/// Default `--retry-base-delay`, in seconds.
+pub const DEFAULT_BASE_DELAY_SECS: u64 = 2;
+/// Ceiling on the doubling delay between attempts.
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// How network calls (IPFS, RPC reads, Etherscan, the pinning service) are retried.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryConfig {
+    /// Attempts in total, the first included.
+    pub max_attempts: u32,
+    /// Delay before the first retry; it doubles on each later one.
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETRIES, Duration::from_secs(DEFAULT_BASE_DELAY_SECS))
+    }
+}
+
+impl RetryConfig {
+    /// `retries` attempts after the first, backing off from `base_delay`.
+    pub fn new(retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts: retries + 1,
+            base_delay,
+            max_delay: DEFAULT_MAX_DELAY.max(base_delay),
+        }
+    }
+
+    /// This policy with every delay at least `floor`, for calls whose failures take
+    /// longer than a network hiccup to clear.
+    pub fn with_min_delay(self, floor: Duration) -> Self {
+        Self {
+            base_delay: self.base_delay.max(floor),
+            max_delay: self.max_delay.max(floor),
+            ..self
+        }
+    }
+
+    /// Delay after failed attempt number `attempt` (1-based).
+    fn delay(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(self.max_delay)
+    }
+}
+
+static CONFIG: OnceLock<RetryConfig> = OnceLock::new();
+
+/// Set the retry policy once, at startup, from `--retries` / `--retry-base-delay`.
+pub fn init(config: RetryConfig) {
+    let _ = CONFIG.set(config);
+}
+
+pub fn config() -> RetryConfig {
+    CONFIG.get().copied().unwrap_or_default()
+}
+
+/// Run `op` until it succeeds, retrying transient network failures (see
+/// [`is_transient`]) with exponential backoff.  Any other error is returned at once.
+/// Each retry is announced on `progress`.
+pub async fn retry_async<T, F, Fut>(what: &str, progress: &dyn ProgressReporter, op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_async_when(what, progress, is_transient, op).await
+}
+
+/// [`retry_async`], retrying every error `should_retry` accepts.
+pub async fn retry_async_when<T, F, Fut>(
+    what: &str,
+    progress: &dyn ProgressReporter,
+    should_retry: impl Fn(&anyhow::Error) -> bool,
+    op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    retry_async_with(config(), what, progress, should_retry, op).await
+}
+
+/// [`retry_async_when`] under `config` instead of the global policy.
+pub async fn retry_async_with<T, F, Fut>(
+    config: RetryConfig,
+    what: &str,
+    progress: &dyn ProgressReporter,
+    should_retry: impl Fn(&anyhow::Error) -> bool,
+    mut op: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < config.max_attempts && should_retry(&e) => {
+                let delay = config.delay(attempt);
+                progress.info(&format!(
+                    "{} {what} failed ({:#}), retrying in {}s (attempt {}/{})...",
+                    style::warning("warning:"),
+                    Redacted(&e),
+                    delay.as_secs_f32(),
+                    attempt + 1,
+                    config.max_attempts
+                ));
+                sleep(delay).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Whether an error is worth retrying: the connection failed or timed out, or the
+/// server answered 429 or a gateway error.  Covers reqwest errors and the RPC
+/// transport's.  Application errors (a Kubo 500, a JSON-RPC revert) are not retried.
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+            is_transient_reqwest(e)
+        } else if let Some(e) = cause.downcast_ref::<TransportError>() {
+            is_transient_transport(e)
+        } else if let Some(alloy::contract::Error::TransportError(e)) = cause.downcast_ref() {
+            is_transient_transport(e)
+        } else {
+            false
+        }
+    })
+}
+
+fn is_transient_transport(error: &TransportError) -> bool {
+    match error {
+        RpcError::Transport(TransportErrorKind::HttpError(e)) => is_transient_status(e.status),
+        RpcError::Transport(TransportErrorKind::Custom(e)) => {
+            e.downcast_ref::<reqwest::Error>().is_some_and(is_transient_reqwest)
+        }
+        RpcError::Transport(kind) => kind.is_retry_err(),
+        _ => false,
+    }
+}
+
+fn is_transient_reqwest(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.status().is_some_and(|s| is_transient_status(s.as_u16()))
+}
+
+fn is_transient_status(status: u16) -> bool {
+    matches!(status, 429 | 502 | 503 | 504)
+}
+
+/// Turn a 429 or gateway-error response into an error [`retry_async`] retries; other
+/// responses are passed through for the caller to check.
+pub fn fail_transient(response: reqwest::Response) -> reqwest::Result<reqwest::Response> {
+    if is_transient_status(response.status().as_u16()) {
+        response.error_for_status()
+    } else {
+        Ok(response)
+    }
+}
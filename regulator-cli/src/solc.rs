@@ -0,0 +1,149 @@
+use anyhow::{Context, Result, bail};
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::forge;
+use crate::tools::{self, Tool};
+
+/// Compilers selectable with `--build-tool` for the generated verifier.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BuildTool {
+    /// Foundry's `forge build` over the whole contracts project
+    #[default]
+    Forge,
+    /// `solc --standard-json` on Verifier.sol alone, for machines without Foundry
+    Solc,
+}
+
+impl BuildTool {
+    /// Compile the verifier staged at `src/Verifier.sol` in `contract_dir`, leaving its
+    /// artifacts where [`forge::artifact_path`] looks for them.
+    pub fn build_verifier(self, contract_dir: &Path, optimizer_runs: u32) -> Result<()> {
+        match self {
+            Self::Forge => forge::build(contract_dir, optimizer_runs),
+            Self::Solc => build(contract_dir, "src/Verifier.sol", optimizer_runs),
+        }
+    }
+
+    /// Name of the build step in timings and progress events.
+    pub fn step(self) -> &'static str {
+        match self {
+            Self::Forge => "forge_build",
+            Self::Solc => "solc_build",
+        }
+    }
+}
+
+/// The messages of the `error`-severity entries in solc's standard JSON output;
+/// warnings are left out.
+pub fn standard_json_errors(output: &serde_json::Value) -> Vec<&str> {
+    output
+        .get("errors")
+        .and_then(|e| e.as_array())
+        .into_iter()
+        .flatten()
+        .filter(|e| e.get("severity").and_then(|s| s.as_str()) == Some("error"))
+        .filter_map(|e| e.get("formattedMessage").or(e.get("message")).and_then(|m| m.as_str()))
+        .collect()
+}
+
+/// Compile the self-contained Solidity file `source_key` (relative to `project_dir`)
+/// with `solc --standard-json`, and write a forge-style artifact for each contract in it
+/// to `out/<file name>/<Contract>.json`.
+///
+/// The artifacts carry what the rest of the CLI reads from forge's: `abi`,
+/// `bytecode` and `deployedBytecode` (`object` and `linkReferences`), and the parsed
+/// compiler `metadata`, so library linking, `--skip-build` source checks, and
+/// block-explorer verification work the same.  Imports are not resolved; the
+/// generated verifier has none.
+pub fn build(project_dir: &Path, source_key: &str, optimizer_runs: u32) -> Result<()> {
+    let source_path = project_dir.join(source_key);
+    let content = std::fs::read_to_string(&source_path)
+        .with_context(|| format!("failed to read {}", source_path.display()))?;
+    let input = serde_json::json!({
+        "language": "Solidity",
+        "sources": { source_key: { "content": content } },
+        "settings": {
+            "optimizer": { "enabled": true, "runs": optimizer_runs },
+            "outputSelection": {
+                "*": { "*": ["abi", "evm.bytecode", "evm.deployedBytecode", "metadata"] }
+            }
+        }
+    });
+
+    let mut child = tools::command(Tool::Solc)
+        .arg("--standard-json")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run `solc --standard-json` -- is solc installed?")?;
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.to_string().as_bytes())
+        .context("failed to send standard JSON input to solc")?;
+    let output = child.wait_with_output().context("failed to wait for solc")?;
+    let result: serde_json::Value = serde_json::from_slice(&output.stdout).with_context(|| {
+        format!(
+            "solc produced no standard JSON output:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        )
+    })?;
+
+    let errors = standard_json_errors(&result);
+    if !errors.is_empty() {
+        bail!("solc failed to compile {}:\n{}", source_path.display(), errors.join("\n"));
+    }
+
+    let contracts = result
+        .pointer(&format!("/contracts/{}", source_key.replace('/', "~1")))
+        .and_then(|c| c.as_object())
+        .filter(|c| !c.is_empty())
+        .with_context(|| format!("solc output has no contracts for {source_key}"))?;
+    let file_name = Path::new(source_key).file_name().unwrap_or(source_key.as_ref());
+    let out_dir = project_dir.join("out").join(file_name);
+    std::fs::create_dir_all(&out_dir).with_context(|| format!("failed to create {}", out_dir.display()))?;
+    for (name, contract) in contracts {
+        let artifact = forge_artifact(contract)
+            .with_context(|| format!("unexpected solc output for {source_key}:{name}"))?;
+        let path = out_dir.join(format!("{name}.json"));
+        std::fs::write(&path, artifact.to_string())
+            .with_context(|| format!("failed to write artifact {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// A forge-style artifact from one contract of solc's standard JSON output.
+fn forge_artifact(contract: &serde_json::Value) -> Result<serde_json::Value> {
+    let bytecode = |key: &str| -> Result<serde_json::Value> {
+        let code = contract
+            .pointer(&format!("/evm/{key}"))
+            .with_context(|| format!("no evm.{key}"))?;
+        let object = code
+            .get("object")
+            .and_then(|o| o.as_str())
+            .with_context(|| format!("no evm.{key}.object"))?;
+        Ok(serde_json::json!({
+            "object": format!("0x{}", object.trim_start_matches("0x")),
+            "linkReferences": code.get("linkReferences").cloned().unwrap_or_else(|| serde_json::json!({})),
+        }))
+    };
+    // solc returns the metadata as a JSON string; forge stores it parsed.
+    let metadata: serde_json::Value = serde_json::from_str(
+        contract
+            .get("metadata")
+            .and_then(|m| m.as_str())
+            .context("no metadata")?,
+    )
+    .context("metadata is not JSON")?;
+
+    Ok(serde_json::json!({
+        "abi": contract.get("abi").cloned().unwrap_or_else(|| serde_json::json!([])),
+        "bytecode": bytecode("bytecode")?,
+        "deployedBytecode": bytecode("deployedBytecode")?,
+        "metadata": metadata,
+    }))
+}
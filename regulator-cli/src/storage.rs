@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+
+use crate::ipfs::{self, PinArgs};
+use crate::manifest::{ComplianceManifest, InputDoc};
+use crate::metadata_schema;
+use crate::progress::ProgressReporter;
+
+/// A file kept by a [`MetadataStore`].
+#[derive(Debug)]
+pub struct StoredRef {
+    /// Reference recorded on-chain as the `metadataHash` / `leavesHash`.  The IPFS store
+    /// returns the bare CID, as existing definitions record it.
+    pub uri: String,
+    /// Stored size as reported by the store.
+    pub size: String,
+    /// The store already had the file, so nothing was uploaded.
+    pub skipped: bool,
+    /// Pin state on the IPFS node; `None` for stores without pinning.
+    pub pin: Option<ipfs::LocalPin>,
+}
+
+impl StoredRef {
+    /// Add this upload to a receipt's `skipped_uploads` and `pin_status`.
+    pub fn record(&self, skipped_uploads: &mut Vec<String>, pin_status: &mut BTreeMap<String, ipfs::LocalPin>) {
+        if self.skipped {
+            skipped_uploads.push(self.uri.clone());
+        }
+        if let Some(pin) = self.pin {
+            pin_status.insert(self.uri.clone(), pin);
+        }
+    }
+}
+
+pub type StoreFuture<'a> = Pin<Box<dyn Future<Output = Result<StoredRef>> + Send + 'a>>;
+
+/// Archives the circuit artifacts a definition points at (the compiled circuit, the
+/// leaves file, the circuit source) and returns the reference to record for them.
+///
+/// The command pipelines only go through this trait, so a new store (Arweave, S3, a
+/// git-backed store) only needs an implementation and a [`StorageBackend`] variant.
+pub trait MetadataStore: Send + Sync {
+    /// Store `file`; `name` describes it (e.g. `compliance circuit`) to stores that
+    /// label what they keep.
+    fn store<'a>(&'a self, file: &'a Path, name: &'a str) -> StoreFuture<'a>;
+}
+
+/// Metadata stores selectable with `--storage-backend`.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum StorageBackend {
+    /// An IPFS node's RPC API (`--ipfs-rpc-url`), with optional remote pinning
+    #[default]
+    Ipfs,
+}
+
+impl StorageBackend {
+    pub fn store<'a>(
+        self,
+        ipfs_rpc_url: &'a str,
+        pin: &'a PinArgs,
+        progress: &'a dyn ProgressReporter,
+    ) -> Box<dyn MetadataStore + 'a> {
+        match self {
+            Self::Ipfs => Box::new(IpfsStore { rpc_url: ipfs_rpc_url, pin, progress }),
+        }
+    }
+}
+
+/// Adds files to an IPFS node, checks (or adds) the local pin, and remote-pins them
+/// when `--remote-pin` is set.
+pub struct IpfsStore<'a> {
+    pub rpc_url: &'a str,
+    pub pin: &'a PinArgs,
+    pub progress: &'a dyn ProgressReporter,
+}
+
+impl MetadataStore for IpfsStore<'_> {
+    fn store<'a>(&'a self, file: &'a Path, name: &'a str) -> StoreFuture<'a> {
+        Box::pin(async move {
+            let response = ipfs::add_file(self.rpc_url, file, self.pin, self.progress)
+                .await
+                .with_context(|| format!("failed to upload {name} to IPFS at {}", self.rpc_url))?;
+            crate::receipt::record_completed(&format!("{name} upload"), "cid", &response.hash);
+            ipfs::remote_pin_if_enabled(self.pin, &response.hash, name, self.progress).await?;
+            Ok(StoredRef {
+                uri: response.hash,
+                size: response.size,
+                skipped: response.skipped,
+                pin: Some(response.pin),
+            })
+        })
+    }
+}
+
+/// The metadata document registered in place of the bare circuit reference when a
+/// version has a `--note` or the project a `compliance.toml`, so the definition's
+/// `metadataHash` resolves to the circuit plus what it means and why it was registered.
+#[derive(Debug, Serialize)]
+pub struct MetadataDocument<'a> {
+    pub circuit_cid: &'a str,
+    /// When the document was assembled, so every document gets a new CID.
+    pub created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'a str>,
+    /// The public inputs documented in `compliance.toml`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_inputs: Option<&'a [InputDoc]>,
+}
+
+impl<'a> MetadataDocument<'a> {
+    pub fn new(circuit_cid: &'a str, note: Option<&'a str>, manifest: Option<&'a ComplianceManifest>) -> Self {
+        Self {
+            circuit_cid,
+            created_at: Utc::now().to_rfc3339(),
+            note,
+            public_inputs: manifest.map(|m| m.inputs.as_slice()),
+        }
+    }
+
+    /// Whether `registered`, a document fetched from a version's `metadataHash`, says
+    /// the same as this one; `created_at` is ignored.
+    pub fn matches(&self, registered: &serde_json::Value) -> bool {
+        let Ok(serde_json::Value::Object(mut this)) = serde_json::to_value(self) else {
+            return false;
+        };
+        let Some(mut registered) = registered.as_object().cloned() else {
+            return false;
+        };
+        this.remove("created_at");
+        registered.remove("created_at");
+        this == registered
+    }
+
+    /// Check the document against the metadata schema, store it, and return the
+    /// reference to register as the `metadataHash`.
+    pub async fn store(&self, store: &dyn MetadataStore) -> Result<StoredRef> {
+        let document = serde_json::to_value(self).context("failed to serialize the metadata document")?;
+        metadata_schema::validate(&document)?;
+        let dir = tempfile::tempdir().context("failed to create a directory for the metadata document")?;
+        let path = dir.path().join("metadata.json");
+        let json = serde_json::to_vec_pretty(&document).context("failed to serialize the metadata document")?;
+        std::fs::write(&path, json).with_context(|| format!("failed to write {}", path.display()))?;
+        store
+            .store(&path, "metadata document")
+            .await
+            .context("failed to store the metadata document")
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::Display;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+/// When to color key lines of stderr output.
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Color when stderr is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Decide once, at startup, whether output is colored.
+pub fn init(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+        }
+    };
+    let _ = ENABLED.set(enabled);
+}
+
+fn paint(code: &str, text: impl Display) -> String {
+    if *ENABLED.get().unwrap_or(&false) {
+        format!("\x1b[{code}m{text}\x1b[0m")
+    } else {
+        text.to_string()
+    }
+}
+
+/// Green: a step or verification succeeded.
+pub fn success(text: impl Display) -> String {
+    paint("32", text)
+}
+
+/// Red: a step or verification failed.
+pub fn failure(text: impl Display) -> String {
+    paint("31", text)
+}
+
+/// Yellow: warnings and skipped steps.
+pub fn warning(text: impl Display) -> String {
+    paint("33", text)
+}
@@ -0,0 +1,155 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::Serialize;
+use std::path::Path;
+
+use crate::commands::update_circuit::UpdateCircuitData;
+use crate::receipt::SCHEMA_VERSION;
+
+/// The `--summary-json` rollup of a batch run: one entry per project published, each
+/// listing every definition it was registered on and how that went.  It is written
+/// whether or not the run succeeded, so a CI job always has one artifact to upload.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub schema_version: u32,
+    pub command: String,
+    pub timestamp: String,
+    /// Definitions registered (or skipped as already registered, or whose calldata was
+    /// printed) across all projects.
+    pub succeeded: usize,
+    pub failed: usize,
+    pub projects: Vec<ProjectSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectSummary {
+    pub project_dir: String,
+    pub outcome: Outcome,
+    /// Why the run stopped, when it failed before recording per-definition outcomes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub cid: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub leaves_cid: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub verifier_address: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub deploy_tx_hash: String,
+    pub definitions: Vec<DefinitionSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DefinitionSummary {
+    pub compliance_definition: String,
+    pub outcome: DefinitionOutcome,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub update_tx_hash: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DefinitionOutcome {
+    Registered,
+    AlreadyRegistered,
+    /// `--print-calldata`: the registration was printed, not sent.
+    CalldataPrinted,
+    /// The registration failed, or the run stopped before its outcome was known (an
+    /// earlier definition may still have been registered; `--continue-on-error`
+    /// records each one).
+    Failed,
+}
+
+impl ProjectSummary {
+    /// Summarize an `update-circuit` run from its receipt data, or from the error it
+    /// stopped with on the definitions it was given.
+    pub fn update_circuit(
+        project_dir: &Path,
+        definitions: &[String],
+        outcome: &Result<UpdateCircuitData>,
+    ) -> Self {
+        match outcome {
+            Ok(data) => {
+                let definitions: Vec<DefinitionSummary> = data
+                    .registrations
+                    .iter()
+                    .map(|r| DefinitionSummary {
+                        compliance_definition: r.compliance_definition.clone(),
+                        outcome: if r.error.is_some() {
+                            DefinitionOutcome::Failed
+                        } else if r.already_registered {
+                            DefinitionOutcome::AlreadyRegistered
+                        } else if r.update_tx_hash.is_empty() {
+                            DefinitionOutcome::CalldataPrinted
+                        } else {
+                            DefinitionOutcome::Registered
+                        },
+                        update_tx_hash: r.update_tx_hash.clone(),
+                        error: r.error.clone(),
+                    })
+                    .collect();
+                Self {
+                    project_dir: data.project_dir.clone(),
+                    outcome: if definitions.iter().any(|d| d.outcome == DefinitionOutcome::Failed) {
+                        Outcome::Failure
+                    } else {
+                        Outcome::Success
+                    },
+                    error: None,
+                    cid: data.cid.clone(),
+                    leaves_cid: data.leaves_cid.clone(),
+                    verifier_address: data.verifier_address.clone(),
+                    deploy_tx_hash: data.deploy_tx_hash.clone(),
+                    definitions,
+                }
+            }
+            Err(e) => Self {
+                project_dir: project_dir.display().to_string(),
+                outcome: Outcome::Failure,
+                error: Some(format!("{:#}", crate::redact::Redacted(e))),
+                cid: String::new(),
+                leaves_cid: String::new(),
+                verifier_address: String::new(),
+                deploy_tx_hash: String::new(),
+                definitions: definitions
+                    .iter()
+                    .map(|d| DefinitionSummary {
+                        compliance_definition: d.clone(),
+                        outcome: DefinitionOutcome::Failed,
+                        update_tx_hash: String::new(),
+                        error: None,
+                    })
+                    .collect(),
+            },
+        }
+    }
+}
+
+/// Write the rollup of `projects` to `path` as pretty-printed JSON.
+pub fn write(path: &Path, command: &str, projects: Vec<ProjectSummary>) -> Result<()> {
+    let (failed, succeeded): (Vec<_>, Vec<_>) = projects
+        .iter()
+        .flat_map(|p| &p.definitions)
+        .partition(|d| d.outcome == DefinitionOutcome::Failed);
+    let summary = Summary {
+        schema_version: SCHEMA_VERSION,
+        command: command.to_string(),
+        timestamp: Utc::now().to_rfc3339(),
+        succeeded: succeeded.len(),
+        failed: failed.len(),
+        projects,
+    };
+    let json = serde_json::to_string_pretty(&summary).context("failed to serialize summary")?;
+    std::fs::write(path, json).with_context(|| format!("failed to write summary to {}", path.display()))?;
+    eprintln!("summary written to {}", path.display());
+    Ok(())
+}
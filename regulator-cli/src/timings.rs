@@ -0,0 +1,77 @@
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use std::fmt;
+use std::time::Instant;
+
+/// Wall-clock durations of each pipeline step, in the order they ran.
+#[derive(Debug, Default)]
+pub struct Timings {
+    steps: Vec<(String, u128)>,
+}
+
+impl Timings {
+    /// Record the time elapsed since `started` under `step`.  Repeated steps accumulate.
+    pub fn record(&mut self, step: &str, started: Instant) {
+        let ms = started.elapsed().as_millis();
+        match self.steps.iter_mut().find(|(name, _)| name == step) {
+            Some((_, total)) => *total += ms,
+            None => self.steps.push((step.to_string(), ms)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Each step with its duration in milliseconds, in the order they ran.
+    pub fn steps(&self) -> impl Iterator<Item = (&str, u128)> {
+        self.steps.iter().map(|(name, ms)| (name.as_str(), *ms))
+    }
+
+    /// Print a per-step breakdown to stderr.
+    pub fn print(&self) {
+        let width = self.steps.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        let total: u128 = self.steps.iter().map(|(_, ms)| ms).sum();
+        eprintln!("\nTimings");
+        for (name, ms) in &self.steps {
+            eprintln!("  {name:<width$}  {ms:>8} ms");
+        }
+        eprintln!("  {:<width$}  {total:>8} ms", "total");
+    }
+}
+
+impl Serialize for Timings {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.steps.len()))?;
+        for (name, ms) in &self.steps {
+            map.serialize_entry(name, ms)?;
+        }
+        map.end()
+    }
+}
+
+/// Reads the `timings_ms` map of a receipt back in file order, which is the order the
+/// steps ran in.
+impl<'de> Deserialize<'de> for Timings {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StepsVisitor;
+
+        impl<'de> Visitor<'de> for StepsVisitor {
+            type Value = Timings;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a map of step names to milliseconds")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Timings, A::Error> {
+                let mut steps = Vec::new();
+                while let Some(entry) = map.next_entry::<String, u128>()? {
+                    steps.push(entry);
+                }
+                Ok(Timings { steps })
+            }
+        }
+
+        deserializer.deserialize_map(StepsVisitor)
+    }
+}
@@ -0,0 +1,65 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::OnceLock;
+
+/// External tools the CLI shells out to.
+#[derive(Clone, Copy)]
+pub enum Tool {
+    Nargo,
+    Bb,
+    Forge,
+    Cast,
+    Solc,
+}
+
+/// Executables given with `--nargo-path`, `--bb-path`, `--forge-path`, `--cast-path`,
+/// and `--solc-path`; `None` runs the tool from PATH.
+#[derive(Default)]
+pub struct ToolPaths {
+    pub nargo: Option<PathBuf>,
+    pub bb: Option<PathBuf>,
+    pub forge: Option<PathBuf>,
+    pub cast: Option<PathBuf>,
+    pub solc: Option<PathBuf>,
+}
+
+static PATHS: OnceLock<ToolPaths> = OnceLock::new();
+
+/// Record the executable overrides once, at startup.
+pub fn init(paths: ToolPaths) {
+    let _ = PATHS.set(paths);
+}
+
+/// The executable given for `tool` on the command line, if any.
+fn override_path(tool: Tool) -> Option<&'static PathBuf> {
+    let paths = PATHS.get()?;
+    match tool {
+        Tool::Nargo => paths.nargo.as_ref(),
+        Tool::Bb => paths.bb.as_ref(),
+        Tool::Forge => paths.forge.as_ref(),
+        Tool::Cast => paths.cast.as_ref(),
+        Tool::Solc => paths.solc.as_ref(),
+    }
+}
+
+/// Whether an executable was given for `tool`, so callers that would otherwise pick
+/// one themselves leave the choice to [`command`].
+pub fn is_overridden(tool: Tool) -> bool {
+    override_path(tool).is_some()
+}
+
+/// A `Command` running `tool`: the overriding executable if one was given, otherwise
+/// the tool's name looked up on PATH.
+pub fn command(tool: Tool) -> Command {
+    let name = match tool {
+        Tool::Nargo => "nargo",
+        Tool::Bb => "bb",
+        Tool::Forge => "forge",
+        Tool::Cast => "cast",
+        Tool::Solc => "solc",
+    };
+    match override_path(tool) {
+        Some(path) => Command::new(path),
+        None => Command::new(name),
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result, bail};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::eth;
+use crate::etherscan;
+
+/// Chains `--watch` is allowed to deploy to: anvil/hardhat and ganache/geth `--dev`.
+const DEV_CHAIN_IDS: &[u64] = &[31337, 1337];
+
+/// Quiet period after the last change before re-publishing, so an editor saving
+/// several files (or writing one in several steps) triggers a single run.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Refuse to watch anything but a local development chain -- every save would
+/// otherwise deploy a verifier and register it for real.
+pub async fn ensure_dev_chain(rpc_url: &str, expected_chain_id: Option<u64>) -> Result<()> {
+    let provider = eth::create_read_provider(rpc_url)?;
+    let chain_id = eth::resolve_chain_id(
+        &provider,
+        expected_chain_id,
+        &crate::progress::TerminalProgress,
+    )
+    .await?;
+    if !DEV_CHAIN_IDS.contains(&chain_id) {
+        bail!(
+            "--watch only runs against local dev chains (chain ID 31337 or 1337), but the RPC is on chain {chain_id} ({})",
+            etherscan::network_name(chain_id)
+        );
+    }
+    Ok(())
+}
+
+/// Watches a Noir project's `src/` directory for changes to `.nr` files.
+pub struct CircuitWatcher {
+    src_dir: PathBuf,
+    events: mpsc::UnboundedReceiver<Event>,
+    // Dropping the watcher stops the events, so it lives as long as the receiver.
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl CircuitWatcher {
+    pub fn new(project_dir: &Path) -> Result<Self> {
+        let src_dir = project_dir.join("src");
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                let _ = tx.send(event);
+            }
+        })
+        .context("failed to start file watcher")?;
+        watcher
+            .watch(&src_dir, RecursiveMode::Recursive)
+            .with_context(|| format!("failed to watch {}", src_dir.display()))?;
+        Ok(Self {
+            src_dir,
+            events,
+            _watcher: watcher,
+        })
+    }
+
+    pub fn src_dir(&self) -> &Path {
+        &self.src_dir
+    }
+
+    /// Wait for a `.nr` file to change, then for the changes to settle, and return the
+    /// files that changed.
+    pub async fn changed(&mut self) -> Result<Vec<PathBuf>> {
+        let mut changed = Vec::new();
+        while changed.is_empty() {
+            let event = self.events.recv().await.context("file watcher stopped")?;
+            collect_circuit_files(&event, &mut changed);
+        }
+        while let Ok(Some(event)) = tokio::time::timeout(DEBOUNCE, self.events.recv()).await {
+            collect_circuit_files(&event, &mut changed);
+        }
+        Ok(changed)
+    }
+}
+
+fn collect_circuit_files(event: &Event, changed: &mut Vec<PathBuf>) {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return;
+    }
+    for path in &event.paths {
+        if path.extension().is_some_and(|ext| ext == "nr") && !changed.contains(path) {
+            changed.push(path.clone());
+        }
+    }
+}
@@ -2,7 +2,7 @@ use assert_cmd::cargo::cargo_bin_cmd;
 use assert_cmd::Command;
 use predicates::prelude::*;
 use std::path::{Path, PathBuf};
-use wiremock::matchers::{method, path};
+use wiremock::matchers::{body_partial_json, body_string_contains, method, path, query_param};
 use wiremock::{Mock, MockServer, ResponseTemplate};
 
 fn cmd() -> Command {
@@ -35,6 +35,10 @@ const PUBLISH_CHAIN_ARGS: [&str; 6] = [
     "0x0000000000000000000000000000000000000001",
 ];
 
+const BYTES32_ZERO: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+/// A well-formed CIDv1 (raw sha2-256 of `[]`) for flags that are checked as CIDs.
+const TEST_LEAVES_CID: &str = "bafkreicpkpg2ddblvigagvf3l6nd5s7f5ujkwtmocg5iopbpcelbeavziu";
+
 // -- Help & subcommand discovery --
 
 #[test]
@@ -109,6 +113,504 @@ fn new_compliance_definition_requires_rpc_url() {
         .stderr(predicate::str::contains("--rpc-url"));
 }
 
+#[tokio::test]
+async fn new_compliance_definition_strict_rejects_expired_window() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_blockNumber" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": "0x100",
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "window_circuit", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "window",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--t-end",
+            "5",
+            "--strict",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "t_end 5 is not after the current block 256",
+        ));
+}
+
+#[tokio::test]
+async fn new_compliance_definition_skip_build_requires_artifact() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "prebuilt_circuit", "fn main() {}\n");
+    let contracts = dir.path().join("contracts");
+    std::fs::create_dir(&contracts).unwrap();
+
+    cmd()
+        .args([
+            "--skip-build",
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "prebuilt",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-dir",
+            contracts.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("did you forget to build?"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn new_compliance_definition_passes_optimizer_runs_to_forge() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "optimized_circuit", "fn main() {}\n");
+    let contracts = dir.path().join("contracts");
+    std::fs::create_dir(&contracts).unwrap();
+
+    // A stand-in forge that echoes its arguments and fails, ending the run.
+    let bin = dir.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let forge = bin.join("forge");
+    std::fs::write(&forge, "#!/bin/sh\necho \"forge $*\" >&2\nexit 1\n").unwrap();
+    std::fs::set_permissions(&forge, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+
+    cmd()
+        .env("PATH", path)
+        .args([
+            "--optimizer-runs",
+            "10000",
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "optimized",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-dir",
+            contracts.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--optimize --optimizer-runs 10000"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn new_compliance_definition_summarizes_forge_errors() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "broken_contracts", "fn main() {}\n");
+    let contracts = dir.path().join("contracts");
+    std::fs::create_dir(&contracts).unwrap();
+
+    // A forge that buries one compiler error among warnings and progress output.
+    let path = fake_tools_path(
+        dir.path(),
+        &[(
+            "forge",
+            r#"#!/bin/sh
+echo "[] Compiling 3 files with Solc 0.8.27"
+i=0; while [ $i -lt 50 ]; do echo "Warning (2072): Unused local variable."; echo "  --> lib/Noise.sol:$i:1:"; echo; i=$((i+1)); done
+echo "Error (2314): Expected ';' but got '}'" >&2
+echo "  --> src/ComplianceDefinition.sol:42:5:" >&2
+echo "Error: Compiler run failed:" >&2
+exit 1
+"#,
+        )],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args([
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "broken",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-dir",
+            contracts.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Error (2314): Expected ';' but got '}'\n  --> src/ComplianceDefinition.sol:42:5:\nError: Compiler run failed:",
+        ))
+        .stderr(predicate::str::contains("Unused local variable").not())
+        .stderr(predicate::str::contains(".last-forge-error.log"));
+
+    let log = std::fs::read_to_string(contracts.join(".last-forge-error.log")).unwrap();
+    assert!(log.contains("Unused local variable"));
+    assert!(log.contains("Expected ';'"));
+}
+
+#[tokio::test]
+async fn new_compliance_definition_salt_predicts_address_and_requires_factory() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": "0x",
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "salted_circuit", "fn main() {}\n");
+    let contracts = dir.path().join("contracts");
+    let artifact_dir = contracts.join("out/ComplianceDefinition.sol");
+    std::fs::create_dir_all(&artifact_dir).unwrap();
+    std::fs::write(
+        artifact_dir.join("ComplianceDefinition.json"),
+        r#"{"bytecode": {"object": "0x6080"}}"#,
+    )
+    .unwrap();
+
+    cmd()
+        .args([
+            "--skip-build",
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "salted",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-dir",
+            contracts.to_str().unwrap(),
+            "--compliance-definition-salt",
+            BYTES32_ZERO,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Predicted address: 0x"))
+        .stderr(predicate::str::contains(
+            "no CREATE2 factory at 0x4e59b44847b379578588920cA78FbF26c0B4956C",
+        ));
+}
+
+#[tokio::test]
+async fn new_compliance_definition_factory_must_exist() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": "0x",
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "factory_circuit", "fn main() {}\n");
+
+    // The factory is checked before anything is compiled, so no tools are needed.
+    cmd()
+        .args([
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "bundled",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--factory",
+            "0x00000000000000000000000000000000000000fa",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no contract at --factory 0x00000000000000000000000000000000000000fa",
+        ));
+}
+
+#[tokio::test]
+async fn register_refuses_address_without_verifier_code() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": "0x",
+        })))
+        .mount(&rpc)
+        .await;
+
+    cmd()
+        .args([
+            "register",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--verifier",
+            "0x00000000000000000000000000000000000000fe",
+            "--circuit-cid",
+            TEST_LEAVES_CID,
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no contract code at verifier 0x00000000000000000000000000000000000000fe",
+        ));
+}
+
+#[test]
+fn register_checks_cid_before_sending() {
+    cmd()
+        .args([
+            "register",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--verifier",
+            "0x00000000000000000000000000000000000000fe",
+            "--circuit-cid",
+            "bafy",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--circuit-cid \"bafy\" is not a valid CID"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn register_ledger_sends_through_cast() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    for (rpc_method, result) in [
+        ("eth_getCode", "0x6080".to_string()),
+        ("eth_call", format!("0x{:064x}", 1)),
+    ] {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": result,
+            })))
+            .mount(&rpc)
+            .await;
+    }
+
+    // A stand-in cast that records its arguments and reports a mined transaction.
+    let dir = tempfile::tempdir().unwrap();
+    let calls = dir.path().join("cast-calls");
+    let path = fake_tools_path(
+        dir.path(),
+        &[(
+            "cast",
+            &format!(
+                "#!/bin/sh\necho \"$*\" >> '{}'\necho '{{\"transactionHash\":\"0x{}\",\"status\":\"0x1\"}}'\n",
+                calls.display(),
+                "ab".repeat(32)
+            ),
+        )],
+    );
+
+    let register = |ledger_args: &[&str]| {
+        let mut register = cmd();
+        register.env("PATH", &path).env_remove("PRIVATE_KEY").args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "register",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--verifier",
+            "0x00000000000000000000000000000000000000fe",
+            "--circuit-cid",
+            TEST_LEAVES_CID,
+            "--rpc-url",
+            &rpc.uri(),
+        ]);
+        register.args(ledger_args);
+        register
+    };
+
+    register(&[]).assert().failure().stderr(predicate::str::contains("--private-key"));
+
+    register(&["--ledger"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("update_tx_hash=0x{}", "ab".repeat(32))));
+    let calls = std::fs::read_to_string(&calls).unwrap();
+    assert!(
+        calls.starts_with(&format!(
+            "send --ledger --json --rpc-url {} 0x0000000000000000000000000000000000000001 0x",
+            rpc.uri()
+        )),
+        "{calls}"
+    );
+
+    // Only register can send through cast.
+    cmd()
+        .env_remove("PRIVATE_KEY")
+        .args([
+            "update-params",
+            "--ledger",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--ledger is only supported by `register`"));
+}
+
+#[test]
+fn update_circuit_deploy_verifier_only_plans_no_registration() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "deploy_only", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--deploy-verifier-only",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("without registering (--deploy-verifier-only)"))
+        .stdout(predicate::str::contains("to register the verifier").not());
+}
+
+#[test]
+fn update_circuit_plans_concurrent_registrations() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "concurrent", "fn main() {}\n");
+    let args = |concurrency: &'static str| {
+        [
+            "--print-plan",
+            "--concurrency",
+            concurrency,
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000002",
+        ]
+        .map(str::to_string)
+    };
+
+    cmd()
+        .args(args("3"))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "call updateCircuit on each definition, up to 3 at once (--concurrency)",
+        ));
+
+    cmd()
+        .args(args("0"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value '0' for '--concurrency <N>'"));
+}
+
+#[test]
+fn new_compliance_definition_factory_conflicts_with_salt() {
+    cmd()
+        .args([
+            "new-compliance-definition",
+            "--circuit-dir",
+            ".",
+            "--name",
+            "bundled",
+            "--rpc-url",
+            "http://localhost:8545",
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--factory",
+            "0x00000000000000000000000000000000000000fa",
+            "--compliance-definition-salt",
+            BYTES32_ZERO,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
 #[test]
 fn new_compliance_definition_requires_private_key() {
     let dir = tempfile::tempdir().unwrap();
@@ -164,80 +666,3307 @@ fn new_compliance_definition_rejects_invalid_contract_dir() {
         .stderr(predicate::str::contains("forge create failed"));
 }
 
-// -- Publish command --
-
 #[test]
-fn publish_requires_path_argument() {
+fn verifier_key_param_conflicts_with_header() {
     cmd()
-        .args(["publish", "--rpc-url", "http://localhost:8545", "--private-key", "0xdead", "--compliance-definition", "0x1"])
+        .args([
+            "--verifier-key-param",
+            "key",
+            "--verifier-key-header",
+            "X-API-Key",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://localhost:8545",
+            "--private-key",
+            "0xdeadbeef",
+            "--merkle-root",
+            "0x00",
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("DIR"));
+        .stderr(predicate::str::contains("cannot be used with"));
 }
 
+// -- Update circuit command --
+
 #[test]
-fn publish_requires_rpc_url() {
+fn definition_abi_rejects_overloaded_update_function() {
     let dir = tempfile::tempdir().unwrap();
+    let abi = dir.path().join("abi.json");
+    std::fs::write(
+        &abi,
+        serde_json::json!([
+            {
+                "type": "function",
+                "name": "publish",
+                "inputs": [{ "name": "verifier", "type": "address" }],
+                "outputs": [],
+                "stateMutability": "nonpayable",
+            },
+            {
+                "type": "function",
+                "name": "publish",
+                "inputs": [
+                    { "name": "verifier", "type": "address" },
+                    { "name": "merkleRoot", "type": "bytes32" },
+                ],
+                "outputs": [],
+                "stateMutability": "nonpayable",
+            },
+        ])
+        .to_string(),
+    )
+    .unwrap();
+
     cmd()
-        .current_dir(dir.path())
-        .env_remove("RPC_URL")
-        .args(["publish", "--private-key", "0xdead", "--compliance-definition", "0x1", "/tmp/x"])
+        .args([
+            "--definition-abi",
+            abi.to_str().unwrap(),
+            "--update-function",
+            "publish",
+            "update-circuit",
+            "--circuit-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .args(PUBLISH_CHAIN_ARGS)
         .assert()
         .failure()
-        .stderr(predicate::str::contains("--rpc-url"));
+        .stderr(predicate::str::contains(
+            "publish is overloaded",
+        ))
+        .stderr(predicate::str::contains("publish(address,bytes32)"));
 }
 
 #[test]
-fn publish_requires_private_key() {
+fn update_circuit_rejects_any_invalid_compliance_definition() {
     let dir = tempfile::tempdir().unwrap();
     cmd()
-        .current_dir(dir.path())
-        .env_remove("PRIVATE_KEY")
-        .args(["publish", "--rpc-url", "http://localhost:8545", "--compliance-definition", "0x1", "/tmp/x"])
+        .args([
+            "update-circuit",
+            "--circuit-dir",
+            dir.path().to_str().unwrap(),
+            "--rpc-url",
+            "http://localhost:8545",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--compliance-definition",
+            "not-an-address",
+        ])
         .assert()
         .failure()
-        .stderr(predicate::str::contains("--private-key"));
+        .stderr(predicate::str::contains(
+            "invalid compliance definition address: not-an-address",
+        ));
 }
 
+#[cfg(unix)]
 #[test]
-fn publish_requires_compliance_definition() {
+fn update_circuit_retries_transient_compile_failure() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "retry_circuit", "fn main() {}\n");
+
+    // A stand-in nargo whose first compile fails with a lock error, then succeeds.
+    let bin = dir.path().join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    let nargo = bin.join("nargo");
+    std::fs::write(
+        &nargo,
+        r#"#!/bin/sh
+if [ "$1" = compile ]; then
+  if [ ! -f .attempted ]; then
+    touch .attempted
+    echo "error: Resource temporarily unavailable (os error 11)" >&2
+    exit 1
+  fi
+  mkdir -p target && echo '{}' > target/retry_circuit.json
+fi
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&nargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default());
+
     cmd()
-        .args(["publish", "--rpc-url", "http://localhost:8545", "--private-key", "0xdead", "/tmp/x"])
+        .env("PATH", path)
+        .args([
+            "--nargo-compile-retries",
+            "1",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+        ])
+        .args(PUBLISH_CHAIN_ARGS)
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("--compliance-definition"));
+        .stderr(predicate::str::contains("transient error (Resource temporarily unavailable)"))
+        .stderr(predicate::str::contains("circuit compiled successfully"));
 }
 
 #[test]
-fn publish_rejects_nonexistent_directory() {
-    let mut args = vec!["publish"];
-    args.extend_from_slice(&PUBLISH_CHAIN_ARGS);
-    args.push("/tmp/nonexistent-noir-project");
+fn update_circuit_reports_malformed_nargo_toml() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("Nargo.toml"), "[package\nname = \"x\"\n").unwrap();
 
     cmd()
-        .args(&args)
+        .args(["update-circuit", "--circuit-dir", dir.path().to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
         .assert()
         .failure()
-        .stderr(predicate::str::contains("not a directory"));
+        .stderr(predicate::str::contains("Nargo.toml is not valid TOML"));
 }
 
 #[test]
-fn publish_rejects_directory_without_nargo_toml() {
+fn update_circuit_reports_nargo_toml_without_package() {
     let dir = tempfile::tempdir().unwrap();
-    let mut args = vec!["publish"];
-    args.extend_from_slice(&PUBLISH_CHAIN_ARGS);
-    args.push(dir.path().to_str().unwrap());
+    std::fs::write(dir.path().join("Nargo.toml"), "[dependencies]\n").unwrap();
 
     cmd()
-        .args(&args)
+        .args(["update-circuit", "--circuit-dir", dir.path().to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
         .assert()
         .failure()
-        .stderr(predicate::str::contains("no Nargo.toml found"));
+        .stderr(predicate::str::contains("Nargo.toml has no [package] section"));
 }
 
-#[test]
-fn publish_rejects_invalid_circuit() {
-    let dir = tempfile::tempdir().unwrap();
+#[tokio::test]
+async fn update_circuit_watch_refuses_non_dev_chain() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "watched_circuit", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "update-circuit",
+            "--watch",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--watch only runs against local dev chains (chain ID 31337 or 1337), but the RPC is on chain 1",
+        ));
+}
+
+/// Write executable stand-in scripts for external tools into `<dir>/bin` and return a
+/// `PATH` that finds them first.
+#[cfg(unix)]
+fn fake_tools_path(dir: &Path, scripts: &[(&str, &str)]) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let bin = dir.join("bin");
+    std::fs::create_dir(&bin).unwrap();
+    for (name, script) in scripts {
+        let path = bin.join(name);
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+    format!("{}:{}", bin.display(), std::env::var("PATH").unwrap_or_default())
+}
+
+#[cfg(unix)]
+#[test]
+fn update_circuit_rejects_old_bb() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "old_bb_circuit", "fn main() {}\n");
+
+    // Stand-ins for a working nargo and a bb that predates --oracle_hash keccak.
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                "#!/bin/sh\nif [ \"$1\" = compile ]; then mkdir -p target && echo '{}' > target/old_bb_circuit.json; fi\n",
+            ),
+            ("bb", "#!/bin/sh\necho 0.61.0\n"),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("bb 0.61.0 is too old: at least 0.82.0 is required"));
+}
+
+#[cfg(unix)]
+#[test]
+fn update_circuit_rejects_compliance_toml_not_matching_abi() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "documented_circuit", "fn main() {}\n");
+    std::fs::write(
+        project.join("compliance.toml"),
+        "[[inputs]]\nname = \"root\"\ndescription = \"Sanctions root\"\nmerkle_root = true\n\n\
+         [[inputs]]\nname = \"block\"\ndescription = \"Block height\"\n",
+    )
+    .unwrap();
+
+    // The compiled ABI has public `root` and `timestamp`, and a private `secret`.
+    let abi = r#"{"abi":{"parameters":[
+        {"name":"root","type":{"kind":"field"},"visibility":"public"},
+        {"name":"timestamp","type":{"kind":"integer","sign":"unsigned","width":64},"visibility":"public"},
+        {"name":"secret","type":{"kind":"field"},"visibility":"private"}]}}"#;
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                &format!(
+                    "#!/bin/sh\nif [ \"$1\" = compile ]; then mkdir -p target && echo '{}' > target/documented_circuit.json; fi\n",
+                    abi.replace('\n', "")
+                ),
+            ),
+            ("bb", "#!/bin/sh\necho 0.82.0\n"),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "compliance.toml does not match the compiled circuit: public inputs not documented: timestamp; not public inputs of the circuit: block",
+        ));
+}
+
+#[test]
+fn update_circuit_writes_summary_when_run_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "summarized_circuit", "fn main() {}\n");
+    let summary = dir.path().join("summary.json");
+
+    // No nargo on PATH: the run fails before any definition is registered.
+    cmd()
+        .env("PATH", "")
+        .args([
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://localhost:8545",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000002",
+            "--summary-json",
+            summary.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("summary written to"));
+
+    let summary: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&summary).unwrap()).unwrap();
+    assert_eq!(summary["command"], "update-circuit");
+    assert_eq!(summary["succeeded"], 0);
+    assert_eq!(summary["failed"], 2);
+    let project = &summary["projects"][0];
+    assert_eq!(project["outcome"], "failure");
+    assert!(project["error"].as_str().unwrap().contains("nargo compile"));
+    assert_eq!(project["definitions"][1]["compliance_definition"], "0x0000000000000000000000000000000000000002");
+    assert_eq!(project["definitions"][1]["outcome"], "failed");
+}
+
+#[test]
+fn update_circuit_simulate_proof_gas_requires_prover_toml() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "unproven_circuit", "fn main() {}\n");
+
+    // Checked before any tool runs or anything is deployed.
+    cmd()
+        .env("PATH", "")
+        .args(["--simulate-proof-gas", "update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--simulate-proof-gas proves the circuit with"))
+        .stderr(predicate::str::contains("Prover.toml, which does not exist"));
+}
+
+#[cfg(unix)]
+#[test]
+fn update_circuit_simulate_proof_gas_reports_unsolvable_witness() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "proven_circuit", "fn main(x: Field) { assert(x == 1); }\n");
+    std::fs::write(project.join("Prover.toml"), "x = \"2\"\n").unwrap();
+
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                "#!/bin/sh\ncase \"$1\" in\n  compile) mkdir -p target && echo '{}' > target/proven_circuit.json ;;\n  execute) echo 'error: Failed constraint' >&2; exit 1 ;;\nesac\n",
+            ),
+            (
+                "bb",
+                "#!/bin/sh\ncase \"$1\" in\n  --version) echo 0.82.0 ;;\n  write_vk) touch \"$5/vk\" ;;\n  write_solidity_verifier) touch \"$5\" ;;\nesac\n",
+            ),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["--simulate-proof-gas", "update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("proving with Prover.toml (--simulate-proof-gas)"))
+        .stderr(predicate::str::contains("are the values in Prover.toml valid inputs?"))
+        .stderr(predicate::str::contains("Failed constraint"));
+}
+
+#[cfg(unix)]
+#[test]
+fn bb_binary_overrides_bb_on_path() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "pinned_bb_circuit", "fn main() {}\n");
+
+    // The bb on PATH is current; the pinned one is too old, so the version error shows
+    // which executable ran.
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                "#!/bin/sh\nif [ \"$1\" = compile ]; then mkdir -p target && echo '{}' > target/pinned_bb_circuit.json; fi\n",
+            ),
+            ("bb", "#!/bin/sh\necho 0.82.0\n"),
+            ("bb-0.61", "#!/bin/sh\necho 0.61.0\n"),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .env("BB_BINARY", dir.path().join("bin/bb-0.61"))
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("bb 0.61.0 is too old"));
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_reads_circuit_from_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let out_dir = dir.path().join("out");
+
+    // Stand-ins for nargo and bb.  The "bytecode" is a copy of main.nr, so the test
+    // can see that the piped source made it into the scaffolded project.
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                r#"#!/bin/sh
+if [ "$1" = compile ]; then
+  name=$(sed -n 's/^name = "\(.*\)"/\1/p' Nargo.toml)
+  mkdir -p target && cp src/main.nr "target/$name.json"
+fi
+"#,
+            ),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  write_vk) echo vk > "$5/vk" ;;
+  write_solidity_verifier) echo "contract HonkVerifier {}" > "$5" ;;
+esac
+"#,
+            ),
+        ],
+    );
+
+    let source = "fn main(x: Field) { assert(x != 0); }\n";
+    cmd()
+        .env("PATH", path)
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "compile",
+            "--stdin-circuit",
+            "--project-name",
+            "piped_circuit",
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+        ])
+        .write_stdin(source)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("verifier_path="));
+
+    assert_eq!(
+        std::fs::read_to_string(out_dir.join("piped_circuit.json")).unwrap(),
+        source
+    );
+    assert!(out_dir.join("vk").exists());
+    assert!(out_dir.join("Verifier.sol").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_records_circuit_dependencies() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "with_deps", "fn main() {}\n");
+    let helpers = create_nargo_project(dir.path(), "helpers", "fn helper() {}\n");
+    std::fs::write(
+        helpers.join("Nargo.toml"),
+        "[package]\nname = \"helpers\"\ntype = \"lib\"\n\n[dependencies]\nsha = { git = \"https://github.com/noir-lang/sha256\", tag = \"v0.1.0\" }\n",
+    )
+    .unwrap();
+    std::fs::write(
+        project.join("Nargo.toml"),
+        "[package]\nname = \"with_deps\"\ntype = \"bin\"\n\n[dependencies]\nhelpers = { path = \"../helpers\" }\n",
+    )
+    .unwrap();
+
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            ("nargo", "#!/bin/sh\nmkdir -p target && echo '{}' > target/with_deps.json\n"),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  write_vk) echo vk > "$5/vk" ;;
+  write_solidity_verifier) echo "contract HonkVerifier {}" > "$5" ;;
+esac
+"#,
+            ),
+        ],
+    );
+    let compile = |receipts: &Path| {
+        cmd()
+            .env("PATH", &path)
+            .args([
+                "--receipts-dir",
+                receipts.to_str().unwrap(),
+                "compile",
+                "--circuit-dir",
+                project.to_str().unwrap(),
+                "--out-dir",
+                dir.path().join("out").to_str().unwrap(),
+            ])
+            .assert()
+            .success()
+    };
+
+    // Without a Nargo.lock the git dependency is only as pinned as its tag.
+    compile(&dir.path().join("unlocked"))
+        .stderr(predicate::str::contains("has dependencies but no Nargo.lock"))
+        .stderr(predicate::str::contains("dependency sha: git+https://github.com/noir-lang/sha256 @ v0.1.0"));
+
+    std::fs::write(
+        project.join("Nargo.lock"),
+        "[[package]]\nname = \"sha\"\ncommit = \"4d7b7a1c\"\n",
+    )
+    .unwrap();
+    let receipts = dir.path().join("locked");
+    compile(&receipts).stderr(predicate::str::contains("no Nargo.lock").not());
+
+    let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(
+        receipt["data"]["dependencies"],
+        serde_json::json!([
+            { "name": "helpers", "source": "path+../helpers" },
+            {
+                "name": "sha",
+                "source": "git+https://github.com/noir-lang/sha256",
+                "requested": "v0.1.0",
+                "resolved": "4d7b7a1c",
+            },
+        ])
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn compile_compresses_artifacts_into_one_archive() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "bundled", "fn main() {}\n");
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            ("nargo", "#!/bin/sh\nmkdir -p target && echo '{}' > target/bundled.json\n"),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  write_vk) echo vk > "$5/vk" ;;
+  write_solidity_verifier) echo "contract HonkVerifier {}" > "$5" ;;
+esac
+"#,
+            ),
+        ],
+    );
+    let compile = |name: &str| {
+        let receipts = dir.path().join(format!("{name}-receipts"));
+        let out_dir = dir.path().join(name);
+        cmd()
+            .env("PATH", &path)
+            .args([
+                "--receipts-dir",
+                receipts.to_str().unwrap(),
+                "compile",
+                "--circuit-dir",
+                project.to_str().unwrap(),
+                "--out-dir",
+                out_dir.to_str().unwrap(),
+                "--compress-artifacts",
+            ])
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("artifacts_sha256="));
+        let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+        let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+        (out_dir, receipt["data"].clone())
+    };
+
+    let (out_dir, data) = compile("first");
+    let entries: Vec<_> = std::fs::read_dir(&out_dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    assert_eq!(entries, ["artifacts.tar.zst"]);
+    let archive = std::fs::read(out_dir.join("artifacts.tar.zst")).unwrap();
+    assert_eq!(archive[..4], [0x28, 0xb5, 0x2f, 0xfd], "not a zstd frame");
+    assert!(data.get("vk_path").is_none());
+    assert_eq!(
+        data["artifacts_archive"]["files"],
+        serde_json::json!(["bundled.json", "vk", "Verifier.sol"])
+    );
+
+    // The same artifacts produce the same archive.
+    let (_, again) = compile("second");
+    assert_eq!(data["artifacts_archive"]["sha256"], again["artifacts_archive"]["sha256"]);
+}
+
+#[test]
+fn compile_rejects_invalid_project_name() {
+    let dir = tempfile::tempdir().unwrap();
+    cmd()
+        .args([
+            "compile",
+            "--stdin-circuit",
+            "--project-name",
+            "my-circuit",
+            "--out-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .write_stdin("fn main() {}\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid project name \"my-circuit\""));
+}
+
+#[cfg(unix)]
+#[test]
+fn update_circuit_explains_nargo_bb_format_mismatch() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "mismatch_circuit", "fn main() {}\n");
+
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                r#"#!/bin/sh
+if [ "$1" = compile ]; then
+  mkdir -p target
+  echo '{"noir_version":"1.0.0-beta.9+abcdef","bytecode":"H4sI"}' > target/mismatch_circuit.json
+fi
+"#,
+            ),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  *) echo "Failed to deserialize circuit: unknown variant" >&2; exit 1 ;;
+esac
+"#,
+            ),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "this circuit was compiled with nargo 1.0.0-beta.9, but bb 0.82.0 could not read its bytecode",
+        ))
+        .stderr(predicate::str::contains("bbup --noir-version 1.0.0-beta.9"));
+}
+
+#[cfg(unix)]
+#[test]
+fn update_circuit_rejects_source_edited_during_compile() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "edited_circuit", "fn main() {}\n");
+
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                r#"#!/bin/sh
+if [ "$1" = compile ]; then
+  printf '// edited\n' >> src/main.nr
+  mkdir -p target
+  printf '{"file_map":{"0":{"path":"%s/src/main.nr","source":"fn main() {}\\n// edited\\n"}}}' "$(pwd -P)" > target/edited_circuit.json
+fi
+"#,
+            ),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  *) touch "$5" "$5/vk" 2>/dev/null; true ;;
+esac
+"#,
+            ),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("than the one read before compiling"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn update_circuit_reports_partial_project_upload() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .and(query_param("recursive", "true"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(concat!(
+            "{\"Name\":\"partial_upload/Nargo.toml\",\"Hash\":\"QmNargoToml\",\"Size\":\"90\"}\n",
+            "{\"Name\":\"partial_upload/src/main.nr\",\"Hash\":\"QmMainNr\",\"Size\":\"21\"}\n",
+            "{\"Message\":\"blockstore: no space left on device\",\"Code\":0,\"Type\":\"error\"}\n",
+        )))
+        .with_priority(1)
+        .mount(&ipfs)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "partial_upload.json",
+            "Hash": "QmCircuit",
+            "Size": "100",
+        })))
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "partial_upload", "fn main() {}\n");
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                r#"#!/bin/sh
+if [ "$1" = compile ]; then
+  mkdir -p target
+  printf '{"file_map":{"0":{"path":"%s/src/main.nr","source":"fn main() {}\\n"}}}' "$(pwd -P)" > target/partial_upload.json
+fi
+"#,
+            ),
+            (
+                "bb",
+                r#"#!/bin/sh
+case "$1" in
+  --version) echo 0.82.0 ;;
+  *) touch "$5" "$5/vk" 2>/dev/null; true ;;
+esac
+"#,
+            ),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["--ipfs-rpc-url", &ipfs.uri(), "--upload-project"])
+        .args(["update-circuit", "--circuit-dir", project.to_str().unwrap()])
+        .args(PUBLISH_CHAIN_ARGS)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed after adding 2 of 2 files"))
+        .stderr(predicate::str::contains("partial_upload/Nargo.toml  QmNargoToml"))
+        .stderr(predicate::str::contains("partial_upload/src/main.nr  QmMainNr"))
+        .stderr(predicate::str::contains("no space left on device"));
+}
+
+// -- Update params command --
+
+/// A throwaway, well-formed private key for tests that need one to parse.
+const TEST_PRIVATE_KEY: &str = "0x0123456789012345678901234567890123456789012345678901234567890123";
+
+/// Mount a JSON-RPC mock that answers `eth_chainId` with the given chain.
+async fn mock_chain_id(server: &MockServer, chain_id: u64) {
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_chainId" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": format!("0x{chain_id:x}"),
+        })))
+        .mount(server)
+        .await;
+}
+
+#[tokio::test]
+async fn update_params_reads_definition_from_receipt() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let receipt = dir.path().join("new-compliance-definition.json");
+    std::fs::write(
+        &receipt,
+        serde_json::json!({
+            "schema_version": 1,
+            "command": "new-compliance-definition",
+            "timestamp": "2026-01-01T00:00:00Z",
+            "data": {
+                "compliance_definition_address": "0x0000000000000000000000000000000000000001",
+                "chain_id": 5,
+                "rpc_url": rpc.uri(),
+            },
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    // The RPC and chain ID both come from the receipt.
+    cmd()
+        .env_remove("RPC_URL")
+        .args([
+            "update-params",
+            "--from-receipt",
+            receipt.to_str().unwrap(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("RPC is on chain 1, but --chain-id 5 was expected"));
+}
+
+#[test]
+fn update_params_checks_leaves_cid_before_sending() {
+    let update_params = |leaves_cid: &str, allow_any: bool| {
+        let mut cmd = cmd();
+        cmd.args(["--retries", "0"]);
+        if allow_any {
+            cmd.arg("--allow-any-metadata");
+        }
+        cmd.args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            leaves_cid,
+        ]);
+        cmd.assert().failure()
+    };
+
+    // Rejected before the RPC is contacted.
+    update_params("bafy", false)
+        .stderr(predicate::str::contains(r#"--leaves-cid "bafy" is not a valid CID"#))
+        .stderr(predicate::str::contains("--allow-any-metadata"))
+        .stderr(predicate::str::contains("chain ID").not());
+    update_params("", false).stderr(predicate::str::contains("empty CID"));
+
+    // Valid CIDv0s and the bypass both get as far as the (unreachable) RPC.
+    update_params("QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn", false)
+        .stderr(predicate::str::contains("not a valid CID").not());
+    update_params("bafy", true).stderr(predicate::str::contains("not a valid CID").not());
+}
+
+#[test]
+fn update_params_rejects_receipt_from_other_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let receipt = dir.path().join("update-params.json");
+    std::fs::write(
+        &receipt,
+        serde_json::json!({
+            "schema_version": 1,
+            "command": "update-params",
+            "timestamp": "2026-01-01T00:00:00Z",
+            "data": {},
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    cmd()
+        .env_remove("RPC_URL")
+        .args([
+            "update-params",
+            "--from-receipt",
+            receipt.to_str().unwrap(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "was written by `update-params`, expected a `new-compliance-definition` receipt",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_rejects_chain_id_mismatch() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--chain-id 11155111 was expected"));
+}
+
+#[tokio::test]
+async fn update_params_fails_over_to_rpc_fallback() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    // Nothing listens on port 1, so the first endpoint fails and the fallback is used.
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--rpc-fallback",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--chain-id",
+            "5",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("RPC endpoint http://127.0.0.1:1 failed"))
+        .stderr(predicate::str::contains(format!("using RPC endpoint {} (chain 1)", rpc.uri())))
+        .stderr(predicate::str::contains("RPC is on chain 1, but --chain-id 5 was expected"));
+}
+
+#[tokio::test]
+async fn deadline_stops_hung_command() {
+    let rpc = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(30)))
+        .mount(&rpc)
+        .await;
+    let receipts = tempfile::tempdir().unwrap();
+
+    cmd()
+        .args([
+            "--deadline",
+            "1",
+            "--receipts-dir",
+            receipts.path().to_str().unwrap(),
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .code(124)
+        .stderr(predicate::str::contains("command exceeded 1s deadline"));
+
+    let receipt = std::fs::read_dir(receipts.path()).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(receipt["command"], "update-params");
+    assert_eq!(receipt["data"]["interrupted"], true);
+}
+
+#[tokio::test]
+async fn deadline_receipt_lists_completed_uploads() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "leaves.json",
+            "Hash": "bafyleaves",
+            "Size": "2",
+        })))
+        .mount(&ipfs)
+        .await;
+    let rpc = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(30)))
+        .mount(&rpc)
+        .await;
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "[]").unwrap();
+    let receipts = dir.path().join("receipts");
+
+    cmd()
+        .args([
+            "--deadline",
+            "2",
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .timeout(std::time::Duration::from_secs(20))
+        .assert()
+        .code(124);
+
+    let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(receipt["data"]["interrupted"], true);
+    assert_eq!(
+        receipt["data"]["completed"],
+        serde_json::json!([
+            { "step": "compliance leaves upload", "kind": "cid", "value": "bafyleaves" },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn update_params_uses_gas_oracle_fees() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/fees"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "maxFeePerGas": "30000000000",
+            "maxPriorityFeePerGas": 1500000000u64,
+        })))
+        .expect(1)
+        .mount(&oracle)
+        .await;
+
+    // The RPC mock cannot accept the transaction; we only check the fees were picked up.
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &format!("{}/fees", oracle.uri()),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "using gas oracle fees: maxFeePerGas=30000000000 maxPriorityFeePerGas=1500000000",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_raises_fees_to_min_gas_price() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/fees"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "maxFeePerGas": 1000,
+            "maxPriorityFeePerGas": 100,
+        })))
+        .mount(&oracle)
+        .await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &format!("{}/fees", oracle.uri()),
+            "--min-gas-price",
+            "0.01",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "raising maxFeePerGas from 1000 to the --min-gas-price floor of 10000000 wei",
+        ));
+}
+
+/// An `eth_call` mock answering calls whose body contains `needle` with `result`.
+fn eth_call_mock(needle: &str, result: &str) -> Mock {
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+        .and(body_string_contains(needle))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": result,
+        })))
+}
+
+/// Mount `eth_call` mocks answering an ENS lookup: the registry's `resolver(bytes32)`
+/// and the resolver's `addr(bytes32)` both return `addr`, and the resolver does not
+/// support wildcard resolution.
+async fn mock_ens(server: &MockServer, addr: &str) {
+    let word = format!("0x{:0>64}", addr.trim_start_matches("0x"));
+    for selector in ["0178b8bf", "3b3b57de"] {
+        eth_call_mock(selector, &word).mount(server).await;
+    }
+    eth_call_mock("0x01ffc9a7", &format!("0x{:0>64}", 0)).mount(server).await;
+}
+
+#[tokio::test]
+async fn update_params_resolves_ens_compliance_definition() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    mock_ens(&rpc, "0x00000000000000000000000000000000000000cd").await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "registry.eth",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "resolved registry.eth -> 0x00000000000000000000000000000000000000cD",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_resolves_normalized_ens_name_through_wildcard_resolver() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    let word = |value: &str| format!("{value:0>64}");
+    // sub.registry.eth has no resolver of its own; registry.eth's supports ENSIP-10.
+    eth_call_mock("0178b8bf", &format!("0x{}", word("")))
+        .with_priority(1)
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&rpc)
+        .await;
+    eth_call_mock("0178b8bf", &format!("0x{}", word("ee"))).mount(&rpc).await;
+    eth_call_mock("0x01ffc9a7", &format!("0x{}", word("1"))).mount(&rpc).await;
+    // resolve(bytes,bytes) with the DNS-encoded normalized name, returning the
+    // ABI-encoded address.
+    let encoded_addr = format!("0x{}{}{}", word("20"), word("20"), word("cd"));
+    eth_call_mock("0373756208726567697374727903657468", &encoded_addr)
+        .mount(&rpc)
+        .await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "Sub.\u{ff32}egistry.ETH",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "resolved sub.registry.eth -> 0x00000000000000000000000000000000000000cD",
+        ));
+}
+
+#[tokio::test]
+async fn new_compliance_definition_rejects_unresolvable_ens_regulator() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    mock_ens(&rpc, "0x0000000000000000000000000000000000000000").await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "ens_circuit", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "ens",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "nobody.eth",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "failed to resolve ENS name nobody.eth: no resolver is set for it",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_start_nonce_skips_nonce_lookup() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    for (rpc_method, result) in [("eth_estimateGas", "0x5208"), ("eth_getTransactionCount", "0x1")] {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": result,
+            })))
+            .mount(&rpc)
+            .await;
+    }
+
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "maxFeePerGas": 1000,
+            "maxPriorityFeePerGas": 100,
+        })))
+        .mount(&oracle)
+        .await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &oracle.uri(),
+            "--start-nonce",
+            "7",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to broadcast updateParams transaction"));
+
+    let methods: Vec<String> = rpc
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|r| r.body_json::<serde_json::Value>().ok())
+        .filter_map(|body| body["method"].as_str().map(str::to_string))
+        .collect();
+    assert!(methods.iter().any(|m| m == "eth_sendRawTransaction"), "{methods:?}");
+    assert!(!methods.iter().any(|m| m == "eth_getTransactionCount"), "{methods:?}");
+}
+
+/// Mock an RPC whose `eth_sendRawTransaction` always fails with `message`.
+async fn mock_send_rejected(rpc: &MockServer, message: &str) {
+    mock_chain_id(rpc, 1).await;
+    for (rpc_method, result) in [("eth_estimateGas", "0x5208"), ("eth_getTransactionCount", "0x1")] {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": result,
+            })))
+            .mount(rpc)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_sendRawTransaction" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": -32000, "message": message },
+        })))
+        .mount(rpc)
+        .await;
+}
+
+#[tokio::test]
+async fn update_params_explains_insufficient_funds() {
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "maxFeePerGas": 1000,
+            "maxPriorityFeePerGas": 100,
+        })))
+        .mount(&oracle)
+        .await;
+
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    for (rpc_method, result) in [
+        ("eth_estimateGas", "0x5208"),
+        ("eth_getTransactionCount", "0x1"),
+        ("eth_getBalance", "0x1"),
+    ] {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": result,
+            })))
+            .mount(&rpc)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_sendRawTransaction" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": {
+                "code": -32000,
+                "message": "insufficient funds for gas * price + value: balance 1, tx cost 21000000",
+            },
+        })))
+        .mount(&rpc)
+        .await;
+
+    // 21000 gas at 1000 wei, against a balance of 1 wei.
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &oracle.uri(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("needs up to 0.000000000021000000 ETH"))
+        .stderr(predicate::str::contains("with at least 0.000000000020999999 ETH on Mainnet"));
+}
+
+/// Mount the calls sending a transaction on a chain whose latest block has no
+/// `baseFeePerGas`, at a 1000 wei `gasPrice`.  The send itself is rejected for
+/// insufficient funds.
+async fn mock_pre_london_chain(rpc: &MockServer) {
+    // A pre-London block: no baseFeePerGas.
+    let block = serde_json::json!({
+        "hash": BYTES32_ZERO,
+        "parentHash": BYTES32_ZERO,
+        "sha3Uncles": BYTES32_ZERO,
+        "miner": "0x0000000000000000000000000000000000000000",
+        "stateRoot": BYTES32_ZERO,
+        "transactionsRoot": BYTES32_ZERO,
+        "receiptsRoot": BYTES32_ZERO,
+        "logsBloom": format!("0x{}", "0".repeat(512)),
+        "difficulty": "0x0",
+        "number": "0x1",
+        "gasLimit": "0x1c9c380",
+        "gasUsed": "0x0",
+        "timestamp": "0x0",
+        "extraData": "0x",
+        "mixHash": BYTES32_ZERO,
+        "nonce": "0x0000000000000000",
+        "transactions": [],
+        "uncles": [],
+    });
+    for (rpc_method, result) in [
+        ("eth_getBlockByNumber", block),
+        ("eth_gasPrice", serde_json::json!("0x3e8")),
+        ("eth_estimateGas", serde_json::json!("0x5208")),
+        ("eth_getTransactionCount", serde_json::json!("0x1")),
+        ("eth_getBalance", serde_json::json!("0x1")),
+    ] {
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({ "method": rpc_method })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": 0,
+                "result": result,
+            })))
+            .mount(rpc)
+            .await;
+    }
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_sendRawTransaction" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": { "code": -32000, "message": "insufficient funds for gas * price + value" },
+        })))
+        .mount(rpc)
+        .await;
+}
+
+#[tokio::test]
+async fn update_params_sends_legacy_tx_on_chain_without_base_fee() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    mock_pre_london_chain(&rpc).await;
+
+    // The funding hint prices the transaction at the 1000 wei gasPrice.
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("sending legacy (type 0) transactions with gasPrice"))
+        .stderr(predicate::str::contains("needs up to 0.000000000021000000 ETH"));
+}
+
+#[tokio::test]
+async fn update_params_warns_that_detected_legacy_tx_ignores_gas_oracle() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    mock_pre_london_chain(&rpc).await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            "http://127.0.0.1:1/fees",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "--gas-oracle returns EIP-1559 fees, so it is not used for legacy transactions",
+        ))
+        .stderr(predicate::str::contains("needs up to 0.000000000021000000 ETH"));
+}
+
+#[test]
+fn legacy_tx_conflicts_with_gas_oracle() {
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--legacy-tx",
+            "--gas-oracle",
+            "http://127.0.0.1:1/fees",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+async fn count_rpc_calls(rpc: &MockServer, rpc_method: &str) -> usize {
+    rpc.received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .filter_map(|r| r.body_json::<serde_json::Value>().ok())
+        .filter(|body| body["method"] == rpc_method)
+        .count()
+}
+
+#[tokio::test]
+async fn update_params_retries_on_nonce_error() {
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "maxFeePerGas": 1000,
+            "maxPriorityFeePerGas": 100,
+        })))
+        .mount(&oracle)
+        .await;
+
+    let update_params = |rpc: &str, retry: bool| {
+        let mut c = cmd();
+        c.args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            rpc,
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &oracle.uri(),
+        ]);
+        if retry {
+            c.arg("--retry-on-nonce-error");
+        }
+        c
+    };
+
+    let rpc = MockServer::start().await;
+    mock_send_rejected(&rpc, "nonce too low").await;
+    update_params(&rpc.uri(), false)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to broadcast updateParams transaction"))
+        .stderr(predicate::str::contains("resubmitting").not());
+    assert_eq!(count_rpc_calls(&rpc, "eth_sendRawTransaction").await, 1);
+
+    let rpc = MockServer::start().await;
+    mock_send_rejected(&rpc, "nonce too low").await;
+    update_params(&rpc.uri(), true)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("resubmitting with pending nonce 1 (retry 3/3)"))
+        .stderr(predicate::str::contains("failed to broadcast updateParams transaction"));
+    assert_eq!(count_rpc_calls(&rpc, "eth_sendRawTransaction").await, 4);
+
+    // "already known" means the node holds this same transaction; resending it at
+    // another nonce would send it twice.
+    let rpc = MockServer::start().await;
+    mock_send_rejected(&rpc, "already known").await;
+    update_params(&rpc.uri(), true)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("already known"))
+        .stderr(predicate::str::contains("resubmitting").not());
+    assert_eq!(count_rpc_calls(&rpc, "eth_sendRawTransaction").await, 1);
+}
+
+#[tokio::test]
+async fn update_params_falls_back_when_gas_oracle_fails() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let oracle = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&oracle)
+        .await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--gas-oracle",
+            &oracle.uri(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("falling back to the node's fee estimate"));
+}
+
+#[tokio::test]
+async fn update_params_skips_upload_when_already_pinned() {
+    let ipfs = MockServer::start().await;
+    // CID of "hello world\n" as produced by `ipfs add` with default settings.
+    Mock::given(method("POST"))
+        .and(path("/api/v0/pin/ls"))
+        .and(query_param("arg", "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Keys": { "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o": { "Type": "recursive" } },
+        })))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&ipfs)
+        .await;
+
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "hello world\n").unwrap();
+
+    // The RPC mock cannot accept the transaction; we only check the upload was skipped.
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--ipfs-skip-existing",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "already pinned on the IPFS node as QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_refuses_upload_over_size_limit() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&ipfs)
+        .await;
+
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "hello world\n").unwrap();
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--max-upload-size",
+            "4",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "refusing to upload 12 bytes, exceeds limit of 4 bytes",
+        ));
+}
+
+#[tokio::test]
+async fn update_params_rejects_unexpected_regulator() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": format!("0x{:0>64}", "bb"),
+        })))
+        .mount(&rpc)
+        .await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+            "--expect-regulator",
+            "0x00000000000000000000000000000000000000aa",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("regulator mismatch"))
+        .stderr(predicate::str::contains("controlled by 0x00000000000000000000000000000000000000bb"));
+}
+
+#[tokio::test]
+async fn update_params_rejects_prefixed_address_on_other_chain() {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+
+    cmd()
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "sep:0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("RPC is on chain 1"));
+}
+
+#[tokio::test]
+async fn update_params_pins_leaves_remotely() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "leaves.json",
+            "Hash": "bafyleaves",
+            "Size": "2",
+        })))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+
+    let pinning = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/pins"))
+        .and(query_param("cid", "bafyleaves"))
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "count": 0, "results": [] })),
+        )
+        .expect(1)
+        .mount(&pinning)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/pins"))
+        .and(body_partial_json(serde_json::json!({ "cid": "bafyleaves" })))
+        .respond_with(ResponseTemplate::new(202).set_body_json(serde_json::json!({
+            "requestid": "req-1",
+            "status": "pinned",
+            "created": "2026-01-01T00:00:00Z",
+            "pin": { "cid": "bafyleaves" },
+        })))
+        .expect(1)
+        .mount(&pinning)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "[]").unwrap();
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--remote-pin",
+            "--pin-service-url",
+            &pinning.uri(),
+            "--pin-token",
+            "secret",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("pinned bafyleaves remotely"));
+}
+
+#[tokio::test]
+async fn update_params_warns_on_implausible_ipfs_size() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "leaves.json",
+            "Hash": "bafyleaves",
+            "Size": "999999",
+        })))
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "[]").unwrap();
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("may have been altered in transit"));
+}
+
+#[tokio::test]
+async fn update_params_pins_unpinned_upload_with_pin_flag() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "leaves.json",
+            "Hash": "bafyleaves",
+            "Size": "10",
+        })))
+        .mount(&ipfs)
+        .await;
+    // Kubo answers 500 for a CID that is not pinned.
+    Mock::given(method("POST"))
+        .and(path("/api/v0/pin/ls"))
+        .and(query_param("arg", "bafyleaves"))
+        .respond_with(ResponseTemplate::new(500).set_body_string("path 'bafyleaves' is not pinned"))
+        .mount(&ipfs)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/pin/add"))
+        .and(query_param("arg", "bafyleaves"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "Pins": ["bafyleaves"] })))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "[]").unwrap();
+    let update_params = |pin: bool| {
+        let mut cmd = cmd();
+        cmd.args(["--ipfs-rpc-url", &ipfs.uri()]);
+        if pin {
+            cmd.arg("--pin");
+        }
+        cmd.args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ]);
+        cmd.assert().failure()
+    };
+
+    update_params(false).stderr(predicate::str::contains(
+        "bafyleaves was added but is not pinned on the IPFS node",
+    ));
+    update_params(true).stderr(predicate::str::contains("pinned bafyleaves on the IPFS node (--pin)"));
+}
+
+#[tokio::test]
+async fn update_params_retries_ipfs_gateway_error() {
+    let ipfs = MockServer::start().await;
+    // Mounted first, so it answers the first upload only.
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "leaves.json",
+            "Hash": "bafyleaves",
+            "Size": "10",
+        })))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let leaves = dir.path().join("leaves.json");
+    std::fs::write(&leaves, "[]").unwrap();
+    cmd()
+        .args(["--ipfs-rpc-url", &ipfs.uri(), "--retries", "1", "--retry-base-delay", "0"])
+        .args([
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            leaves.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("IPFS upload failed"))
+        .stderr(predicate::str::contains("503"))
+        .stderr(predicate::str::contains("retrying in 0s (attempt 2/2)"))
+        .stderr(predicate::str::contains("bafyleaves"));
+}
+
+#[test]
+fn library_flag_rejects_malformed_value() {
+    cmd()
+        .args(["--library", "ZKTranscriptLib", "verify", "--address", "0x1", "--contract-name", "a:b", "--chain-id", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("expected <name>=<address>"));
+}
+
+#[test]
+fn backend_rejects_unknown_value() {
+    cmd()
+        .args([
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--backend",
+            "gnark",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 'gnark' for '--backend"));
+}
+
+#[test]
+fn nargo_arg_rejects_reserved_flag() {
+    cmd()
+        .args(["--nargo-arg=--program-dir=elsewhere", "verify", "--address", "0x1", "--contract-name", "a:b", "--chain-id", "1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--program-dir is set by regulator-cli"));
+}
+
+#[test]
+fn nargo_check_arg_warns_that_it_goes_to_compile() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "check_arg", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--nargo-check-arg=--deny-warnings",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "--nargo-check-arg is deprecated: circuits are validated by `nargo compile`, so --deny-warnings will be passed to `nargo compile`; use --nargo-arg instead",
+        ));
+}
+
+#[test]
+fn key_name_requires_keyring_key_source() {
+    cmd()
+        .env_remove("PRIVATE_KEY")
+        .env_remove("KEY_NAME")
+        .args([
+            "update-params",
+            "--key-name",
+            "deployer",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:8545",
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--key-source keyring"));
+}
+
+#[test]
+fn keyring_key_source_rejects_private_key() {
+    cmd()
+        .args([
+            "update-params",
+            "--key-source",
+            "keyring",
+            "--key-name",
+            "deployer",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:8545",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be combined with --key-source keyring"));
+}
+
+#[test]
+fn keyring_key_source_ignores_private_key_from_env() {
+    cmd()
+        .env("PRIVATE_KEY", TEST_PRIVATE_KEY)
+        .env_remove("DBUS_SESSION_BUS_ADDRESS")
+        .args([
+            "update-params",
+            "--key-source",
+            "keyring",
+            "--key-name",
+            "deployer",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:8545",
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("cannot be combined").not())
+        .stderr(predicate::str::contains("failed to read key \"deployer\" from the OS keychain"));
+}
+
+#[test]
+fn key_import_rejects_invalid_key() {
+    cmd()
+        .args(["key", "import", "--key-name", "deployer"])
+        .write_stdin("not-a-key\n")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("failed to parse private key"));
+}
+
+#[test]
+fn show_config_reports_sources_and_redacts_secrets() {
+    cmd()
+        .env("RPC_URL", "http://rpc.example")
+        .env("PRIVATE_KEY", TEST_PRIVATE_KEY)
+        .args([
+            "--show-config=json",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-file",
+            "leaves.json",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(r#""value": "http://rpc.example",
+      "source": "env RPC_URL""#))
+        .stdout(predicate::str::contains("<redacted>"))
+        .stdout(predicate::str::contains(TEST_PRIVATE_KEY).not());
+}
+
+#[test]
+fn print_plan_lists_steps_without_running_them() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_circuit", "fn main() {}\n");
+    let receipts = dir.path().join("receipts");
+
+    // Nothing listens on the RPC URL: the plan must not need it.
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--print-plan",
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "new-compliance-definition",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--name",
+            "planned",
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--regulator",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+            "--leaves-cid",
+            "QmLeaves",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("11155111 (Sepolia) via http://127.0.0.1:1"))
+        .stdout(predicate::str::contains("verification:  no (no Etherscan API key or --verifier-url)"))
+        .stdout(predicate::str::contains("3. deploy ComplianceDefinition"))
+        .stdout(predicate::str::contains("use the pre-pinned leaves CID QmLeaves (no upload)"))
+        .stdout(predicate::str::contains("call updateCircuit on the new ComplianceDefinition"));
+    assert!(!receipts.exists(), "--print-plan must not write a receipt");
+}
+
+#[test]
+fn print_plan_lists_verify_interface_check() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_interface", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--verify-interface",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "check that HonkVerifier implements verify(bytes,bytes32[]) (--verify-interface)",
+        ));
+}
+
+#[test]
+fn print_plan_lists_version_note_upload() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_note", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--definition-version-note",
+            "Q3 sanctions list update",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "upload a version note document with the circuit CID and \"Q3 sanctions list update\"",
+        ))
+        .stdout(predicate::str::contains("register its CID as the metadataHash (--note)"));
+}
+
+#[test]
+fn print_plan_notes_parallel_library_deployment() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_parallel", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--parallel-libraries",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "deploy HonkVerifier, sending its library deployments together (--parallel-libraries)",
+        ));
+}
+
+#[test]
+fn print_plan_lists_on_chain_label() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_label", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--on-chain-label",
+            "v2 sanctions",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "then setLabel(\"v2 sanctions\") to label the version (skipped if the definition has no setLabel)",
+        ));
+}
+
+#[test]
+fn print_plan_checks_expected_verifier_hash() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_hash", "fn main() {}\n");
+    let hash = format!("0x{}", "ab".repeat(32));
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--expected-verifier-hash",
+            &hash,
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "check that the verifier bytecode hashes to {hash} (--expected-verifier-bytecode-hash)"
+        )));
+}
+
+#[test]
+fn print_plan_reuses_journaled_deployments_when_idempotent() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_idempotent", "fn main() {}\n");
+    let receipts = dir.path().join("receipts");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--idempotent",
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "deploy HonkVerifier, or reuse the deployment of the same bytecode recorded in {} (--idempotent)",
+            receipts.join("deployments.lock.json").display()
+        )));
+}
+
+#[test]
+fn print_plan_compiles_verifier_with_solc() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "plan_solc", "fn main() {}\n");
+
+    cmd()
+        .args([
+            "--print-plan",
+            "--build-tool",
+            "solc",
+            "update-circuit",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--rpc-url",
+            "http://127.0.0.1:1",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--chain-id",
+            "11155111",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("with solc --standard-json (optimizer runs"))
+        .stdout(predicate::str::contains("--build-tool solc)"))
+        .stdout(predicate::str::contains("with forge").not());
+}
+
+// -- Show receipt command --
+
+#[test]
+fn show_receipt_links_results_on_the_receipts_network() {
+    let dir = tempfile::tempdir().unwrap();
+    let receipt = dir.path().join("new-compliance-definition.json");
+    std::fs::write(
+        &receipt,
+        r#"{
+  "schema_version": 1,
+  "command": "new-compliance-definition",
+  "timestamp": "2026-04-01T12:00:00+00:00",
+  "data": {
+    "name": "kyc",
+    "compliance_definition_address": "0x1111111111111111111111111111111111111111",
+    "compliance_definition_tx": "0xdef0",
+    "chain_id": 11155111,
+    "cid": "bafycircuit",
+    "leaves_cid": "",
+    "verifier_address": "0x2222222222222222222222222222222222222222",
+    "future_field": "kept"
+  },
+  "timings_ms": { "nargo_compile": 1500, "ipfs_upload": 20 }
+}"#,
+    )
+    .unwrap();
+
+    cmd()
+        .args(["show-receipt", receipt.to_str().unwrap(), "--ipfs-gateway", "https://gw.example/"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("network:  Sepolia (chain 11155111)"))
+        .stdout(predicate::str::contains(
+            "https://sepolia.etherscan.io/address/0x1111111111111111111111111111111111111111",
+        ))
+        .stdout(predicate::str::contains("https://sepolia.etherscan.io/tx/0xdef0"))
+        .stdout(predicate::str::contains("https://gw.example/ipfs/bafycircuit"))
+        .stdout(predicate::str::contains("ipfs/(none)").not())
+        .stdout(predicate::str::contains("future_field:"))
+        .stdout(predicate::str::is_match(r"nargo_compile\s+1500 ms[\s\S]*ipfs_upload").unwrap());
+}
+
+#[test]
+fn show_receipt_without_a_chain_omits_explorer_links() {
+    let dir = tempfile::tempdir().unwrap();
+    let receipt = dir.path().join("update-params.json");
+    std::fs::write(
+        &receipt,
+        r#"{"schema_version":1,"command":"update-params","timestamp":"2026-04-01T12:00:00+00:00","data":{"compliance_definition":"0x1111111111111111111111111111111111111111","merkle_root":"0x01","leaves_file":"leaves.json","leaves_cid":"bafyleaves","update_tx_hash":"0xabcd"}}"#,
+    )
+    .unwrap();
+
+    cmd()
+        .args(["show-receipt", receipt.to_str().unwrap()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("network:  not recorded (pass --chain-id"))
+        .stdout(predicate::str::contains("https://ipfs.io/ipfs/bafyleaves"))
+        .stdout(predicate::str::contains("etherscan").not());
+
+    cmd()
+        .args(["show-receipt", receipt.to_str().unwrap(), "--chain-id", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("https://etherscan.io/tx/0xabcd"));
+}
+
+// -- Metadata schema command --
+
+#[test]
+fn metadata_schema_prints_json_schema() {
+    let output = cmd().arg("metadata-schema").assert().success().get_output().stdout.clone();
+    let schema: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(schema["required"], serde_json::json!(["circuit_cid", "created_at"]));
+    assert_eq!(schema["properties"]["created_at"]["format"], "date-time");
+    assert_eq!(schema["properties"]["public_inputs"]["items"]["required"], serde_json::json!(["name", "description"]));
+}
+
+// -- Doctor command --
+
+#[test]
+fn doctor_reports_missing_tools() {
+    cmd()
+        .env("PATH", "")
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("failed to run `nargo --version` -- is nargo installed?"))
+        .stderr(predicate::str::contains("doctor found problems: nargo, proving backend, forge"));
+}
+
+#[cfg(unix)]
+#[test]
+fn doctor_deep_reports_failing_stage() {
+    let dir = tempfile::tempdir().unwrap();
+    // A toolchain whose every step succeeds except bb verifying the proof it made.
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            (
+                "nargo",
+                "#!/bin/sh\ncase \"$1\" in\n  --version) echo 'nargo version = 1.0.0' ;;\n  compile) mkdir -p target && echo '{}' > target/doctor_self_test.json ;;\n  execute) touch target/doctor_self_test.gz ;;\nesac\n",
+            ),
+            (
+                "bb",
+                "#!/bin/sh\ncase \"$1\" in\n  --version) echo 0.82.0 ;;\n  write_vk) touch \"$5/vk\" ;;\n  write_solidity_verifier) touch \"$5\" ;;\n  prove) touch \"$7/proof\" \"$7/public_inputs\" ;;\n  verify) echo 'Proof verification failed' >&2; exit 1 ;;\nesac\n",
+            ),
+            ("forge", "#!/bin/sh\necho 'forge Version: 1.0.0'\n"),
+        ],
+    );
+
+    cmd()
+        .env("PATH", path)
+        .args(["doctor", "--deep"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("prove"))
+        .stdout(predicate::str::contains("Proof verification failed"))
+        .stderr(predicate::str::contains("doctor found problems: self-test stage `verify proof`"));
+}
+
+#[cfg(unix)]
+#[test]
+fn version_reports_cli_and_tool_versions() {
+    let dir = tempfile::tempdir().unwrap();
+    // nargo, bb, and forge are installed; cast is not.
+    let path = fake_tools_path(
+        dir.path(),
+        &[
+            ("nargo", "#!/bin/sh\necho 'nargo version = 1.0.0'\necho 'noirc version = 1.0.0'\n"),
+            ("bb", "#!/bin/sh\necho 0.82.0\n"),
+            ("forge", "#!/bin/sh\necho 'forge Version: 1.0.0'\n"),
+        ],
+    );
+    let path = path.split(':').next().unwrap().to_string();
+
+    let output = cmd().env("PATH", &path).args(["version", "--json"]).output().unwrap();
+    assert!(output.status.success());
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["version"], env!("CARGO_PKG_VERSION"));
+    assert!(report["git_commit"].is_string());
+    assert_eq!(report["tools"]["nargo"]["version"], "nargo version = 1.0.0");
+    assert_eq!(report["tools"]["bb"]["version"], "0.82.0");
+    assert_eq!(report["tools"]["forge"]["version"], "forge Version: 1.0.0");
+    assert!(report["tools"]["cast"]["error"].as_str().unwrap().contains("is cast installed?"));
+
+    cmd()
+        .env("PATH", &path)
+        .arg("version")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!("regulator-cli {} (commit ", env!("CARGO_PKG_VERSION"))))
+        .stdout(predicate::str::contains("cast   unavailable: failed to run `cast --version`"));
+}
+
+// -- Receipt digest command --
+
+fn receipt_digest(receipt: &Path) -> String {
+    let output = cmd()
+        .args(["receipt-digest", "--receipt", receipt.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn receipt_digest_ignores_key_order_and_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let pretty = dir.path().join("pretty.json");
+    std::fs::write(
+        &pretty,
+        r#"{
+  "schema_version": 1,
+  "command": "update-params",
+  "data": { "merkle_root": "0x01", "leaves_cid": "bafy" },
+  "timings_ms": { "ipfs_upload": 12 }
+}"#,
+    )
+    .unwrap();
+    let compact = dir.path().join("compact.json");
+    std::fs::write(
+        &compact,
+        r#"{"timings_ms":{"ipfs_upload":12},"data":{"leaves_cid":"bafy","merkle_root":"0x01"},"command":"update-params","schema_version":1}"#,
+    )
+    .unwrap();
+    let changed = dir.path().join("changed.json");
+    std::fs::write(
+        &changed,
+        r#"{"timings_ms":{"ipfs_upload":12},"data":{"leaves_cid":"bafy","merkle_root":"0x02"},"command":"update-params","schema_version":1}"#,
+    )
+    .unwrap();
+
+    let digest = receipt_digest(&pretty);
+    assert!(digest.starts_with("digest=0x"), "{digest}");
+    assert_eq!(digest, receipt_digest(&compact));
+    assert_ne!(digest, receipt_digest(&changed));
+}
+
+// -- Serve command --
+
+#[test]
+fn serve_requires_private_key() {
+    cmd()
+        .env_remove("PRIVATE_KEY")
+        .args(["serve", "--rpc-url", "http://127.0.0.1:8545"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--private-key"));
+}
+
+#[test]
+fn serve_refuses_non_loopback_host_without_token() {
+    cmd()
+        .env_remove("SERVE_TOKEN")
+        .args([
+            "serve",
+            "--host",
+            "0.0.0.0",
+            "--rpc-url",
+            "http://127.0.0.1:8545",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("refusing to listen on 0.0.0.0 without --token"));
+}
+
+// -- Verify command --
+
+#[test]
+fn verify_rejects_prefix_conflicting_with_chain_id() {
+    cmd()
+        .args([
+            "verify",
+            "--address",
+            "base:0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("prefixed for chain 8453"));
+}
+
+#[test]
+fn verify_rejects_unknown_chain_prefix() {
+    cmd()
+        .args([
+            "verify",
+            "--address",
+            "nochain:0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown EIP-3770 chain short name"));
+}
+
+#[test]
+fn verify_requires_chain_id_or_rpc_url() {
+    cmd()
+        .env_remove("RPC_URL")
+        .args([
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--chain-id"));
+}
+
+#[test]
+fn verify_requires_api_key() {
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key"));
+}
+
+#[test]
+fn check_verification_requires_chain_id() {
+    cmd()
+        .args([
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--chain-id"));
+}
+
+#[test]
+fn check_verification_requires_api_key() {
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "sep:0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key"));
+}
+
+#[tokio::test]
+async fn deterministic_receipt_reuses_path_for_identical_runs() {
+    let explorer = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("action", "checkverifystatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "1",
+            "message": "OK",
+            "result": "Pass - Verified",
+        })))
+        .mount(&explorer)
+        .await;
+
+    let receipts = tempfile::tempdir().unwrap();
+    for _ in 0..2 {
+        cmd()
+            .args([
+                "--deterministic-receipt",
+                "--receipts-dir",
+                receipts.path().to_str().unwrap(),
+                "--verifier-url",
+                &explorer.uri(),
+                "check-verification",
+                "--guid",
+                "abc123",
+                "--address",
+                "sep:0x0000000000000000000000000000000000000001",
+            ])
+            .assert()
+            .success();
+    }
+
+    let names: Vec<String> = std::fs::read_dir(receipts.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(names.len(), 1, "{names:?}");
+    let hash = names[0]
+        .strip_prefix("check-verification-")
+        .and_then(|rest| rest.strip_suffix(".json"))
+        .unwrap();
+    assert_eq!(hash.len(), 16);
+    assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+}
+
+#[tokio::test]
+async fn fail_on_verification_failure_sets_exit_code() {
+    let explorer = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("action", "checkverifystatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Fail - Unable to verify",
+        })))
+        .mount(&explorer)
+        .await;
+
+    let receipts = tempfile::tempdir().unwrap();
+    let check = |strict: bool| {
+        let mut command = cmd();
+        command.args([
+            "--receipts-dir",
+            receipts.path().to_str().unwrap(),
+            "--verifier-url",
+            &explorer.uri(),
+        ]);
+        if strict {
+            command.arg("--fail-on-verification-failure");
+        }
+        command.args([
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "sep:0x0000000000000000000000000000000000000001",
+        ]);
+        command.assert()
+    };
+
+    check(false).success();
+    check(true)
+        .failure()
+        .stderr(predicate::str::contains("Fail - Unable to verify (--fail-on-verification-failure)"));
+    // The receipt is written before the command fails.
+    assert_eq!(std::fs::read_dir(receipts.path()).unwrap().count(), 2);
+}
+
+#[tokio::test]
+async fn retry_warning_masks_api_key() {
+    let explorer = MockServer::start().await;
+    // The first status check hits a gateway error; the retry succeeds.
+    Mock::given(method("GET"))
+        .and(query_param("action", "checkverifystatus"))
+        .respond_with(ResponseTemplate::new(503))
+        .up_to_n_times(1)
+        .mount(&explorer)
+        .await;
+    Mock::given(method("GET"))
+        .and(query_param("action", "checkverifystatus"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "1",
+            "message": "OK",
+            "result": "Pass - Verified",
+        })))
+        .mount(&explorer)
+        .await;
+
+    let receipts = tempfile::tempdir().unwrap();
+    cmd()
+        .args([
+            "--receipts-dir",
+            receipts.path().to_str().unwrap(),
+            "--retries",
+            "1",
+            "--retry-base-delay",
+            "0",
+            "--verifier-url",
+            &explorer.uri(),
+            "--etherscan-api-key",
+            "SECRETAPIKEY1234",
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "sep:0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("retrying in 0s (attempt 2/2)"))
+        .stderr(predicate::str::contains("apikey=***"))
+        .stderr(predicate::str::contains("SECRETAPIKEY1234").not());
+}
+
+#[test]
+fn rpc_url_key_is_masked_in_errors() {
+    cmd()
+        .args([
+            "--retries",
+            "0",
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            "http://127.0.0.1:1/v3/0123456789abcdef0123456789abcdef",
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("127.0.0.1:1/v3/***"))
+        .stderr(predicate::str::contains("0123456789abcdef0123456789abcdef").not());
+}
+
+#[tokio::test]
+async fn check_verification_uses_chains_file_api_url() {
+    let explorer = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(query_param("action", "checkverifystatus"))
+        .and(query_param("chainid", "11155111"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "1",
+            "message": "OK",
+            "result": "Pass - Verified",
+        })))
+        .expect(1)
+        .mount(&explorer)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let chains_file = dir.path().join("chains.toml");
+    std::fs::write(
+        &chains_file,
+        format!(
+            "[[chain]]\nid = 1\napi_url = \"http://127.0.0.1:1/unused\"\n\n[[chain]]\nid = 11155111\napi_url = \"{}\"\n",
+            explorer.uri()
+        ),
+    )
+    .unwrap();
+
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "--chains-file",
+            chains_file.to_str().unwrap(),
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "sep:0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .success();
+}
+
+#[test]
+fn chains_file_rejects_duplicate_gate_limit() {
+    let dir = tempfile::tempdir().unwrap();
+    let chains_file = dir.path().join("chains.toml");
+    std::fs::write(
+        &chains_file,
+        "[[chain]]\nid = 1\ngate_limit = 1048576\n\n[[chain]]\nid = 1\ngate_limit = 262144\n",
+    )
+    .unwrap();
+
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "--chains-file",
+            chains_file.to_str().unwrap(),
+            "check-verification",
+            "--guid",
+            "abc123",
+            "--address",
+            "eth:0x0000000000000000000000000000000000000001",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("chain 1 has more than one gate_limit"));
+}
+
+#[test]
+fn color_always_colors_errors() {
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--color",
+            "always",
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("\x1b[31mError:\x1b[0m"));
+}
+
+#[test]
+fn color_never_keeps_plain_errors() {
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--color",
+            "never",
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::starts_with("Error: "));
+}
+
+/// Write a minimal forge artifact for `src/<file>:<contract>` whose metadata
+/// references the given source paths.
+fn write_forge_artifact(contract_dir: &Path, file: &str, contract: &str, sources: &[&str]) {
+    let sources: serde_json::Map<String, serde_json::Value> = sources
+        .iter()
+        .map(|s| (s.to_string(), serde_json::json!({})))
+        .collect();
+    let metadata = serde_json::json!({
+        "compiler": { "version": "0.8.28+commit.7893614a" },
+        "settings": { "optimizer": { "enabled": true, "runs": 1 } },
+        "sources": sources,
+    });
+    let artifact = serde_json::json!({
+        "bytecode": { "object": "0x00" },
+        "rawMetadata": metadata.to_string(),
+    });
+    let out = contract_dir.join("out").join(file);
+    std::fs::create_dir_all(&out).unwrap();
+    std::fs::write(out.join(format!("{contract}.json")), artifact.to_string()).unwrap();
+}
+
+#[test]
+fn verify_reports_unresolvable_source() {
+    let dir = tempfile::tempdir().unwrap();
+    write_forge_artifact(dir.path(), "Verifier.sol", "HonkVerifier", &["lib/missing/Lib.sol"]);
+
+    cmd()
+        .args([
+            "--etherscan-api-key",
+            "key",
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+            "--contract-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--contract-source-root"));
+}
+
+#[test]
+fn verify_rejects_malformed_constructor_args() {
+    let dir = tempfile::tempdir().unwrap();
+    write_forge_artifact(dir.path(), "Verifier.sol", "HonkVerifier", &["src/Verifier.sol"]);
+
+    cmd()
+        .args([
+            "--etherscan-api-key",
+            "key",
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+            "--contract-dir",
+            dir.path().to_str().unwrap(),
+            "--constructor-args",
+            "0xnot-hex",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid constructor arguments (expected ABI-encoded hex)"));
+}
+
+#[tokio::test]
+async fn verify_does_not_resubmit_rejected_source() {
+    let explorer = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(query_param("chainid", "1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "status": "0",
+            "message": "NOTOK",
+            "result": "Fail - Unable to verify. Compiled contract deployment bytecode does NOT match",
+        })))
+        .expect(1)
+        .mount(&explorer)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    write_forge_artifact(dir.path(), "Verifier.sol", "HonkVerifier", &["src/Verifier.sol"]);
+    std::fs::create_dir_all(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src/Verifier.sol"), "contract HonkVerifier {}\n").unwrap();
+
+    cmd()
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "--retries",
+            "2",
+            "--verifier-url",
+            &explorer.uri(),
+            "--etherscan-api-key",
+            "key",
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+            "--contract-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .assert()
+        .stderr(predicate::str::contains("submission failed: "))
+        .stderr(predicate::str::contains("retrying").not());
+}
+
+/// Run `verify --check-verification-input` against an artifact whose runtime code is
+/// `deployed_code`, with a stand-in solc that always compiles to `0x6001`.  With
+/// `solc_path`, the stand-in is passed as `--solc-path` and a broken solc is installed
+/// under `~/.svm`, which must then be ignored.
+#[cfg(unix)]
+fn check_verification_input(deployed_code: &str, solc_path: bool) -> assert_cmd::assert::Assert {
+    let dir = tempfile::tempdir().unwrap();
+    write_forge_artifact(dir.path(), "Verifier.sol", "HonkVerifier", &["src/Verifier.sol"]);
+    std::fs::create_dir_all(dir.path().join("src")).unwrap();
+    std::fs::write(dir.path().join("src/Verifier.sol"), "contract HonkVerifier {}\n").unwrap();
+    let artifact = dir.path().join("out/Verifier.sol/HonkVerifier.json");
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&artifact).unwrap()).unwrap();
+    json["deployedBytecode"] = serde_json::json!({ "object": deployed_code });
+    std::fs::write(&artifact, json.to_string()).unwrap();
+
+    let path = fake_tools_path(
+        dir.path(),
+        &[(
+            "solc",
+            r#"#!/bin/sh
+if [ "$1" = --version ]; then
+  echo "Version: 0.8.28+commit.7893614a.Linux.g++"
+  exit 0
+fi
+grep -q '"src/Verifier.sol"' || exit 1
+echo '{"contracts":{"src/Verifier.sol":{"HonkVerifier":{"evm":{"deployedBytecode":{"object":"6001"}}}}}}'
+"#,
+        )],
+    );
+    let mut solc_args = Vec::new();
+    if solc_path {
+        use std::os::unix::fs::PermissionsExt;
+
+        let svm = dir.path().join(".svm/0.8.28");
+        std::fs::create_dir_all(&svm).unwrap();
+        std::fs::write(svm.join("solc-0.8.28"), "#!/bin/sh
+exit 1
+").unwrap();
+        std::fs::set_permissions(svm.join("solc-0.8.28"), std::fs::Permissions::from_mode(0o755))
+            .unwrap();
+        solc_args =
+            vec!["--solc-path".to_string(), dir.path().join("bin/solc").display().to_string()];
+    }
+
+    cmd()
+        .env("PATH", path)
+        .env("HOME", dir.path())
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .env_remove("SOLC_BINARY")
+        .args(&solc_args)
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--contract-dir",
+            dir.path().to_str().unwrap(),
+            "--check-verification-input",
+        ])
+        .assert()
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_check_verification_input_matches_offline() {
+    check_verification_input("0x6001", false)
+        .success()
+        .stdout(predicate::str::contains("compiler_version=v0.8.28+commit.7893614a"))
+        .stdout(predicate::str::contains("verification_input=match"));
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_check_verification_input_reports_mismatch() {
+    check_verification_input("0x6002", false)
+        .failure()
+        .stdout(predicate::str::contains("verification_input=mismatch"))
+        .stderr(predicate::str::contains("does not reproduce the runtime bytecode"));
+}
+
+#[cfg(unix)]
+#[test]
+fn verify_check_verification_input_prefers_solc_path_over_svm() {
+    check_verification_input("0x6001", true)
+        .success()
+        .stdout(predicate::str::contains("verification_input=match"));
+}
+
+// -- Publish command --
+
+#[test]
+fn publish_requires_path_argument() {
+    cmd()
+        .args(["publish", "--rpc-url", "http://localhost:8545", "--private-key", "0xdead", "--compliance-definition", "0x1"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("DIR"));
+}
+
+#[test]
+fn publish_requires_rpc_url() {
+    let dir = tempfile::tempdir().unwrap();
+    cmd()
+        .current_dir(dir.path())
+        .env_remove("RPC_URL")
+        .args(["publish", "--private-key", "0xdead", "--compliance-definition", "0x1", "/tmp/x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--rpc-url"));
+}
+
+#[test]
+fn publish_requires_private_key() {
+    let dir = tempfile::tempdir().unwrap();
+    cmd()
+        .current_dir(dir.path())
+        .env_remove("PRIVATE_KEY")
+        .args(["publish", "--rpc-url", "http://localhost:8545", "--compliance-definition", "0x1", "/tmp/x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--private-key"));
+}
+
+#[test]
+fn publish_requires_compliance_definition() {
+    cmd()
+        .args(["publish", "--rpc-url", "http://localhost:8545", "--private-key", "0xdead", "/tmp/x"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--compliance-definition"));
+}
+
+#[test]
+fn publish_rejects_nonexistent_directory() {
+    let mut args = vec!["publish"];
+    args.extend_from_slice(&PUBLISH_CHAIN_ARGS);
+    args.push("/tmp/nonexistent-noir-project");
+
+    cmd()
+        .args(&args)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a directory"));
+}
+
+#[test]
+fn publish_rejects_directory_without_nargo_toml() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut args = vec!["publish"];
+    args.extend_from_slice(&PUBLISH_CHAIN_ARGS);
+    args.push(dir.path().to_str().unwrap());
+
+    cmd()
+        .args(&args)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("no Nargo.toml found"));
+}
+
+#[test]
+fn publish_rejects_invalid_circuit() {
+    let dir = tempfile::tempdir().unwrap();
     let project = create_nargo_project(dir.path(), "bad_circuit", "this is not valid noir");
     let mut args = vec!["publish"];
     args.extend_from_slice(&PUBLISH_CHAIN_ARGS);
@@ -296,3 +4025,590 @@ fn update_is_not_yet_implemented() {
         .failure()
         .stderr(predicate::str::contains("not yet implemented"));
 }
+
+// -- Audit command --
+
+/// Run `audit --skip-build` against a prebuilt ComplianceDefinition artifact with
+/// runtime code `artifact_code`, while the chain reports `deployed_code`.
+async fn audit(artifact_code: &str, deployed_code: &str) -> assert_cmd::assert::Assert {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_getCode" })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": deployed_code,
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let artifact_dir = dir.path().join("out/ComplianceDefinition.sol");
+    std::fs::create_dir_all(&artifact_dir).unwrap();
+    std::fs::write(
+        artifact_dir.join("ComplianceDefinition.json"),
+        serde_json::json!({ "deployedBytecode": { "object": artifact_code } }).to_string(),
+    )
+    .unwrap();
+
+    cmd()
+        .args([
+            "--skip-build",
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "audit",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--contract-dir",
+            dir.path().to_str().unwrap(),
+        ])
+        .assert()
+}
+
+#[tokio::test]
+async fn audit_ignores_metadata_hash() {
+    // Same code, different CBOR metadata (`a1 41 xx`, length 0x0003).
+    audit("0x6080604052a141010003", "0x6080604052a141020003")
+        .await
+        .success()
+        .stdout(predicate::str::contains("match=true"));
+}
+
+#[tokio::test]
+async fn audit_reports_code_mismatch() {
+    audit("0x6080604052a141010003", "0x6080604053a141010003")
+        .await
+        .failure()
+        .stdout(predicate::str::contains("match=false"))
+        .stderr(predicate::str::contains("does not match the reference ComplianceDefinition"));
+}
+
+// -- Check command --
+
+/// Run `check --block 100` for account 0x..1234 against a definition whose `verify`
+/// returns `verified` -- answered only for a call from that account at that block.
+async fn check(verified: bool) -> assert_cmd::assert::Assert {
+    let rpc = MockServer::start().await;
+    mock_chain_id(&rpc, 1).await;
+    Mock::given(method("POST"))
+        .and(body_partial_json(serde_json::json!({ "method": "eth_call" })))
+        .and(body_string_contains("0x0000000000000000000000000000000000001234"))
+        .and(body_string_contains("\"0x64\""))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "result": format!("0x{:064x}", u8::from(verified)),
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let proof = dir.path().join("proof");
+    std::fs::write(&proof, [0xab; 64]).unwrap();
+
+    cmd()
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "check",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--account",
+            "0x0000000000000000000000000000000000001234",
+            "--proof",
+            proof.to_str().unwrap(),
+            "--block",
+            "100",
+            "--rpc-url",
+            &rpc.uri(),
+        ])
+        .assert()
+}
+
+#[tokio::test]
+async fn check_reports_compliant_account() {
+    check(true)
+        .await
+        .success()
+        .stdout(predicate::str::contains("compliant=true"))
+        .stderr(predicate::str::contains("is compliant with"));
+}
+
+#[tokio::test]
+async fn check_fails_for_rejected_proof() {
+    check(false)
+        .await
+        .failure()
+        .stdout(predicate::str::contains("compliant=false"))
+        .stderr(predicate::str::contains("at block 100: verify() returned false"));
+}
+
+// -- Proof calldata command --
+
+#[test]
+fn proof_calldata_reports_encoded_size() {
+    let dir = tempfile::tempdir().unwrap();
+    let proof = dir.path().join("proof");
+    let public_inputs = dir.path().join("public_inputs");
+    std::fs::write(&proof, [1u8; 64]).unwrap();
+    std::fs::write(&public_inputs, [5u8; 32]).unwrap();
+
+    // selector + two offsets + (length + 64-byte proof) + (length + one field)
+    cmd()
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "proof-calldata",
+            "--proof",
+            proof.to_str().unwrap(),
+            "--public-inputs",
+            public_inputs.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("proof_bytes=64"))
+        .stdout(predicate::str::contains("public_inputs=1"))
+        .stdout(predicate::str::contains("calldata_bytes=228"));
+}
+
+#[cfg(unix)]
+#[test]
+fn log_file_records_timestamped_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let proof = dir.path().join("proof");
+    let log = dir.path().join("run.log");
+    std::fs::write(&proof, [1u8; 64]).unwrap();
+
+    cmd()
+        .args([
+            "--log-file",
+            log.to_str().unwrap(),
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "proof-calldata",
+            "--proof",
+            proof.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("proof_bytes=64"));
+
+    cmd()
+        .env_remove("ETHERSCAN_API_KEY")
+        .env_remove("VERIFIER_URL")
+        .args([
+            "--log-file",
+            log.to_str().unwrap(),
+            "verify",
+            "--address",
+            "0x0000000000000000000000000000000000000001",
+            "--contract-name",
+            "src/Verifier.sol:HonkVerifier",
+            "--chain-id",
+            "1",
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("API key"));
+
+    let log = std::fs::read_to_string(&log).unwrap();
+    let line = |needle: &str| {
+        log.lines()
+            .find(|l| l.contains(needle))
+            .unwrap_or_else(|| panic!("{needle:?} missing from log:\n{log}"))
+            .to_string()
+    };
+    let result = line("proof_bytes=64");
+    assert!(result.contains(" [stdout] proof_bytes=64"), "{result}");
+    assert!(result.starts_with("20") && result.as_bytes()[10] == b'T', "{result}");
+    assert!(line("API key").contains(" [stderr] Error: "));
+}
+
+#[test]
+fn proof_calldata_rejects_partial_public_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let proof = dir.path().join("proof");
+    let public_inputs = dir.path().join("public_inputs");
+    std::fs::write(&proof, [1u8; 64]).unwrap();
+    std::fs::write(&public_inputs, [5u8; 33]).unwrap();
+
+    cmd()
+        .args([
+            "proof-calldata",
+            "--proof",
+            proof.to_str().unwrap(),
+            "--public-inputs",
+            public_inputs.to_str().unwrap(),
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("not a whole number of 32-byte fields"));
+}
+
+// -- Gen prover toml command --
+
+/// A compiled-circuit ABI for `fn main(root: pub Field, balance: u64, path: [Field; 2], user: User)`
+/// with `struct User { id: u8, active: bool }`.
+const PROVER_ABI: &str = r#"{"noir_version": "1.0.0", "abi": {"parameters": [
+    {"name": "root", "type": {"kind": "field"}, "visibility": "public"},
+    {"name": "balance", "type": {"kind": "integer", "sign": "unsigned", "width": 64}, "visibility": "private"},
+    {"name": "path", "type": {"kind": "array", "length": 2, "type": {"kind": "field"}}, "visibility": "private"},
+    {"name": "user", "type": {"kind": "struct", "path": "User", "fields": [
+        {"name": "id", "type": {"kind": "integer", "sign": "unsigned", "width": 8}},
+        {"name": "active", "type": {"kind": "boolean"}}
+    ]}, "visibility": "private"}
+], "return_type": null, "error_types": {}}}"#;
+
+#[test]
+fn gen_prover_toml_types_values_from_abi() {
+    let dir = tempfile::tempdir().unwrap();
+    let abi = dir.path().join("circuit.json");
+    let json = dir.path().join("witness.json");
+    let output = dir.path().join("Prover.toml");
+    std::fs::write(&abi, PROVER_ABI).unwrap();
+    std::fs::write(
+        &json,
+        r#"{"root": "0x1f", "balance": 1000, "path": ["1", 2], "user": {"id": 7, "active": true}}"#,
+    )
+    .unwrap();
+
+    cmd()
+        .args([
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "gen-prover-toml",
+            "--json",
+            json.to_str().unwrap(),
+            "--abi",
+            abi.to_str().unwrap(),
+            "--output",
+            output.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let toml = std::fs::read_to_string(&output).unwrap();
+    for line in [
+        r#"root = "0x1f""#,
+        r#"balance = "1000""#,
+        r#"path = ["1", "2"]"#,
+        "[user]",
+        r#"id = "7""#,
+        "active = true",
+    ] {
+        assert!(toml.lines().any(|l| l == line), "{line:?} missing from:\n{toml}");
+    }
+}
+
+#[test]
+fn gen_prover_toml_rejects_mistyped_and_missing_values() {
+    let dir = tempfile::tempdir().unwrap();
+    let abi = dir.path().join("circuit.json");
+    let json = dir.path().join("witness.json");
+    std::fs::write(&abi, PROVER_ABI).unwrap();
+
+    let gen_prover_toml = || {
+        let mut c = cmd();
+        c.args([
+            "gen-prover-toml",
+            "--json",
+            json.to_str().unwrap(),
+            "--abi",
+            abi.to_str().unwrap(),
+            "--output",
+            dir.path().join("Prover.toml").to_str().unwrap(),
+        ]);
+        c
+    };
+
+    std::fs::write(&json, r#"{"root": "1", "balance": 1}"#).unwrap();
+    gen_prover_toml()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("missing parameters: path, user"));
+
+    std::fs::write(
+        &json,
+        r#"{"root": "1", "balance": 1, "path": ["1", "2"], "user": {"id": 256, "active": true}}"#,
+    )
+    .unwrap();
+    gen_prover_toml()
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("user.id: 256 does not fit in u8"));
+}
+
+// -- Upload command --
+
+#[tokio::test]
+async fn upload_archives_source_without_tools() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .and(body_string_contains("fn main() {}"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "main.nr",
+            "Hash": "QmSource",
+            "Size": "21",
+        })))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "archived", "fn main() {}\n");
+    let receipts = dir.path().join("receipts");
+
+    // An empty PATH proves nargo, bb, and forge are not needed.
+    cmd()
+        .env("PATH", "")
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "upload",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cid=QmSource"));
+
+    let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(receipt["command"], "upload");
+    assert_eq!(receipt["data"]["cid"], "QmSource");
+
+    std::fs::remove_file(project.join("Nargo.toml")).unwrap();
+    cmd()
+        .args(["--ipfs-rpc-url", &ipfs.uri(), "upload", "--circuit-dir", project.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Nargo.toml"));
+}
+
+#[tokio::test]
+async fn upload_receipt_records_cid_v1() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "main.nr",
+            "Hash": "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o",
+            "Size": "21",
+        })))
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "versioned", "fn main() {}\n");
+    let receipts = dir.path().join("receipts");
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "upload",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(
+        receipt["data"]["cid_v1"]["QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"],
+        "bafybeicg2rebjoofv4kbyovkw7af3rpiitvnl6i7ckcywaq6xjcxnc2mby"
+    );
+}
+
+#[test]
+fn upload_compiled_requires_compiled_circuit() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "uncompiled", "fn main() {}\n");
+
+    cmd()
+        .args(["upload", "--circuit-dir", project.to_str().unwrap(), "--ipfs-source", "compiled"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("compiled circuit not found at"))
+        .stderr(predicate::str::contains("target/uncompiled.json"));
+}
+
+#[tokio::test]
+async fn upload_both_archives_source_and_compiled_circuit() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "archived_both", "fn main() {}\n");
+    let main_nr = project.join("src/main.nr").canonicalize().unwrap();
+    std::fs::create_dir(project.join("target")).unwrap();
+    std::fs::write(
+        project.join("target/archived_both.json"),
+        serde_json::json!({
+            "file_map": { "1": { "path": main_nr.to_str().unwrap(), "source": "fn main() {}\n" } },
+        })
+        .to_string(),
+    )
+    .unwrap();
+
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .and(body_string_contains("archived_both/src/main.nr"))
+        .and(body_string_contains("archived_both/target/archived_both.json"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(concat!(
+            "{\"Name\":\"archived_both/src/main.nr\",\"Hash\":\"QmMainNr\",\"Size\":\"21\"}\n",
+            "{\"Name\":\"archived_both/target/archived_both.json\",\"Hash\":\"QmCompiled\",\"Size\":\"90\"}\n",
+            "{\"Name\":\"archived_both\",\"Hash\":\"QmArchive\",\"Size\":\"200\"}\n",
+        )))
+        .expect(1)
+        .mount(&ipfs)
+        .await;
+    let receipts = dir.path().join("receipts");
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--receipts-dir",
+            receipts.to_str().unwrap(),
+            "upload",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+            "--ipfs-source",
+            "both",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("cid=QmArchive"));
+
+    let receipt = std::fs::read_dir(&receipts).unwrap().next().unwrap().unwrap().path();
+    let receipt: serde_json::Value = serde_json::from_slice(&std::fs::read(receipt).unwrap()).unwrap();
+    assert_eq!(receipt["data"]["ipfs_source"], "both");
+    assert!(receipt["data"]["compiled_file"].as_str().unwrap().ends_with("target/archived_both.json"));
+}
+
+#[tokio::test]
+async fn env_out_writes_sourceable_exports() {
+    let ipfs = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/api/v0/add"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "Name": "main.nr",
+            "Hash": "QmSource",
+            "Size": "21",
+        })))
+        .mount(&ipfs)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(&dir.path().join("it's here"), "exported", "fn main() {}\n");
+    let env_file = dir.path().join("deployment.env");
+
+    cmd()
+        .args([
+            "--ipfs-rpc-url",
+            &ipfs.uri(),
+            "--receipts-dir",
+            dir.path().join("receipts").to_str().unwrap(),
+            "--env-out",
+            env_file.to_str().unwrap(),
+            "upload",
+            "--circuit-dir",
+            project.to_str().unwrap(),
+        ])
+        .assert()
+        .success();
+
+    let exports = std::fs::read_to_string(&env_file).unwrap();
+    assert!(exports.contains("export CID='QmSource'\n"), "{exports}");
+
+    // The quoted project path survives a round trip through the shell.
+    let sourced = std::process::Command::new("sh")
+        .arg("-c")
+        .arg(format!(". '{}' && printf %s \"$PROJECT_DIR\"", env_file.display()))
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(sourced.stdout).unwrap(), project.display().to_string());
+}
+
+#[test]
+fn upload_suggests_package_type_for_misplaced_entry_point() {
+    let dir = tempfile::tempdir().unwrap();
+    let project = create_nargo_project(dir.path(), "library", "fn helper() {}\n");
+    std::fs::rename(project.join("src/main.nr"), project.join("src/lib.nr")).unwrap();
+    std::fs::write(project.join("src/utils.nr"), "").unwrap();
+
+    cmd()
+        .args(["upload", "--circuit-dir", project.to_str().unwrap()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("source file not found"))
+        .stderr(predicate::str::contains("files under src/: lib.nr, utils.nr"))
+        .stderr(predicate::str::contains(
+            "found src/lib.nr -- did you mean type = \"lib\" in Nargo.toml?",
+        ));
+}
+
+#[test]
+fn upload_rejects_unknown_storage_backend() {
+    cmd()
+        .args(["--storage-backend", "s3", "upload", "--circuit-dir", "."])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid value 's3' for '--storage-backend"))
+        .stderr(predicate::str::contains("possible values: ipfs"));
+}
+
+#[tokio::test]
+async fn errors_mask_private_key() {
+    let rpc = MockServer::start().await;
+    // A misbehaving node that echoes the signing key back in its error.
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": 0,
+            "error": {
+                "code": -32000,
+                "message": format!("bad key {}", TEST_PRIVATE_KEY.trim_start_matches("0x")),
+            },
+        })))
+        .mount(&rpc)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("run.log");
+    let output = cmd()
+        .args([
+            "--log-file",
+            log.to_str().unwrap(),
+            "update-params",
+            "--compliance-definition",
+            "0x0000000000000000000000000000000000000001",
+            "--rpc-url",
+            &rpc.uri(),
+            "--private-key",
+            TEST_PRIVATE_KEY,
+            "--merkle-root",
+            BYTES32_ZERO,
+            "--leaves-cid",
+            TEST_LEAVES_CID,
+        ])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("bad key ***"))
+        .get_output()
+        .clone();
+
+    let key = TEST_PRIVATE_KEY.trim_start_matches("0x");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!stderr.contains(key), "{stderr}");
+    let log = std::fs::read_to_string(&log).unwrap();
+    assert!(!log.contains(key), "{log}");
+}